@@ -0,0 +1,139 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use serde::{ Deserialize, Serialize };
+use thiserror::Error;
+
+use crate::log_error;
+
+/// An address on a specific chain
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AddressEntry {
+    pub chain: String,
+    pub address: String,
+}
+
+/// List of addresses, used for the allowlist of trusted addresses, e.g., the relayers of a bridge,
+/// and the denylist of known malicious addresses.
+///
+/// Example:
+/// ```json
+/// {
+///     "addresses": [
+///         { "chain": "ethereum", "address": "0xa58A9d3A5E240b09Da3Bc0BFc011AF3d20D31763" }
+///     ]
+/// }
+/// ```
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AddressList {
+    pub addresses: Vec<AddressEntry>,
+}
+
+impl AddressList {
+    pub fn from_file(path: &str) -> Result<Self, AddressListError> {
+        let data = std::fs::read_to_string(path).map_err(|_| AddressListError::FileNotFound(path.to_string()))?;
+        serde_json::from_str(&data).map_err(|e| AddressListError::InvalidFormat(path.to_string(), e.to_string()))
+    }
+
+    /// Returns true if the address is listed for the chain. Chains and addresses are compared case insensitive.
+    pub fn contains(&self, chain: &str, address: &str) -> bool {
+        self.addresses
+            .iter()
+            .any(|e| e.chain.eq_ignore_ascii_case(chain) && e.address.eq_ignore_ascii_case(address))
+    }
+
+    /// Returns the first of the addresses that is listed for the chain
+    pub fn find<'a>(&self, chain: &str, addresses: &[&'a str]) -> Option<&'a str> {
+        addresses
+            .iter()
+            .find(|a| self.contains(chain, a))
+            .copied()
+    }
+}
+
+/// Address list that is reloaded when its file is modified, so it can be edited at runtime.
+/// A missing file is an empty list.
+pub struct WatchedAddressList {
+    path: PathBuf,
+    /// The loaded list and the modification time of the file when it was loaded
+    state: Mutex<(AddressList, Option<SystemTime>)>,
+}
+
+impl WatchedAddressList {
+    pub fn new(path: PathBuf) -> Self {
+        let list = Self {
+            path,
+            state: Mutex::new((AddressList::default(), None)),
+        };
+        list.current();
+        list
+    }
+
+    /// Returns the list and reloads it first if the file changed.
+    /// If the changed file is invalid, the previous list is kept.
+    pub fn current(&self) -> AddressList {
+        let modified = std::fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        let mut state = self.state.lock().unwrap();
+        if modified != state.1 {
+            state.1 = modified;
+            if modified.is_none() {
+                state.0 = AddressList::default();
+            } else {
+                match AddressList::from_file(self.path.to_str().unwrap()) {
+                    Ok(list) => {
+                        state.0 = list;
+                    }
+                    Err(e) => log_error!("Error: {}", e),
+                }
+            }
+        }
+        state.0.clone()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AddressListError {
+    #[error("the address list {0} does not exist")]
+    FileNotFound(String),
+    #[error("the address list {0} is invalid: {1}")]
+    InvalidFormat(String, String),
+}
+
+#[test]
+fn test_address_list() {
+    let list: AddressList = serde_json
+        ::from_str(
+            r#"{"addresses": [{"chain": "ethereum", "address": "0xa58A9d3A5E240b09Da3Bc0BFc011AF3d20D31763"}]}"#
+        )
+        .unwrap();
+
+    assert!(list.contains("Ethereum", "0xa58a9d3a5e240b09da3bc0bfc011af3d20d31763"));
+    assert!(!list.contains("bsc", "0xa58A9d3A5E240b09Da3Bc0BFc011AF3d20D31763"));
+    assert_eq!(
+        list.find("ethereum", &["0xdead", "0xA58A9D3A5E240B09DA3BC0BFC011AF3D20D31763"]),
+        Some("0xA58A9D3A5E240B09DA3BC0BFC011AF3D20D31763")
+    );
+    assert_eq!(list.find("ethereum", &["0xdead"]), None);
+    assert!(AddressList::from_file("config/does_not_exist.json").is_err());
+}
+
+#[test]
+fn test_watched_address_list() {
+    let dir = crate::utils::TempDir::new("denylist");
+    let path = dir.join("denylist.json");
+
+    let list = WatchedAddressList::new(path.clone());
+    assert!(list.current().addresses.is_empty());
+
+    std::fs::write(&path, r#"{"addresses": [{"chain": "bsc", "address": "0xbad"}]}"#).unwrap();
+    assert!(list.current().contains("bsc", "0xBAD"));
+
+    // An invalid edit keeps the previous list
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    std::fs::write(&path, "{").unwrap();
+    assert!(list.current().contains("bsc", "0xbad"));
+
+    std::fs::remove_file(&path).unwrap();
+    assert!(list.current().addresses.is_empty());
+}
@@ -1,7 +1,14 @@
+use std::collections::HashMap;
+use std::sync::{ mpsc, Mutex };
+use std::time::Duration;
 use std::{mem::MaybeUninit, sync::Once};
 
 use serde::{Deserialize, Serialize};
-use ws::Sender;
+use thiserror::Error;
+use ws::{ CloseCode, Sender };
+
+use crate::sockets::subscription::forget_subscription;
+use crate::{ log_error, log_info };
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -17,37 +24,276 @@ pub struct Connection {
     pub rpc_url: String,
     #[serde(rename = "ws_url")]
     pub ws_url: Option<String>,
+    /// Further endpoints of the chain that `call()` tries in order if an endpoint fails
+    #[serde(rename = "fallback_urls", default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback_urls: Vec<String>,
+    /// Spread the calls over all endpoints instead of always starting with `rpc_url`
+    #[serde(rename = "round_robin", default)]
+    pub round_robin: bool,
 }
 
 impl ConnectionConfig {
-    pub fn from_file(path: &str) -> Self {
+    /// Loads `connections.json` of the config directory
+    pub fn load() -> Result<Self, ConnectionError> {
+        let path = crate::utils::get_directories().config_path("connections.json");
+        #[cfg(test)]
+        {
+            // Registered connections do not need the file
+            let mut config = Self::from_file(&path.to_string_lossy()).unwrap_or_default();
+            config.connections.splice(0..0, REGISTERED_CONNECTIONS.lock().unwrap().iter().cloned());
+            return Ok(config);
+        }
+        #[cfg(not(test))]
+        Self::from_file(&path.to_string_lossy())
+    }
+
+    /// Makes the connections available to `load`, e.g. to `call()` and `block_timestamp()` in tests.
+    /// They take precedence over the connections of `connections.json`.
+    #[cfg(test)]
+    pub fn register(self) {
+        REGISTERED_CONNECTIONS.lock().unwrap().extend(self.connections);
+    }
+
+    /// Loads the connections and resolves the urls from the environment variables.
+    /// An url is either the name of an environment variable or contains `${VAR}` placeholders,
+    /// e.g. `wss://mainnet.infura.io/ws/v3/${INFURA_KEY}`.
+    /// Every resolved `rpc_url` and every literal one must be a websocket url (`ws://` or `wss://`),
+    /// e.g. a literal `http://` url fails with the name of the chain. Names of unset environment variables are kept.
+    pub fn from_file(path: &str) -> Result<Self, ConnectionError> {
         dotenv::dotenv().ok();
 
-        let data = std::fs::read_to_string(path).unwrap();
-        let mut config: ConnectionConfig = serde_json::from_str(&data).unwrap();
+        let data = std::fs::read_to_string(path).map_err(|_| ConnectionError::FileNotFound(path.to_string()))?;
+        let mut config: ConnectionConfig = serde_json::from_str(&data).map_err(|e| ConnectionError::InvalidFormat(e.to_string()))?;
 
         for c in &mut config.connections {
-            let env_var = &c.rpc_url;
-
-            let e = std::env::var(env_var);
-            match e {
-                Ok(v) => {
-                    // println!("{}: {}", env_var, v);
-                    c.rpc_url = v.clone();
-                    let w = v.replace("http", "ws");
-                    c.ws_url = Some(w);
+            c.fallback_urls = c.fallback_urls
+                .iter()
+                .map(|url| resolve_url(&c.name, url))
+                .collect::<Result<Vec<String>, ConnectionError>>()?;
+            if let Some(ws_url) = &c.ws_url {
+                c.ws_url = Some(interpolate_env(&c.name, ws_url)?);
+            }
+            let url = if c.rpc_url.contains("${") {
+                interpolate_env(&c.name, &c.rpc_url)?
+            } else {
+                match std::env::var(&c.rpc_url) {
+                    Ok(v) => {
+                        c.ws_url = Some(v.clone());
+                        v
+                    }
+                    // The chain is not used without its environment variable
+                    Err(_) if is_variable_name(&c.rpc_url) => {
+                        continue;
+                    }
+                    // A literal url
+                    Err(_) => c.rpc_url.clone(),
+                }
+            };
+            validate_ws_url(&c.name, &url)?;
+            c.rpc_url = url;
+        }
+
+        Ok(config)
+    }
+}
+
+impl Connection {
+    /// Url for the JSON RPC calls over HTTP, i.e., `ws://` becomes `http://` and `wss://` becomes `https://`
+    pub fn http_url(&self) -> String {
+        to_http_url(&self.rpc_url)
+    }
+
+    /// Urls of all endpoints in the order they are tried. With `round_robin` every call starts at the next endpoint.
+    pub fn http_urls(&self) -> Vec<String> {
+        let mut urls = std::iter
+            ::once(&self.rpc_url)
+            .chain(self.fallback_urls.iter())
+            .map(|url| to_http_url(url))
+            .collect::<Vec<String>>();
+        let count = urls.len();
+        if self.round_robin && count > 1 {
+            let mut next = ROUND_ROBIN.lock().unwrap();
+            let position = next.get_or_insert_with(HashMap::new).entry(self.name.clone()).or_default();
+            urls.rotate_left(*position % count);
+            *position = (*position + 1) % count;
+        }
+        urls
+    }
+
+    /// Posts the JSON RPC request to the endpoints of the chain until one answers.
+    /// An endpoint that can't be reached or answers with a 5xx status is skipped.
+    pub fn post(&self, body: &serde_json::Value) -> Result<reqwest::blocking::Response, String> {
+        let client = crate::utils::get_http_client();
+        let mut errors = vec![];
+        for url in self.http_urls() {
+            match client.post(&url).json(body).send() {
+                Ok(res) if res.status().is_server_error() => {
+                    log_error!("Error: {} answered with {}, trying the next endpoint", self.name, res.status());
+                    errors.push(format!("{}", res.status()));
+                }
+                Ok(res) => {
+                    return Ok(res);
                 }
                 Err(e) => {
-                    // println!("{}: {}", env_var, e);
-                    continue;
+                    log_error!("Error: failed to reach an endpoint of {}, trying the next endpoint: {}", self.name, e);
+                    errors.push(e.to_string());
                 }
             }
         }
+        Err(format!("all endpoints of {} failed: {}", self.name, errors.join(", ")))
+    }
 
-        config
+    /// Checks that the websocket of the chain opens and that an endpoint answers a trivial RPC request,
+    /// `getHealth` for Solana and `eth_chainId` for all other chains
+    pub fn check(&self, timeout: Duration) -> Result<(), String> {
+        check_websocket(&self.rpc_url, timeout).map_err(|e| format!("websocket: {}", e))?;
+        let method = match self.name.as_str() {
+            "solana" => "getHealth",
+            _ => "eth_chainId",
+        };
+        let request = serde_json::json!({ "jsonrpc": "2.0", "method": method, "params": [], "id": 1 });
+        let response: serde_json::Value = self
+            .post(&request)
+            .and_then(|res| res.json().map_err(|e| e.to_string()))
+            .map_err(|e| format!("rpc: {}", e))?;
+        match (response.get("result"), response.get("error")) {
+            (_, Some(error)) => Err(format!("rpc: {} returned {}", method, error)),
+            (Some(_), None) => Ok(()),
+            (None, None) => Err(format!("rpc: {} returned no result", method)),
+        }
     }
 }
 
+/// Opens a websocket to the url and closes it again. Fails if it is not open within the timeout.
+fn check_websocket(url: &str, timeout: Duration) -> Result<(), String> {
+    struct Probe {
+        out: Sender,
+        opened: mpsc::Sender<Result<(), String>>,
+    }
+    impl ws::Handler for Probe {
+        fn on_open(&mut self, _: ws::Handshake) -> ws::Result<()> {
+            let _ = self.opened.send(Ok(()));
+            self.out.close(CloseCode::Normal)
+        }
+
+        fn on_error(&mut self, err: ws::Error) {
+            let _ = self.opened.send(Err(err.to_string()));
+            let _ = self.out.shutdown();
+        }
+    }
+
+    let (opened, result) = mpsc::channel();
+    let url = url.to_string();
+    std::thread::spawn(move || {
+        if let Err(e) = ws::connect(url, |out| Probe { out, opened: opened.clone() }) {
+            let _ = opened.send(Err(e.to_string()));
+        }
+    });
+    result.recv_timeout(timeout).unwrap_or(Err(format!("not open after {:?}", timeout)))
+}
+
+/// Connections of the tests, see `ConnectionConfig::register`
+#[cfg(test)]
+static REGISTERED_CONNECTIONS: Mutex<Vec<Connection>> = Mutex::new(Vec::new());
+
+/// Position of the endpoint the next call of each chain starts with
+static ROUND_ROBIN: Mutex<Option<HashMap<String, usize>>> = Mutex::new(None);
+
+fn to_http_url(url: &str) -> String {
+    if let Some(rest) = url.strip_prefix("wss://") {
+        format!("https://{}", rest)
+    } else if let Some(rest) = url.strip_prefix("ws://") {
+        format!("http://{}", rest)
+    } else {
+        url.to_string()
+    }
+}
+
+/// Resolves a fallback url like the `rpc_url`, i.e., from an environment variable or its `${VAR}` placeholders
+fn resolve_url(chain: &str, url: &str) -> Result<String, ConnectionError> {
+    let url = if url.contains("${") {
+        interpolate_env(chain, url)?
+    } else {
+        std::env::var(url).unwrap_or(url.to_string())
+    };
+    match url.split_once("://") {
+        Some(("http" | "https", _)) => Ok(url),
+        _ => validate_ws_url(chain, &url).map(|_| url),
+    }
+}
+
+/// Replaces every `${VAR}` in the url with the value of the environment variable
+fn interpolate_env(chain: &str, url: &str) -> Result<String, ConnectionError> {
+    let mut resolved = String::new();
+    let mut rest = url;
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..].find('}').ok_or(ConnectionError::InvalidUrl {
+            chain: chain.to_string(),
+            url: url.to_string(),
+        })?;
+        let var = &rest[start + 2..start + end];
+        let value = std::env::var(var).map_err(|_| ConnectionError::UnsetVariable {
+            chain: chain.to_string(),
+            var: var.to_string(),
+        })?;
+        resolved.push_str(&rest[..start]);
+        resolved.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    resolved.push_str(rest);
+    Ok(resolved)
+}
+
+/// Check if the url is the name of an environment variable, e.g. `ETHEREUM_API`
+fn is_variable_name(url: &str) -> bool {
+    url.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') &&
+        url.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Check that the url has the `ws` or `wss` scheme and a host
+fn validate_ws_url(chain: &str, url: &str) -> Result<(), ConnectionError> {
+    match url.split_once("://") {
+        Some((scheme, rest)) if !rest.is_empty() && !rest.starts_with('/') => {
+            match scheme {
+                "ws" | "wss" => Ok(()),
+                _ =>
+                    Err(ConnectionError::InvalidScheme {
+                        chain: chain.to_string(),
+                        url: url.to_string(),
+                    }),
+            }
+        }
+        _ =>
+            Err(ConnectionError::InvalidUrl {
+                chain: chain.to_string(),
+                url: url.to_string(),
+            }),
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ConnectionError {
+    #[error("the connection file {0:?} can not be read")]
+    FileNotFound(String),
+    #[error("the connection file is invalid: {0}")]
+    InvalidFormat(String),
+    #[error("the rpc_url {url:?} of chain {chain:?} must use the ws:// or wss:// scheme")]
+    InvalidScheme {
+        chain: String,
+        url: String,
+    },
+    #[error("the rpc_url {url:?} of chain {chain:?} is not a valid url")]
+    InvalidUrl {
+        chain: String,
+        url: String,
+    },
+    #[error("the environment variable {var:?} of chain {chain:?} is not set")]
+    UnsetVariable {
+        chain: String,
+        var: String,
+    },
+}
+
 #[derive(Debug, Clone)]
 pub struct ConnectionList {
     pub connections: Vec<(String, Sender)>,
@@ -81,6 +327,34 @@ impl ConnectionList {
         self.connections.retain(|(n, _)| n != name);
     }
 
+    /// Cancels the subscriptions of the chain with their stored ids, e.g. eth_unsubscribe or logsUnsubscribe,
+    /// then closes and removes the connection. Without a confirmed subscription the connection is only closed
+    pub fn disconnect(&mut self, name: &str) {
+        if let Some(sender) = self.get(name) {
+            for request in forget_subscription(name) {
+                match sender.send(request.to_string()) {
+                    Ok(_) => log_info!("Unsubscribed from {}", name),
+                    Err(e) => log_error!("Error: failed to unsubscribe from {}: {}", name, e),
+                }
+            }
+            if let Err(e) = sender.close(CloseCode::Normal) {
+                log_error!("Error: failed to close the connection to {}: {}", name, e);
+            }
+        }
+        self.remove(name);
+    }
+
+    /// Disconnects all chains, e.g. on shutdown
+    pub fn disconnect_all(&mut self) {
+        let names = self.connections
+            .iter()
+            .map(|(n, _)| n.clone())
+            .collect::<Vec<String>>();
+        for name in names {
+            self.disconnect(&name);
+        }
+    }
+
     pub fn get(&self, name: &str) -> Option<&Sender> {
         let out = self
             .connections
@@ -108,3 +382,250 @@ pub fn get_established_connections() -> &'static mut ConnectionList {
         MAYBE.assume_init_mut()
     }
 }
+
+/// Disconnects all chains on SIGINT or SIGTERM before the process exits, so the nodes drop the subscriptions
+#[cfg(unix)]
+pub fn setup_shutdown_signal() -> std::thread::JoinHandle<()> {
+    use tokio::signal::unix::{ signal, SignalKind };
+
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let mut interrupt = signal(SignalKind::interrupt()).unwrap();
+            let mut terminate = signal(SignalKind::terminate()).unwrap();
+            tokio::select! {
+                _ = interrupt.recv() => {}
+                _ = terminate.recv() => {}
+            }
+        });
+        get_established_connections().disconnect_all();
+        // Give the websocket threads time to send the unsubscribe requests
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        std::process::exit(0);
+    })
+}
+
+#[test]
+fn test_validate_ws_url() {
+    assert_eq!(validate_ws_url("ethereum", "wss://mainnet.infura.io/ws/v3/key"), Ok(()));
+    assert_eq!(validate_ws_url("ethereum", "ws://127.0.0.1:8545"), Ok(()));
+
+    assert_eq!(
+        validate_ws_url("ethereum", "https://mainnet.infura.io/v3/key"),
+        Err(ConnectionError::InvalidScheme {
+            chain: "ethereum".to_string(),
+            url: "https://mainnet.infura.io/v3/key".to_string(),
+        })
+    );
+    assert!(validate_ws_url("bsc", "http://localhost:8545").is_err());
+
+    let err = validate_ws_url("solana", "YOUR_API_URL").unwrap_err();
+    assert_eq!(
+        err,
+        ConnectionError::InvalidUrl { chain: "solana".to_string(), url: "YOUR_API_URL".to_string() }
+    );
+    assert_eq!(err.to_string(), "the rpc_url \"YOUR_API_URL\" of chain \"solana\" is not a valid url");
+    assert!(validate_ws_url("solana", "wss://").is_err());
+
+    let connection = Connection {
+        name: "ethereum".to_string(),
+        rpc_url: "wss://eth.example.org/v2/key".to_string(),
+        ws_url: None,
+        ..Connection::default()
+    };
+    assert_eq!(connection.http_url(), "https://eth.example.org/v2/key");
+}
+
+#[test]
+fn test_literal_rpc_url() {
+    let dir = crate::utils::TempDir::new("literal_connections");
+    let path = dir.join("literal_connections.json");
+    let load = |rpc_url: &str| {
+        std::fs::write(
+            &path,
+            format!(r#"{{"connections": [{{"name": "bsc", "rpc_url": "{}"}}]}}"#, rpc_url)
+        ).unwrap();
+        ConnectionConfig::from_file(path.to_str().unwrap())
+    };
+
+    assert_eq!(load("wss://bsc.example.org/ws").unwrap().connections[0].rpc_url, "wss://bsc.example.org/ws");
+    assert_eq!(
+        load("http://localhost:8545"),
+        Err(ConnectionError::InvalidScheme { chain: "bsc".to_string(), url: "http://localhost:8545".to_string() })
+    );
+    assert_eq!(
+        load("https://bsc.example.org"),
+        Err(ConnectionError::InvalidScheme { chain: "bsc".to_string(), url: "https://bsc.example.org".to_string() })
+    );
+    assert_eq!(
+        load("bsc.example.org/ws"),
+        Err(ConnectionError::InvalidUrl { chain: "bsc".to_string(), url: "bsc.example.org/ws".to_string() })
+    );
+    // The name of an unset environment variable
+    assert_eq!(load("BRIGADE_UNSET_API").unwrap().connections[0].rpc_url, "BRIGADE_UNSET_API");
+
+}
+
+#[test]
+fn test_env_interpolation() {
+    let dir = crate::utils::TempDir::new("connections");
+    let path = dir.join("connections.json");
+    std::env::set_var("INFURA_KEY", "secret");
+    std::fs::write(
+        &path,
+        r#"{"connections": [{"name": "ethereum", "rpc_url": "wss://mainnet.infura.io/ws/v3/${INFURA_KEY}"}]}"#
+    ).unwrap();
+    let config = ConnectionConfig::from_file(path.to_str().unwrap()).unwrap();
+    assert_eq!(config.connections[0].rpc_url, "wss://mainnet.infura.io/ws/v3/secret");
+    assert_eq!(config.connections[0].http_url(), "https://mainnet.infura.io/ws/v3/secret");
+
+    std::fs::write(
+        &path,
+        r#"{"connections": [{"name": "ethereum", "rpc_url": "wss://mainnet.infura.io/ws/v3/${BRIGADE_UNSET_KEY}"}]}"#
+    ).unwrap();
+    assert_eq!(
+        ConnectionConfig::from_file(path.to_str().unwrap()),
+        Err(ConnectionError::UnsetVariable { chain: "ethereum".to_string(), var: "BRIGADE_UNSET_KEY".to_string() })
+    );
+
+}
+
+#[test]
+fn test_endpoint_failover() {
+    use std::net::TcpListener;
+
+    // Mocked node that answers every request with the status and body
+    let mock = |status: &'static str, body: &'static str| {
+        let (addr, _) = crate::utils::mock_http_server(None, move |_| (status, body.to_string()));
+        format!("ws://{}", addr)
+    };
+    let failing = mock("503 Service Unavailable", "");
+    let working = mock("200 OK", r#"{"jsonrpc":"2.0","id":"1","result":"0x2"}"#);
+    let request = serde_json::json!({"jsonrpc": "2.0", "method": "eth_blockNumber", "params": [], "id": "1"});
+
+    // The first endpoint fails, the result comes from the second one
+    let connection = Connection {
+        name: "failover_chain".to_string(),
+        rpc_url: failing.clone(),
+        fallback_urls: vec![working.clone()],
+        ..Connection::default()
+    };
+    let response: serde_json::Value = connection.post(&request).unwrap().json().unwrap();
+    assert_eq!(response["result"], "0x2");
+
+    // An unreachable endpoint is skipped as well
+    let unreachable = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("ws://{}", listener.local_addr().unwrap())
+    };
+    let connection = Connection { rpc_url: unreachable.clone(), ..connection };
+    let response: serde_json::Value = connection.post(&request).unwrap().json().unwrap();
+    assert_eq!(response["result"], "0x2");
+
+    let connection = Connection { fallback_urls: vec![failing.clone()], ..connection };
+    assert!(connection.post(&request).is_err());
+
+    // Round robin starts every call at the next endpoint
+    let connection = Connection {
+        name: "round_robin_chain".to_string(),
+        rpc_url: "wss://a.example.org".to_string(),
+        fallback_urls: vec!["https://b.example.org".to_string()],
+        round_robin: true,
+        ..Connection::default()
+    };
+    assert_eq!(connection.http_urls(), vec!["https://a.example.org", "https://b.example.org"]);
+    assert_eq!(connection.http_urls(), vec!["https://b.example.org", "https://a.example.org"]);
+    assert_eq!(connection.http_urls(), vec!["https://a.example.org", "https://b.example.org"]);
+    assert_eq!(
+        Connection { round_robin: false, ..connection }.http_urls(),
+        vec!["https://a.example.org", "https://b.example.org"]
+    );
+}
+
+#[test]
+fn test_connection_check() {
+    // Node that accepts websockets and answers plain HTTP requests with the chain id
+    struct Node;
+    impl ws::Handler for Node {
+        fn on_request(&mut self, req: &ws::Request) -> ws::Result<ws::Response> {
+            if req.header("upgrade").is_some() {
+                return ws::Response::from_request(req);
+            }
+            let body = r#"{"jsonrpc":"2.0","id":1,"result":"0x1"}"#;
+            let mut res = ws::Response::new(200, "OK", body.as_bytes().to_vec());
+            res.headers_mut().push(("Content-Type".to_string(), b"application/json".to_vec()));
+            res.headers_mut().push(("Content-Length".to_string(), body.len().to_string().into_bytes()));
+            Ok(res)
+        }
+    }
+    let node = ws::Builder::new().build(|_| Node).unwrap();
+    let node = node.bind("127.0.0.1:0").unwrap();
+    let addr = node.local_addr().unwrap();
+    std::thread::spawn(move || node.run().unwrap());
+
+    let down = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("ws://{}", listener.local_addr().unwrap())
+    };
+    let up = Connection { name: "doctor_up".to_string(), rpc_url: format!("ws://{}", addr), ..Connection::default() };
+    let down = Connection { name: "doctor_down".to_string(), rpc_url: down, ..Connection::default() };
+
+    assert_eq!(up.check(Duration::from_secs(5)), Ok(()));
+    let err = down.check(Duration::from_secs(5)).unwrap_err();
+    assert!(err.starts_with("websocket:"), "{}", err);
+}
+
+#[test]
+fn test_unsubscribe_on_disconnect() {
+    use std::sync::mpsc;
+    use serde_json::{ json, Value };
+
+    use crate::sockets::subscription::{ unsubscribe_requests, Subscription };
+
+    // Node that records the messages it receives
+    let (messages_tx, messages_rx) = mpsc::channel::<String>();
+    let node = ws::Builder::new().build(move |_| {
+        let messages_tx = messages_tx.clone();
+        move |msg: ws::Message| {
+            messages_tx.send(msg.to_string()).unwrap();
+            Ok(())
+        }
+    }).unwrap();
+    let node = node.bind("127.0.0.1:0").unwrap();
+    let addr = node.local_addr().unwrap();
+    std::thread::spawn(move || node.run().unwrap());
+
+    // Client that hands out its sender once the connection is open
+    struct Client {
+        out: Sender,
+        opened: mpsc::Sender<Sender>,
+    }
+    impl ws::Handler for Client {
+        fn on_open(&mut self, _: ws::Handshake) -> ws::Result<()> {
+            self.opened.send(self.out.clone()).unwrap();
+            Ok(())
+        }
+    }
+
+    let (sender_tx, sender_rx) = mpsc::channel();
+    let client = std::thread::spawn(move || {
+        ws::connect(format!("ws://{}", addr), move |out| Client { out, opened: sender_tx.clone() }).unwrap();
+    });
+
+    let mut connections = ConnectionList::new();
+    connections.insert("unsubscribe_chain".to_string(), sender_rx.recv().unwrap());
+    let confirmation = json!({ "jsonrpc": "2.0", "id": 1, "result": "0x9ce59a13059e417087c02d3236a0b1cc" });
+    assert!(Subscription::new("unsubscribe_chain", "eth_subscribe").confirm(&confirmation).is_some());
+
+    connections.disconnect("unsubscribe_chain");
+    assert_eq!(connections.len(), 0);
+    assert!(unsubscribe_requests("unsubscribe_chain").is_empty());
+
+    let unsubscribe: Value = serde_json::from_str(
+        &messages_rx.recv_timeout(std::time::Duration::from_secs(5)).unwrap()
+    ).unwrap();
+    assert_eq!(unsubscribe["method"], "eth_unsubscribe");
+    assert_eq!(unsubscribe["params"], json!(["0x9ce59a13059e417087c02d3236a0b1cc"]));
+    // The connection is closed after the unsubscribe request
+    client.join().unwrap();
+}
@@ -1,5 +1,9 @@
 use serde::Deserialize;
 use serde::Serialize;
+use serde_json::{ json, Value };
+use thiserror::Error;
+
+use crate::utils::{ canonical_event_signature, get_ethereum_topic_ids };
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -16,3 +20,205 @@ pub struct EthereumSubscriptionParams {
     pub address: String,
     pub topics: Vec<String>,
 }
+
+/// Typed `logs` filter of `eth_subscribe`, an alternative to the raw `filter` of a chain config.
+///
+/// Example:
+/// ```json
+/// "log_filter": {
+///     "addresses": ["0x63A993502e74828ddba5710327AFC6dc78d661b2"],
+///     "topics": [["Transfer(address,address,uint256)"], [], ["0x000000000000000000000000a58a9d3a5e240b09da3bc0bfc011af3d20d31763"]]
+/// }
+/// ```
+/// Every entry of `topics` is one position of the log. An empty position matches any topic and a position
+/// with several topics matches any of them. Topics are either 32 byte hashes or event signatures.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogFilter {
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    #[serde(default)]
+    pub topics: Vec<Vec<String>>,
+}
+
+impl LogFilter {
+    /// Checks the addresses and topics, so a broken filter is rejected when the config is loaded
+    pub fn validate(&self) -> Result<(), LogFilterError> {
+        for address in self.addresses.iter() {
+            if !is_hex_of_length(address, 40) {
+                return Err(LogFilterError::InvalidAddress(address.clone()));
+            }
+        }
+        if self.topics.len() > 4 {
+            return Err(LogFilterError::TooManyTopics(self.topics.len()));
+        }
+        for topic in self.topics.iter().flatten() {
+            if !is_hex_of_length(topic, 64) && !topic.contains('(') {
+                return Err(LogFilterError::InvalidTopic(topic.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Params of the `eth_subscribe` request, i.e., `["logs", {"address": ..., "topics": [...]}]`.
+    /// Addresses and topics are lowercased and event signatures are hashed.
+    pub fn to_json(&self) -> Value {
+        let mut filter = serde_json::Map::new();
+        let addresses = self.addresses
+            .iter()
+            .map(|a| a.to_lowercase())
+            .collect::<Vec<String>>();
+        match addresses.len() {
+            0 => {}
+            1 => {
+                filter.insert("address".to_string(), json!(addresses[0]));
+            }
+            _ => {
+                filter.insert("address".to_string(), json!(addresses));
+            }
+        }
+        if !self.topics.is_empty() {
+            let topics = self.topics
+                .iter()
+                .map(|position| topic_position(position))
+                .collect::<Vec<Value>>();
+            filter.insert("topics".to_string(), json!(topics));
+        }
+        json!(["logs", filter])
+    }
+}
+
+/// Checks the `topics` of a raw `logs` filter, i.e., `["logs", {"topics": [...]}]`. Every position is either null,
+/// a 32 byte hex topic or an OR-set of those.
+pub fn validate_raw_topics(filter: &Value) -> Result<(), LogFilterError> {
+    if filter.get(0).and_then(|m| m.as_str()) != Some("logs") {
+        return Ok(());
+    }
+    let topics = match filter.get(1).and_then(|f| f.get("topics")) {
+        Some(Value::Array(topics)) => topics,
+        Some(other) => {
+            return Err(LogFilterError::InvalidTopicPosition(other.to_string()));
+        }
+        None => {
+            return Ok(());
+        }
+    };
+    if topics.len() > 4 {
+        return Err(LogFilterError::TooManyTopics(topics.len()));
+    }
+    for position in topics {
+        let set = match position {
+            Value::Null => vec![],
+            Value::String(_) => vec![position],
+            Value::Array(set) => set.iter().collect(),
+            _ => {
+                return Err(LogFilterError::InvalidTopicPosition(position.to_string()));
+            }
+        };
+        for topic in set {
+            match topic.as_str() {
+                Some(t) if is_hex_of_length(t, 64) => {}
+                _ => {
+                    return Err(LogFilterError::InvalidTopic(topic.to_string()));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// One position of the `topics` filter: null if it matches any topic, the topic itself or the OR-set of several topics.
+/// Topics are lowercased and event signatures are hashed.
+pub fn topic_position(position: &[String]) -> Value {
+    let position = position
+        .iter()
+        .map(|t| topic_id(t))
+        .collect::<Vec<String>>();
+    match position.len() {
+        0 => Value::Null,
+        1 => json!(position[0]),
+        _ => json!(position),
+    }
+}
+
+/// Topic hash of an event signature or the lowercased hex topic
+pub fn topic_id(topic: &str) -> String {
+    if topic.contains('(') {
+        get_ethereum_topic_ids(&canonical_event_signature(topic))
+    } else {
+        topic.to_lowercase()
+    }
+}
+
+fn is_hex_of_length(s: &str, length: usize) -> bool {
+    s.strip_prefix("0x").is_some_and(|h| h.len() == length && h.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum LogFilterError {
+    #[error("the filter address {0} is not a 20 byte hex address")]
+    InvalidAddress(String),
+    #[error("the filter topic {0} is neither a 32 byte hex topic nor an event signature")]
+    InvalidTopic(String),
+    #[error("a log has at most 4 topics but the filter has {0}")]
+    TooManyTopics(usize),
+    #[error("the filter topic position {0} is neither null, a topic nor an array of topics")]
+    InvalidTopicPosition(String),
+}
+
+#[test]
+fn test_log_filter_params() {
+    let filter: LogFilter = serde_json
+        ::from_str(
+            r#"{
+            "addresses": ["0x63A993502e74828ddba5710327AFC6dc78d661b2"],
+            "topics": [["Transfer(address indexed from, address indexed to, uint value)"], [], ["0xA68CE84C815A939744A011DF05ADCF79B74994AADA52C5810979C254042F553D", "0xa68ce84c815a939744a011df05adcf79b74994aada52c5810979c254042f553e"]]
+        }"#
+        )
+        .unwrap();
+    assert!(filter.validate().is_ok());
+    assert_eq!(
+        filter.to_json(),
+        json!([
+            "logs",
+            {
+                "address": "0x63a993502e74828ddba5710327afc6dc78d661b2",
+                "topics": [
+                    "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef",
+                    null,
+                    [
+                        "0xa68ce84c815a939744a011df05adcf79b74994aada52c5810979c254042f553d",
+                        "0xa68ce84c815a939744a011df05adcf79b74994aada52c5810979c254042f553e"
+                    ]
+                ]
+            }
+        ])
+    );
+
+    let filter = LogFilter {
+        addresses: vec!["0x1".to_string(), "0x63A993502e74828ddba5710327AFC6dc78d661b2".to_string()],
+        topics: vec![],
+    };
+    assert_eq!(filter.validate(), Err(LogFilterError::InvalidAddress("0x1".to_string())));
+    let filter = LogFilter { addresses: vec![], topics: vec![vec!["0xa68c".to_string()]] };
+    assert_eq!(filter.validate(), Err(LogFilterError::InvalidTopic("0xa68c".to_string())));
+    assert_eq!(LogFilter::default().to_json(), json!(["logs", {}]));
+}
+
+#[test]
+fn test_raw_topic_or_set() {
+    let transfer = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+    let approval = "0x8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925";
+    assert!(validate_raw_topics(&json!(["logs", {"topics": [[transfer, approval], null, transfer]}])).is_ok());
+    assert!(validate_raw_topics(&json!(["newHeads"])).is_ok());
+    assert_eq!(
+        validate_raw_topics(&json!(["logs", {"topics": [["Transfer(address,address,uint256)"]]}])),
+        Err(LogFilterError::InvalidTopic("\"Transfer(address,address,uint256)\"".to_string()))
+    );
+    assert_eq!(
+        validate_raw_topics(&json!(["logs", {"topics": [1]}])),
+        Err(LogFilterError::InvalidTopicPosition("1".to_string()))
+    );
+
+    let filter = LogFilter { addresses: vec![], topics: vec![vec!["Transfer(address,address,uint256)".to_string(), approval.to_string()]] };
+    assert_eq!(filter.to_json(), json!(["logs", {"topics": [[transfer, approval]]}]));
+}
@@ -10,10 +10,13 @@ use crate::{
     message_formats::solana_message::Res,
     properties::Properties,
     sockets::{ self, ethereum_socket, socket, solana_socket },
+    sockets::subscription::{ subscription_timeout, Subscription, SUBSCRIPTION_REQUEST_ID, SUBSCRIPTION_TIMEOUT_TOKEN },
 };
+use ethereum_config::{ validate_raw_topics, LogFilter, LogFilterError };
 
+pub mod address_list;
 pub mod connection;
-mod ethereum_config;
+pub mod ethereum_config;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -31,7 +34,15 @@ pub struct ChainConfig {
     pub contract_name: Option<String>,
     #[serde(rename = "contract_address")]
     pub contract_address: String,
+    /// EIP-155 chain id, chains with an id are connected like Ethereum, e.g. L2s
+    #[serde(rename = "chain_id", default, skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<u64>,
+    /// Raw params of the subscription request or an array of them to subscribe to several filters over one connection
+    #[serde(default)]
     pub filter: Value,
+    /// Typed `logs` filter of `eth_subscribe`, used instead of the raw `filter` if set
+    #[serde(rename = "log_filter", default, skip_serializing_if = "Option::is_none")]
+    pub log_filter: Option<LogFilter>,
 }
 
 // /// Configuration for connecting to a Blockchain and getting the events
@@ -63,11 +74,68 @@ impl ChainConfig {
             name,
             contract_name,
             contract_address,
+            chain_id: None,
             subscription_method,
             filter,
+            log_filter: None,
         }
     }
 
+    /// Checks the typed filter of the config or the topics of each raw `logs` filter
+    pub fn validate(&self) -> std::result::Result<(), LogFilterError> {
+        match &self.log_filter {
+            Some(filter) => filter.validate(),
+            None => self.raw_filters().iter().try_for_each(validate_raw_topics),
+        }
+    }
+
+    /// The raw `filter` is either the params of one subscription request, e.g. `["logs", {...}]`,
+    /// or an array of them, e.g. `[["logs", {...}], ["logs", {...}]]`
+    fn raw_filters(&self) -> Vec<Value> {
+        match &self.filter {
+            Value::Array(filters) if !filters.is_empty() && filters.iter().all(|f| f.is_array()) => filters.clone(),
+            filter => vec![filter.clone()],
+        }
+    }
+
+    /// Params of the subscription requests, built from the typed filter or one per raw `filter`
+    pub fn subscription_params(&self) -> Vec<Value> {
+        match &self.log_filter {
+            Some(filter) => vec![filter.to_json()],
+            None => self.raw_filters(),
+        }
+    }
+
+    /// One subscription request per filter, their ids count up from `SUBSCRIPTION_REQUEST_ID`
+    pub fn subscription_requests(&self) -> Vec<Value> {
+        self.subscription_params()
+            .into_iter()
+            .zip(SUBSCRIPTION_REQUEST_ID..)
+            .map(|(params, id)| {
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": self.get_subscription_method(),
+                    "params": params,
+                    "id": id
+                })
+            })
+            .collect()
+    }
+
+    /// Sends all subscription requests over the connection
+    pub fn subscribe(&self, out: &ws::Sender) -> Result<()> {
+        for request in self.subscription_requests() {
+            out.send(request.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Tracks the confirmation of every subscription request
+    fn subscription(&self) -> Subscription {
+        Subscription::new(&self.name, &self.get_subscription_method())
+            .with_requests(self.subscription_params().len() as u64)
+    }
+
     pub fn get_name(&self) -> String {
         self.name.clone()
     }
@@ -84,6 +152,7 @@ impl ChainConfig {
         match self.name.to_lowercase().as_str() {
             "solana" => self.connect_solana(event_channel),
             "ethereum" => self.connect_ethereum(event_channel),
+            _ if self.chain_id.is_some() => self.connect_ethereum(event_channel),
             _ => self.connect_generic(event_channel),
         }
     }
@@ -91,24 +160,23 @@ impl ChainConfig {
     // TODO: Check for replay if yes then connect to replay handlers
 
     fn connect_generic(&self, event_channel: Sender<Properties>) -> Result<()> {
-        let request =
-            json!({
-            "jsonrpc": "2.0",
-            "method": self.get_subscription_method(),
-            "params": self.filter,
-            "id": 1
-        });
-
         // Check if Chain exists already
         if let Some(con) = connection::get_established_connections().get(&self.name) {
             println!("Chain {} is already connected", self.name);
 
-            con.send(request.to_string()).unwrap();
+            self.subscribe(con).unwrap();
             return Ok(());
         } else {
             //load config
-            let connection_config: ConnectionConfig =
-                ConnectionConfig::from_file("config/connections.json");
+            let connection_config: ConnectionConfig = match
+                ConnectionConfig::load()
+            {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return Err(ws::Error::new(ws::ErrorKind::Internal, e.to_string()));
+                }
+            };
             let target_chain = connection_config.connections.iter().find(|x| x.name == self.name);
             if let Some(chain) = target_chain {
                 ws::connect(chain.rpc_url.clone(), |out| {
@@ -116,7 +184,7 @@ impl ChainConfig {
                         ::get_established_connections()
                         .insert(self.name.clone(), out);
                     match sender {
-                        Some(o) => o.send(request.to_string()).unwrap(),
+                        Some(o) => self.subscribe(o).unwrap(),
                         None => println!("No connection found for {}", self.name),
                     }
                     // Choose correct websocket implementation
@@ -136,23 +204,23 @@ impl ChainConfig {
     }
 
     fn connect_solana(&self, event_channel: Sender<Properties>) -> Result<()> {
-        let request =
-            json!({
-            "jsonrpc": "2.0",
-            "method": self.get_subscription_method(),
-            "params": self.filter,
-            "id": 1
-        });
         // Check if Chain exists already
         if let Some(con) = connection::get_established_connections().get(&self.name) {
             println!("Chain {} is already connected", self.name);
 
-            con.send(request.to_string()).unwrap();
+            self.subscribe(con).unwrap();
             return Ok(());
         } else {
             //load config
-            let connection_config: ConnectionConfig =
-                ConnectionConfig::from_file("config/connections.json");
+            let connection_config: ConnectionConfig = match
+                ConnectionConfig::load()
+            {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return Err(ws::Error::new(ws::ErrorKind::Internal, e.to_string()));
+                }
+            };
             let target_chain = connection_config.connections.iter().find(|x| x.name == self.name);
             if let Some(chain) = target_chain {
                 ws::connect(chain.rpc_url.clone(), |out| {
@@ -160,15 +228,21 @@ impl ChainConfig {
                         ::get_established_connections()
                         .insert(self.name.clone(), out);
                     match sender {
-                        Some(o) => o.send(request.to_string()).unwrap(),
+                        Some(o) => {
+                            self.subscribe(o).unwrap();
+                            // The handler checks that the subscription was confirmed in time
+                            o.timeout(subscription_timeout().as_millis() as u64, SUBSCRIPTION_TIMEOUT_TOKEN).unwrap();
+                        }
                         None => println!("No connection found for {}", self.name),
                     }
 
-                    solana_socket::SolanaSocketHandler::new(
+                    let mut handler = solana_socket::SolanaSocketHandler::new(
                         vec![],
                         event_channel.to_owned(),
-                        chain.rpc_url.clone()
-                    )
+                        chain.http_url()
+                    );
+                    handler.subscription = self.subscription();
+                    handler
                 }).unwrap();
             } else {
                 println!("No connection found for {}", self.name);
@@ -178,14 +252,6 @@ impl ChainConfig {
     }
 
     fn connect_ethereum(&self, event_channel: Sender<Properties>) -> Result<()> {
-        let request =
-            json!({
-            "jsonrpc": "2.0",
-            "method": self.get_subscription_method(),
-            "params": self.filter,
-            "id": 1
-        });
-
         // // Check if Chain exists already
         // if let Some(con) = connection::get_established_connections().get(&self.name) {
         //     println!("Chain {} is already connected", self.name);
@@ -198,8 +264,15 @@ impl ChainConfig {
         // } else {
             println!("Making new connection to {}", self.name);
             //load config
-            let connection_config: ConnectionConfig =
-                ConnectionConfig::from_file("config/connections.json");
+            let connection_config: ConnectionConfig = match
+                ConnectionConfig::load()
+            {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return Err(ws::Error::new(ws::ErrorKind::Internal, e.to_string()));
+                }
+            };
             let target_chain = connection_config.connections.iter().find(|x| x.name == self.name);
             if let Some(chain) = target_chain {
                 ws::connect(chain.rpc_url.clone(), |out| {
@@ -208,20 +281,26 @@ impl ChainConfig {
                         .insert(self.name.clone(), out);
                     match sender {
                         Some(o) => {
-                            match o.send(request.to_string()) {
+                            match self.subscribe(o) {
                                 Ok(_) => println!("Request sent"),
                                 Err(e) => eprintln!("Error: {}", e),
                             }
+                            // The handler checks that the subscription was confirmed in time
+                            if let Err(e) = o.timeout(subscription_timeout().as_millis() as u64, SUBSCRIPTION_TIMEOUT_TOKEN) {
+                                eprintln!("Error: {}", e);
+                            }
                         }
                         None => println!("No connection found for {}", self.name),
                     }
                     // Choose correct websocket implementation
                     // Process incoming WebSocket messages handled by the WebSocketClientHandler
-                    ethereum_socket::EthereumSocketHandler::new(
+                    let mut handler = ethereum_socket::EthereumSocketHandler::new(
                         vec![],
                         event_channel.to_owned(),
-                        chain.rpc_url.clone()
-                    )
+                        chain.http_url()
+                    ).with_chain(self.name.clone(), self.chain_id);
+                    handler.subscription = self.subscription();
+                    handler
                 }).unwrap();
             } else {
                 println!("No connection found for {}", self.name);
@@ -230,3 +309,101 @@ impl ChainConfig {
         Ok(())
     }
 }
+
+#[test]
+fn test_chain_config_filter() {
+    let raw: ChainConfig = serde_json
+        ::from_str(
+            r#"{
+            "subscription_method": "eth_subscribe",
+            "name": "ethereum",
+            "contract_address": "0x63A993502e74828ddba5710327AFC6dc78d661b2",
+            "filter": ["logs", {"address": "0x63A993502e74828ddba5710327AFC6dc78d661b2"}]
+        }"#
+        )
+        .unwrap();
+    assert_eq!(raw.subscription_params(), vec![json!(["logs", {"address": "0x63A993502e74828ddba5710327AFC6dc78d661b2"}])]);
+
+    let typed: ChainConfig = serde_json
+        ::from_str(
+            r#"{
+            "subscription_method": "eth_subscribe",
+            "name": "ethereum",
+            "contract_address": "0x63A993502e74828ddba5710327AFC6dc78d661b2",
+            "log_filter": {"addresses": ["0x63A993502e74828ddba5710327AFC6dc78d661b2"]}
+        }"#
+        )
+        .unwrap();
+    assert!(typed.validate().is_ok());
+    assert_eq!(typed.subscription_params(), vec![json!(["logs", {"address": "0x63a993502e74828ddba5710327afc6dc78d661b2"}])]);
+
+    let invalid = ChainConfig {
+        log_filter: Some(LogFilter { addresses: vec!["0xa58A".to_string()], topics: vec![] }),
+        ..typed
+    };
+    assert!(invalid.validate().is_err());
+}
+
+#[test]
+fn test_multiple_filters() {
+    use std::sync::mpsc;
+
+    let config: ChainConfig = serde_json
+        ::from_str(
+            r#"{
+            "subscription_method": "eth_subscribe",
+            "name": "multi_filter_ethereum",
+            "contract_address": "",
+            "filter": [
+                ["logs", {"address": "0x63A993502e74828ddba5710327AFC6dc78d661b2"}],
+                ["logs", {"address": "0x3ee18B2214AFF97000D974cf647E7C347E8fa585"}]
+            ]
+        }"#
+        )
+        .unwrap();
+    assert!(config.validate().is_ok());
+    assert_eq!(config.subscription_params().len(), 2);
+
+    // Node that records the messages it receives
+    let (messages_tx, messages_rx) = mpsc::channel::<String>();
+    let node = ws::Builder::new().build(move |_| {
+        let messages_tx = messages_tx.clone();
+        move |msg: ws::Message| {
+            messages_tx.send(msg.to_string()).unwrap();
+            Ok(())
+        }
+    }).unwrap();
+    let node = node.bind("127.0.0.1:0").unwrap();
+    let addr = node.local_addr().unwrap();
+    std::thread::spawn(move || node.run().unwrap());
+
+    // Client that subscribes once the connection is open
+    struct Client {
+        out: ws::Sender,
+        config: ChainConfig,
+    }
+    impl ws::Handler for Client {
+        fn on_open(&mut self, _: ws::Handshake) -> Result<()> {
+            self.config.subscribe(&self.out)
+        }
+    }
+    let client_config = config.clone();
+    std::thread::spawn(move || {
+        ws::connect(format!("ws://{}", addr), |out| Client { out, config: client_config.clone() }).unwrap();
+    });
+
+    let requests = (0..2)
+        .map(|_| serde_json::from_str::<Value>(&messages_rx.recv_timeout(std::time::Duration::from_secs(5)).unwrap()).unwrap())
+        .collect::<Vec<Value>>();
+    assert_eq!(requests.iter().map(|r| r["id"].as_u64().unwrap()).collect::<Vec<u64>>(), vec![1, 2]);
+    assert_eq!(requests[0]["params"][1]["address"], "0x63A993502e74828ddba5710327AFC6dc78d661b2");
+    assert_eq!(requests[1]["params"][1]["address"], "0x3ee18B2214AFF97000D974cf647E7C347E8fa585");
+    assert!(requests.iter().all(|r| r["method"] == "eth_subscribe"));
+
+    // The handler waits for the confirmation of both subscriptions
+    let mut subscription = config.subscription();
+    assert!(subscription.confirm(&json!({ "jsonrpc": "2.0", "id": 1, "result": "0x1" })).is_some());
+    assert!(subscription.check_timeout().is_err());
+    assert!(subscription.confirm(&json!({ "jsonrpc": "2.0", "id": 2, "result": "0x2" })).is_some());
+    assert!(subscription.check_timeout().is_ok());
+}
@@ -5,7 +5,15 @@ use owo_colors::OwoColorize;
 use properties::custom_functions::execute_custom_function;
 use properties::Properties;
 use serde_json::Value;
-use sockets::event_socket::{ Allowance, BlockingQueue, Event };
+use sockets::event_socket::{
+    Allowance,
+    BlockingQueue,
+    Event,
+    EventBacklog,
+    EventFormat,
+    QueuePolicy,
+    DEFAULT_QUEUE_CAPACITY,
+};
 use std::cmp::min;
 use std::collections::{ HashMap, HashSet };
 use std::fs::{ File, OpenOptions };
@@ -29,11 +37,29 @@ use clap::Parser;
 use chrono::{ DateTime, Datelike, Local, Timelike };
 
 use crate::configs::BridgeConfig;
-use crate::configs::connection::ConnectionConfig;
+use crate::configs::address_list::{ AddressList, WatchedAddressList };
+use crate::configs::connection::{ setup_shutdown_signal, ConnectionConfig };
 use crate::inference::ModelFeature;
-use crate::properties::talon::TalonFile;
+use crate::properties::lint::lint;
+use crate::properties::store::{ get_property_store, setup_reload_signal, PropertyFile, PropertyFormat, PropertySet };
+use crate::sockets::circuit_breaker::{ get_circuit_breakers, BreakerConfig, CircuitBreakers };
+use crate::sockets::enricher::{ get_enricher, TransactionFields };
+use crate::sockets::health::{ get_health_state, setup_health_endpoint };
 use crate::sockets::replay_ethereum_socket;
-use crate::utils::{ get_startup_time, Evaluation };
+use crate::sockets::subscription::set_subscription_timeout;
+use crate::sockets::webhook::AlertWebhook;
+use crate::utils::{
+    detect_color,
+    get_directories,
+    get_startup_time,
+    set_color_enabled,
+    set_directories,
+    set_log_level,
+    Directories,
+    Evaluation,
+    LogLevel,
+    Paint,
+};
 
 mod configs;
 mod inference;
@@ -48,6 +74,23 @@ static IS_TRAINED: AtomicBool = AtomicBool::new(false);
 static TRAINED_ON: AtomicU64 = AtomicU64::new(500);
 static ALREADY_TRAINED: AtomicU64 = AtomicU64::new(0);
 const FEATURE_VEC_LENGTH: usize = 10;
+/// Maximum number of property files that are evaluated concurrently for an event
+const PROPERTY_WORKERS: usize = 4;
+/// Deadline of the evaluation of a property file in milliseconds, 0 disables it
+static PROPERTY_TIMEOUT_MS: AtomicU64 = AtomicU64::new(0);
+/// Allow the event if a property file exceeds the deadline instead of denying it
+static TIMEOUT_FAIL_OPEN: AtomicBool = AtomicBool::new(false);
+/// Deny events without a matching property or rule instead of reporting them as NoMatch
+static DEFAULT_DENY: AtomicBool = AtomicBool::new(false);
+/// Number of RPC requests a single event may make before a warning is logged, 0 disables the warning
+static RPC_CALL_BUDGET: AtomicU64 = AtomicU64::new(0);
+/// Allow the events of a chain whose circuit breaker is open instead of denying them
+static BREAKER_FAIL_OPEN: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Set when a property file of the current event failed to evaluate
+    static PROPERTY_FAILED: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
 
 /// Arguments to the program
 #[derive(Parser, Debug)]
@@ -83,6 +126,134 @@ struct Args {
     /// Path to replay config
     #[arg(long)]
     replay_config: Option<PathBuf>,
+    /// Resume the replay after the last processed block of the checkpoint file
+    #[arg(long, requires = "replay")]
+    resume: bool,
+    /// Maximal number of replayed events per second that are passed to the event loop. Default: no limit
+    #[arg(long, requires = "replay")]
+    replay_rate: Option<f64>,
+    /// Number of past events sent to newly connected clients of the TCP Port. Default: 100
+    #[arg(long)]
+    event_backlog: Option<usize>,
+    /// Serialization of the events on the TCP Port. Default: compact
+    #[arg(long, value_enum)]
+    event_format: Option<EventFormat>,
+    /// Number of events that are queued for the TCP Port before the queue policy applies. Default: 10000
+    #[arg(long)]
+    queue_capacity: Option<usize>,
+    /// What happens when the event queue is full: block the event loop or drop the oldest event. Default: block
+    #[arg(long, value_enum)]
+    queue_policy: Option<QueuePolicy>,
+    /// POST denied transactions as JSON to this url, e.g. a Slack or PagerDuty webhook
+    #[arg(long)]
+    alert_webhook: Option<String>,
+    /// Serve the connection status of the chains at http://<address>/health, e.g. 127.0.0.1:8081
+    #[arg(long)]
+    health: Option<String>,
+    /// Print the ASTs and variables of every event
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+    /// Only print failures
+    #[arg(short, long)]
+    quiet: bool,
+    /// Allow transactions from or to the addresses in this file without evaluating the properties.
+    /// Default: config/allowlist.json if it exists
+    #[arg(long)]
+    allowlist: Option<PathBuf>,
+    /// Deny transactions from or to the addresses in this file without evaluating the properties.
+    /// The file is reloaded when it changes. Default: config/denylist.json
+    #[arg(long)]
+    denylist: Option<PathBuf>,
+    /// Deadline for the evaluation of each property file in milliseconds. Default: no deadline
+    #[arg(long)]
+    property_timeout: Option<u64>,
+    /// Decision of a property that exceeds the deadline. Default: deny
+    #[arg(long, value_enum)]
+    timeout_policy: Option<TimeoutPolicy>,
+    /// Time a node has to confirm the subscription in milliseconds, otherwise the chain is reported as dead. Default: 10000
+    #[arg(long)]
+    subscription_timeout: Option<u64>,
+    /// Deny events that no property or rule applies to instead of letting them pass
+    #[arg(long)]
+    default_deny: bool,
+    /// Log a warning if a single event makes more RPC requests with call(), including the enrichment. Default: no budget
+    #[arg(long)]
+    rpc_call_budget: Option<u64>,
+    /// Open the circuit breaker of a chain after this many consecutive events failed to be enriched or evaluated.
+    /// Default: no circuit breaker
+    #[arg(long)]
+    breaker_threshold: Option<u32>,
+    /// Window in seconds in which the failures must happen to open the breaker. Default: 60
+    #[arg(long)]
+    breaker_window: Option<u64>,
+    /// Seconds an open breaker waits before the next event of the chain is evaluated again. Default: 30
+    #[arg(long)]
+    breaker_cooldown: Option<u64>,
+    /// Decision for the events of a chain whose breaker is open. Default: deny
+    #[arg(long, value_enum)]
+    breaker_policy: Option<BreakerPolicy>,
+    /// Maximal nesting depth of expressions, deeper expressions fail instead of overflowing the stack. Default: 128
+    #[arg(long)]
+    max_expression_depth: Option<usize>,
+    /// Fail the conversion of "0x" and "" into numbers instead of reading them as zero
+    #[arg(long)]
+    strict_numbers: bool,
+    /// Value of variables that do not exist, e.g. fields an event does not have. Default: NA
+    #[arg(long)]
+    missing_value: Option<String>,
+    /// Further variables that outlive the event like $keystore and $map, e.g. `counter_*,last_seen`.
+    /// A trailing * matches all variables with the prefix
+    #[arg(long, value_delimiter = ',')]
+    persistent: Vec<String>,
+    /// Print without colors. Colors are also disabled by NO_COLOR or if stdout is not a terminal
+    #[arg(long)]
+    no_color: bool,
+    /// Directory with the properties, rules and functions. Default: $BRIGADE_BASE_DIR or the working directory
+    #[arg(long)]
+    base_dir: Option<PathBuf>,
+    /// Directory with the config files. Default: $BRIGADE_CONFIG_DIR or config in the base directory
+    #[arg(long)]
+    config_dir: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Evaluate a single expression without connecting to any chain, e.g. `brigade eval "$value < 1000" --vars vars.json`
+    Eval {
+        expression: String,
+        /// Predefined variables in the format of --predefined-variables
+        #[arg(long)]
+        vars: Option<PathBuf>,
+    },
+    /// Check all property files and Talon rules without running them. Exits with 1 if any is invalid
+    Lint {
+        /// Default: properties
+        #[arg(long)]
+        properties: Option<PathBuf>,
+        /// Default: rules
+        #[arg(long)]
+        rules: Option<PathBuf>,
+    },
+    /// Evaluate a single event read as JSON from stdin and print the decision, e.g. `brigade check < event.json`.
+    /// Exits with 0 if the event is allowed, 1 if it is denied and 2 if it is invalid
+    Check,
+    /// Check that every chain of connections.json is reachable over its websocket and RPC endpoints.
+    /// Exits with 1 if any chain fails
+    Doctor {
+        /// Seconds to wait for a websocket to open. Default: 5
+        #[arg(long, default_value_t = 5)]
+        timeout: u64,
+    },
+    /// Write a minimal example of the config, properties, rules and functions directories.
+    /// Existing files are not overwritten
+    Init,
+    /// Print the topic0 of event signatures, e.g. `brigade topic "Transfer(address,address,uint256)"`
+    Topic {
+        #[arg(required = true)]
+        signatures: Vec<String>,
+    },
 }
 
 fn main() {
@@ -98,6 +269,58 @@ fn main() {
         LOG_TIMESTAMPS.store(true, std::sync::atomic::Ordering::Relaxed);
     }
 
+    if args.verbose {
+        set_log_level(LogLevel::Debug);
+    } else if args.quiet {
+        set_log_level(LogLevel::Error);
+    }
+
+    set_color_enabled(detect_color(args.no_color));
+
+    // All relative paths are resolved against these directories
+    set_directories(
+        Directories::new(
+            args.base_dir.clone().or(std::env::var_os("BRIGADE_BASE_DIR").map(PathBuf::from)),
+            args.config_dir.clone().or(std::env::var_os("BRIGADE_CONFIG_DIR").map(PathBuf::from))
+        )
+    );
+    let directories = get_directories();
+
+    if let Some(timeout) = args.property_timeout {
+        PROPERTY_TIMEOUT_MS.store(timeout, atomic::Ordering::Relaxed);
+    }
+    TIMEOUT_FAIL_OPEN.store(args.timeout_policy == Some(TimeoutPolicy::Allow), atomic::Ordering::Relaxed);
+
+    if let Some(timeout) = args.subscription_timeout {
+        set_subscription_timeout(Duration::from_millis(timeout));
+    }
+
+    DEFAULT_DENY.store(args.default_deny, atomic::Ordering::Relaxed);
+    RPC_CALL_BUDGET.store(args.rpc_call_budget.unwrap_or(0), atomic::Ordering::Relaxed);
+
+    let defaults = BreakerConfig::default();
+    get_circuit_breakers()
+        .lock()
+        .unwrap()
+        .configure(BreakerConfig {
+            threshold: args.breaker_threshold.unwrap_or(defaults.threshold),
+            window: args.breaker_window.map(Duration::from_secs).unwrap_or(defaults.window),
+            cooldown: args.breaker_cooldown.map(Duration::from_secs).unwrap_or(defaults.cooldown),
+        });
+    BREAKER_FAIL_OPEN.store(args.breaker_policy == Some(BreakerPolicy::Allow), atomic::Ordering::Relaxed);
+
+    if let Some(depth) = args.max_expression_depth {
+        set_max_expression_depth(depth);
+    }
+
+    set_strict_numbers(args.strict_numbers);
+
+    if let Some(missing_value) = &args.missing_value {
+        set_missing_variable_value(missing_value);
+    }
+
+    set_persistent_variables(&args.persistent);
+
     // Log starting point
     let current_datetime: DateTime<Local> = Local::now();
     let hour = current_datetime.hour();
@@ -112,71 +335,172 @@ fn main() {
     // Setup persistent Hashmap
     set_var!("map", VarValues::Map(HashMap::new()));
 
+    // Setup named constants, e.g. $ether
+    let constants_file = directories.config_path("constants.json");
+    if let Err(e) = load_constants(Some(constants_file.as_path()).filter(|p| p.exists())) {
+        eprintln!("Error: invalid constants file {}: {}", constants_file.display(), e);
+    }
+
     // Setup predefined variables
     if let Some(predefined_variables) = args.predefined_variables {
-        let var_file = predefined_variables.as_path();
-        let contents = fs::read_to_string(var_file).unwrap();
-        let values: Value = serde_json::from_str(&contents).unwrap();
-        match values.as_array() {
-            Some(content) => {
-                for var in content.iter() {
-                    match build_ast_root(var.as_str().unwrap()) {
-                        Ok(root) => {
-                            root.print("");
-                            match root.evaluate() {
-                                Ok(val) => {
-                                    println!("{}: {}", var, val.get_value());
-                                }
-                                Err(e) => {
-                                    println!("Error when parsing {}", var);
-                                    eprintln!("Error: {}", e);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            println!("Can't create AST from {}", var);
-                            eprintln!("Error: {}", e);
-                        }
+        if let Err(e) = load_predefined_variables(predefined_variables.as_path()) {
+            println!("Resuming without variables");
+            eprintln!("Error: {}", e);
+        }
+        println!("Variables: {:?}", *get_variable_map_instance());
+    }
+
+    if let Some(Command::Init) = &args.command {
+        match init_example(&directories) {
+            Ok(files) => {
+                for (path, created) in files {
+                    match created {
+                        true => println!("Created {}", path.display()),
+                        false => println!("Skipped {}, it already exists", path.display()),
                     }
                 }
             }
-            None => {
-                println!("Resuming without variables");
-                eprintln!("Error: Wrong file format");
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(Command::Lint { properties, rules }) = &args.command {
+        let code = run_lint(
+            &properties.clone().unwrap_or(directories.base_path("properties")),
+            &rules.clone().unwrap_or(directories.base_path("rules"))
+        );
+        std::process::exit(code);
+    }
+
+    if let Some(Command::Check) = args.command {
+        let allowlist = args.allowlist.or(Some(directories.config_path("allowlist.json")).filter(|p| p.exists()));
+        let denylist = args.denylist.or(Some(directories.config_path("denylist.json")).filter(|p| p.exists()));
+        let load = |path: Option<PathBuf>| match path {
+            Some(path) => AddressList::from_file(path.to_str().unwrap()).unwrap_or_else(|e| panic!("{}", e)),
+            None => AddressList::default(),
+        };
+        let mut input = String::new();
+        let decision = io::stdin()
+            .read_to_string(&mut input)
+            .map_err(|e| e.to_string())
+            .and_then(|_| check_event(&input, &load(allowlist), &load(denylist)));
+        match decision {
+            Ok((allowance, checked)) => {
+                let (result, reasons, code) = match allowance {
+                    Allowance::Allow => ("allow", vec![], 0),
+                    Allowance::NoMatch => ("no match", vec![], 0),
+                    Allowance::Deny(reasons) => ("deny", reasons, 1),
+                };
+                println!("{}", serde_json::json!({ "result": result, "reasons": reasons, "checked": checked }));
+                std::process::exit(code);
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    if let Some(Command::Doctor { timeout }) = &args.command {
+        let code = match ConnectionConfig::load() {
+            Ok(config) => run_doctor(&config, Duration::from_secs(*timeout)),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                1
+            }
+        };
+        std::process::exit(code);
+    }
+
+    if let Some(Command::Topic { signatures }) = &args.command {
+        for line in topic_lines(signatures) {
+            println!("{}", line);
+        }
+        return;
+    }
+
+    if let Some(Command::Eval { expression, vars }) = args.command {
+        // The tree is only printed in the debug log level
+        set_log_level(LogLevel::Debug);
+        match eval_expression(&expression, vars.as_deref()) {
+            Ok(val) => {
+                let (data_type, value) = val.get_constant_info();
+                println!("{}: {}", data_type, value);
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
             }
         }
-        println!("Variables: {:?}", get_variable_map_instance());
+        return;
     }
 
     println!("Connecting at {}", ip_addr);
 
+    if let Some(health_addr) = args.health {
+        match setup_health_endpoint(health_addr.clone()) {
+            Ok(_) => println!("Health endpoint at http://{}/health", health_addr),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+
     // Start threads for Chains and Events
     // let mut thread_ids = vec![];
-    let dir = Path::new("config");
+    let dir = directories.config.clone();
 
     // Build Message Channels
     let (tx, rx): (Sender<Properties>, Receiver<Properties>) = mpsc::channel();
 
+    let event_backlog = args.event_backlog.unwrap_or(100);
+    let event_format = args.event_format.unwrap_or_default();
+    let event_queue: Arc<BlockingQueue<Event>> = Arc::new(
+        BlockingQueue::with_capacity(
+            args.queue_capacity.unwrap_or(DEFAULT_QUEUE_CAPACITY),
+            args.queue_policy.unwrap_or(QueuePolicy::Block)
+        )
+    );
+    get_health_state().lock().unwrap().set_event_queue(event_queue.clone());
+    let alert_webhook = args.alert_webhook.map(|url| AlertWebhook::spawn(url, 5).0);
+    // Explicit allowlist or the default one if it exists
+    let allowlist_path = args.allowlist.or(
+        Some(directories.config_path("allowlist.json")).filter(|p| p.exists())
+    );
+    let allowlist = match allowlist_path {
+        Some(path) =>
+            AddressList::from_file(path.to_str().unwrap()).unwrap_or_else(|e| panic!("{}", e)),
+        None => AddressList::default(),
+    };
+    log_info!("Allowlisted addresses: {}", allowlist.addresses.len());
+    let denylist = WatchedAddressList::new(
+        args.denylist.unwrap_or(directories.config_path("denylist.json"))
+    );
+    log_info!("Denylisted addresses: {}", denylist.current().addresses.len());
+    log_info!("Loaded properties: {}", get_property_store().current().files.len());
+    setup_reload_signal(get_property_store());
+    setup_shutdown_signal();
     let event_thread = thread::spawn(move || {
         // Setup the Event Socket
-        let event_queue: Arc<BlockingQueue<Event>> = Arc::new(BlockingQueue::new());
-        let (handle1, handle2) = setup_event_ws(ip_addr, event_queue.clone()).unwrap();
-
-        // Event Loop
-        loop {
-            let property = rx.recv().unwrap();
-            event_loop(property.clone(), event_queue.clone());
-        }
-
-        handle1.join().unwrap();
-        handle2.join().unwrap();
+        let (handle1, handle2) = setup_event_ws(
+            ip_addr,
+            event_queue.clone(),
+            event_backlog,
+            event_format
+        ).unwrap();
+
+        receive_events(rx, event_queue, alert_webhook.as_ref(), &allowlist, &denylist);
+        // The TCP Port keeps running until the program terminates
+        drop((handle1, handle2));
     });
 
     // thread_ids.push(event_thread);
 
     if !args.replay {
         // Run through all files in directory dir and print their paths
-        for entry in fs::read_dir(dir).unwrap() {
+        for entry in fs::read_dir(&dir).unwrap() {
             sleep(Duration::from_millis(100));
             let path = entry.unwrap().path();
             println!("{}", path.display());
@@ -200,6 +524,10 @@ fn main() {
             let contents = fs::read_to_string(&path).unwrap();
             if let Ok(bd) = serde_json::from_str::<BridgeConfig>(&contents){
 for config in bd.contracts {
+                if let Err(e) = config.validate() {
+                    println!("Skipping {} in {}: {}", config.get_name(), path.display(), e);
+                    continue;
+                }
                 let sender_clone = sender.clone();
 
                 let contract_name =
@@ -212,9 +540,13 @@ for config in bd.contracts {
 
                 // TODO: if replay then connect_replay instead of connect
                 // Instead of connecting, we replay the blocks by sending the transaction to replay a block
+                get_health_state().lock().unwrap().register_chain(&config.get_name());
                 match config.connect(sender_clone) {
                     Ok(_) => println!("Connected to {}", config.get_name()),
-                    Err(_) => println!("Failed to connect to {}", config.get_name()),
+                    Err(_) => {
+                        println!("Failed to connect to {}", config.get_name());
+                        get_health_state().lock().unwrap().mark_dead(&config.get_name());
+                    }
                 }
             }
             }else{
@@ -266,47 +598,85 @@ for config in bd.contracts {
                 }
             )
             .collect();
-        let step_len = config.page_length.unwrap_or(10000);
-        let step = if config.paging.unwrap_or(false) { step_len } else { 10_000 };
-
+        // Throttle the events of all chains together
+        let (tx, pacer) = match args.replay_rate {
+            Some(rate) => {
+                let (paced, pacer) = replay_ethereum_socket::pace_events(
+                    tx,
+                    replay_ethereum_socket::RateLimiter::paced(Some(rate))
+                );
+                (paced, Some(pacer))
+            }
+            None => (tx, None),
+        };
+        let source = config.source().unwrap_or_else(|e| panic!("Invalid replay config: {}", e));
+        let file_logs = source.read_logs().unwrap_or_else(|e| panic!("Invalid replay source: {}", e));
         for (id, chain) in config.chains.iter().enumerate() {
             let tx_clone = tx.clone();
-                    let connections = ConnectionConfig::from_file("config/connections.json");
-                    let chain_connection = connections.connections
-                        .iter()
-                        .find(|x| x.name == chain.name)
-                        .unwrap();
+                    // Logs from a file are replayed without any connection
+                    let rpc_url = match source {
+                        replay_ethereum_socket::ReplaySource::File(_) => String::new(),
+                        replay_ethereum_socket::ReplaySource::Rpc => {
+                            let connections = ConnectionConfig::load()
+                                .unwrap_or_else(|e| panic!("Invalid connection config: {}", e));
+                            connections.connections
+                                .iter()
+                                .find(|x| x.name == chain.name)
+                                .unwrap()
+                                .http_url()
+                        }
+                    };
 
                     // call the replay function and then invoke the replay handler and send the resulting properties via tx to rx
                     let replayer = replay_ethereum_socket::ReplayEthereumSocketHandler {
                         chain_name: chain.name.to_string(),
                         config: chain.clone(),
-                        rpc_url: chain_connection.rpc_url.to_string(),
+                        rpc_url,
+                        rate_limiter: replay_ethereum_socket::RateLimiter::new(
+                            config.requests_per_second
+                        ),
                     };
 
                     // let txs = replayer.get_all_logs().unwrap();
 
-                    for i in (start[id]..=end[id]).step_by(step as usize) {
-                        let end_block = min(end[id], i + step);
-                        let txs = replayer.get_logs(
-                            format!("0x{:x}", i),
-                            format!("0x{:x}", end_block)
-                        );
-                        match txs {
-                            Ok(txs) => {
-                                println!("Length of txs: {}", txs.len());
-                                // Send to tx
-                                for t in txs {
-                                    tx_clone.send(t).unwrap();
-                                }
-                            }
-                            Err(e) => eprintln!("Error: {}", e),
-                        }
+                    let checkpoint = config.checkpoint(&chain.name);
+                    let first = if args.resume { checkpoint.resume_from(start[id]) } else { start[id] };
+                    if first > end[id] {
+                        println!("Replay {} already finished at block {}", chain.name, end[id]);
+                        continue;
                     }
-                
+                    let step = config.page_size(first, end[id]);
+                    let pages = replay_ethereum_socket::page_ranges(first, end[id], step);
+                    // Report the progress together with the timestamps
+                    let mut progress = replay_ethereum_socket::ReplayProgress::new(
+                        chain.name.to_string(),
+                        first,
+                        end[id]
+                    );
+                    let report_progress = LOG_TIMESTAMPS.load(atomic::Ordering::Relaxed);
+                    replay_ethereum_socket::replay_pages(
+                        &pages,
+                        config.workers.unwrap_or(1),
+                        |from, to| match source {
+                            replay_ethereum_socket::ReplaySource::File(_) => replayer.file_logs(&file_logs, from, to),
+                            replay_ethereum_socket::ReplaySource::Rpc =>
+                                replayer.get_logs(format!("0x{:x}", from), format!("0x{:x}", to)),
+                        },
+                        &tx_clone,
+                        if report_progress { Some(&mut progress) } else { None },
+                        &config.page_retry(&chain.name),
+                        Some(&checkpoint)
+                    );
         }
 
-        // TODO: Terminate the program gracefully
+        // All chains are replayed, the event thread ends once it evaluated and sent the remaining events
+        drop(tx);
+        if let Some(pacer) = pacer {
+            pacer.join().unwrap();
+        }
+        event_thread.join().unwrap();
+        println!("Replay finished");
+        return;
     }
 
     // sleep(Duration::from_secs(5));
@@ -335,12 +705,254 @@ for config in bd.contracts {
     drop(event_thread);
 }
 
-fn event_loop(property: Properties, event_queue: Arc<BlockingQueue<Event>>) -> bool {
-    let mut ev: Evaluation = Evaluation::default();
-    ev.id = LAST_ID.load(atomic::Ordering::Relaxed);
-    LAST_ID.store(ev.id + 1, atomic::Ordering::Relaxed);
-    let now = Instant::now();
+/// Evaluates the properties received from the chains until all senders are dropped,
+/// e.g., when all chains of a replay are done. Then waits until the queued events are sent.
+fn receive_events(
+    rx: Receiver<Properties>,
+    event_queue: Arc<BlockingQueue<Event>>,
+    alert_webhook: Option<&AlertWebhook>,
+    allowlist: &AddressList,
+    denylist: &WatchedAddressList
+) {
+    while let Ok(property) = rx.recv() {
+        event_loop(property, event_queue.clone(), alert_webhook, allowlist, &denylist.current());
+    }
+    log_info!("All chains are done, sending the remaining {} events", event_queue.len());
+    event_queue.wait_until_empty();
+}
+
+/// Files of the example written by `brigade init`, relative to the config or the base directory
+const EXAMPLE_CONFIG_FILES: [(&str, &str); 2] = [
+    (
+        "connections.json",
+        r#"{
+    "connections": [
+        {
+            "name": "ethereum",
+            "rpc_url": "ETHEREUM_API",
+            "ws_url": "ETHEREUM_API"
+        }
+    ]
+}
+"#,
+    ),
+    (
+        "example_config.json",
+        r#"{
+    "contracts": [
+        {
+            "subscription_method": "eth_subscribe",
+            "name": "ethereum",
+            "contract_name": "usdc",
+            "contract_address": "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
+            "log_filter": {
+                "addresses": ["0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"],
+                "topics": [["Transfer(address,address,uint256)"]]
+            }
+        }
+    ]
+}
+"#,
+    ),
+];
+
+const EXAMPLE_BASE_FILES: [(&str, &str); 4] = [
+    (
+        "properties/example_transfer.json",
+        r#"{
+    "event": "Transfer(address indexed from, address indexed to, uint256 value)",
+    "chain_name": "ethereum",
+    "properties": {
+        "amount": "event_param(value)"
+    },
+    "pattern": [
+        "scale($amount, 6) < 1000000"
+    ]
+}
+"#,
+    ),
+    (
+        "rules/example_limit.talon",
+        r#"event: Transfer(address,address,uint256)
+chain_name: ethereum
+{
+// Payers may move at most 1000 ether per day
+scale(daily_volume($payer_address, $value), 18) < 1000
+}
+"#,
+    ),
+    (
+        "functions/ethereum/rpc.json",
+        r#"{
+    "jsonrpc": "2.0",
+    "method": "$method",
+    "params": [],
+    "id": "1"
+}
+"#,
+    ),
+    (
+        "functions/ethereum/get_balance.json",
+        r#"{
+    "jsonrpc": "2.0",
+    "method": "eth_getBalance",
+    "params": ["$account", "$blocknumber"],
+    "id": "0"
+}
+"#,
+    ),
+];
+
+/// Writes the example files and returns their paths with true if a file was created and false if it existed
+fn init_example(directories: &Directories) -> io::Result<Vec<(PathBuf, bool)>> {
+    let files = EXAMPLE_CONFIG_FILES.iter()
+        .map(|(path, contents)| (directories.config_path(path), contents))
+        .chain(EXAMPLE_BASE_FILES.iter().map(|(path, contents)| (directories.base_path(path), contents)));
+    let mut written = vec![];
+    for (path, contents) in files {
+        if path.exists() {
+            written.push((path, false));
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, contents)?;
+        written.push((path, true));
+    }
+    Ok(written)
+}
 
+/// Checks every chain and prints OK or the reason it failed. Returns the exit code, 1 if any chain failed
+fn run_doctor(config: &ConnectionConfig, timeout: Duration) -> i32 {
+    let mut code = 0;
+    for connection in &config.connections {
+        match connection.check(timeout) {
+            Ok(_) => println!("{}: OK", connection.name),
+            Err(e) => {
+                println!("{}: failed, {}", connection.name, e);
+                code = 1;
+            }
+        }
+    }
+    code
+}
+
+/// One `signature: topic` line per event signature, the signatures are canonicalized before hashing
+fn topic_lines(signatures: &[String]) -> Vec<String> {
+    signatures
+        .iter()
+        .map(|signature| {
+            let canonical = utils::canonical_event_signature(signature);
+            format!("{}: {}", canonical, utils::get_ethereum_topic_ids(&canonical))
+        })
+        .collect()
+}
+
+/// Prints the errors of the property files and Talon rules and returns the exit code
+fn run_lint(properties: &Path, rules: &Path) -> i32 {
+    match lint(properties, rules) {
+        Ok(count) => {
+            println!("Checked {} files", count);
+            0
+        }
+        Err(errors) => {
+            for e in errors.iter() {
+                eprintln!("Error: {}", e);
+            }
+            1
+        }
+    }
+}
+
+/// Evaluates the patterns of the json file, e.g. `["$keystore.push(0xaabb)", "assign(limit, 1000)"]`,
+/// to create the variables
+fn load_predefined_variables(var_file: &Path) -> Result<(), String> {
+    let contents = fs::read_to_string(var_file).map_err(|e| e.to_string())?;
+    let values: Value = serde_json::from_str(&contents).map_err(|e| e.to_string())?;
+    let content = values.as_array().ok_or("Wrong file format".to_string())?;
+    for var in content.iter() {
+        match build_ast_root(var.as_str().unwrap_or_default()) {
+            Ok(root) => {
+                root.print("");
+                match root.evaluate() {
+                    Ok(val) => {
+                        println!("{}: {}", var, val.get_value());
+                    }
+                    Err(e) => {
+                        println!("Error when parsing {}", var);
+                        eprintln!("Error: {}", e);
+                    }
+                }
+            }
+            Err(e) => {
+                println!("Can't create AST from {}", var);
+                eprintln!("Error: {}", e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Seeds the named constants `$ether` (10^18), `$gwei` (10^9) and `$max_uint` (2^256-1) as variables.
+/// The file is a JSON object that adds or overrides constants with decimal or hex values, e.g. `{"usdc": "1000000"}`
+fn load_constants(file: Option<&Path>) -> Result<(), String> {
+    let mut constants = vec![
+        ("ether".to_string(), u256::from(10u64).pow(18)),
+        ("gwei".to_string(), u256::from(10u64).pow(9)),
+        ("max_uint".to_string(), u256::MAX),
+    ];
+    if let Some(file) = file {
+        let contents = fs::read_to_string(file).map_err(|e| e.to_string())?;
+        let values: Value = serde_json::from_str(&contents).map_err(|e| e.to_string())?;
+        let values = values.as_object().ok_or("Wrong file format".to_string())?;
+        for (name, value) in values {
+            let parsed = match value {
+                Value::Number(n) => n.as_u64().map(u256::from),
+                Value::String(s) if s.starts_with("0x") => u256::from_str_hex(s).ok(),
+                Value::String(s) => u256::from_str(s).ok(),
+                _ => None,
+            };
+            let parsed = parsed.ok_or(format!("{} is not an unsigned number: {}", name, value))?;
+            constants.push((name.clone(), parsed));
+        }
+    }
+    for (name, value) in constants {
+        set_var!(name, value);
+    }
+    Ok(())
+}
+
+/// Evaluates the expression with the variables of the file and prints its AST in the debug log level
+fn eval_expression(expression: &str, vars: Option<&Path>) -> Result<ASTConstant, String> {
+    if let Some(vars) = vars {
+        load_predefined_variables(vars)?;
+    }
+    let root = build_ast_root(expression).map_err(|e| e.to_string())?;
+    root.print("");
+    root.evaluate().map_err(|e| e.to_string())
+}
+
+/// Evaluates a single event in the JSON format of `Properties` without connecting to any chain
+fn check_event(
+    input: &str,
+    allowlist: &AddressList,
+    denylist: &AddressList
+) -> Result<(Allowance, Vec<String>), String> {
+    let property: Properties = serde_json::from_str(input).map_err(|e| e.to_string())?;
+    if property.occured_event.is_none() || property.src_chain.is_none() {
+        return Err("the event needs an occured_event and a src_chain".to_string());
+    }
+    let scope = EventScope::enter();
+    set_event_variables(&property);
+    let (allowance, checked) = evaluate_properties(&property, None, allowlist, denylist);
+    take_rpc_calls();
+    scope.exit();
+    Ok((apply_default_policy(allowance, DEFAULT_DENY.load(atomic::Ordering::Relaxed)), checked))
+}
+
+/// Sets the fields of the event as variables of the current scope, e.g. `$value`
+fn set_event_variables(property: &Properties) {
     // Build generic Variables from property description
     let prp = property.serialize();
 
@@ -351,6 +963,14 @@ fn event_loop(property: Properties, event_queue: Arc<BlockingQueue<Event>>) -> b
             let v = &value.as_str().unwrap()[5..];
             // println!("{}: {}", bn, v);
             set_var!(bn, u256::from_str(v).unwrap());
+            // A block lower than the highest block of the chain is a reorg or a replayed event, see is_reorg()
+            if let (Some(chain), Some(block)) = (&property.src_chain, property.block_number) {
+                let reorg = record_block_number(chain, block);
+                if reorg {
+                    log_error!("Warning: block {} of {} is lower than the highest block seen, possible reorg", block, chain);
+                }
+                set_var!("reorg", reorg);
+            }
             continue;
         }
         if value.is_string() && value.as_str().unwrap().starts_with("u256:") {
@@ -369,35 +989,87 @@ fn event_loop(property: Properties, event_queue: Arc<BlockingQueue<Event>>) -> b
         }
     }
 
+    // event_param() decodes the raw log of the event itself
+    if !property.topics.is_empty() {
+        set_var!("event_topics", property.topics.clone());
+        set_var!("event_data", property.data.clone());
+    }
+
+    // The payer as a map, e.g. $payer.get(balance) - $payer.get(previous_balance)
+    if let Some(payer) = &property.payer {
+        set_var!("payer", payer.to_var());
+    }
+}
+
+fn event_loop(
+    property: Properties,
+    event_queue: Arc<BlockingQueue<Event>>,
+    alert_webhook: Option<&AlertWebhook>,
+    allowlist: &AddressList,
+    denylist: &AddressList
+) -> bool {
+    let mut ev: Evaluation = Evaluation::default();
+    ev.id = LAST_ID.load(atomic::Ordering::Relaxed);
+    LAST_ID.store(ev.id + 1, atomic::Ordering::Relaxed);
+    let now = Instant::now();
+
+    // Variables created during the event are dropped with the scope
+    let scope = EventScope::enter();
+    set_event_variables(&property);
+
     // Print the variables before the properties are processed
-    print_variables(&get_variable_map_instance());
+    print_variables(&scope.variables());
+
+    if let Some(chain) = &property.src_chain {
+        get_health_state().lock().unwrap().record_event(chain);
+    }
 
     // Which Event?
     let event = property.occured_event.clone().unwrap();
-    println!("Event: {}", event.blue());
-    println!("Transaction Hash: {}", property.transaction_hash.clone().unwrap().blue());
-    println!("Chain: {}", property.src_chain.clone().unwrap().blue());
+    log_debug!("Event: {}", event.if_color(|s| s.blue()));
+    log_debug!("Transaction Hash: {}", property.transaction_hash.clone().unwrap().if_color(|s| s.blue()));
+    log_debug!("Chain: {}", property.src_chain.clone().unwrap().if_color(|s| s.blue()));
 
     ev.event_type = event.clone();
 
-    // Results of the separate files
-    let mut results: Vec<bool> = vec![];
-    // Which files were relevant
-    let mut checked_vec: Vec<String> = vec![];
-    // Which file was failed
-    let mut fail_reason: Vec<String> = vec![];
-
-    // Process the properties
-    process_json_properties(property.clone(), &mut results, &mut checked_vec, &mut fail_reason);
+    // Retrieve the transaction fields of the source chain
+    let chain = property.src_chain.clone().unwrap_or("ethereum".to_string());
+    let decision = decide_unevaluated(
+        &property,
+        &mut get_circuit_breakers().lock().unwrap(),
+        allowlist,
+        denylist,
+        BREAKER_FAIL_OPEN.load(atomic::Ordering::Relaxed)
+    );
+    let (fields, is_allowed, checked_vec) = if let Some(allowance) = decision {
+        (None, allowance, vec![])
+    } else {
+        PROPERTY_FAILED.with(|f| f.set(false));
+        let enricher = get_enricher(&chain, property.src_chain_id);
+        let fields = enricher.enrich(&property);
+        if let Some(l1_block_number) = fields.as_ref().and_then(|f| f.l1_block_number.clone()) {
+            set_var!("l1_block_number", l1_block_number);
+        }
 
-    // process_talon_code(property.clone(), &mut results, &mut fail_reason);
+        let (is_allowed, checked_vec) = evaluate_properties(
+            &property,
+            fields.as_ref(),
+            allowlist,
+            denylist
+        );
+        let failed = (fields.is_none() && enricher.provides_fields()) || PROPERTY_FAILED.with(|f| f.replace(false));
+        record_breaker_outcome(&chain, failed, alert_webhook);
+        (fields, apply_default_policy(is_allowed, DEFAULT_DENY.load(atomic::Ordering::Relaxed)), checked_vec)
+    };
+    record_event_rpc_calls(&property, RPC_CALL_BUDGET.load(atomic::Ordering::Relaxed));
 
     ev.duration = now.elapsed().as_millis();
 
-    let is_allowed: Allowance = if results.iter().all(|x| *x) {
-        Allowance::Allow
-    } else {
-        Allowance::Deny(fail_reason.clone())
+    let allowed = matches!(is_allowed, Allowance::Allow | Allowance::NoMatch);
+    let no_match = is_allowed == Allowance::NoMatch;
+    let fail_reason = match &is_allowed {
+        Allowance::Allow | Allowance::NoMatch => vec![],
+        Allowance::Deny(reasons) => reasons.clone(),
     };
     let event = Event {
         result: is_allowed,
@@ -406,324 +1078,535 @@ fn event_loop(property: Properties, event_queue: Arc<BlockingQueue<Event>>) -> b
         transaction_hash: property.transaction_hash.clone().unwrap(),
     };
 
+    if let Some(webhook) = alert_webhook {
+        webhook.alert(&event);
+    }
+
     event_queue.push(event);
 
+    // Remember the values read with prev() for the next event of the payer
+    record_previous_values("map", &event_subject());
+
     // Clear all non persistent variables
-    let map = get_variable_map_instance();
-    /*
-     * This removes all variables generated during the call of the property.
-     * Some Variables however are needed to be kept for future calls of the property.
-     * They can be defined here.
-     */
-    map.retain(|k, _| (*k == "keystore" || *k == "map" || k.contains("_contract")));
-
-    print_variables(&map);
-
-    // pub contract_address: String,
-    let call = format!(
-        "call({}, eth_getTransactionByHash, [{}]).get(result)",
-        property.src_chain.unwrap_or("ethereum".to_string()),
-        property.transaction_hash.clone().unwrap()
-    );
-    let root = build_ast_root(call.as_str()).unwrap();
-    root.print("");
-    let val = root.evaluate().unwrap();
-    println!("{}", val.get_value());
-
-    ev.contract_address = val
-        .get_map()
-        .get("to")
-        .expect("Failed to get 'to' from message")
-        .get_value();
-    // pub msg_sender: String,
-    ev.msg_sender = val
-        .get_map()
-        .get("from")
-        .expect("Failed to get 'from' from message")
-        .get_value();
-    // pub block_number: String,
-    ev.block_number = val
-        .get_map()
-        .get("blockNumber")
-        .expect("Failed to get 'blockNumber' from message")
-        .get_value();
-    // pub msg_value: u256,
-    let v = val.get_map().get("value").expect("Failed to get 'value' from message").get_value();
-    ev.msg_value = u256::from_str_hex(&v).unwrap();
+    scope.exit();
+
+    print_variables(&get_variable_map_instance());
+
+    if let Some(fields) = fields {
+        ev.contract_address = fields.contract_address;
+        ev.msg_sender = fields.msg_sender;
+        ev.block_number = fields.block_number;
+        ev.msg_value = fields.msg_value;
+    }
 
     if fail_reason.len() > 0 {
         // pub pattern_type: String,
         ev.pattern_type = fail_reason.join(" AND ").to_string();
+    } else if no_match {
+        ev.pattern_type = "no match".to_string();
     } else {
         ev.pattern_type = "allowed".to_string();
     }
 
     log_evaluation(ev);
 
-    // Check all results and only allow when all are true
-    if results.iter().all(|x| *x) {
-        println!("{} transaction: {}", "Allow".green(), property.transaction_hash.clone().unwrap());
+    if no_match {
+        log_info!("{} transaction: {}", "NoMatch".if_color(|s| s.yellow()), property.transaction_hash.clone().unwrap());
+        true
+    } else if allowed {
+        log_info!("{} transaction: {}", "Allow".if_color(|s| s.green()), property.transaction_hash.clone().unwrap());
         true
     } else {
-        println!("{} transaction: {}", "Deny".red(), property.transaction_hash.clone().unwrap());
+        log_info!("{} transaction: {}", "Deny".if_color(|s| s.red()), property.transaction_hash.clone().unwrap());
         false
     }
 }
 
-fn process_json_properties(
-    property: Properties,
-    results: &mut Vec<bool>,
-    checked_vec: &mut Vec<String>,
-    fail_reason: &mut Vec<String>
-) -> bool {
-    let event = property.occured_event.clone().unwrap();
-    // println!("Dir_len {}", fs::read_dir("properties").unwrap().count());
-    // Find Property Files which are triggered by the Event and the chain
-    for file in fs::read_dir("properties").unwrap() {
-        let path = file.unwrap().path();
-        let def_file: Value = serde_json
-            ::from_str(fs::read_to_string(&path).unwrap().as_str())
-            .unwrap();
-        let name = path.file_name().unwrap().to_str().unwrap();
-        // println!("File: {:?}", serde_json::to_string_pretty(&def_file).unwrap());
-
-        // Ignore events not triggered by the event or on the wrong chain
-        if property.src_chain.clone().unwrap().to_lowercase() == "ethereum" {
-            if let Some(ev) = def_file.get("event") {
-                let ev = ev.as_str().unwrap();
-                if ev != event {
-                    let hashed_event = utils::get_ethereum_topic_ids(ev);
-                    if hashed_event != event {
-                        // println!("hashed_event: {}, event: {}", hashed_event, event);
-                        continue;
-                    }
-                }
-            } else {
-                continue;
-            }
-        } else {
-            // Non Ethereum Chains
-            if
-                def_file.get("event").unwrap().as_str().unwrap() != event ||
-                def_file.get("chain_name").unwrap().as_str().unwrap().to_lowercase() !=
-                    property.src_chain.clone().unwrap().to_lowercase()
-            {
-                // println!("Continuing...");
-                continue;
-            }
+/// Evaluates the properties of the event and returns the decision and the checked files.
+/// Transactions from or to a denylisted address are denied and transactions from or to an allowlisted address
+/// are allowed without evaluating the properties. The denylist takes precedence.
+fn evaluate_properties(
+    property: &Properties,
+    fields: Option<&TransactionFields>,
+    allowlist: &AddressList,
+    denylist: &AddressList
+) -> (Allowance, Vec<String>) {
+    let chain = property.src_chain.clone().unwrap_or_default();
+    let mut addresses = vec![];
+    if let Some(payer) = &property.payer_address {
+        addresses.push(payer.as_str());
+    }
+    if let Some(fields) = fields {
+        addresses.push(fields.msg_sender.as_str());
+        addresses.push(fields.contract_address.as_str());
+    }
+    if let Some(allowance) = check_address_lists(property, &addresses, allowlist, denylist) {
+        return (allowance, vec![]);
+    }
+
+    // Results of the separate files
+    let mut results: Vec<bool> = vec![];
+    // Which files were relevant
+    let mut checked_vec: Vec<String> = vec![];
+    // Which file was failed
+    let mut fail_reason: Vec<String> = vec![];
+
+    // Process the properties and the rules written in the Talon format
+    // The properties of the event stay the same even if they are reloaded meanwhile
+    let properties = get_property_store().current();
+    let evaluated = process_properties(
+        property.clone(),
+        &properties,
+        &mut results,
+        &mut checked_vec,
+        &mut fail_reason
+    );
+    if !evaluated {
+        PROPERTY_FAILED.with(|f| f.set(true));
+    }
+
+    // Check all results and only allow when all are true
+    if results.is_empty() && checked_vec.is_empty() {
+        (Allowance::NoMatch, checked_vec)
+    } else if results.iter().all(|x| *x) {
+        (Allowance::Allow, checked_vec)
+    } else {
+        (Allowance::Deny(fail_reason), checked_vec)
+    }
+}
+
+/// Reports the RPC requests of the event to the health endpoint and warns if they exceed the budget.
+/// Returns the number of requests.
+fn record_event_rpc_calls(property: &Properties, budget: u64) -> u64 {
+    let calls = take_rpc_calls();
+    let total = calls.values().sum::<u64>();
+    if budget > 0 && total > budget {
+        log_error!(
+            "Warning: transaction {} made {} RPC requests, the budget is {} ({:?})",
+            property.transaction_hash.clone().unwrap_or_default(),
+            total,
+            budget,
+            calls
+        );
+    }
+    get_health_state().lock().unwrap().record_rpc_calls(&calls);
+    total
+}
+
+/// Records whether the event of the chain failed in its circuit breaker.
+/// When the breaker opens, a single alert is logged and sent to the webhook.
+fn record_breaker_outcome(chain: &str, failed: bool, alert_webhook: Option<&AlertWebhook>) {
+    let mut breakers = get_circuit_breakers().lock().unwrap();
+    if breakers.record(chain, failed, Instant::now()) {
+        let failures = breakers.chains.get(chain).map(|b| b.failures()).unwrap_or_default();
+        log_error!("Circuit breaker of {} opened after {} failed events", chain, failures);
+        if let Some(webhook) = alert_webhook {
+            webhook.alert_breaker(chain, failures);
         }
-        // Following files are all correct
-        // Push onto checked Vec
-        checked_vec.push(name.clone().to_owned());
+    }
+    if breakers.is_enabled() {
+        get_health_state().lock().unwrap().set_breaker_state(chain, breakers.state(chain));
+    }
+}
 
-        // Execute Custom Functions and get Variables
-        match execute_custom_function(&def_file) {
-            Ok(_) => {}
-            Err(e) => {
-                println!("Error: {}", e);
-                return false;
-            }
+/// Looks the addresses up in the denylist and then in the allowlist.
+/// Returns the decision of the first list that contains one of them
+fn check_address_lists(
+    property: &Properties,
+    addresses: &[&str],
+    allowlist: &AddressList,
+    denylist: &AddressList
+) -> Option<Allowance> {
+    let chain = property.src_chain.clone().unwrap_or_default();
+    if let Some(address) = denylist.find(&chain, addresses) {
+        log_info!(
+            "{} transaction: {} From: denylist ({})",
+            "Deny".if_color(|s| s.red()),
+            property.transaction_hash.clone().unwrap_or_default(),
+            address
+        );
+        return Some(Allowance::Deny(vec!["denylist".to_string()]));
+    }
+    if let Some(address) = allowlist.find(&chain, addresses) {
+        log_info!(
+            "{} transaction: {} From: allowlist ({})",
+            "Allow".if_color(|s| s.green()),
+            property.transaction_hash.clone().unwrap_or_default(),
+            address
+        );
+        return Some(Allowance::Allow);
+    }
+    None
+}
+
+/// Decision for an event that is neither enriched nor evaluated, None if the event is evaluated.
+/// The payer is looked up in the lists first, which needs no RPC request,
+/// so the lists also apply to the events of a chain whose circuit breaker is open.
+fn decide_unevaluated(
+    property: &Properties,
+    breakers: &mut CircuitBreakers,
+    allowlist: &AddressList,
+    denylist: &AddressList,
+    fail_open: bool
+) -> Option<Allowance> {
+    let payer: Vec<&str> = property.payer_address.iter().map(String::as_str).collect();
+    if let Some(allowance) = check_address_lists(property, &payer, allowlist, denylist) {
+        return Some(allowance);
+    }
+    // The chain is failing, its events get the breaker policy until the cooldown passed
+    let chain = property.src_chain.clone().unwrap_or("ethereum".to_string());
+    if breakers.allows(&chain, Instant::now()) { None } else { Some(breaker_policy(fail_open)) }
+}
+
+/// Decision for an event of a chain whose circuit breaker is open
+fn breaker_policy(fail_open: bool) -> Allowance {
+    if fail_open { Allowance::Allow } else { Allowance::Deny(vec!["circuit breaker open".to_string()]) }
+}
+
+/// With `--default-deny` an event without a matching property or rule is denied instead of passing as `NoMatch`
+fn apply_default_policy(allowance: Allowance, default_deny: bool) -> Allowance {
+    match allowance {
+        Allowance::NoMatch if default_deny => Allowance::Deny(vec!["no matching rule".to_string()]),
+        allowance => allowance,
+    }
+}
+
+/// Returns true if the property with the `chain_name` applies to the chain.
+/// A property without a `chain_name` or with `"chain_name": "*"` applies to all chains.
+fn matches_chain(chain_name: Option<&str>, chain: &str) -> bool {
+    match chain_name {
+        None | Some("*") => true,
+        Some(chain_name) => chain_name.eq_ignore_ascii_case(chain),
+    }
+}
+
+/// Outcome of a property file for an event
+enum PropertyOutcome {
+    Allow,
+    /// Denied with the reasons, i.e. the file name or the denied lines of a Talon file
+    Deny(Vec<String>),
+    /// The custom functions or the joined pattern failed, the remaining files are not aggregated
+    Failed,
+    /// The evaluation exceeded the property timeout
+    TimedOut,
+}
+
+/// What happens to an event whose property exceeds the timeout
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+enum TimeoutPolicy {
+    /// Fail closed, the property denies the event
+    Deny,
+    /// Fail open, the property allows the event
+    Allow,
+}
+
+/// What happens to an event of a chain whose circuit breaker is open
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+enum BreakerPolicy {
+    /// Fail closed, the event is denied
+    Deny,
+    /// Fail open, the event is allowed
+    Allow,
+}
+
+/// Evaluates the property file with the transient variables of the event and returns the outcome with the
+/// values read by `prev()`. With a timeout the file is evaluated on its own thread, which is abandoned
+/// when the deadline passes, so a slow `call(...)` can not stall the event loop.
+///
+/// An abandoned thread can not be stopped, it is detached and keeps running until the evaluation ends.
+/// Its transient variables are dropped, but it can still change the persistent variables, e.g. with
+/// `incr` or `$map.insert`, after the event was already allowed or denied.
+fn evaluate_with_timeout(
+    property: &Properties,
+    file: &PropertyFile,
+    variables: &VariableMap,
+    timeout: Option<Duration>
+) -> (PropertyOutcome, Vec<(String, VarValues)>, HashMap<String, u64>) {
+    let task = {
+        let (property, file, variables) = (property.clone(), file.clone(), variables.clone());
+        move || {
+            let _scope = EventScope::enter_with(variables);
+            let outcome = evaluate_property_file(&property, &file);
+            (outcome, take_tracked_values(), take_rpc_calls())
         }
+    };
+    let Some(timeout) = timeout else {
+        return task();
+    };
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(task());
+    });
+    match rx.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            log_error!("Error: {} timed out after {}ms", file.name, timeout.as_millis());
+            (PropertyOutcome::TimedOut, vec![], HashMap::new())
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            log_error!("Error: the evaluation of {} panicked", file.name);
+            (PropertyOutcome::Deny(vec![file.name.clone()]), vec![], HashMap::new())
+        }
+    }
+}
 
-        // parse pattern into AST
-        let pattern = def_file.get("pattern").unwrap().as_array().unwrap();
+/// Returns true if the property file is triggered by the event on its chain
+fn property_matches(property: &Properties, def_file: &Value, event: &str) -> bool {
+    let Some(file_event) = def_file.get("event").and_then(|e| e.as_str()) else {
+        return false;
+    };
+    is_triggered(
+        property,
+        def_file.get("chain_name").and_then(|c| c.as_str()),
+        def_file.get("chain_id").and_then(|c| c.as_u64()),
+        file_event,
+        event
+    )
+}
 
-        let patterns = pattern
-            .iter()
-            .map(|p| p.as_str().unwrap().to_string())
-            .collect::<Vec<String>>();
-        let mut line_results = vec![];
-        for p in patterns {
-            match build_ast_root(&p) {
-                Ok(root) => {
-                    root.print("");
-                    match root.evaluate() {
-                        Ok(v) => {
-                            let ret: String = v.get_value();
-                            line_results.push(ret);
-                        }
-                        Err(e) => {
-                            println!("Error: {}", e);
-                            line_results.push("false".to_string());
-                            // return false;
-                        }
-                    }
-                }
-                Err(e) => {
-                    println!("Error: {}", e);
-                    line_results.push("false".to_string());
-                    // return false;
+/// Returns true if a property file or Talon rule with the `chain_name`, `chain_id` and `file_event`
+/// is triggered by the `event` of the property.
+/// The chain_id takes precedence over the chain_name.
+fn is_triggered(
+    property: &Properties,
+    chain_name: Option<&str>,
+    chain_id: Option<u64>,
+    file_event: &str,
+    event: &str
+) -> bool {
+    let chain = property.src_chain.as_deref().unwrap_or_default();
+    let is_chain = match chain_id {
+        Some(chain_id) => property.src_chain_id == Some(chain_id),
+        None => matches_chain(chain_name, chain),
+    };
+    if !is_chain {
+        return false;
+    }
+    // Chains with a chain_id speak Ethereum JSON-RPC, the event is either the topic or the signature
+    if chain.eq_ignore_ascii_case("ethereum") || property.src_chain_id.is_some() {
+        utils::event_matches_topic(file_event, event)
+    } else {
+        file_event == event
+    }
+}
+
+/// Runs `f` for every item on at most `workers` threads and returns the results in the order of the items
+fn run_bounded<T, R, F>(items: &[T], workers: usize, f: F) -> Vec<R>
+    where T: Sync, R: Send, F: Fn(&T) -> R + Sync
+{
+    let next = atomic::AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    thread::scope(|s| {
+        for _ in 0..workers.max(1).min(items.len()) {
+            s.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, atomic::Ordering::Relaxed);
+                    let Some(item) = items.get(i) else {
+                        break;
+                    };
+                    let result = f(item);
+                    results.lock().unwrap()[i] = Some(result);
                 }
-            }
+            });
         }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|r| r.unwrap())
+        .collect()
+}
 
-        // Join all line results with && in one string
-        let processed_pattern = line_results
-            .iter()
-            .map(|p| p.as_str().to_string())
-            .collect::<Vec<String>>()
-            .join(" && ");
-
-        // let processed_pattern = pattern
-        //     .iter()
-        //     .map(|p| p.as_str().unwrap().to_string())
-        //     .collect::<Vec<String>>()
-        //     .join(" && ");
-        // // println!("Pattern: {}", processed_pattern);
-
-        match build_ast_root(&processed_pattern) {
-            Ok(root) => {
-                // root.print("");
-
-                // Evaluate AST
-                let val = root.evaluate();
-                match val {
-                    Ok(v) => {
-                        let ret: String = v.get_value();
-                        println!("Pattern: {}", ret.fg::<DarkCyan>());
-                        // Save result
-                        if ret == "true" {
-                            println!(
-                                "{} transaction: {} From: {}",
-                                "Allow".green(),
-                                property.transaction_hash.clone().unwrap(),
-                                name.yellow()
-                            );
-                            results.push(true);
-                        } else {
-                            println!(
-                                "{} transaction: {} From: {}",
-                                "Deny".red(),
-                                property.transaction_hash.clone().unwrap(),
-                                name.yellow()
-                            );
-                            fail_reason.push(name.clone().to_string());
-                            results.push(false);
-                        }
-                    }
-                    Err(e) => {
-                        println!(
+/// Evaluates the property file in the scope of the current thread
+fn evaluate_property_file(property: &Properties, file: &PropertyFile) -> PropertyOutcome {
+    if file.format == PropertyFormat::Talon {
+        return evaluate_talon_rules(property, file);
+    }
+    let def_file = &file.definition;
+    let name = file.name.as_str();
+
+    // Signature of the matched event to resolve its parameters with event_param()
+    if let Some(ev) = def_file.get("event").and_then(|ev| ev.as_str()) {
+        set_var!("event_signature", ev);
+    }
+
+    // Execute Custom Functions and get Variables
+    match execute_custom_function(def_file) {
+        Ok(_) => {}
+        Err(e) => {
+            log_error!("Error: {}", e);
+            return PropertyOutcome::Failed;
+        }
+    }
+
+    // Evaluate the compiled pattern lines
+    let (line_results, errors) = file.evaluate_lines();
+    for e in errors {
+        log_error!("Error: {}", e);
+    }
+
+    // Join all line results with && in one string
+    let processed_pattern = line_results
+        .iter()
+        .map(|p| p.as_str().to_string())
+        .collect::<Vec<String>>()
+        .join(" && ");
+
+    match build_ast_root(&processed_pattern) {
+        Ok(root) => {
+            // Evaluate AST
+            match root.evaluate() {
+                Ok(v) => {
+                    let ret: String = v.get_value();
+                    log_debug!("Pattern: {}", ret.fg::<DarkCyan>());
+                    // Save result
+                    if ret == "true" {
+                        log_info!(
                             "{} transaction: {} From: {}",
-                            "Deny".red(),
+                            "Allow".if_color(|s| s.green()),
                             property.transaction_hash.clone().unwrap(),
-                            name.yellow()
+                            name.if_color(|s| s.yellow())
                         );
-                        println!("Error: {}", e);
-                        fail_reason.push(name.clone().to_string());
-                        results.push(false);
+                        PropertyOutcome::Allow
+                    } else {
+                        log_info!(
+                            "{} transaction: {} From: {}",
+                            "Deny".if_color(|s| s.red()),
+                            property.transaction_hash.clone().unwrap(),
+                            name.if_color(|s| s.yellow())
+                        );
+                        PropertyOutcome::Deny(vec![name.to_string()])
                     }
                 }
-            }
-            Err(e) => {
-                println!("Error: {}", e);
-                return false;
+                Err(e) => {
+                    log_info!(
+                        "{} transaction: {} From: {}",
+                        "Deny".if_color(|s| s.red()),
+                        property.transaction_hash.clone().unwrap(),
+                        name.if_color(|s| s.yellow())
+                    );
+                    log_error!("Error: {}", e);
+                    PropertyOutcome::Deny(vec![name.to_string()])
+                }
             }
         }
+        Err(e) => {
+            log_error!("Error: {}", e);
+            PropertyOutcome::Failed
+        }
     }
-    return true;
 }
 
-fn process_talon_code(
+/// Evaluates the property files and Talon rules that are triggered by the event.
+/// The files are independent, so they are evaluated concurrently on at most `PROPERTY_WORKERS` threads.
+/// Every worker starts with the transient variables of the event, the variables a file creates are local to the file.
+/// The outcomes are merged in the order of the files, so `fail_reason` is deterministic.
+fn process_properties(
     property: Properties,
+    properties: &PropertySet,
     results: &mut Vec<bool>,
+    checked_vec: &mut Vec<String>,
     fail_reason: &mut Vec<String>
-) {
-    let event = property.occured_event.unwrap();
-
-    for file in fs::read_dir("rules").unwrap() {
-        let mut execute = false;
-        let path = file.unwrap().path();
-        match TalonFile::read_from_file(path.as_path()) {
-            Ok(def_file) => {
-                // Found a valid file
-                // Check if the event matches
-                if event == def_file.event {
-                    println!("Found: {}", path.to_str().unwrap());
-                    // Execute the code
-                    execute = true;
-                }
-                if !execute {
-                    let hashed_event = utils::get_ethereum_topic_ids(def_file.event.as_str());
-                    if hashed_event != event {
-                        execute = true;
-                    }
-                    // Skip wrong events
-                    continue;
-                }
+) -> bool {
+    let event = property.occured_event.clone().unwrap();
+    // Find Property Files which are triggered by the Event and the chain
+    let matched = properties.files
+        .iter()
+        .filter(|file| property_matches(&property, &file.definition, &event))
+        .collect::<Vec<&PropertyFile>>();
+
+    let variables = EventScope::transient_variables();
+    let timeout = match PROPERTY_TIMEOUT_MS.load(atomic::Ordering::Relaxed) {
+        0 => None,
+        ms => Some(Duration::from_millis(ms)),
+    };
+    let outcomes = run_bounded(&matched, PROPERTY_WORKERS, |file| {
+        evaluate_with_timeout(&property, file, &variables, timeout)
+    });
 
-                if execute {
-                    let rules = def_file.rules;
-                    let roots = build_code(&rules).unwrap();
-                    println!("Roots: {:?}", roots);
-                    for (l, root) in roots.iter().enumerate() {
-                        root.print("");
-                        // Evaluate AST
-                        let val = root.evaluate();
-                        match val {
-                            Ok(v) => {
-                                let ret: String = v.get_value();
-                                println!("Rule: {}", ret.fg::<DarkCyan>());
-                                // Save result
-                                if ret == "true" {
-                                    println!(
-                                        "{} transaction: {} From: {}",
-                                        "Allow".green(),
-                                        property.transaction_hash.clone().unwrap(),
-                                        def_file.name.yellow()
-                                    );
-                                    results.push(true);
-                                } else {
-                                    println!(
-                                        "{} transaction: {} From: {} Line {}",
-                                        "Deny".red(),
-                                        property.transaction_hash.clone().unwrap(),
-                                        def_file.name.yellow(),
-                                        l
-                                    );
-                                    fail_reason.push(format!("{}: Line {}", def_file.name, l));
-                                    results.push(false);
-                                }
-                            }
-                            Err(e) => {
-                                println!(
-                                    "{} transaction: {} From: {} Line {}",
-                                    "Deny".red(),
-                                    property.transaction_hash.clone().unwrap(),
-                                    def_file.name.yellow(),
-                                    l
-                                );
-                                println!("Error: {}", e);
-                                fail_reason.push(format!("{}: Line {}", def_file.name, l));
-                                results.push(false);
-                            }
-                        }
-                    }
-                }
+    for (file, (outcome, tracked, rpc_calls)) in matched.iter().zip(outcomes) {
+        // Remember the values read with prev() in the thread of the event
+        adopt_tracked_values(tracked);
+        adopt_rpc_calls(rpc_calls);
+        checked_vec.push(file.name.clone());
+        match outcome {
+            PropertyOutcome::Allow => results.push(true),
+            PropertyOutcome::Deny(reasons) => {
+                fail_reason.extend(reasons);
+                results.push(false);
             }
-            Err(e) => {
-                println!("Failed to read: {}", path.to_str().unwrap());
-                println!("Error: {}", e);
+            PropertyOutcome::TimedOut if TIMEOUT_FAIL_OPEN.load(atomic::Ordering::Relaxed) => {
+                results.push(true);
+            }
+            PropertyOutcome::TimedOut => {
+                fail_reason.push(format!("{}: timeout", file.name));
+                results.push(false);
+            }
+            PropertyOutcome::Failed => {
+                return false;
             }
         }
     }
+    return true;
+}
+
+/// Evaluates the rules of a Talon file, every rule line must be true.
+/// Each denied line is a reason of its own, e.g. `limit.talon: Line 2`.
+fn evaluate_talon_rules(property: &Properties, file: &PropertyFile) -> PropertyOutcome {
+    if let Some(ev) = file.definition.get("event").and_then(|ev| ev.as_str()) {
+        set_var!("event_signature", ev);
+    }
+    let (line_results, errors) = file.evaluate_lines();
+    for e in errors {
+        log_error!("Error: {}", e);
+    }
+    let denied = file.patterns
+        .iter()
+        .zip(line_results)
+        .filter(|(_, result)| result != "true")
+        .map(|(pattern, _)| {
+            log_info!(
+                "{} transaction: {} From: {} Line {}",
+                "Deny".if_color(|s| s.red()),
+                property.transaction_hash.clone().unwrap(),
+                file.name.if_color(|s| s.yellow()),
+                pattern.line
+            );
+            format!("{}: Line {}", file.name, pattern.line)
+        })
+        .collect::<Vec<String>>();
+    if denied.is_empty() { PropertyOutcome::Allow } else { PropertyOutcome::Deny(denied) }
 }
 
 // Setup a TCP thread acting as a broadcaster for events
 fn setup_event_ws(
     addr: String,
-    event_queue: Arc<BlockingQueue<Event>>
+    event_queue: Arc<BlockingQueue<Event>>,
+    backlog_size: usize,
+    format: EventFormat
 ) -> Result<(JoinHandle<()>, JoinHandle<()>), String> {
     // Build TCP Endpoint
     let listener = TcpListener::bind(addr).expect("Failed to bind Address");
     let connections: Arc<Mutex<Vec<TcpStream>>> = Arc::new(Mutex::new(Vec::new()));
     let connections_clone = Arc::clone(&connections);
+    // Last events for clients connecting later. Always locked after the connections.
+    let backlog: Arc<Mutex<EventBacklog>> = Arc::new(Mutex::new(EventBacklog::new(backlog_size)));
+    let backlog_clone = Arc::clone(&backlog);
 
     // Get Connections
     let connection_handler = thread::spawn(move || {
         for stream in listener.incoming() {
             let mut connections = connections_clone.lock().unwrap();
-            println!("New connection: {}", stream.as_ref().unwrap().peer_addr().unwrap());
-            connections.push(stream.unwrap());
+            let mut stream = stream.unwrap();
+            println!("New connection: {}", stream.peer_addr().unwrap());
+            // Send the backlog before the live events
+            for event in backlog_clone.lock().unwrap().events() {
+                if let Err(e) = stream.write_all(&format.encode(event)) {
+                    println!("Error {}: {}", stream.peer_addr().unwrap(), e);
+                }
+            }
+            connections.push(stream);
         }
     });
     //let event_queue_clone: Arc<BlockingQueue<Event>> = Arc::clone(&event_queue);
@@ -733,21 +1616,31 @@ fn setup_event_ws(
     let event_handler = thread::spawn(move || {
         loop {
             let event = event_queue.pop();
-            println!("{:?}", event);
-
-            for (id, mut x) in connections_clone2.lock().unwrap().iter_mut().enumerate() {
-                match x.write_all(&serde_json::to_vec(&event).unwrap()) {
+            log_debug!("{:?}", event);
+
+            let mut connections = connections_clone2.lock().unwrap();
+            backlog.lock().unwrap().push(event.clone());
+            // The connections are already locked, closed streams are removed after the broadcast
+            let mut closed: Vec<usize> = vec![];
+            let bytes = format.encode(&event);
+            for (id, x) in connections.iter_mut().enumerate() {
+                match x.write_all(&bytes) {
                     Ok(_) => {}
                     Err(e) =>
                         match e.kind() {
-                            ErrorKind::ConnectionAborted | ErrorKind::ConnectionReset => {
-                                x.shutdown(std::net::Shutdown::Both).unwrap();
-                                connections_clone2.lock().unwrap().remove(id);
+                            | ErrorKind::ConnectionAborted
+                            | ErrorKind::ConnectionReset
+                            | ErrorKind::BrokenPipe => {
+                                let _ = x.shutdown(std::net::Shutdown::Both);
+                                closed.push(id);
                             }
-                            _ => println!("Error {}: {}", x.peer_addr().unwrap(), e),
+                            _ => println!("Error: {}", e),
                         }
                 }
             }
+            for id in closed.into_iter().rev() {
+                connections.remove(id);
+            }
             // connections_clone2.lock().unwrap().iter().for_each(|mut x| {
             //     match x.write_all(&serde_json::to_vec(&event).unwrap()) {
             //         Ok(_) => {},
@@ -777,12 +1670,557 @@ fn log_evaluation(evaluation: utils::Evaluation) {
     evaluation.store();
 }
 
+#[test]
+fn test_constants() {
+    load_constants(None).unwrap();
+    for (pattern, expected) in [
+        ("$ether == 1000000000000000000", "true"),
+        ("$gwei == 1000000000", "true"),
+        ("$max_uint == 115792089237316195423570985008687907853269984665640564039457584007913129639935", "true"),
+    ] {
+        assert_eq!(build_ast_root(pattern).unwrap().evaluate().unwrap().get_value(), expected, "{}", pattern);
+    }
+
+    let dir = crate::utils::TempDir::new("constants");
+    let file = dir.join("constants.json");
+    fs::write(&file, r#"{"usdc": 1000000, "wad": "0xde0b6b3a7640000"}"#).unwrap();
+    load_constants(Some(&file)).unwrap();
+    assert_eq!(build_ast_root("$usdc == 1000000").unwrap().evaluate().unwrap().get_value(), "true");
+    assert_eq!(build_ast_root("$wad == $ether").unwrap().evaluate().unwrap().get_value(), "true");
+
+    fs::write(&file, r#"{"usdc": "a lot"}"#).unwrap();
+    assert!(load_constants(Some(&file)).is_err());
+}
+
+#[test]
+fn test_reorg_predicate() {
+    let event = |block: u64| {
+        let mut property = Properties::new();
+        property.src_chain = Some("reorg_predicate_chain".to_string());
+        property.block_number = Some(u256::from(block));
+        let _scope = EventScope::enter();
+        set_event_variables(&property);
+        build_ast_root("is_reorg()").unwrap().evaluate().unwrap().get_value()
+    };
+    assert_eq!(event(200), "false");
+    assert_eq!(event(201), "false");
+    assert_eq!(event(199), "true");
+    assert_eq!(event(202), "false");
+}
+
+#[test]
+fn test_allowlist_bypass() {
+    use crate::configs::address_list::AddressEntry;
+
+    // The privileged account check denies every other payer
+    let mut property = Properties::new();
+    property.occured_event = Some("PrivilegedEvent()".to_string());
+    property.src_chain = Some("ethereum".to_string());
+    property.transaction_hash = Some("0x1".to_string());
+    property.payer_address = Some("0x000000000000000000000000000000000000dEaD".to_string());
+
+    let _scope = EventScope::enter();
+    set_var!("payer_address", property.payer_address.clone().unwrap());
+
+    let empty = AddressList::default();
+    let (allowance, checked) = evaluate_properties(&property, None, &empty, &empty);
+    assert_eq!(allowance, Allowance::Deny(vec!["generic_privilege_escalation.json".to_string()]));
+    assert_eq!(checked, vec!["generic_privilege_escalation.json".to_string()]);
+
+    let allowlist = AddressList {
+        addresses: vec![AddressEntry {
+            chain: "ethereum".to_string(),
+            address: "0x000000000000000000000000000000000000dead".to_string(),
+        }],
+    };
+    let (allowance, checked) = evaluate_properties(&property, None, &allowlist, &empty);
+    assert_eq!(allowance, Allowance::Allow);
+    assert!(checked.is_empty());
+}
+
+#[test]
+fn test_open_breaker_address_lists() {
+    use crate::configs::address_list::AddressEntry;
+
+    let mut property = Properties::new();
+    property.occured_event = Some("PrivilegedEvent()".to_string());
+    property.src_chain = Some("ethereum".to_string());
+    property.transaction_hash = Some("0x4".to_string());
+    property.payer_address = Some("0x000000000000000000000000000000000000dEaD".to_string());
+
+    let mut breakers = CircuitBreakers::default();
+    breakers.configure(BreakerConfig { threshold: 1, ..BreakerConfig::default() });
+    let empty = AddressList::default();
+    let payer = AddressList {
+        addresses: vec![AddressEntry {
+            chain: "ethereum".to_string(),
+            address: "0x000000000000000000000000000000000000dead".to_string(),
+        }],
+    };
+
+    // A closed breaker evaluates the events of unlisted payers
+    assert_eq!(decide_unevaluated(&property, &mut breakers, &empty, &empty, true), None);
+
+    assert!(breakers.record("ethereum", true, Instant::now()));
+    // A denylisted payer is denied even if the breaker policy allows the events of the chain
+    assert_eq!(
+        decide_unevaluated(&property, &mut breakers, &empty, &payer, true),
+        Some(Allowance::Deny(vec!["denylist".to_string()]))
+    );
+    // An allowlisted payer is allowed even if the breaker policy denies them
+    assert_eq!(decide_unevaluated(&property, &mut breakers, &payer, &empty, false), Some(Allowance::Allow));
+    // Unlisted payers get the breaker policy
+    assert_eq!(
+        decide_unevaluated(&property, &mut breakers, &empty, &empty, false),
+        Some(Allowance::Deny(vec!["circuit breaker open".to_string()]))
+    );
+    assert_eq!(decide_unevaluated(&property, &mut breakers, &empty, &empty, true), Some(Allowance::Allow));
+}
+
+#[test]
+fn test_no_matching_property() {
+    let mut property = Properties::new();
+    property.occured_event = Some("UnmonitoredEvent()".to_string());
+    property.src_chain = Some("ethereum".to_string());
+    property.transaction_hash = Some("0x3".to_string());
+
+    let _scope = EventScope::enter();
+    let empty = AddressList::default();
+    let (allowance, checked) = evaluate_properties(&property, None, &empty, &empty);
+    assert_eq!(allowance, Allowance::NoMatch);
+    assert!(checked.is_empty());
+
+    // Unmatched events pass unless --default-deny is set
+    assert_eq!(apply_default_policy(allowance.clone(), false), Allowance::NoMatch);
+    assert_eq!(
+        apply_default_policy(allowance, true),
+        Allowance::Deny(vec!["no matching rule".to_string()])
+    );
+    // Matched events are not affected
+    assert_eq!(apply_default_policy(Allowance::Allow, true), Allowance::Allow);
+}
+
+#[test]
+fn test_denylist_deny() {
+    use crate::configs::address_list::AddressEntry;
+    use crate::sockets::enricher::TransactionFields;
+
+    // The payer is the privileged account, so the properties would allow the transaction
+    let mut property = Properties::new();
+    property.occured_event = Some("PrivilegedEvent()".to_string());
+    property.src_chain = Some("ethereum".to_string());
+    property.transaction_hash = Some("0x2".to_string());
+    property.payer_address = Some("0xa58A9d3A5E240b09Da3Bc0BFc011AF3d20D31763".to_string());
+
+    let _scope = EventScope::enter();
+    set_var!("payer_address", property.payer_address.clone().unwrap());
+
+    let empty = AddressList::default();
+    let (allowance, _) = evaluate_properties(&property, None, &empty, &empty);
+    assert_eq!(allowance, Allowance::Allow);
+
+    let fields = TransactionFields {
+        contract_address: "0x00000000000000000000000000000000000000BB".to_string(),
+        ..Default::default()
+    };
+    let denylist = AddressList {
+        addresses: vec![AddressEntry {
+            chain: "ethereum".to_string(),
+            address: "0x00000000000000000000000000000000000000bb".to_string(),
+        }],
+    };
+    let (allowance, checked) = evaluate_properties(&property, Some(&fields), &empty, &denylist);
+    assert_eq!(allowance, Allowance::Deny(vec!["denylist".to_string()]));
+    // No property file was evaluated
+    assert!(checked.is_empty());
+
+    // The denylist takes precedence over the allowlist
+    let (allowance, _) = evaluate_properties(&property, Some(&fields), &denylist, &denylist);
+    assert_eq!(allowance, Allowance::Deny(vec!["denylist".to_string()]));
+}
+
+#[test]
+fn test_wildcard_chain() {
+    let dir = crate::utils::TempDir::new("wildcard");
+    fs::write(
+        dir.join("any_chain.json"),
+        r#"{"event": "Limit()", "chain_name": "*", "properties": {}, "pattern": ["1 < 2"]}"#
+    ).unwrap();
+    fs::write(
+        dir.join("bsc_only.json"),
+        r#"{"event": "Limit()", "chain_name": "bsc", "properties": {}, "pattern": ["1 < 2"]}"#
+    ).unwrap();
+    let (properties, errors) = PropertySet::load(&dir).unwrap();
+    assert!(errors.is_empty());
+
+    let _scope = EventScope::enter();
+    let check = |chain: &str, event: String| -> Vec<String> {
+        let mut property = Properties::new();
+        property.occured_event = Some(event);
+        property.src_chain = Some(chain.to_string());
+        property.transaction_hash = Some("0x1".to_string());
+        let mut checked = vec![];
+        process_properties(property, &properties, &mut vec![], &mut checked, &mut vec![]);
+        checked
+    };
+
+    // Ethereum matches the topic of the signature
+    assert_eq!(check("ethereum", utils::get_ethereum_topic_ids("Limit()")), vec!["any_chain.json"]);
+    assert_eq!(check("solana", "Limit()".to_string()), vec!["any_chain.json"]);
+    assert_eq!(check("bsc", "Limit()".to_string()), vec!["any_chain.json", "bsc_only.json"]);
+    // The event must still match
+    assert!(check("solana", "Other()".to_string()).is_empty());
+
+}
+
+#[test]
+fn test_parallel_properties() {
+    use crate::configs::connection::Connection;
+
+    // Mocked node that answers each request of block_timestamp() after a delay
+    let delay = Duration::from_millis(400);
+    let (addr, _) = utils::mock_http_server(None, move |_| {
+        sleep(delay);
+        ("200 OK", r#"{"jsonrpc":"2.0","id":1,"result":{"number":"0x10","timestamp":"0x6553f100"}}"#.to_string())
+    });
+    ConnectionConfig {
+        connections: vec![Connection {
+            name: "parallel_chain".to_string(),
+            rpc_url: format!("ws://{}", addr),
+            ..Connection::default()
+        }],
+    }.register();
+
+    let dir = crate::utils::TempDir::new("parallel");
+    let files = [
+        ("a_slow.json", "block_timestamp(parallel_chain) > 0"),
+        ("b_slow.json", "block_timestamp(parallel_chain) < 1"),
+        ("c_local.json", "assign(parallel_limit, 5)"),
+    ];
+    for (name, pattern) in files {
+        let definition = serde_json::json!({
+            "event": "Limit()",
+            "chain_name": "solana",
+            "properties": {},
+            "pattern": [pattern, "$parallel_limit < 10"],
+        });
+        fs::write(dir.join(name), definition.to_string()).unwrap();
+    }
+    let (properties, errors) = PropertySet::load(&dir).unwrap();
+    assert!(errors.is_empty());
+
+    let _scope = EventScope::enter();
+    set_var!("parallel_limit", u256::from(7u64));
+    let mut property = Properties::new();
+    property.occured_event = Some("Limit()".to_string());
+    property.src_chain = Some("solana".to_string());
+    property.transaction_hash = Some("0x1".to_string());
+    let (mut results, mut checked, mut fail_reason) = (vec![], vec![], vec![]);
+    let now = Instant::now();
+    assert!(process_properties(property, &properties, &mut results, &mut checked, &mut fail_reason));
+    let elapsed = now.elapsed();
+
+    // Both slow requests ran at the same time
+    assert!(elapsed < delay * 2, "{:?}", elapsed);
+    // The outcomes are merged in the order of the files
+    assert_eq!(checked, vec!["a_slow.json", "b_slow.json", "c_local.json"]);
+    assert_eq!(results, vec![true, false, true]);
+    assert_eq!(fail_reason, vec!["b_slow.json"]);
+    // Variables assigned by a file do not leak into the event
+    assert_eq!(get_var!(u256 "parallel_limit"), Some(u256::from(7u64)));
+
+}
+
+#[test]
+fn test_property_timeout() {
+    use crate::configs::connection::Connection;
+
+    // Mocked node that answers block_timestamp() after the deadline
+    let delay = Duration::from_millis(1000);
+    let (addr, _) = utils::mock_http_server(Some(1), move |_| {
+        sleep(delay);
+        ("200 OK", String::new())
+    });
+    ConnectionConfig {
+        connections: vec![Connection {
+            name: "timeout_chain".to_string(),
+            rpc_url: format!("ws://{}", addr),
+            ..Connection::default()
+        }],
+    }.register();
+
+    let dir = crate::utils::TempDir::new("timeout");
+    fs::write(
+        dir.join("slow.json"),
+        r#"{"event": "Limit()", "chain_name": "solana", "properties": {}, "pattern": ["block_timestamp(timeout_chain) > 0"]}"#
+    ).unwrap();
+    let (properties, _) = PropertySet::load(&dir).unwrap();
+    let mut property = Properties::new();
+    property.occured_event = Some("Limit()".to_string());
+    property.src_chain = Some("solana".to_string());
+    property.transaction_hash = Some("0x1".to_string());
+
+    let now = Instant::now();
+    let (outcome, _, _) = evaluate_with_timeout(
+        &property,
+        &properties.files[0],
+        &VariableMap::new(),
+        Some(Duration::from_millis(100))
+    );
+    // The evaluation is abandoned at the deadline
+    assert!(matches!(outcome, PropertyOutcome::TimedOut));
+    assert!(now.elapsed() < delay);
+
+}
+
+#[test]
+fn test_run_bounded() {
+    let running = atomic::AtomicUsize::new(0);
+    let max_running = atomic::AtomicUsize::new(0);
+    let results = run_bounded(&[3u64, 1, 2, 5, 4], 2, |n| {
+        let now = running.fetch_add(1, atomic::Ordering::SeqCst) + 1;
+        max_running.fetch_max(now, atomic::Ordering::SeqCst);
+        sleep(Duration::from_millis(20 * n));
+        running.fetch_sub(1, atomic::Ordering::SeqCst);
+        n * 10
+    });
+    assert_eq!(results, vec![30, 10, 20, 50, 40]);
+    assert!(max_running.load(atomic::Ordering::SeqCst) <= 2);
+}
+
+#[test]
+fn test_receive_events_ends_without_senders() {
+    let (tx, rx): (Sender<Properties>, Receiver<Properties>) = mpsc::channel();
+    let event_queue: Arc<BlockingQueue<Event>> = Arc::new(BlockingQueue::new());
+    event_queue.push(Event {
+        result: Allowance::Allow,
+        checked: vec![],
+        chain: "ethereum".to_string(),
+        transaction_hash: "0x1".to_string(),
+    });
+
+    let queue = event_queue.clone();
+    let event_thread = thread::spawn(move || {
+        let denylist = WatchedAddressList::new(PathBuf::from("config/does_not_exist.json"));
+        receive_events(rx, queue, None, &AddressList::default(), &denylist);
+    });
+    // Slow broadcaster
+    let queue = event_queue.clone();
+    let broadcaster = thread::spawn(move || {
+        sleep(Duration::from_millis(100));
+        queue.pop()
+    });
+
+    drop(tx);
+    // The thread ends without panicking and only after the queued event was sent
+    event_thread.join().unwrap();
+    assert_eq!(event_queue.len(), 0);
+    assert_eq!(broadcaster.join().unwrap().transaction_hash, "0x1");
+}
+
+#[test]
+fn test_talon_rules() {
+    let dir = crate::utils::TempDir::new("rules");
+    fs::write(
+        dir.join("limit.talon"),
+        "event: Limit(uint256 value)\nchain_name: ethereum\n{\n1 < 2\n$value < 1000\n}\n"
+    ).unwrap();
+    fs::write(dir.join("other.talon"), "event: Other()\n{\n1 > 2\n}\n").unwrap();
+    fs::write(dir.join("notes.txt"), "event: Limit(uint256 value)\n{\n1 > 2\n}\n").unwrap();
+
+    // The rules are compiled once like the property files
+    let mut rules = PropertySet::default();
+    assert!(rules.load_rules(&dir).is_empty());
+    assert_eq!(rules.files.len(), 2);
+
+    let mut property = Properties::new();
+    property.occured_event = Some(utils::get_ethereum_topic_ids("Limit(uint256)"));
+    property.src_chain = Some("ethereum".to_string());
+    property.transaction_hash = Some("0x1".to_string());
+
+    let check = |value: u64| {
+        let _scope = EventScope::enter();
+        set_var!("value", u256::from(value));
+        let (mut results, mut checked, mut fail_reason) = (vec![], vec![], vec![]);
+        assert!(process_properties(property.clone(), &rules, &mut results, &mut checked, &mut fail_reason));
+        (results, checked, fail_reason)
+    };
+
+    let (results, checked, fail_reason) = check(10);
+    assert_eq!(results, vec![true]);
+    assert_eq!(checked, vec!["limit.talon"]);
+    assert!(fail_reason.is_empty());
+
+    // The second rule denies the transaction
+    let (results, _, fail_reason) = check(5000);
+    assert_eq!(results, vec![false]);
+    assert_eq!(fail_reason, vec!["limit.talon: Line 2"]);
+
+}
+
+#[test]
+fn test_eval_subcommand() {
+    let dir = crate::utils::TempDir::new("eval_vars");
+    let vars = dir.join("eval_vars.json");
+    fs::write(&vars, r#"["assign(eval_limit, 1000)"]"#).unwrap();
+
+    let args = Args::try_parse_from([
+        "brigade",
+        "eval",
+        "$eval_limit + 24 == 1024",
+        "--vars",
+        vars.to_str().unwrap(),
+    ]).unwrap();
+    let Some(Command::Eval { expression, vars: var_file }) = args.command else {
+        panic!("expected the eval subcommand");
+    };
+    let val = eval_expression(&expression, var_file.as_deref()).unwrap();
+    assert_eq!(val.get_constant_info(), ("Bool", "true".to_string()));
+
+    assert!(eval_expression("1 < 2", Some(Path::new("does_not_exist.json"))).is_err());
+}
+
+#[test]
+fn test_init_example() {
+    use crate::properties::ast::RPCRequest;
+
+    let dir = crate::utils::TempDir::new("init");
+    let directories = Directories::new(Some(dir.to_path_buf()), None);
+    let files = init_example(&directories).unwrap();
+    assert!(files.iter().all(|(_, created)| *created));
+
+    // The files parse with the deserializers of the monitor
+    let connections = ConnectionConfig::from_file(dir.join("config/connections.json").to_str().unwrap()).unwrap();
+    assert_eq!(connections.connections[0].name, "ethereum");
+    let bridge: BridgeConfig = serde_json::from_str(
+        &fs::read_to_string(dir.join("config/example_config.json")).unwrap()
+    ).unwrap();
+    assert!(bridge.contracts.iter().all(|c| c.validate().is_ok()));
+    assert_eq!(lint(&dir.join("properties"), &dir.join("rules")).map_err(|e| format!("{:?}", e)), Ok(2));
+    let rpc: RPCRequest = serde_json::from_str(&fs::read_to_string(dir.join("functions/ethereum/rpc.json")).unwrap()).unwrap();
+    assert_eq!(rpc.method, "$method");
+
+    // Existing files are kept
+    fs::write(dir.join("config/connections.json"), "{}").unwrap();
+    let files = init_example(&directories).unwrap();
+    assert!(files.iter().all(|(_, created)| !*created));
+    assert_eq!(fs::read_to_string(dir.join("config/connections.json")).unwrap(), "{}");
+}
+
+#[test]
+fn test_topic_subcommand() {
+    let args = Args::try_parse_from([
+        "brigade",
+        "topic",
+        "Transfer(address indexed from, address indexed to, uint value)",
+        "Approval(address,address,uint256)",
+    ]).unwrap();
+    let Some(Command::Topic { signatures }) = args.command else {
+        panic!("expected the topic subcommand");
+    };
+    assert_eq!(topic_lines(&signatures), vec![
+        "Transfer(address,address,uint256): 0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef",
+        "Approval(address,address,uint256): 0x8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925",
+    ]);
+    assert!(Args::try_parse_from(["brigade", "topic"]).is_err());
+}
+
+#[test]
+fn test_lint_exit_code() {
+    let dir = crate::utils::TempDir::new("lint_exit");
+    fs::write(dir.join("valid.json"), r#"{"event": "Limit()", "chain_name": "*", "pattern": ["1 < 2"]}"#).unwrap();
+    assert_eq!(run_lint(&dir, &dir.join("rules")), 0);
+
+    fs::write(dir.join("invalid.json"), r#"{"event": "Limit()", "pattern": ["1 < 2"]}"#).unwrap();
+    assert_eq!(run_lint(&dir, &dir.join("rules")), 1);
+}
+
+#[test]
+fn test_rpc_call_counter() {
+    let dir = crate::utils::TempDir::new("rpc_calls");
+    // The requests fail without a connection, but they are counted anyway
+    fs::write(
+        dir.join("calls.json"),
+        r#"{
+            "event": "Limit()",
+            "chain_name": "*",
+            "properties": {
+                "balance": "call(rpc_counter_chain, eth_getBalance, [0x1, latest]).get(result)",
+                "nonce": "call(rpc_counter_chain, eth_getTransactionCount, [0x1, latest]).get(result)",
+                "block": "call(rpc_counter_other, eth_blockNumber, []).get(result)"
+            },
+            "pattern": ["1 < 2"]
+        }"#
+    ).unwrap();
+    let (properties, _) = PropertySet::load(&dir).unwrap();
+
+    let _scope = EventScope::enter();
+    let mut property = Properties::new();
+    property.occured_event = Some("Limit()".to_string());
+    property.src_chain = Some("rpc_counter_chain".to_string());
+    property.transaction_hash = Some("0x1".to_string());
+    process_properties(property.clone(), &properties, &mut vec![], &mut vec![], &mut vec![]);
+
+    // The calls of the worker threads are counted for the event
+    assert_eq!(record_event_rpc_calls(&property, 2), 3);
+    let health = get_health_state().lock().unwrap();
+    assert_eq!(health.rpc_calls.get("rpc_counter_chain"), Some(&2));
+    assert_eq!(health.rpc_calls.get("rpc_counter_other"), Some(&1));
+    drop(health);
+    // The counter starts over with the next event
+    assert_eq!(record_event_rpc_calls(&property, 2), 0);
+
+}
+
+#[test]
+fn test_chain_id_matching() {
+    let dir = crate::utils::TempDir::new("chain_id");
+    fs::write(
+        dir.join("arbitrum.json"),
+        r#"{"event": "Limit()", "chain_id": 42161, "properties": {}, "pattern": ["1 < 2"]}"#
+    ).unwrap();
+    fs::write(
+        dir.join("optimism.json"),
+        r#"{"event": "Limit()", "chain_name": "ethereum", "chain_id": 10, "properties": {}, "pattern": ["1 < 2"]}"#
+    ).unwrap();
+    fs::write(
+        dir.join("by_name.json"),
+        r#"{"event": "Limit()", "chain_name": "arbitrum", "properties": {}, "pattern": ["1 < 2"]}"#
+    ).unwrap();
+    let (mut properties, _) = PropertySet::load(&dir).unwrap();
+    // Talon rules match the chain_id the same way
+    let rules = crate::utils::TempDir::new("chain_id_rules");
+    fs::write(rules.join("arbitrum.talon"), "event: Limit()\nchain_id: 42161\n{\n1 < 2\n}\n").unwrap();
+    assert!(properties.load_rules(&rules).is_empty());
+
+    let _scope = EventScope::enter();
+    let check = |chain: &str, chain_id: Option<u64>| -> Vec<String> {
+        let mut property = Properties::new();
+        // Both chains emit the same topic
+        property.occured_event = Some(utils::get_ethereum_topic_ids("Limit()"));
+        property.src_chain = Some(chain.to_string());
+        property.src_chain_id = chain_id;
+        property.transaction_hash = Some("0x1".to_string());
+        let mut checked = vec![];
+        process_properties(property, &properties, &mut vec![], &mut checked, &mut vec![]);
+        checked
+    };
+
+    assert_eq!(check("arbitrum", Some(42161)), vec!["arbitrum.json", "by_name.json", "arbitrum.talon"]);
+    assert_eq!(check("optimism", Some(10)), vec!["optimism.json"]);
+    // Without an id only the name matches
+    assert!(check("ethereum", None).is_empty());
+
+}
+
 #[test]
 fn test_event_broadcast() {
     let event_queue: Arc<BlockingQueue<Event>> = Arc::new(BlockingQueue::new());
     let (handle1, handle2) = setup_event_ws(
         "127.0.0.1:8080".to_string(),
-        event_queue.clone()
+        event_queue.clone(),
+        0,
+        EventFormat::Compact
     ).unwrap();
 
     let remote_thread = thread::spawn(move || {
@@ -837,3 +2275,69 @@ fn test_receiving() {
     remote_thread2.join().unwrap();
     remote_thread3.join().unwrap();
 }
+
+#[test]
+fn test_event_backlog() {
+    let event_queue: Arc<BlockingQueue<Event>> = Arc::new(BlockingQueue::new());
+    let _handles = setup_event_ws(
+        "127.0.0.1:18324".to_string(),
+        event_queue.clone(),
+        2,
+        EventFormat::Compact
+    ).unwrap();
+
+    for hash in ["0x1", "0x2", "0x3"] {
+        event_queue.push(Event {
+            result: Allowance::Deny(vec!["definition1".to_string()]),
+            checked: vec!["definition1".to_string()],
+            chain: "ethereum".to_string(),
+            transaction_hash: hash.to_string(),
+        });
+    }
+    // Wait until the events are broadcasted without any client
+    sleep(Duration::from_millis(200));
+
+    let remote = TcpStream::connect("127.0.0.1:18324").unwrap();
+    let received: Vec<String> = serde_json::Deserializer
+        ::from_reader(remote)
+        .into_iter::<Event>()
+        .take(2)
+        .map(|e| e.unwrap().transaction_hash)
+        .collect();
+    // Only the last two events are kept
+    assert_eq!(received, vec!["0x2", "0x3"]);
+}
+
+#[test]
+fn test_ndjson_broadcast() {
+    use std::io::{ BufRead, BufReader };
+
+    let event_queue: Arc<BlockingQueue<Event>> = Arc::new(BlockingQueue::new());
+    let _handles = setup_event_ws(
+        "127.0.0.1:18325".to_string(),
+        event_queue.clone(),
+        0,
+        EventFormat::Ndjson
+    ).unwrap();
+    let remote = TcpStream::connect("127.0.0.1:18325").unwrap();
+    // Wait until the connection is accepted
+    sleep(Duration::from_millis(200));
+
+    for hash in ["0x1", "0x2"] {
+        event_queue.push(Event {
+            result: Allowance::Allow,
+            checked: vec![],
+            chain: "ethereum".to_string(),
+            transaction_hash: hash.to_string(),
+        });
+    }
+
+    let mut reader = BufReader::new(remote);
+    for hash in ["0x1", "0x2"] {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        // Each event is one line
+        assert!(line.ends_with('\n'));
+        assert_eq!(serde_json::from_str::<Event>(&line).unwrap().transaction_hash, hash);
+    }
+}
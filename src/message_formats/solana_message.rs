@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+// **************************************
+// Solana logsSubscribe Notification
+// **************************************
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LogMessage {
@@ -33,9 +36,17 @@ pub struct Ctx {
 #[serde(rename_all = "camelCase")]
 pub struct Val {
     pub signature: String,
+    /// `null` if the transaction succeeded, otherwise the error, e.g. `{"InstructionError": [0, {"Custom": 1}]}`
+    #[serde(default)]
     pub err: Value,
     pub logs: Vec<String>,
 }
+
+impl Val {
+    pub fn is_failed(&self) -> bool {
+        !self.err.is_null()
+    }
+}
 // **************************************
 // Solana Transaction Message
 // **************************************
@@ -62,12 +73,13 @@ pub struct TransactionResult {
 pub struct Meta {
     pub compute_units_consumed: i64,
     pub err: Value,
-    pub fee: i64,
+    /// Lamports are unsigned 64 bit numbers
+    pub fee: u64,
     pub inner_instructions: Vec<Value>,
     pub log_messages: Vec<String>,
-    pub post_balances: Vec<i64>,
+    pub post_balances: Vec<u64>,
     pub post_token_balances: Vec<Value>,
-    pub pre_balances: Vec<i64>,
+    pub pre_balances: Vec<u64>,
     pub pre_token_balances: Vec<Value>,
     pub rewards: Vec<Value>,
     pub status: TransactionStatus,
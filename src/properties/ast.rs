@@ -1,18 +1,36 @@
 use core::panic;
-use std::collections::{ HashMap, VecDeque };
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::{ BTreeMap, HashMap, VecDeque };
 use std::env::args;
 use std::fs;
 use std::mem::uninitialized;
 use std::path::Path;
+use std::sync::atomic::{ self, AtomicBool, AtomicUsize };
 
 use crate::configs::connection::{ConnectionConfig, get_established_connections};
-use crate::utils::Evaluation;
-use crate::{ get_var, set_var, utils };
+use crate::utils::{ log_enabled, Evaluation, LogLevel, Paint };
+use crate::{ get_var, log_debug, log_error, set_var, utils };
 
+use super::custom_functions::block_timestamp;
 use super::error::ASTError;
 
-use super::environment::{ get_variable, get_variable_map_instance, VarValues, VariableMap };
-use ethnum::{ i256, u256 };
+use super::environment::{
+    accumulate_daily,
+    event_subject,
+    get_counter,
+    get_variable,
+    get_variable_map_instance,
+    increment_counter,
+    lookup_variable,
+    missing_variable_value,
+    previous_value,
+    record_rpc_call,
+    VarValues,
+    VariableMap,
+};
+use chrono::Utc;
+use ethnum::{ i256, u256, AsI256 };
 use owo_colors::OwoColorize;
 use serde::{ Deserialize, Serialize };
 use serde_json::Value;
@@ -23,14 +41,75 @@ use std::str::FromStr;
 /// The AST consists of Nodes see ASTNode struct
 /// When evaluating the AST an ASTConstant is returned. See ASTConstant struct
 
+/// Default of the maximal nesting depth of an expression, the same in debug and release builds.
+/// A left associative chain like `1 + 1 + ... + 1` is nested one level per term.
+/// An operator level takes about 9 KB of the stack in debug builds and 0.5 KB in release builds,
+/// so 128 levels fit on the 2 MB stack of a thread. Functions take about 60 KB in debug builds and 4 KB in release builds.
+const DEFAULT_MAX_EXPRESSION_DEPTH: usize = 128;
+
+static MAX_EXPRESSION_DEPTH: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_EXPRESSION_DEPTH);
+
+thread_local! {
+    /// Number of nodes that are currently evaluated in the thread
+    static EVALUATION_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Sets how deep expressions may be nested, deeper expressions are rejected instead of overflowing the stack
+pub fn set_max_expression_depth(depth: usize) {
+    MAX_EXPRESSION_DEPTH.store(depth, atomic::Ordering::Relaxed);
+}
+
+pub fn max_expression_depth() -> usize {
+    MAX_EXPRESSION_DEPTH.load(atomic::Ordering::Relaxed)
+}
+
+/// If true, `"0x"` and `""` can't be converted into numbers instead of being zero
+static STRICT_NUMBERS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_strict_numbers(strict: bool) {
+    STRICT_NUMBERS.store(strict, atomic::Ordering::Relaxed);
+}
+
+pub fn strict_numbers() -> bool {
+    STRICT_NUMBERS.load(atomic::Ordering::Relaxed)
+}
+
+/// Counts a node in the evaluation depth of the thread while it is evaluated
+struct DepthGuard;
+
+impl DepthGuard {
+    fn enter() -> Result<Self, ASTError> {
+        let max = max_expression_depth();
+        EVALUATION_DEPTH.with(|depth| {
+            if depth.get() >= max {
+                return Err(ASTError::MaxDepthExceeded(max));
+            }
+            depth.set(depth.get() + 1);
+            Ok(DepthGuard)
+        })
+    }
+}
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        EVALUATION_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
 /// The conversion target types for the AST results
 #[derive(Debug, Clone, PartialEq)]
 pub enum ConversionTarget {
     String,
     Number,
+    /// Unsigned number parsed in base 16, even if the string only consists of decimal digits
+    HexNumber,
     SignedNumber,
     Hex,
     Address,
+    /// Fixed 32-byte hex value, e.g. a hash, 0x-prefixed, lowercase and padded with leading zeros
+    Bytes32,
+    /// Number of seconds of a duration like 30s, 5m, 1h or 2d
+    Duration,
     Unknown(String),
 }
 
@@ -39,9 +118,12 @@ impl From<&str> for ConversionTarget {
         match s.to_lowercase().as_str() {
             "string" | "'string'" => ConversionTarget::String,
             "u256" | "'u256'" => ConversionTarget::Number,
+            "hexnum" | "'hexnum'" => ConversionTarget::HexNumber,
             "i256" | "'i256'" => ConversionTarget::SignedNumber,
             "hex" | "'hex'" => ConversionTarget::Hex,
             "address" | "'address'" => ConversionTarget::Address,
+            "bytes32" | "'bytes32'" => ConversionTarget::Bytes32,
+            "duration" | "'duration'" => ConversionTarget::Duration,
             _ => ConversionTarget::Unknown(s.to_string()),
         }
     }
@@ -101,6 +183,11 @@ pub enum ArithmeticOperator {
     Divide,
     Modulo,
     Negate,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
 }
 
 impl ArithmeticOperator {
@@ -112,9 +199,26 @@ impl ArithmeticOperator {
             ArithmeticOperator::Divide => "/",
             ArithmeticOperator::Modulo => "%",
             ArithmeticOperator::Negate => "neg",
+            ArithmeticOperator::BitAnd => "&",
+            ArithmeticOperator::BitOr => "|",
+            ArithmeticOperator::BitXor => "^",
+            ArithmeticOperator::ShiftLeft => "<<",
+            ArithmeticOperator::ShiftRight => ">>",
         }
     }
 
+    /// Bitwise operators work on the 256 bit representation of integers
+    pub fn is_bitwise(&self) -> bool {
+        matches!(
+            self,
+            | ArithmeticOperator::BitAnd
+            | ArithmeticOperator::BitOr
+            | ArithmeticOperator::BitXor
+            | ArithmeticOperator::ShiftLeft
+            | ArithmeticOperator::ShiftRight
+        )
+    }
+
     pub fn from_str(string: &str) -> Result<ArithmeticOperator, ASTError> {
         match string {
             "+" => Ok(ArithmeticOperator::Add),
@@ -123,6 +227,11 @@ impl ArithmeticOperator {
             "/" => Ok(ArithmeticOperator::Divide),
             "%" => Ok(ArithmeticOperator::Modulo),
             "neg" => Ok(ArithmeticOperator::Negate),
+            "&" => Ok(ArithmeticOperator::BitAnd),
+            "|" => Ok(ArithmeticOperator::BitOr),
+            "^" => Ok(ArithmeticOperator::BitXor),
+            "<<" => Ok(ArithmeticOperator::ShiftLeft),
+            ">>" => Ok(ArithmeticOperator::ShiftRight),
             _ => Err(ASTError::InvalidArithmeticOperator(string.to_owned())),
         }
     }
@@ -141,10 +250,25 @@ pub enum Functions {
     Remove, // Remove key from Map
     Get, // Get Value by Key from Map
     Assign, // Set Variable
+    Let, // Set Variable and return its value let(name, value)
     ToLower, // Transform String into lower case
     ToUpper, // Transform String into upper case
     Custom, // RPC Calls into a Blockchain
     Require, // Require a condition to execute a statement require(cond, stmt)
+    Decode, // Decode ABI encoded 32 byte words decode(data, [types])
+    AbiEncode, // ABI encode arguments into 32 byte words abi_encode(a, b, ...)
+    Scale, // Divide by 10^decimals, e.g., wei to ether scale(value, decimals)
+    Unscale, // Multiply by 10^decimals, e.g., ether to wei unscale(value, decimals)
+    Incr, // Increment the counter of a key in the persistent map incr(key)
+    Count, // Get the counter of a key in the persistent map count(key)
+    Now, // Current unix time in seconds now()
+    BlockTimestamp, // Timestamp of the latest block of a chain block_timestamp(chain)
+    Prev, // Value of a variable on the previous event of the payer prev(variable)
+    EventParam, // Decoded parameter of the current log by its name in the event signature event_param(name)
+    DailyVolume, // Running total of a key on the current UTC day in the persistent map daily_volume(key, value)
+    Selector, // 4 byte function selector of a signature selector(signature)
+    Concat, // Join the string representations of the arguments concat(a, b, ...)
+    IsReorg, // True if the block of the event is lower than the highest block seen on its chain is_reorg()
 }
 
 impl Functions {
@@ -161,10 +285,25 @@ impl Functions {
             Functions::Remove => "remove",
             Functions::Get => "get",
             Functions::Assign => "assign",
+            Functions::Let => "let",
             Functions::ToLower => "tolower",
             Functions::ToUpper => "toupper",
             Functions::Custom => "call",
             Functions::Require => "require",
+            Functions::Decode => "decode",
+            Functions::AbiEncode => "abi_encode",
+            Functions::Scale => "scale",
+            Functions::Unscale => "unscale",
+            Functions::Incr => "incr",
+            Functions::Count => "count",
+            Functions::Now => "now",
+            Functions::BlockTimestamp => "block_timestamp",
+            Functions::Prev => "prev",
+            Functions::EventParam => "event_param",
+            Functions::DailyVolume => "daily_volume",
+            Functions::Selector => "selector",
+            Functions::Concat => "concat",
+            Functions::IsReorg => "is_reorg",
         }
     }
 
@@ -181,14 +320,35 @@ impl Functions {
             "remove" => Ok(Functions::Remove),
             "get" => Ok(Functions::Get),
             "assign" => Ok(Functions::Assign),
+            "let" => Ok(Functions::Let),
             "tolower" | "toLower" => Ok(Functions::ToLower),
             "toupper" | "toUpper" => Ok(Functions::ToUpper),
             "call" => Ok(Functions::Custom),
             "require" => Ok(Functions::Require),
+            "decode" => Ok(Functions::Decode),
+            "abi_encode" => Ok(Functions::AbiEncode),
+            "scale" => Ok(Functions::Scale),
+            "unscale" => Ok(Functions::Unscale),
+            "incr" => Ok(Functions::Incr),
+            "count" => Ok(Functions::Count),
+            "now" => Ok(Functions::Now),
+            "block_timestamp" => Ok(Functions::BlockTimestamp),
+            "prev" => Ok(Functions::Prev),
+            "event_param" => Ok(Functions::EventParam),
+            "daily_volume" | "dailyVolume" => Ok(Functions::DailyVolume),
+            "selector" => Ok(Functions::Selector),
+            "concat" => Ok(Functions::Concat),
+            "is_reorg" | "isReorg" => Ok(Functions::IsReorg),
             _ => Err(ASTError::InvalidFunction(string.to_owned())),
         }
     }
 
+    /// Functions with an arbitrary number of arguments.
+    /// They must be invoked as `function(a, b, ...)` because the number of arguments is counted in the parentheses.
+    pub fn is_variadic(&self) -> bool {
+        matches!(self, Functions::AbiEncode | Functions::Concat)
+    }
+
     pub fn get_args(string: &str) -> Option<Vec<String>> {
         let s = string[0..string.len() - 1].to_owned();
         Some(
@@ -211,6 +371,7 @@ pub enum ASTNode {
     // Constants
     ConstantBool(bool),
     ConstantNumber(u256),
+    ConstantHexNumber(u256), // Number written as a 0x literal, rendered back in hex in the logs
     ConstantSignedNumber(i256),
     ConstantString(String),
     Array(Vec<Box<ASTNode>>),
@@ -276,6 +437,60 @@ pub enum ASTConstant {
     Map(HashMap<String, ASTConstant>),
 }
 
+/// Converts a JSON value, e.g. an RPC response, directly into a constant, so nested objects are maps that can be
+/// navigated with `.get()`. `null` is the string `None` and numbers that are no integers are kept as strings.
+impl From<&Value> for ASTConstant {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Bool(value) => ASTConstant::Bool(*value),
+            Value::Number(n) => json_number(n),
+            Value::String(value) => ASTConstant::String(value.clone()),
+            Value::Array(arr) => ASTConstant::Array(arr.iter().map(ASTConstant::from).collect()),
+            Value::Object(map) =>
+                ASTConstant::Map(
+                    map
+                        .iter()
+                        .map(|(k, v)| (k.clone(), ASTConstant::from(v)))
+                        .collect()
+                ),
+            Value::Null => ASTConstant::String("None".to_string()),
+        }
+    }
+}
+
+/// Converts a JSON number of any size, e.g. a token amount above `u64::MAX`, into a number.
+/// Non-negative integers become unsigned and negative ones signed numbers. Floats are truncated towards zero,
+/// e.g. `1.9` becomes 1 and `-1.9` becomes -1. Numbers that don't fit into 256 bits or use an exponent stay strings.
+fn json_number(n: &serde_json::Number) -> ASTConstant {
+    let text = n.to_string();
+    let integer = match text.split_once('.') {
+        Some((integer, fraction)) if fraction.chars().all(|c| c.is_ascii_digit()) => integer,
+        _ => &text,
+    };
+    match integer.strip_prefix('-') {
+        Some(digits) if digits.trim_start_matches('0').is_empty() => ASTConstant::Number(u256::ZERO),
+        Some(_) =>
+            integer
+                .parse::<i256>()
+                .map(ASTConstant::SignedNumber)
+                .unwrap_or(ASTConstant::String(text.clone())),
+        None =>
+            integer
+                .parse::<u256>()
+                .map(ASTConstant::Number)
+                .unwrap_or(ASTConstant::String(text.clone())),
+    }
+}
+
+/// Decodes a base58 Solana public key, i.e. 32 to 44 characters of the base58 alphabet decoding to 32 bytes.
+/// Other strings return None, so ordinary words are not taken for base58.
+fn decode_solana_pubkey(v: &str) -> Option<Vec<u8>> {
+    if !(32..=44).contains(&v.len()) {
+        return None;
+    }
+    bs58::decode(v).into_vec().ok().filter(|bytes| bytes.len() == 32)
+}
+
 impl ASTConstant {
     pub fn get_map(&self) -> &HashMap<String, ASTConstant> {
         match self {
@@ -284,7 +499,32 @@ impl ASTConstant {
         }
     }
 
+    /// `"0x"` and `""` are zero like in Ethereum, e.g. the `value` of a transaction without value.
+    /// In strict mode they are no numbers. Returns `None` for all other values and targets.
+    fn convert_empty_hex(&self, target: &ConversionTarget, strict: bool) -> Option<Result<ASTConstant, ASTError>> {
+        let ASTConstant::String(v) = self else {
+            return None;
+        };
+        if !v.is_empty() && v != "0x" {
+            return None;
+        }
+        let zero = match target {
+            ConversionTarget::Number | ConversionTarget::HexNumber => ASTConstant::Number(u256::ZERO),
+            ConversionTarget::SignedNumber => ASTConstant::SignedNumber(i256::ZERO),
+            _ => {
+                return None;
+            }
+        };
+        match strict {
+            true => Some(Err(ASTError::InvalidNumberConversion(v.clone()))),
+            false => Some(Ok(zero)),
+        }
+    }
+
     pub fn convert(&self, target: ConversionTarget) -> Result<ASTConstant, ASTError> {
+        if let Some(result) = self.convert_empty_hex(&target, strict_numbers()) {
+            return result;
+        }
         match target {
             ConversionTarget::String => Ok(ASTConstant::String(self.get_value())),
             ConversionTarget::Number => {
@@ -292,9 +532,6 @@ impl ASTConstant {
                     ASTConstant::Number(v) => Ok(ASTConstant::Number(*v)),
                     ASTConstant::SignedNumber(v) => Ok(ASTConstant::SignedNumber(*v)),
                     ASTConstant::String(v) => {
-                        if v == "0x" {
-                            return Err(ASTError::InvalidNumberConversion(v.clone()));
-                        }
                         if v.starts_with("0x") {
                             Ok(ASTConstant::Number(u256::from_str_hex(v).unwrap()))
                         } else if v.starts_with("u256:") || v.starts_with("i256:") {
@@ -334,6 +571,25 @@ impl ASTConstant {
                         ),
                 }
             }
+            ConversionTarget::HexNumber =>
+                match self {
+                    // Raw hex values without 0x might already be read as decimal numbers, so reinterpret their digits
+                    ASTConstant::Number(_) | ASTConstant::String(_) => {
+                        let v = self.get_value();
+                        let digits = v.strip_prefix("0x").unwrap_or(&v);
+                        match u256::from_str_radix(digits, 16) {
+                            Ok(n) if !digits.is_empty() => Ok(ASTConstant::Number(n)),
+                            _ => Err(ASTError::InvalidConversion(v.to_string(), "hex number".to_string())),
+                        }
+                    }
+                    _ =>
+                        Err(
+                            ASTError::InvalidConversion(
+                                self.get_value().to_string(),
+                                "hex number".to_string()
+                            )
+                        ),
+                }
             ConversionTarget::SignedNumber =>
                 match self {
                     ASTConstant::Number(v) => Ok(ASTConstant::SignedNumber(v.as_i256())),
@@ -377,12 +633,12 @@ impl ASTConstant {
                             match u256::from_str_radix(v, 16) {
                                 Ok(v) => Ok(ASTConstant::String(format!("0x{:x}", v))),
                                 Err(e) => {
-                                    // Check if other encoding:
-                                    match bs58::decode(v).into_vec() {
-                                        Ok(v) => {
+                                    // Check if a base58 encoded Solana public key:
+                                    match decode_solana_pubkey(v) {
+                                        Some(v) => {
                                             Ok(ASTConstant::String(format!("0x{}", hex::encode(v))))
                                         }
-                                        Err(e) =>
+                                        None =>
                                             Err(
                                                 ASTError::InvalidConversion(
                                                     v.to_string(),
@@ -428,8 +684,41 @@ impl ASTConstant {
                             )
                         ),
                 }
+            ConversionTarget::Bytes32 =>
+                match self {
+                    ASTConstant::Number(v) => Ok(ASTConstant::String(format!("0x{:064x}", *v))),
+                    ASTConstant::String(v) => {
+                        let digits = v.strip_prefix("0x").or(v.strip_prefix("0X")).unwrap_or(v).trim_start_matches('0');
+                        if digits.len() > 64 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                            return Err(ASTError::InvalidConversion(v.to_string(), "bytes32".to_string()));
+                        }
+                        Ok(ASTConstant::String(format!("0x{:0>64}", digits.to_lowercase())))
+                    }
+                    _ =>
+                        Err(
+                            ASTError::InvalidConversion(
+                                self.get_value().to_string(),
+                                "bytes32".to_string()
+                            )
+                        ),
+                }
+            ConversionTarget::Duration =>
+                match self {
+                    ASTConstant::Number(v) => Ok(ASTConstant::Number(*v)),
+                    ASTConstant::String(v) =>
+                        parse_duration(v)
+                            .map(ASTConstant::Number)
+                            .ok_or(ASTError::InvalidConversion(v.to_string(), "duration".to_string())),
+                    _ =>
+                        Err(
+                            ASTError::InvalidConversion(
+                                self.get_value().to_string(),
+                                "duration".to_string()
+                            )
+                        ),
+                }
             ConversionTarget::Unknown(s) => {
-                println!("Unknown conversion target {}", s);
+                log_error!("Unknown conversion target {}", s);
                 Err(ASTError::UnknownConversionTarget(s))
             }
         }
@@ -442,7 +731,7 @@ impl ASTConstant {
             ASTConstant::SignedNumber(value) => ("SignedNumber", value.to_string()),
             ASTConstant::String(value) => ("String", value.clone()),
             ASTConstant::Array(value) => ("Array", format!("{:?}", value)),
-            ASTConstant::Map(value) => ("Map", format!("{:?}", value)),
+            ASTConstant::Map(value) => ("Map", format!("{:?}", value.iter().collect::<BTreeMap<_, _>>())),
         }
     }
 
@@ -461,7 +750,10 @@ impl ASTConstant {
                 return format!("[{}]", s);
             }
             ASTConstant::Map(value) => {
-                let s = value
+                // Sort the keys, so the same map is always stringified the same way
+                let mut entries = value.iter().collect::<Vec<_>>();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                let s = entries
                     .iter()
                     .map(|(key, value)| format!("{}: {}", key, value.get_value()))
                     .collect::<Vec<String>>()
@@ -500,16 +792,21 @@ impl ASTConstant {
 }
 
 impl ASTNode {
+    /// Print the tree, only in debug output
     pub fn print(&self, prefix: &str) {
+        if !log_enabled(LogLevel::Debug) {
+            return;
+        }
         match self {
-            ASTNode::ConstantBool(b) => println!("{}└── {}: {}", prefix, "Bool".yellow(), b),
-            ASTNode::ConstantNumber(n) => println!("{}└── {}: {}", prefix, "Number".yellow(), n),
+            ASTNode::ConstantBool(b) => println!("{}└── {}: {}", prefix, "Bool".if_color(|s| s.yellow()), b),
+            ASTNode::ConstantNumber(n) => println!("{}└── {}: {}", prefix, "Number".if_color(|s| s.yellow()), n),
+            ASTNode::ConstantHexNumber(n) => println!("{}└── {}: 0x{:x}", prefix, "Number".if_color(|s| s.yellow()), n),
             ASTNode::ConstantSignedNumber(n) => {
-                println!("{}└── {}: {}", prefix, "SignedNumber".yellow(), n)
+                println!("{}└── {}: {}", prefix, "SignedNumber".if_color(|s| s.yellow()), n)
             }
-            ASTNode::ConstantString(s) => println!("{}└── {}: {}", prefix, "String".yellow(), s),
+            ASTNode::ConstantString(s) => println!("{}└── {}: {}", prefix, "String".if_color(|s| s.yellow()), s),
             ASTNode::Array(arr) => {
-                println!("{}└── {}:", prefix, "Array".green());
+                println!("{}└── {}:", prefix, "Array".if_color(|s| s.green()));
                 let last = arr.len() - 1;
                 for (i, v) in arr.iter().enumerate() {
                     let new_prefix = if i == last { "   " } else { "│  " };
@@ -518,9 +815,9 @@ impl ASTNode {
                 }
             }
             ASTNode::Map(map) => {
-                println!("{}└── {}:", prefix, "Map".green());
+                println!("{}└── {}:", prefix, "Map".if_color(|s| s.green()));
                 let last = map.len() - 1;
-                for (id, (k, v)) in map.iter().enumerate() {
+                for (id, (k, v)) in map.iter().collect::<BTreeMap<_, _>>().into_iter().enumerate() {
                     let new_prefix = if id == last { "   " } else { "│  " };
                     // println!("{}{}", prefix, new_prefix);
                     v.print(&format!("{}{}", prefix, new_prefix));
@@ -529,23 +826,23 @@ impl ASTNode {
             ASTNode::Variable(name) => {
                 if let Some(v) = get_var!(name) {
                     match v.get_string() {
-                        None => println!("{}└── {}: {:?}", prefix, name.blue(), v),
+                        None => println!("{}└── {}: {:?}", prefix, name.if_color(|s| s.blue()), v),
                         Some(s) => {
                             if let Ok(u) = u256::from_str_hex(s) {
                                 println!(
                                     "{}└── {}: {} ({})",
                                     prefix,
-                                    name.blue(),
-                                    u.green(),
-                                    s.magenta()
+                                    name.if_color(|s| s.blue()),
+                                    u.if_color(|s| s.green()),
+                                    s.if_color(|s| s.magenta())
                                 );
                             } else {
-                                println!("{}└── {}: {}", prefix, name.blue(), s);
+                                println!("{}└── {}: {}", prefix, name.if_color(|s| s.blue()), s);
                             }
                         }
                     }
                 } else {
-                    println!("{}└── {}: {}", prefix, "Variable".red(), name)
+                    println!("{}└── {}: {}", prefix, "Variable".if_color(|s| s.red()), name)
                 }
             }
             ASTNode::UnaryArithmetic(operator, value) => {
@@ -587,7 +884,7 @@ impl ASTNode {
                 right.print(&format!("{}    ", prefix));
             }
             ASTNode::Function(func, args) => {
-                println!("{}└── {}: {}", prefix, "Function".cyan(), func.to_string());
+                println!("{}└── {}: {}", prefix, "Function".if_color(|s| s.cyan()), func.to_string());
 
                 let last = args.len() - 1;
                 for (i, arg) in args.iter().enumerate() {
@@ -599,10 +896,41 @@ impl ASTNode {
         }
     }
 
+    /// Nesting depth of the tree, a single node has the depth 1
+    pub fn depth(&self) -> usize {
+        // Iterative, so pathological trees can be measured without recursion
+        let mut max = 0;
+        let mut nodes = vec![(self, 1)];
+        while let Some((node, depth)) = nodes.pop() {
+            max = max.max(depth);
+            match node {
+                ASTNode::Array(children) | ASTNode::Function(_, children) => {
+                    nodes.extend(children.iter().map(|c| (c.as_ref(), depth + 1)));
+                }
+                ASTNode::Map(map) => nodes.extend(map.values().map(|c| (c.as_ref(), depth + 1))),
+                ASTNode::UnaryArithmetic(_, value) | ASTNode::UnaryLogic(_, value) => {
+                    nodes.push((value, depth + 1));
+                }
+                ASTNode::BinaryArithmetic(_, left, right) | ASTNode::BinaryLogic(_, left, right) => {
+                    nodes.push((left, depth + 1));
+                    nodes.push((right, depth + 1));
+                }
+                _ => {}
+            }
+        }
+        max
+    }
+
+    /// Evaluates the tree. Fails with `MaxDepthExceeded` if the evaluation is nested deeper than the maximal expression depth.
     pub fn evaluate(&self) -> Result<ASTConstant, ASTError> {
+        let _depth = DepthGuard::enter()?;
+        self.evaluate_node()
+    }
+
+    fn evaluate_node(&self) -> Result<ASTConstant, ASTError> {
         match self {
             ASTNode::ConstantBool(value) => Ok(ASTConstant::Bool(*value)),
-            ASTNode::ConstantNumber(value) => Ok(ASTConstant::Number(*value)),
+            ASTNode::ConstantNumber(value) | ASTNode::ConstantHexNumber(value) => Ok(ASTConstant::Number(*value)),
             ASTNode::ConstantSignedNumber(value) => Ok(ASTConstant::SignedNumber(*value)),
             ASTNode::ConstantString(value) => Ok(ASTConstant::String(value.clone())),
             ASTNode::Map(map) => {
@@ -618,13 +946,12 @@ impl ASTNode {
                         // println!("{:?} in Map {:p}", value, get_variable_map_instance());
                         Ok(value.to_ASTNode().evaluate()?)
                     }
-                    // None => Err(ASTError::VariableNotFound { var: name.clone() }),
-                    None => Ok(ASTConstant::String("NA".to_string())),
+                    None => Ok(ASTConstant::String(missing_variable_value())),
                 }
             }
             ASTNode::UnaryArithmetic(operator, value) => {
                 // Implementation of Unary Arithmetic Operations
-                let val = value.evaluate()?;
+                let val = value.evaluate_numeric_operand()?;
                 match operator {
                     ArithmeticOperator::Negate =>
                         match val {
@@ -661,565 +988,9 @@ impl ASTNode {
                 }
             }
             ASTNode::BinaryArithmetic(operator, left, right) => {
-                // Implementation of Binary Arithmetic Operations
-                let left = left.evaluate()?;
-                let left_clone = left.clone();
-                let right = right.evaluate()?;
-                let right_clone = right.clone();
-                match left {
-                    ASTConstant::String(l) => {
-                        // Convert string to number
-                        match right {
-                            ASTConstant::Number(r) => {
-                                let conv = left_clone.convert(ConversionTarget::Number);
-                                // let val = left_clone.convert(ConversionTarget::Number).unwrap();
-                                if let Ok(val) = conv {
-                                    let val_node = ASTNode::ConstantNumber(
-                                        u256::from_str(val.get_value().as_str()).unwrap()
-                                    );
-                                    ASTNode::BinaryArithmetic(
-                                        operator.clone(),
-                                        Box::new(val_node),
-                                        Box::new(ASTNode::ConstantNumber(r))
-                                    ).evaluate()
-                                } else {
-                                    Err(conv.err().unwrap())
-                                }
-                            }
-                            ASTConstant::SignedNumber(r) => {
-                                let val = left_clone
-                                    .convert(ConversionTarget::SignedNumber)
-                                    .unwrap();
-                                let val_node = ASTNode::ConstantSignedNumber(
-                                    i256::from_str(val.get_value().as_str()).unwrap()
-                                );
-                                ASTNode::BinaryArithmetic(
-                                    operator.clone(),
-                                    Box::new(val_node),
-                                    Box::new(ASTNode::ConstantSignedNumber(r))
-                                ).evaluate()
-                            }
-                            ASTConstant::String(s) => {
-                                let val = left_clone.convert(ConversionTarget::Number).unwrap();
-                                let val_node = ASTNode::ConstantNumber(
-                                    u256::from_str(val.get_value().as_str()).unwrap()
-                                );
-                                let r_val = right_clone.convert(ConversionTarget::Number).unwrap();
-                                let r_val_node = ASTNode::ConstantNumber(
-                                    u256::from_str(r_val.get_value().as_str()).unwrap()
-                                );
-                                ASTNode::BinaryArithmetic(
-                                    operator.clone(),
-                                    Box::new(val_node),
-                                    Box::new(r_val_node)
-                                ).evaluate()
-                            }
-                            _ =>
-                                Err(
-                                    ASTError::InvalidArithmeticOperator(
-                                        operator.to_string().to_owned()
-                                    )
-                                ),
-                        }
-                    }
-                    ASTConstant::SignedNumber(left) => {
-                        match right {
-                            ASTConstant::SignedNumber(right) =>
-                                match operator {
-                                    ArithmeticOperator::Add => {
-                                        Ok(ASTConstant::SignedNumber(left + right))
-                                    }
-                                    ArithmeticOperator::Subtract => {
-                                        Ok(ASTConstant::SignedNumber(left - right))
-                                    }
-                                    ArithmeticOperator::Multiply => {
-                                        Ok(ASTConstant::SignedNumber(left * right))
-                                    }
-                                    ArithmeticOperator::Divide => {
-                                        Ok(ASTConstant::SignedNumber(left / right))
-                                    }
-                                    ArithmeticOperator::Modulo => {
-                                        Ok(ASTConstant::SignedNumber(left % right))
-                                    }
-                                    _ =>
-                                        Err(
-                                            ASTError::InvalidArithmeticOperator(
-                                                operator.to_string().to_owned()
-                                            )
-                                        ),
-                                }
-                            ASTConstant::Array(value) => {
-                                match operator {
-                                    ArithmeticOperator::Add => {
-                                        // Add value to all ASTConstants
-                                        let arr = value
-                                            .iter()
-                                            .map(|value| {
-                                                let mut element = value.clone();
-                                                match &mut element {
-                                                    ASTConstant::SignedNumber(num) => {
-                                                        *num += left;
-                                                    }
-                                                    ASTConstant::Array(inner_array) => {
-                                                        // *inner_array = inner_array.iter().map(|inner| {
-
-                                                        // })
-                                                        todo!("Add to Nested Array");
-                                                    }
-                                                    _ => unreachable!(),
-                                                }
-                                                element
-                                            })
-                                            .collect();
-                                        Ok(ASTConstant::Array(arr))
-                                    }
-                                    ArithmeticOperator::Subtract => { todo!("Implement Subtract") }
-                                    ArithmeticOperator::Multiply => {
-                                        let arr = value
-                                            .iter()
-                                            .map(|value| {
-                                                let mut element = value.clone();
-                                                match &mut element {
-                                                    ASTConstant::SignedNumber(num) => {
-                                                        *num *= left;
-                                                    }
-                                                    _ => unreachable!(),
-                                                }
-                                                element
-                                            })
-                                            .collect();
-                                        Ok(ASTConstant::Array(arr))
-                                    }
-                                    _ =>
-                                        Err(
-                                            ASTError::InvalidArithmeticOperator(
-                                                operator.to_string().to_owned()
-                                            )
-                                        ),
-                                }
-                            }
-                            ASTConstant::String(value) => {
-                                // Try String Conversion
-                                if value.starts_with("0x") {
-                                    let v = utils::hex_string_to_u256(&value[2..]);
-                                    match operator {
-                                        ArithmeticOperator::Add => {
-                                            Ok(ASTConstant::SignedNumber(left + v.as_i256()))
-                                        }
-                                        ArithmeticOperator::Subtract => {
-                                            Ok(ASTConstant::SignedNumber(left - v.as_i256()))
-                                        }
-                                        ArithmeticOperator::Multiply => {
-                                            Ok(ASTConstant::SignedNumber(left * v.as_i256()))
-                                        }
-                                        ArithmeticOperator::Divide => {
-                                            Ok(ASTConstant::SignedNumber(left / v.as_i256()))
-                                        }
-                                        ArithmeticOperator::Modulo => {
-                                            Ok(ASTConstant::SignedNumber(left % v.as_i256()))
-                                        }
-                                        _ =>
-                                            Err(
-                                                ASTError::InvalidArithmeticOperator(
-                                                    operator.to_string().to_owned()
-                                                )
-                                            ),
-                                    }
-                                } else if value.starts_with("u256:") {
-                                    let v = u256::from_str(&value[5..]).unwrap();
-                                    match operator {
-                                        ArithmeticOperator::Add => {
-                                            Ok(ASTConstant::SignedNumber(left + v.as_i256()))
-                                        }
-                                        ArithmeticOperator::Subtract => {
-                                            Ok(ASTConstant::SignedNumber(left - v.as_i256()))
-                                        }
-                                        ArithmeticOperator::Multiply => {
-                                            Ok(ASTConstant::SignedNumber(left * v.as_i256()))
-                                        }
-                                        ArithmeticOperator::Divide => {
-                                            Ok(ASTConstant::SignedNumber(left / v.as_i256()))
-                                        }
-                                        ArithmeticOperator::Modulo => {
-                                            Ok(ASTConstant::SignedNumber(left % v.as_i256()))
-                                        }
-                                        _ =>
-                                            Err(
-                                                ASTError::InvalidArithmeticOperator(
-                                                    operator.to_string().to_owned()
-                                                )
-                                            ),
-                                    }
-                                } else if value.starts_with("i256:") {
-                                    let v = i256::from_str(&value[5..]).unwrap();
-                                    match operator {
-                                        ArithmeticOperator::Add => {
-                                            Ok(ASTConstant::SignedNumber(left + v))
-                                        }
-                                        ArithmeticOperator::Subtract => {
-                                            Ok(ASTConstant::SignedNumber(left - v))
-                                        }
-                                        ArithmeticOperator::Multiply => {
-                                            Ok(ASTConstant::SignedNumber(left * v))
-                                        }
-                                        ArithmeticOperator::Divide => {
-                                            Ok(ASTConstant::SignedNumber(left / v))
-                                        }
-                                        ArithmeticOperator::Modulo => {
-                                            Ok(ASTConstant::SignedNumber(left % v))
-                                        }
-                                        _ =>
-                                            Err(
-                                                ASTError::InvalidArithmeticOperator(
-                                                    operator.to_string().to_owned()
-                                                )
-                                            ),
-                                    }
-                                } else {
-                                    Err(ASTError::InvalidConversion(value, "Number".to_string()))
-                                }
-                            }
-                            _ => Err(ASTError::InvalidBinaryOperator),
-                        }
-                    }
-                    ASTConstant::Number(left) => {
-                        match right {
-                            ASTConstant::Number(right) =>
-                                match operator {
-                                    ArithmeticOperator::Add =>
-                                        match u256::checked_add(left, right) {
-                                            Some(value) => Ok(ASTConstant::Number(value)),
-                                            None =>
-                                                Err(
-                                                    ASTError::OverflowError(
-                                                        format!("{} + {}", left, right)
-                                                    )
-                                                ),
-                                        }
-                                    ArithmeticOperator::Subtract => {
-                                        match u256::checked_sub(left, right) {
-                                            Some(value) => Ok(ASTConstant::Number(value)),
-                                            None =>
-                                                Err(
-                                                    ASTError::OverflowError(
-                                                        format!("{} - {}", left, right)
-                                                    )
-                                                ),
-                                        }
-                                    }
-                                    ArithmeticOperator::Multiply => {
-                                        match u256::checked_mul(left, right) {
-                                            Some(value) => Ok(ASTConstant::Number(value)),
-                                            None =>
-                                                Err(
-                                                    ASTError::OverflowError(
-                                                        format!("{} * {}", left, right)
-                                                    )
-                                                ),
-                                        }
-                                    }
-                                    ArithmeticOperator::Divide => {
-                                        match u256::checked_div(left, right) {
-                                            Some(value) => Ok(ASTConstant::Number(value)),
-                                            None =>
-                                                Err(
-                                                    ASTError::OverflowError(
-                                                        format!("{} / {}", left, right)
-                                                    )
-                                                ),
-                                        }
-                                    }
-                                    ArithmeticOperator::Modulo =>
-                                        Ok(ASTConstant::Number(left % right)),
-                                    _ =>
-                                        Err(
-                                            ASTError::InvalidArithmeticOperator(
-                                                operator.to_string().to_owned()
-                                            )
-                                        ),
-                                }
-                            ASTConstant::Array(value) => {
-                                match operator {
-                                    ArithmeticOperator::Add => {
-                                        // Add value to all ASTConstants
-                                        let arr = value
-                                            .iter()
-                                            .map(|value| {
-                                                let mut element = value.clone();
-                                                match &mut element {
-                                                    ASTConstant::Number(num) => {
-                                                        *num += left;
-                                                    }
-                                                    ASTConstant::Array(inner_array) => {
-                                                        // *inner_array = inner_array.iter().map(|inner| {
-
-                                                        // })
-                                                        todo!("Add to Nested Array");
-                                                    }
-                                                    _ => unreachable!(),
-                                                }
-                                                element
-                                            })
-                                            .collect();
-                                        Ok(ASTConstant::Array(arr))
-                                    }
-                                    ArithmeticOperator::Subtract => { todo!("Implement Subtract") }
-                                    ArithmeticOperator::Multiply => {
-                                        let arr = value
-                                            .iter()
-                                            .map(|value| {
-                                                let mut element = value.clone();
-                                                match &mut element {
-                                                    ASTConstant::Number(num) => {
-                                                        *num *= left;
-                                                    }
-                                                    _ => unreachable!(),
-                                                }
-                                                element
-                                            })
-                                            .collect();
-                                        Ok(ASTConstant::Array(arr))
-                                    }
-                                    _ =>
-                                        Err(
-                                            ASTError::InvalidArithmeticOperator(
-                                                operator.to_string().to_owned()
-                                            )
-                                        ),
-                                }
-                            }
-                            ASTConstant::SignedNumber(value) => {
-                                if value >= 0 {
-                                    let v = value.as_u256();
-                                    match operator {
-                                        ArithmeticOperator::Add => {
-                                            Ok(ASTConstant::Number(left + v))
-                                        }
-                                        ArithmeticOperator::Subtract => {
-                                            Ok(ASTConstant::Number(left - v))
-                                        }
-                                        ArithmeticOperator::Multiply => {
-                                            Ok(ASTConstant::Number(left * v))
-                                        }
-                                        ArithmeticOperator::Divide => {
-                                            Ok(ASTConstant::Number(left / v))
-                                        }
-                                        ArithmeticOperator::Modulo => {
-                                            Ok(ASTConstant::Number(left % v))
-                                        }
-                                        _ =>
-                                            Err(
-                                                ASTError::InvalidArithmeticOperator(
-                                                    operator.to_string().to_owned()
-                                                )
-                                            ),
-                                    }
-                                } else {
-                                    match operator {
-                                        ArithmeticOperator::Add => {
-                                            Ok(ASTConstant::SignedNumber(left.as_i256() + value))
-                                        }
-                                        ArithmeticOperator::Subtract => {
-                                            Ok(ASTConstant::SignedNumber(left.as_i256() - value))
-                                        }
-                                        ArithmeticOperator::Multiply => {
-                                            Ok(ASTConstant::SignedNumber(left.as_i256() * value))
-                                        }
-                                        ArithmeticOperator::Divide => {
-                                            Ok(ASTConstant::SignedNumber(left.as_i256() / value))
-                                        }
-                                        ArithmeticOperator::Modulo => {
-                                            Ok(ASTConstant::SignedNumber(left.as_i256() % value))
-                                        }
-                                        _ =>
-                                            Err(
-                                                ASTError::InvalidArithmeticOperator(
-                                                    operator.to_string().to_owned()
-                                                )
-                                            ),
-                                    }
-                                }
-                            }
-                            ASTConstant::String(value) => {
-                                // Try String Conversion
-                                if value.starts_with("0x") {
-                                    let v = utils::hex_string_to_u256(&value[2..]);
-                                    match operator {
-                                        ArithmeticOperator::Add => {
-                                            Ok(ASTConstant::Number(left + v))
-                                        }
-                                        ArithmeticOperator::Subtract => {
-                                            Ok(ASTConstant::Number(left - v))
-                                        }
-                                        ArithmeticOperator::Multiply => {
-                                            Ok(ASTConstant::Number(left * v))
-                                        }
-                                        ArithmeticOperator::Divide => {
-                                            Ok(ASTConstant::Number(left / v))
-                                        }
-                                        ArithmeticOperator::Modulo => {
-                                            Ok(ASTConstant::Number(left % v))
-                                        }
-                                        _ =>
-                                            Err(
-                                                ASTError::InvalidArithmeticOperator(
-                                                    operator.to_string().to_owned()
-                                                )
-                                            ),
-                                    }
-                                } else if value.starts_with("u256:") {
-                                    let v = u256::from_str(&value[5..]).unwrap();
-                                    match operator {
-                                        ArithmeticOperator::Add => {
-                                            Ok(ASTConstant::Number(left + v))
-                                        }
-                                        ArithmeticOperator::Subtract => {
-                                            Ok(ASTConstant::Number(left - v))
-                                        }
-                                        ArithmeticOperator::Multiply => {
-                                            Ok(ASTConstant::Number(left * v))
-                                        }
-                                        ArithmeticOperator::Divide => {
-                                            Ok(ASTConstant::Number(left / v))
-                                        }
-                                        ArithmeticOperator::Modulo => {
-                                            Ok(ASTConstant::Number(left % v))
-                                        }
-                                        _ =>
-                                            Err(
-                                                ASTError::InvalidArithmeticOperator(
-                                                    operator.to_string().to_owned()
-                                                )
-                                            ),
-                                    }
-                                } else if value.starts_with("i256:") {
-                                    let v = i256::from_str(&value[5..]).unwrap();
-                                    match operator {
-                                        ArithmeticOperator::Add => {
-                                            Ok(ASTConstant::SignedNumber(left.as_i256() + v))
-                                        }
-                                        ArithmeticOperator::Subtract => {
-                                            Ok(ASTConstant::SignedNumber(left.as_i256() - v))
-                                        }
-                                        ArithmeticOperator::Multiply => {
-                                            Ok(ASTConstant::SignedNumber(left.as_i256() * v))
-                                        }
-                                        ArithmeticOperator::Divide => {
-                                            Ok(ASTConstant::SignedNumber(left.as_i256() / v))
-                                        }
-                                        ArithmeticOperator::Modulo => {
-                                            Ok(ASTConstant::SignedNumber(left.as_i256() % v))
-                                        }
-                                        _ =>
-                                            Err(
-                                                ASTError::InvalidArithmeticOperator(
-                                                    operator.to_string().to_owned()
-                                                )
-                                            ),
-                                    }
-                                } else {
-                                    Err(ASTError::InvalidConversion(value, "Number".to_string()))
-                                }
-                            }
-                            _ => Err(ASTError::InvalidConstant(operator.to_string().to_owned())),
-                        }
-                    }
-                    ASTConstant::Array(value) => {
-                        match right {
-                            ASTConstant::Number(right) => {
-                                match operator {
-                                    ArithmeticOperator::Add => {
-                                        let arr = value
-                                            .iter()
-                                            .map(|value| {
-                                                let mut element = value.clone();
-                                                match &mut element {
-                                                    ASTConstant::Number(num) => {
-                                                        *num += right;
-                                                    }
-                                                    ASTConstant::Array(inner_array) => {
-                                                        // *inner_array = inner_array.iter().map(|inner| {
-
-                                                        // })
-                                                        todo!("Add to Nested Array");
-                                                    }
-                                                    _ => unreachable!(),
-                                                }
-                                                element
-                                            })
-                                            .collect();
-                                        Ok(ASTConstant::Array(arr))
-                                    }
-                                    ArithmeticOperator::Subtract => {
-                                        let arr = value
-                                            .iter()
-                                            .map(|value| {
-                                                let mut element = value.clone();
-                                                match &mut element {
-                                                    ASTConstant::Number(num) => {
-                                                        *num -= right;
-                                                    }
-                                                    ASTConstant::Array(inner_array) => {
-                                                        todo!("Add to Nested Array");
-                                                    }
-                                                    _ => unreachable!(),
-                                                }
-                                                element
-                                            })
-                                            .collect();
-                                        Ok(ASTConstant::Array(arr))
-                                    }
-                                    ArithmeticOperator::Multiply => {
-                                        let arr = value
-                                            .iter()
-                                            .map(|value| {
-                                                let mut element = value.clone();
-                                                match &mut element {
-                                                    ASTConstant::Number(num) => {
-                                                        *num *= right;
-                                                    }
-                                                    ASTConstant::Array(inner_array) => {
-                                                        todo!("Add to Nested Array");
-                                                    }
-                                                    _ => unreachable!(),
-                                                }
-                                                element
-                                            })
-                                            .collect();
-                                        Ok(ASTConstant::Array(arr))
-                                    }
-                                    ArithmeticOperator::Divide => {
-                                        let arr = value
-                                            .iter()
-                                            .map(|value| {
-                                                let mut element = value.clone();
-                                                match &mut element {
-                                                    ASTConstant::Number(num) => {
-                                                        *num = *num / right;
-                                                    }
-                                                    ASTConstant::Array(inner_array) => {
-                                                        todo!("Add to Nested Array");
-                                                    }
-                                                    _ => unreachable!(),
-                                                }
-                                                element
-                                            })
-                                            .collect();
-                                        Ok(ASTConstant::Array(arr))
-                                    }
-                                    _ =>
-                                        Err(
-                                            ASTError::InvalidArithmeticOperator(
-                                                operator.to_string().to_owned()
-                                            )
-                                        ),
-                                }
-                            }
-                            _ => Err(ASTError::InvalidConstant(operator.to_string().to_owned())),
-                        }
-                    }
-                    _ => Err(ASTError::InvalidConstant(operator.to_string().to_owned())),
-                }
+                let left = left.evaluate_numeric_operand()?;
+                let right = right.evaluate_numeric_operand()?;
+                Self::binary_arithmetic(operator, left, right)
             }
             ASTNode::UnaryLogic(operator, value) => {
                 let val = value.evaluate()?;
@@ -1256,1072 +1027,1719 @@ impl ASTNode {
                 }
             }
             ASTNode::BinaryLogic(operator, left, right) => {
-                let left = left.evaluate()?;
-                let right = right.evaluate()?;
-                match left {
-                    ASTConstant::SignedNumber(left) =>
-                        match right {
-                            ASTConstant::Number(right) =>
-                                match operator {
-                                    LogicOperator::Equal =>
-                                        Ok(ASTConstant::Bool(left == right.as_i256())),
-                                    LogicOperator::NotEqual => {
-                                        Ok(ASTConstant::Bool(left != right.as_i256()))
-                                    }
-                                    LogicOperator::Greater =>
-                                        Ok(ASTConstant::Bool(left > right.as_i256())),
-                                    LogicOperator::Less =>
-                                        Ok(ASTConstant::Bool(left < right.as_i256())),
-                                    LogicOperator::GreaterOrEqual => {
-                                        Ok(ASTConstant::Bool(left >= right.as_i256()))
-                                    }
-                                    LogicOperator::LessOrEqual => {
-                                        Ok(ASTConstant::Bool(left <= right.as_i256()))
-                                    }
-                                    _ => Err(ASTError::InvalidBinaryOperator),
-                                }
-                            ASTConstant::SignedNumber(right) =>
-                                match operator {
-                                    LogicOperator::Equal => Ok(ASTConstant::Bool(left == right)),
-                                    LogicOperator::NotEqual => Ok(ASTConstant::Bool(left != right)),
-                                    LogicOperator::Greater => Ok(ASTConstant::Bool(left > right)),
-                                    LogicOperator::Less => Ok(ASTConstant::Bool(left < right)),
-                                    LogicOperator::GreaterOrEqual =>
-                                        Ok(ASTConstant::Bool(left >= right)),
-                                    LogicOperator::LessOrEqual =>
-                                        Ok(ASTConstant::Bool(left <= right)),
-                                    _ => Err(ASTError::InvalidBinaryOperator),
-                                }
-                            ASTConstant::Array(right) => {
-                                todo!("Implement Array Logic with signed numbers")
+                // Only orderings need numbers, a missing variable can still be compared for equality
+                let (left, right) = match operator {
+                    | LogicOperator::Greater
+                    | LogicOperator::Less
+                    | LogicOperator::GreaterOrEqual
+                    | LogicOperator::LessOrEqual => {
+                        (left.evaluate_numeric_operand()?, right.evaluate_numeric_operand()?)
+                    }
+                    _ => (left.evaluate()?, right.evaluate()?),
+                };
+                Self::binary_logic(operator, left, right)
+            }
+            ASTNode::Function(function_name, args) => Self::evaluate_function(function_name, args),
+            ASTNode::Array(val) => {
+                let mut arr = vec![];
+                for v in val {
+                    arr.push(v.evaluate()?);
+                }
+                Ok(ASTConstant::Array(arr))
+            }
+        }
+    }
+
+    /// Applies the arithmetic operator to the evaluated operands.
+    /// Kept out of evaluate_node, so the stack frame of each level of a nested expression stays small.
+    fn binary_arithmetic(
+        operator: &ArithmeticOperator,
+        left: ASTConstant,
+        right: ASTConstant
+    ) -> Result<ASTConstant, ASTError> {
+        // Implementation of Binary Arithmetic Operations
+        let left_clone = left.clone();
+        let right_clone = right.clone();
+        if operator.is_bitwise() {
+            return bitwise(operator, left, right);
+        }
+        match left {
+            ASTConstant::String(l) => {
+                // Convert string to number
+                match right {
+                    ASTConstant::Number(r) => {
+                        let conv = left_clone.convert(ConversionTarget::Number);
+                        // let val = left_clone.convert(ConversionTarget::Number).unwrap();
+                        if let Ok(val) = conv {
+                            let val_node = ASTNode::ConstantNumber(
+                                u256::from_str(val.get_value().as_str()).unwrap()
+                            );
+                            ASTNode::BinaryArithmetic(
+                                operator.clone(),
+                                Box::new(val_node),
+                                Box::new(ASTNode::ConstantNumber(r))
+                            ).evaluate()
+                        } else {
+                            Err(conv.err().unwrap())
+                        }
+                    }
+                    ASTConstant::SignedNumber(r) => {
+                        let val = left_clone
+                            .convert(ConversionTarget::SignedNumber)
+                            .unwrap();
+                        let val_node = ASTNode::ConstantSignedNumber(
+                            i256::from_str(val.get_value().as_str()).unwrap()
+                        );
+                        ASTNode::BinaryArithmetic(
+                            operator.clone(),
+                            Box::new(val_node),
+                            Box::new(ASTNode::ConstantSignedNumber(r))
+                        ).evaluate()
+                    }
+                    ASTConstant::String(s) => {
+                        let val = left_clone.convert(ConversionTarget::Number).unwrap();
+                        let val_node = ASTNode::ConstantNumber(
+                            u256::from_str(val.get_value().as_str()).unwrap()
+                        );
+                        let r_val = right_clone.convert(ConversionTarget::Number).unwrap();
+                        let r_val_node = ASTNode::ConstantNumber(
+                            u256::from_str(r_val.get_value().as_str()).unwrap()
+                        );
+                        ASTNode::BinaryArithmetic(
+                            operator.clone(),
+                            Box::new(val_node),
+                            Box::new(r_val_node)
+                        ).evaluate()
+                    }
+                    _ =>
+                        Err(
+                            ASTError::InvalidArithmeticOperator(
+                                operator.to_string().to_owned()
+                            )
+                        ),
+                }
+            }
+            ASTConstant::SignedNumber(left) => {
+                match right {
+                    ASTConstant::SignedNumber(right) =>
+                        match operator {
+                            ArithmeticOperator::Add => {
+                                Ok(ASTConstant::SignedNumber(left + right))
                             }
-                            ASTConstant::String(right) => {
-                                if right.starts_with("0x") {
-                                    let v = i256::from_str_hex(&right).unwrap();
-                                    match operator {
-                                        LogicOperator::Equal => Ok(ASTConstant::Bool(left == v)),
-                                        LogicOperator::NotEqual => Ok(ASTConstant::Bool(left != v)),
-                                        LogicOperator::Greater => Ok(ASTConstant::Bool(left > v)),
-                                        LogicOperator::Less => Ok(ASTConstant::Bool(left < v)),
-                                        LogicOperator::GreaterOrEqual => {
-                                            Ok(ASTConstant::Bool(left >= v))
-                                        }
-                                        LogicOperator::LessOrEqual =>
-                                            Ok(ASTConstant::Bool(left <= v)),
-                                        _ => Err(ASTError::InvalidBinaryOperator),
-                                    }
-                                } else if right.starts_with("u256:") {
-                                    let v = u256
-                                        ::from_str(&right[5..])
-                                        .unwrap()
-                                        .as_i256();
-                                    match operator {
-                                        LogicOperator::Equal => Ok(ASTConstant::Bool(left == v)),
-                                        LogicOperator::NotEqual => Ok(ASTConstant::Bool(left != v)),
-                                        LogicOperator::Greater => Ok(ASTConstant::Bool(left > v)),
-                                        LogicOperator::Less => Ok(ASTConstant::Bool(left < v)),
-                                        LogicOperator::GreaterOrEqual => {
-                                            Ok(ASTConstant::Bool(left >= v))
+                            ArithmeticOperator::Subtract => {
+                                Ok(ASTConstant::SignedNumber(left - right))
+                            }
+                            ArithmeticOperator::Multiply => {
+                                Ok(ASTConstant::SignedNumber(left * right))
+                            }
+                            ArithmeticOperator::Divide => {
+                                Ok(ASTConstant::SignedNumber(left / right))
+                            }
+                            ArithmeticOperator::Modulo => {
+                                Ok(ASTConstant::SignedNumber(left % right))
+                            }
+                            _ =>
+                                Err(
+                                    ASTError::InvalidArithmeticOperator(
+                                        operator.to_string().to_owned()
+                                    )
+                                ),
+                        }
+                    ASTConstant::Array(value) => {
+                        match operator {
+                            ArithmeticOperator::Add => {
+                                // Add value to all ASTConstants
+                                let arr = value
+                                    .iter()
+                                    .map(|value| {
+                                        let mut element = value.clone();
+                                        match &mut element {
+                                            ASTConstant::SignedNumber(num) => {
+                                                *num += left;
+                                            }
+                                            ASTConstant::Array(inner_array) => {
+                                                // *inner_array = inner_array.iter().map(|inner| {
+
+                                                // })
+                                                todo!("Add to Nested Array");
+                                            }
+                                            _ => unreachable!(),
                                         }
-                                        LogicOperator::LessOrEqual =>
-                                            Ok(ASTConstant::Bool(left <= v)),
-                                        _ => Err(ASTError::InvalidBinaryOperator),
-                                    }
-                                } else if right.starts_with("i256:") {
-                                    let v = i256::from_str(&right[5..]).unwrap();
-                                    match operator {
-                                        LogicOperator::Equal => Ok(ASTConstant::Bool(left == v)),
-                                        LogicOperator::NotEqual => Ok(ASTConstant::Bool(left != v)),
-                                        LogicOperator::Greater => Ok(ASTConstant::Bool(left > v)),
-                                        LogicOperator::Less => Ok(ASTConstant::Bool(left < v)),
-                                        LogicOperator::GreaterOrEqual => {
-                                            Ok(ASTConstant::Bool(left >= v))
+                                        element
+                                    })
+                                    .collect();
+                                Ok(ASTConstant::Array(arr))
+                            }
+                            ArithmeticOperator::Subtract => { todo!("Implement Subtract") }
+                            ArithmeticOperator::Multiply => {
+                                let arr = value
+                                    .iter()
+                                    .map(|value| {
+                                        let mut element = value.clone();
+                                        match &mut element {
+                                            ASTConstant::SignedNumber(num) => {
+                                                *num *= left;
+                                            }
+                                            _ => unreachable!(),
                                         }
-                                        LogicOperator::LessOrEqual =>
-                                            Ok(ASTConstant::Bool(left <= v)),
-                                        _ => Err(ASTError::InvalidBinaryOperator),
-                                    }
-                                } else {
-                                    Err(ASTError::InvalidConversion(right, "Number".to_string()))
-                                }
+                                        element
+                                    })
+                                    .collect();
+                                Ok(ASTConstant::Array(arr))
                             }
-                            _ => Err(ASTError::InvalidBinaryOperator),
+                            _ =>
+                                Err(
+                                    ASTError::InvalidArithmeticOperator(
+                                        operator.to_string().to_owned()
+                                    )
+                                ),
                         }
-                    ASTConstant::Bool(left) =>
-                        match right {
-                            ASTConstant::Bool(right) =>
-                                match operator {
-                                    LogicOperator::And => Ok(ASTConstant::Bool(left && right)),
-                                    LogicOperator::Or => Ok(ASTConstant::Bool(left || right)),
-                                    LogicOperator::Equal => Ok(ASTConstant::Bool(left == right)),
-                                    LogicOperator::NotEqual => Ok(ASTConstant::Bool(left != right)),
-                                    _ => Err(ASTError::InvalidBinaryOperator),
+                    }
+                    ASTConstant::String(value) => {
+                        // Try String Conversion
+                        if value.starts_with("0x") {
+                            let v = utils::hex_string_to_u256(&value[2..]);
+                            match operator {
+                                ArithmeticOperator::Add => {
+                                    Ok(ASTConstant::SignedNumber(left + v.as_i256()))
                                 }
-                            | ASTConstant::Number(_)
-                            | ASTConstant::String(_)
-                            | ASTConstant::SignedNumber(_) =>
-                                match operator {
-                                    LogicOperator::And => Ok(ASTConstant::Bool(left)),
-                                    LogicOperator::Or => Ok(ASTConstant::Bool(left)),
-                                    _ => Err(ASTError::InvalidBinaryOperator),
+                                ArithmeticOperator::Subtract => {
+                                    Ok(ASTConstant::SignedNumber(left - v.as_i256()))
                                 }
-                            _ => Err(ASTError::InvalidConstant(operator.to_string().to_owned())),
-                        }
-                    ASTConstant::Number(left) =>
-                        match right {
-                            ASTConstant::Number(right) =>
-                                match operator {
-                                    LogicOperator::Equal => Ok(ASTConstant::Bool(left == right)),
-                                    LogicOperator::NotEqual => Ok(ASTConstant::Bool(left != right)),
-                                    LogicOperator::Greater => Ok(ASTConstant::Bool(left > right)),
-                                    LogicOperator::Less => Ok(ASTConstant::Bool(left < right)),
-                                    LogicOperator::GreaterOrEqual =>
-                                        Ok(ASTConstant::Bool(left >= right)),
-                                    LogicOperator::LessOrEqual =>
-                                        Ok(ASTConstant::Bool(left <= right)),
-                                    _ => Err(ASTError::InvalidBinaryOperator),
+                                ArithmeticOperator::Multiply => {
+                                    Ok(ASTConstant::SignedNumber(left * v.as_i256()))
                                 }
-                            ASTConstant::SignedNumber(right) =>
-                                match operator {
-                                    LogicOperator::Equal =>
-                                        Ok(ASTConstant::Bool(left.as_i256() == right)),
-                                    LogicOperator::NotEqual => {
-                                        Ok(ASTConstant::Bool(left.as_i256() != right))
-                                    }
-                                    LogicOperator::Greater =>
-                                        Ok(ASTConstant::Bool(left.as_i256() > right)),
-                                    LogicOperator::Less =>
-                                        Ok(ASTConstant::Bool(left.as_i256() < right)),
-                                    LogicOperator::GreaterOrEqual => {
-                                        Ok(ASTConstant::Bool(left.as_i256() >= right))
-                                    }
-                                    LogicOperator::LessOrEqual => {
-                                        Ok(ASTConstant::Bool(left.as_i256() <= right))
-                                    }
-                                    _ => Err(ASTError::InvalidBinaryOperator),
+                                ArithmeticOperator::Divide => {
+                                    Ok(ASTConstant::SignedNumber(left / v.as_i256()))
                                 }
-                            ASTConstant::String(right) => {
-                                if right.starts_with("0x") {
-                                    let v = u256::from_str_hex(&right).unwrap();
-                                    match operator {
-                                        LogicOperator::Equal => Ok(ASTConstant::Bool(left == v)),
-                                        LogicOperator::NotEqual => Ok(ASTConstant::Bool(left != v)),
-                                        LogicOperator::Greater => Ok(ASTConstant::Bool(left > v)),
-                                        LogicOperator::Less => Ok(ASTConstant::Bool(left < v)),
-                                        LogicOperator::GreaterOrEqual => {
-                                            Ok(ASTConstant::Bool(left >= v))
-                                        }
-                                        LogicOperator::LessOrEqual =>
-                                            Ok(ASTConstant::Bool(left <= v)),
-                                        _ => Err(ASTError::InvalidBinaryOperator),
-                                    }
-                                } else if right.starts_with("u256:") {
-                                    let v = u256::from_str(&right[5..]).unwrap();
-                                    match operator {
-                                        LogicOperator::Equal => Ok(ASTConstant::Bool(left == v)),
-                                        LogicOperator::NotEqual => Ok(ASTConstant::Bool(left != v)),
-                                        LogicOperator::Greater => Ok(ASTConstant::Bool(left > v)),
-                                        LogicOperator::Less => Ok(ASTConstant::Bool(left < v)),
-                                        LogicOperator::GreaterOrEqual => {
-                                            Ok(ASTConstant::Bool(left >= v))
-                                        }
-                                        LogicOperator::LessOrEqual =>
-                                            Ok(ASTConstant::Bool(left <= v)),
-                                        _ => Err(ASTError::InvalidBinaryOperator),
-                                    }
-                                } else if right.starts_with("i256:") {
-                                    let v = i256::from_str(&right[5..]).unwrap();
-                                    match operator {
-                                        LogicOperator::Equal => {
-                                            Ok(ASTConstant::Bool(left.as_i256() == v))
-                                        }
-                                        LogicOperator::NotEqual => {
-                                            Ok(ASTConstant::Bool(left.as_i256() != v))
-                                        }
-                                        LogicOperator::Greater => {
-                                            Ok(ASTConstant::Bool(left.as_i256() > v))
-                                        }
-                                        LogicOperator::Less => {
-                                            Ok(ASTConstant::Bool(left.as_i256() < v))
-                                        }
-                                        LogicOperator::GreaterOrEqual => {
-                                            Ok(ASTConstant::Bool(left.as_i256() >= v))
-                                        }
-                                        LogicOperator::LessOrEqual => {
-                                            Ok(ASTConstant::Bool(left.as_i256() <= v))
-                                        }
-                                        _ => Err(ASTError::InvalidBinaryOperator),
-                                    }
-                                } else {
-                                    Err(ASTError::InvalidConversion(right, "Number".to_string()))
+                                ArithmeticOperator::Modulo => {
+                                    Ok(ASTConstant::SignedNumber(left % v.as_i256()))
                                 }
+                                _ =>
+                                    Err(
+                                        ASTError::InvalidArithmeticOperator(
+                                            operator.to_string().to_owned()
+                                        )
+                                    ),
                             }
-                            _ => Err(ASTError::InvalidConstant(operator.to_string().to_owned())),
-                        }
-                    ASTConstant::String(left) =>
-                        match right {
-                            ASTConstant::String(right) =>
-                                match operator {
-                                    LogicOperator::Equal => Ok(ASTConstant::Bool(left == right)),
-                                    LogicOperator::NotEqual => Ok(ASTConstant::Bool(left != right)),
-                                    _ => Err(ASTError::InvalidBinaryOperator),
+                        } else if value.starts_with("u256:") {
+                            let v = u256::from_str(&value[5..]).unwrap();
+                            match operator {
+                                ArithmeticOperator::Add => {
+                                    Ok(ASTConstant::SignedNumber(left + v.as_i256()))
                                 }
-                            ASTConstant::Number(right) => {
-                                if left.starts_with("0x") {
-                                    let l = u256::from_str_hex(&left).unwrap();
-                                    match operator {
-                                        LogicOperator::Equal => Ok(ASTConstant::Bool(l == right)),
-                                        LogicOperator::NotEqual =>
-                                            Ok(ASTConstant::Bool(l != right)),
-                                        LogicOperator::Greater => Ok(ASTConstant::Bool(l > right)),
-                                        LogicOperator::Less => Ok(ASTConstant::Bool(l < right)),
-                                        LogicOperator::GreaterOrEqual => {
-                                            Ok(ASTConstant::Bool(l >= right))
-                                        }
-                                        LogicOperator::LessOrEqual =>
-                                            Ok(ASTConstant::Bool(l <= right)),
-                                        _ => Err(ASTError::InvalidBinaryOperator),
-                                    }
-                                } else if left.starts_with("u256:") {
-                                    let l = u256::from_str(&left[5..]).unwrap();
-                                    match operator {
-                                        LogicOperator::Equal => Ok(ASTConstant::Bool(l == right)),
-                                        LogicOperator::NotEqual =>
-                                            Ok(ASTConstant::Bool(l != right)),
-                                        LogicOperator::Greater => Ok(ASTConstant::Bool(l > right)),
-                                        LogicOperator::Less => Ok(ASTConstant::Bool(l < right)),
-                                        LogicOperator::GreaterOrEqual => {
-                                            Ok(ASTConstant::Bool(l >= right))
-                                        }
-                                        LogicOperator::LessOrEqual =>
-                                            Ok(ASTConstant::Bool(l <= right)),
-                                        _ => Err(ASTError::InvalidBinaryOperator),
-                                    }
-                                } else if left.starts_with("i256:") {
-                                    let l = i256::from_str(&left[5..]).unwrap();
-                                    match operator {
-                                        LogicOperator::Equal => {
-                                            Ok(ASTConstant::Bool(l == right.as_i256()))
-                                        }
-                                        LogicOperator::NotEqual => {
-                                            Ok(ASTConstant::Bool(l != right.as_i256()))
-                                        }
-                                        LogicOperator::Greater => {
-                                            Ok(ASTConstant::Bool(l > right.as_i256()))
-                                        }
-                                        LogicOperator::Less => {
-                                            Ok(ASTConstant::Bool(l < right.as_i256()))
-                                        }
-                                        LogicOperator::GreaterOrEqual => {
-                                            Ok(ASTConstant::Bool(l >= right.as_i256()))
-                                        }
-                                        LogicOperator::LessOrEqual => {
-                                            Ok(ASTConstant::Bool(l <= right.as_i256()))
-                                        }
-                                        _ => Err(ASTError::InvalidBinaryOperator),
-                                    }
-                                } else {
-                                    Err(ASTError::InvalidConversion(left, "Number".to_string()))
+                                ArithmeticOperator::Subtract => {
+                                    Ok(ASTConstant::SignedNumber(left - v.as_i256()))
                                 }
-                            }
-                            ASTConstant::SignedNumber(right) => {
-                                if left.starts_with("0x") {
-                                    let l = i256::from_str_hex(&left).unwrap();
-                                    match operator {
-                                        LogicOperator::Equal => Ok(ASTConstant::Bool(l == right)),
-                                        LogicOperator::NotEqual =>
-                                            Ok(ASTConstant::Bool(l != right)),
-                                        LogicOperator::Greater => Ok(ASTConstant::Bool(l > right)),
-                                        LogicOperator::Less => Ok(ASTConstant::Bool(l < right)),
-                                        LogicOperator::GreaterOrEqual => {
-                                            Ok(ASTConstant::Bool(l >= right))
-                                        }
-                                        LogicOperator::LessOrEqual =>
-                                            Ok(ASTConstant::Bool(l <= right)),
-                                        _ => Err(ASTError::InvalidBinaryOperator),
-                                    }
-                                } else if left.starts_with("u256:") {
-                                    let l = i256::from_str(&left[5..]).unwrap();
-                                    match operator {
-                                        LogicOperator::Equal => Ok(ASTConstant::Bool(l == right)),
-                                        LogicOperator::NotEqual =>
-                                            Ok(ASTConstant::Bool(l != right)),
-                                        LogicOperator::Greater => Ok(ASTConstant::Bool(l > right)),
-                                        LogicOperator::Less => Ok(ASTConstant::Bool(l < right)),
-                                        LogicOperator::GreaterOrEqual => {
-                                            Ok(ASTConstant::Bool(l >= right))
-                                        }
-                                        LogicOperator::LessOrEqual =>
-                                            Ok(ASTConstant::Bool(l <= right)),
-                                        _ => Err(ASTError::InvalidBinaryOperator),
-                                    }
-                                } else if left.starts_with("i256:") {
-                                    let l = i256::from_str(&left[5..]).unwrap();
-                                    match operator {
-                                        LogicOperator::Equal => Ok(ASTConstant::Bool(l == right)),
-                                        LogicOperator::NotEqual =>
-                                            Ok(ASTConstant::Bool(l != right)),
-                                        LogicOperator::Greater => Ok(ASTConstant::Bool(l > right)),
-                                        LogicOperator::Less => Ok(ASTConstant::Bool(l < right)),
-                                        LogicOperator::GreaterOrEqual => {
-                                            Ok(ASTConstant::Bool(l >= right))
-                                        }
-                                        LogicOperator::LessOrEqual =>
-                                            Ok(ASTConstant::Bool(l <= right)),
-                                        _ => Err(ASTError::InvalidBinaryOperator),
-                                    }
-                                } else {
-                                    Err(ASTError::InvalidConversion(left, "Number".to_string()))
+                                ArithmeticOperator::Multiply => {
+                                    Ok(ASTConstant::SignedNumber(left * v.as_i256()))
                                 }
+                                ArithmeticOperator::Divide => {
+                                    Ok(ASTConstant::SignedNumber(left / v.as_i256()))
+                                }
+                                ArithmeticOperator::Modulo => {
+                                    Ok(ASTConstant::SignedNumber(left % v.as_i256()))
+                                }
+                                _ =>
+                                    Err(
+                                        ASTError::InvalidArithmeticOperator(
+                                            operator.to_string().to_owned()
+                                        )
+                                    ),
                             }
-                            ASTConstant::Bool(right) =>
-                                match operator {
-                                    LogicOperator::And => Ok(ASTConstant::Bool(right)),
-                                    LogicOperator::Or => Ok(ASTConstant::Bool(right)),
-                                    _ => Err(ASTError::InvalidBinaryOperator),
+                        } else if value.starts_with("i256:") {
+                            let v = i256::from_str(&value[5..]).unwrap();
+                            match operator {
+                                ArithmeticOperator::Add => {
+                                    Ok(ASTConstant::SignedNumber(left + v))
+                                }
+                                ArithmeticOperator::Subtract => {
+                                    Ok(ASTConstant::SignedNumber(left - v))
+                                }
+                                ArithmeticOperator::Multiply => {
+                                    Ok(ASTConstant::SignedNumber(left * v))
                                 }
-                            _ => Err(ASTError::InvalidConstant(operator.to_string().to_owned())),
+                                ArithmeticOperator::Divide => {
+                                    Ok(ASTConstant::SignedNumber(left / v))
+                                }
+                                ArithmeticOperator::Modulo => {
+                                    Ok(ASTConstant::SignedNumber(left % v))
+                                }
+                                _ =>
+                                    Err(
+                                        ASTError::InvalidArithmeticOperator(
+                                            operator.to_string().to_owned()
+                                        )
+                                    ),
+                            }
+                        } else {
+                            Err(ASTError::InvalidConversion(value, "Number".to_string()))
                         }
-                    ASTConstant::Array(left) => {
-                        match right {
-                            ASTConstant::SignedNumber(right) =>
-                                match operator {
-                                    LogicOperator::Greater =>
-                                        Ok(
-                                            ASTConstant::Bool(
-                                                left.iter().all(|element| {
-                                                    match element {
-                                                        ASTConstant::SignedNumber(num) =>
-                                                            *num > right,
-                                                        _ => unreachable!(),
-                                                    }
-                                                })
-                                            )
-                                        ),
-                                    LogicOperator::Less =>
-                                        Ok(
-                                            ASTConstant::Bool(
-                                                left.iter().all(|element| {
-                                                    match element {
-                                                        ASTConstant::SignedNumber(num) =>
-                                                            *num < right,
-                                                        _ => unreachable!(),
-                                                    }
-                                                })
-                                            )
-                                        ),
-                                    LogicOperator::GreaterOrEqual =>
-                                        Ok(
-                                            ASTConstant::Bool(
-                                                left.iter().all(|element| {
-                                                    match element {
-                                                        ASTConstant::SignedNumber(num) =>
-                                                            *num >= right,
-                                                        _ => unreachable!(),
-                                                    }
-                                                })
-                                            )
-                                        ),
-                                    LogicOperator::LessOrEqual =>
-                                        Ok(
-                                            ASTConstant::Bool(
-                                                left.iter().all(|element| {
-                                                    match element {
-                                                        ASTConstant::SignedNumber(num) =>
-                                                            *num <= right,
-                                                        _ => unreachable!(),
-                                                    }
-                                                })
-                                            )
-                                        ),
-                                    LogicOperator::Equal =>
-                                        Ok(
-                                            ASTConstant::Bool(
-                                                left.iter().all(|element| {
-                                                    match element {
-                                                        ASTConstant::SignedNumber(num) =>
-                                                            *num == right,
-                                                        _ => unreachable!(),
-                                                    }
-                                                })
-                                            )
-                                        ),
-                                    LogicOperator::NotEqual =>
-                                        Ok(
-                                            ASTConstant::Bool(
-                                                left.iter().all(|element| {
-                                                    match element {
-                                                        ASTConstant::SignedNumber(num) =>
-                                                            *num != right,
-                                                        _ => unreachable!(),
-                                                    }
-                                                })
+                    }
+                    _ => Err(ASTError::InvalidBinaryOperator),
+                }
+            }
+            ASTConstant::Number(left) => {
+                match right {
+                    ASTConstant::Number(right) =>
+                        match operator {
+                            ArithmeticOperator::Add =>
+                                match u256::checked_add(left, right) {
+                                    Some(value) => Ok(ASTConstant::Number(value)),
+                                    None =>
+                                        Err(
+                                            ASTError::OverflowError(
+                                                format!("{} + {}", left, right)
                                             )
                                         ),
-                                    _ => Err(ASTError::InvalidBinaryOperator),
                                 }
-                            ASTConstant::Number(right) =>
-                                match operator {
-                                    LogicOperator::Greater =>
-                                        Ok(
-                                            ASTConstant::Bool(
-                                                left.iter().all(|element| {
-                                                    match element {
-                                                        ASTConstant::Number(num) => *num > right,
-                                                        _ => unreachable!(),
-                                                    }
-                                                })
-                                            )
-                                        ),
-                                    LogicOperator::Less =>
-                                        Ok(
-                                            ASTConstant::Bool(
-                                                left.iter().all(|element| {
-                                                    match element {
-                                                        ASTConstant::Number(num) => *num < right,
-                                                        _ => unreachable!(),
-                                                    }
-                                                })
-                                            )
-                                        ),
-                                    LogicOperator::GreaterOrEqual =>
-                                        Ok(
-                                            ASTConstant::Bool(
-                                                left.iter().all(|element| {
-                                                    match element {
-                                                        ASTConstant::Number(num) => *num >= right,
-                                                        _ => unreachable!(),
-                                                    }
-                                                })
-                                            )
-                                        ),
-                                    LogicOperator::LessOrEqual =>
-                                        Ok(
-                                            ASTConstant::Bool(
-                                                left.iter().all(|element| {
-                                                    match element {
-                                                        ASTConstant::Number(num) => *num <= right,
-                                                        _ => unreachable!(),
-                                                    }
-                                                })
+                            ArithmeticOperator::Subtract => {
+                                match u256::checked_sub(left, right) {
+                                    Some(value) => Ok(ASTConstant::Number(value)),
+                                    None =>
+                                        Err(
+                                            ASTError::OverflowError(
+                                                format!("{} - {}", left, right)
                                             )
                                         ),
-                                    LogicOperator::Equal =>
-                                        Ok(
-                                            ASTConstant::Bool(
-                                                left.iter().all(|element| {
-                                                    match element {
-                                                        ASTConstant::Number(num) => *num == right,
-                                                        _ => unreachable!(),
-                                                    }
-                                                })
+                                }
+                            }
+                            ArithmeticOperator::Multiply => {
+                                match u256::checked_mul(left, right) {
+                                    Some(value) => Ok(ASTConstant::Number(value)),
+                                    None =>
+                                        Err(
+                                            ASTError::OverflowError(
+                                                format!("{} * {}", left, right)
                                             )
                                         ),
-                                    LogicOperator::NotEqual =>
-                                        Ok(
-                                            ASTConstant::Bool(
-                                                left.iter().all(|element| {
-                                                    match element {
-                                                        ASTConstant::Number(num) => *num != right,
-                                                        _ => unreachable!(),
-                                                    }
-                                                })
+                                }
+                            }
+                            ArithmeticOperator::Divide => {
+                                match u256::checked_div(left, right) {
+                                    Some(value) => Ok(ASTConstant::Number(value)),
+                                    None =>
+                                        Err(
+                                            ASTError::OverflowError(
+                                                format!("{} / {}", left, right)
                                             )
                                         ),
-                                    _ => Err(ASTError::InvalidBinaryOperator),
                                 }
-                            ASTConstant::Array(right) => {
-                                match operator {
-                                    LogicOperator::Equal => {
-                                        for i in 0..left.len() {
-                                            // println!("{:?}", left[i].get_value());
-                                            // println!("{:?}", right[i].get_value());
-                                            if left[i].get_value() != right[i].get_value() {
-                                                return Ok(ASTConstant::Bool(false));
+                            }
+                            ArithmeticOperator::Modulo =>
+                                Ok(ASTConstant::Number(left % right)),
+                            _ =>
+                                Err(
+                                    ASTError::InvalidArithmeticOperator(
+                                        operator.to_string().to_owned()
+                                    )
+                                ),
+                        }
+                    ASTConstant::Array(value) => {
+                        match operator {
+                            ArithmeticOperator::Add => {
+                                // Add value to all ASTConstants
+                                let arr = value
+                                    .iter()
+                                    .map(|value| {
+                                        let mut element = value.clone();
+                                        match &mut element {
+                                            ASTConstant::Number(num) => {
+                                                *num += left;
+                                            }
+                                            ASTConstant::Array(inner_array) => {
+                                                // *inner_array = inner_array.iter().map(|inner| {
+
+                                                // })
+                                                todo!("Add to Nested Array");
                                             }
-                                            // println!("is equal");
+                                            _ => unreachable!(),
                                         }
-                                        Ok(ASTConstant::Bool(true))
-                                    }
-                                    LogicOperator::NotEqual => {
-                                        for i in 0..left.len() {
-                                            // println!("{:?}", left[i].get_value());
-                                            // println!("{:?}", right[i].get_value());
-                                            if left[i].get_value() != right[i].get_value() {
-                                                return Ok(ASTConstant::Bool(true));
+                                        element
+                                    })
+                                    .collect();
+                                Ok(ASTConstant::Array(arr))
+                            }
+                            ArithmeticOperator::Subtract => { todo!("Implement Subtract") }
+                            ArithmeticOperator::Multiply => {
+                                let arr = value
+                                    .iter()
+                                    .map(|value| {
+                                        let mut element = value.clone();
+                                        match &mut element {
+                                            ASTConstant::Number(num) => {
+                                                *num *= left;
                                             }
-                                            // println!("is equal");
+                                            _ => unreachable!(),
                                         }
-                                        Ok(ASTConstant::Bool(false))
-                                    }
-                                    | LogicOperator::Greater
-                                    | LogicOperator::Less
-                                    | LogicOperator::GreaterOrEqual
-                                    | LogicOperator::LessOrEqual => {
-                                        todo!("Implement logic operator for array");
-                                    }
-                                    _ => Err(ASTError::InvalidBinaryOperator),
+                                        element
+                                    })
+                                    .collect();
+                                Ok(ASTConstant::Array(arr))
+                            }
+                            _ =>
+                                Err(
+                                    ASTError::InvalidArithmeticOperator(
+                                        operator.to_string().to_owned()
+                                    )
+                                ),
+                        }
+                    }
+                    ASTConstant::SignedNumber(value) => {
+                        if value >= 0 {
+                            let v = value.as_u256();
+                            match operator {
+                                ArithmeticOperator::Add => {
+                                    Ok(ASTConstant::Number(left + v))
                                 }
+                                ArithmeticOperator::Subtract => {
+                                    Ok(ASTConstant::Number(left - v))
+                                }
+                                ArithmeticOperator::Multiply => {
+                                    Ok(ASTConstant::Number(left * v))
+                                }
+                                ArithmeticOperator::Divide => {
+                                    Ok(ASTConstant::Number(left / v))
+                                }
+                                ArithmeticOperator::Modulo => {
+                                    Ok(ASTConstant::Number(left % v))
+                                }
+                                _ =>
+                                    Err(
+                                        ASTError::InvalidArithmeticOperator(
+                                            operator.to_string().to_owned()
+                                        )
+                                    ),
                             }
-                            _ => Err(ASTError::InvalidConstant(operator.to_string().to_owned())),
+                        } else {
+                            match operator {
+                                ArithmeticOperator::Add => {
+                                    Ok(ASTConstant::SignedNumber(left.as_i256() + value))
+                                }
+                                ArithmeticOperator::Subtract => {
+                                    Ok(ASTConstant::SignedNumber(left.as_i256() - value))
+                                }
+                                ArithmeticOperator::Multiply => {
+                                    Ok(ASTConstant::SignedNumber(left.as_i256() * value))
+                                }
+                                ArithmeticOperator::Divide => {
+                                    Ok(ASTConstant::SignedNumber(left.as_i256() / value))
+                                }
+                                ArithmeticOperator::Modulo => {
+                                    Ok(ASTConstant::SignedNumber(left.as_i256() % value))
+                                }
+                                _ =>
+                                    Err(
+                                        ASTError::InvalidArithmeticOperator(
+                                            operator.to_string().to_owned()
+                                        )
+                                    ),
+                            }
+                        }
+                    }
+                    ASTConstant::String(value) => {
+                        // Try String Conversion
+                        if value.starts_with("0x") {
+                            let v = utils::hex_string_to_u256(&value[2..]);
+                            match operator {
+                                ArithmeticOperator::Add => {
+                                    Ok(ASTConstant::Number(left + v))
+                                }
+                                ArithmeticOperator::Subtract => {
+                                    Ok(ASTConstant::Number(left - v))
+                                }
+                                ArithmeticOperator::Multiply => {
+                                    Ok(ASTConstant::Number(left * v))
+                                }
+                                ArithmeticOperator::Divide => {
+                                    Ok(ASTConstant::Number(left / v))
+                                }
+                                ArithmeticOperator::Modulo => {
+                                    Ok(ASTConstant::Number(left % v))
+                                }
+                                _ =>
+                                    Err(
+                                        ASTError::InvalidArithmeticOperator(
+                                            operator.to_string().to_owned()
+                                        )
+                                    ),
+                            }
+                        } else if value.starts_with("u256:") {
+                            let v = u256::from_str(&value[5..]).unwrap();
+                            match operator {
+                                ArithmeticOperator::Add => {
+                                    Ok(ASTConstant::Number(left + v))
+                                }
+                                ArithmeticOperator::Subtract => {
+                                    Ok(ASTConstant::Number(left - v))
+                                }
+                                ArithmeticOperator::Multiply => {
+                                    Ok(ASTConstant::Number(left * v))
+                                }
+                                ArithmeticOperator::Divide => {
+                                    Ok(ASTConstant::Number(left / v))
+                                }
+                                ArithmeticOperator::Modulo => {
+                                    Ok(ASTConstant::Number(left % v))
+                                }
+                                _ =>
+                                    Err(
+                                        ASTError::InvalidArithmeticOperator(
+                                            operator.to_string().to_owned()
+                                        )
+                                    ),
+                            }
+                        } else if value.starts_with("i256:") {
+                            let v = i256::from_str(&value[5..]).unwrap();
+                            match operator {
+                                ArithmeticOperator::Add => {
+                                    Ok(ASTConstant::SignedNumber(left.as_i256() + v))
+                                }
+                                ArithmeticOperator::Subtract => {
+                                    Ok(ASTConstant::SignedNumber(left.as_i256() - v))
+                                }
+                                ArithmeticOperator::Multiply => {
+                                    Ok(ASTConstant::SignedNumber(left.as_i256() * v))
+                                }
+                                ArithmeticOperator::Divide => {
+                                    Ok(ASTConstant::SignedNumber(left.as_i256() / v))
+                                }
+                                ArithmeticOperator::Modulo => {
+                                    Ok(ASTConstant::SignedNumber(left.as_i256() % v))
+                                }
+                                _ =>
+                                    Err(
+                                        ASTError::InvalidArithmeticOperator(
+                                            operator.to_string().to_owned()
+                                        )
+                                    ),
+                            }
+                        } else {
+                            Err(ASTError::InvalidConversion(value, "Number".to_string()))
+                        }
+                    }
+                    _ => Err(ASTError::InvalidConstant(operator.to_string().to_owned())),
+                }
+            }
+            ASTConstant::Array(value) => {
+                match right {
+                    ASTConstant::Number(right) => {
+                        match operator {
+                            ArithmeticOperator::Add => {
+                                let arr = value
+                                    .iter()
+                                    .map(|value| {
+                                        let mut element = value.clone();
+                                        match &mut element {
+                                            ASTConstant::Number(num) => {
+                                                *num += right;
+                                            }
+                                            ASTConstant::Array(inner_array) => {
+                                                // *inner_array = inner_array.iter().map(|inner| {
+
+                                                // })
+                                                todo!("Add to Nested Array");
+                                            }
+                                            _ => unreachable!(),
+                                        }
+                                        element
+                                    })
+                                    .collect();
+                                Ok(ASTConstant::Array(arr))
+                            }
+                            ArithmeticOperator::Subtract => {
+                                let arr = value
+                                    .iter()
+                                    .map(|value| {
+                                        let mut element = value.clone();
+                                        match &mut element {
+                                            ASTConstant::Number(num) => {
+                                                *num -= right;
+                                            }
+                                            ASTConstant::Array(inner_array) => {
+                                                todo!("Add to Nested Array");
+                                            }
+                                            _ => unreachable!(),
+                                        }
+                                        element
+                                    })
+                                    .collect();
+                                Ok(ASTConstant::Array(arr))
+                            }
+                            ArithmeticOperator::Multiply => {
+                                let arr = value
+                                    .iter()
+                                    .map(|value| {
+                                        let mut element = value.clone();
+                                        match &mut element {
+                                            ASTConstant::Number(num) => {
+                                                *num *= right;
+                                            }
+                                            ASTConstant::Array(inner_array) => {
+                                                todo!("Add to Nested Array");
+                                            }
+                                            _ => unreachable!(),
+                                        }
+                                        element
+                                    })
+                                    .collect();
+                                Ok(ASTConstant::Array(arr))
+                            }
+                            ArithmeticOperator::Divide => {
+                                let arr = value
+                                    .iter()
+                                    .map(|value| {
+                                        let mut element = value.clone();
+                                        match &mut element {
+                                            ASTConstant::Number(num) => {
+                                                *num = *num / right;
+                                            }
+                                            ASTConstant::Array(inner_array) => {
+                                                todo!("Add to Nested Array");
+                                            }
+                                            _ => unreachable!(),
+                                        }
+                                        element
+                                    })
+                                    .collect();
+                                Ok(ASTConstant::Array(arr))
+                            }
+                            _ =>
+                                Err(
+                                    ASTError::InvalidArithmeticOperator(
+                                        operator.to_string().to_owned()
+                                    )
+                                ),
+                        }
+                    }
+                    _ => Err(ASTError::InvalidConstant(operator.to_string().to_owned())),
+                }
+            }
+            _ => Err(ASTError::InvalidConstant(operator.to_string().to_owned())),
+        }
+    }
+
+    /// Applies the logic operator to the evaluated operands
+    fn binary_logic(operator: &LogicOperator, left: ASTConstant, right: ASTConstant) -> Result<ASTConstant, ASTError> {
+        match left {
+            ASTConstant::SignedNumber(left) =>
+                match right {
+                    ASTConstant::Number(right) =>
+                        compare_ordering(operator, cmp_unsigned_signed(right, left).reverse()),
+                    ASTConstant::SignedNumber(right) =>
+                        match operator {
+                            LogicOperator::Equal => Ok(ASTConstant::Bool(left == right)),
+                            LogicOperator::NotEqual => Ok(ASTConstant::Bool(left != right)),
+                            LogicOperator::Greater => Ok(ASTConstant::Bool(left > right)),
+                            LogicOperator::Less => Ok(ASTConstant::Bool(left < right)),
+                            LogicOperator::GreaterOrEqual =>
+                                Ok(ASTConstant::Bool(left >= right)),
+                            LogicOperator::LessOrEqual =>
+                                Ok(ASTConstant::Bool(left <= right)),
+                            _ => Err(ASTError::InvalidBinaryOperator),
+                        }
+                    ASTConstant::Array(right) => {
+                        todo!("Implement Array Logic with signed numbers")
+                    }
+                    ASTConstant::String(right) => {
+                        if right.starts_with("0x") {
+                            let v = i256
+                                ::from_str_hex(&right)
+                                .map_err(|_| ASTError::InvalidConversion(right.clone(), "Number".to_string()))?;
+                            match operator {
+                                LogicOperator::Equal => Ok(ASTConstant::Bool(left == v)),
+                                LogicOperator::NotEqual => Ok(ASTConstant::Bool(left != v)),
+                                LogicOperator::Greater => Ok(ASTConstant::Bool(left > v)),
+                                LogicOperator::Less => Ok(ASTConstant::Bool(left < v)),
+                                LogicOperator::GreaterOrEqual => {
+                                    Ok(ASTConstant::Bool(left >= v))
+                                }
+                                LogicOperator::LessOrEqual =>
+                                    Ok(ASTConstant::Bool(left <= v)),
+                                _ => Err(ASTError::InvalidBinaryOperator),
+                            }
+                        } else if right.starts_with("u256:") {
+                            let v = u256::from_str(&right[5..]).unwrap();
+                            compare_ordering(operator, cmp_unsigned_signed(v, left).reverse())
+                        } else if right.starts_with("i256:") {
+                            let v = i256::from_str(&right[5..]).unwrap();
+                            match operator {
+                                LogicOperator::Equal => Ok(ASTConstant::Bool(left == v)),
+                                LogicOperator::NotEqual => Ok(ASTConstant::Bool(left != v)),
+                                LogicOperator::Greater => Ok(ASTConstant::Bool(left > v)),
+                                LogicOperator::Less => Ok(ASTConstant::Bool(left < v)),
+                                LogicOperator::GreaterOrEqual => {
+                                    Ok(ASTConstant::Bool(left >= v))
+                                }
+                                LogicOperator::LessOrEqual =>
+                                    Ok(ASTConstant::Bool(left <= v)),
+                                _ => Err(ASTError::InvalidBinaryOperator),
+                            }
+                        } else {
+                            Err(ASTError::InvalidConversion(right, "Number".to_string()))
+                        }
+                    }
+                    _ => Err(ASTError::InvalidBinaryOperator),
+                }
+            ASTConstant::Bool(left) =>
+                match right {
+                    ASTConstant::Bool(right) =>
+                        match operator {
+                            LogicOperator::And => Ok(ASTConstant::Bool(left && right)),
+                            LogicOperator::Or => Ok(ASTConstant::Bool(left || right)),
+                            LogicOperator::Equal => Ok(ASTConstant::Bool(left == right)),
+                            LogicOperator::NotEqual => Ok(ASTConstant::Bool(left != right)),
+                            _ => Err(ASTError::InvalidBinaryOperator),
+                        }
+                    | ASTConstant::Number(_)
+                    | ASTConstant::String(_)
+                    | ASTConstant::SignedNumber(_) =>
+                        match operator {
+                            LogicOperator::And => Ok(ASTConstant::Bool(left)),
+                            LogicOperator::Or => Ok(ASTConstant::Bool(left)),
+                            _ => Err(ASTError::InvalidBinaryOperator),
+                        }
+                    _ => Err(ASTError::InvalidConstant(operator.to_string().to_owned())),
+                }
+            ASTConstant::Number(left) =>
+                match right {
+                    ASTConstant::Number(right) =>
+                        match operator {
+                            LogicOperator::Equal => Ok(ASTConstant::Bool(left == right)),
+                            LogicOperator::NotEqual => Ok(ASTConstant::Bool(left != right)),
+                            LogicOperator::Greater => Ok(ASTConstant::Bool(left > right)),
+                            LogicOperator::Less => Ok(ASTConstant::Bool(left < right)),
+                            LogicOperator::GreaterOrEqual =>
+                                Ok(ASTConstant::Bool(left >= right)),
+                            LogicOperator::LessOrEqual =>
+                                Ok(ASTConstant::Bool(left <= right)),
+                            _ => Err(ASTError::InvalidBinaryOperator),
+                        }
+                    ASTConstant::SignedNumber(right) =>
+                        compare_ordering(operator, cmp_unsigned_signed(left, right)),
+                    ASTConstant::String(right) => {
+                        if right.starts_with("0x") {
+                            let v = u256
+                                ::from_str_hex(&right)
+                                .map_err(|_| ASTError::InvalidConversion(right.clone(), "Number".to_string()))?;
+                            match operator {
+                                LogicOperator::Equal => Ok(ASTConstant::Bool(left == v)),
+                                LogicOperator::NotEqual => Ok(ASTConstant::Bool(left != v)),
+                                LogicOperator::Greater => Ok(ASTConstant::Bool(left > v)),
+                                LogicOperator::Less => Ok(ASTConstant::Bool(left < v)),
+                                LogicOperator::GreaterOrEqual => {
+                                    Ok(ASTConstant::Bool(left >= v))
+                                }
+                                LogicOperator::LessOrEqual =>
+                                    Ok(ASTConstant::Bool(left <= v)),
+                                _ => Err(ASTError::InvalidBinaryOperator),
+                            }
+                        } else if right.starts_with("u256:") {
+                            let v = u256::from_str(&right[5..]).unwrap();
+                            match operator {
+                                LogicOperator::Equal => Ok(ASTConstant::Bool(left == v)),
+                                LogicOperator::NotEqual => Ok(ASTConstant::Bool(left != v)),
+                                LogicOperator::Greater => Ok(ASTConstant::Bool(left > v)),
+                                LogicOperator::Less => Ok(ASTConstant::Bool(left < v)),
+                                LogicOperator::GreaterOrEqual => {
+                                    Ok(ASTConstant::Bool(left >= v))
+                                }
+                                LogicOperator::LessOrEqual =>
+                                    Ok(ASTConstant::Bool(left <= v)),
+                                _ => Err(ASTError::InvalidBinaryOperator),
+                            }
+                        } else if right.starts_with("i256:") {
+                            let v = i256::from_str(&right[5..]).unwrap();
+                            compare_ordering(operator, cmp_unsigned_signed(left, v))
+                        } else {
+                            Err(ASTError::InvalidConversion(right, "Number".to_string()))
+                        }
+                    }
+                    _ => Err(ASTError::InvalidConstant(operator.to_string().to_owned())),
+                }
+            ASTConstant::String(left) =>
+                match right {
+                    ASTConstant::String(right) =>
+                        match operator {
+                            LogicOperator::Equal => Ok(ASTConstant::Bool(left == right)),
+                            LogicOperator::NotEqual => Ok(ASTConstant::Bool(left != right)),
+                            | LogicOperator::Greater
+                            | LogicOperator::Less
+                            | LogicOperator::GreaterOrEqual
+                            | LogicOperator::LessOrEqual => {
+                                // Numbers in strings are compared by value, all other strings lexicographically
+                                let ordering = match (numeric_string(&left)?, numeric_string(&right)?) {
+                                    (Some(l), Some(r)) => cmp_numbers(&l, &r),
+                                    _ => left.cmp(&right),
+                                };
+                                compare_ordering(operator, ordering)
+                            }
+                            _ => Err(ASTError::InvalidBinaryOperator),
+                        }
+                    ASTConstant::Number(right) => {
+                        if left.starts_with("0x") {
+                            let l = u256
+                                ::from_str_hex(&left)
+                                .map_err(|_| ASTError::InvalidConversion(left.clone(), "Number".to_string()))?;
+                            match operator {
+                                LogicOperator::Equal => Ok(ASTConstant::Bool(l == right)),
+                                LogicOperator::NotEqual =>
+                                    Ok(ASTConstant::Bool(l != right)),
+                                LogicOperator::Greater => Ok(ASTConstant::Bool(l > right)),
+                                LogicOperator::Less => Ok(ASTConstant::Bool(l < right)),
+                                LogicOperator::GreaterOrEqual => {
+                                    Ok(ASTConstant::Bool(l >= right))
+                                }
+                                LogicOperator::LessOrEqual =>
+                                    Ok(ASTConstant::Bool(l <= right)),
+                                _ => Err(ASTError::InvalidBinaryOperator),
+                            }
+                        } else if left.starts_with("u256:") {
+                            let l = u256::from_str(&left[5..]).unwrap();
+                            match operator {
+                                LogicOperator::Equal => Ok(ASTConstant::Bool(l == right)),
+                                LogicOperator::NotEqual =>
+                                    Ok(ASTConstant::Bool(l != right)),
+                                LogicOperator::Greater => Ok(ASTConstant::Bool(l > right)),
+                                LogicOperator::Less => Ok(ASTConstant::Bool(l < right)),
+                                LogicOperator::GreaterOrEqual => {
+                                    Ok(ASTConstant::Bool(l >= right))
+                                }
+                                LogicOperator::LessOrEqual =>
+                                    Ok(ASTConstant::Bool(l <= right)),
+                                _ => Err(ASTError::InvalidBinaryOperator),
+                            }
+                        } else if left.starts_with("i256:") {
+                            let l = i256::from_str(&left[5..]).unwrap();
+                            match operator {
+                                LogicOperator::Equal => {
+                                    Ok(ASTConstant::Bool(l == right.as_i256()))
+                                }
+                                LogicOperator::NotEqual => {
+                                    Ok(ASTConstant::Bool(l != right.as_i256()))
+                                }
+                                LogicOperator::Greater => {
+                                    Ok(ASTConstant::Bool(l > right.as_i256()))
+                                }
+                                LogicOperator::Less => {
+                                    Ok(ASTConstant::Bool(l < right.as_i256()))
+                                }
+                                LogicOperator::GreaterOrEqual => {
+                                    Ok(ASTConstant::Bool(l >= right.as_i256()))
+                                }
+                                LogicOperator::LessOrEqual => {
+                                    Ok(ASTConstant::Bool(l <= right.as_i256()))
+                                }
+                                _ => Err(ASTError::InvalidBinaryOperator),
+                            }
+                        } else {
+                            Err(ASTError::InvalidConversion(left, "Number".to_string()))
+                        }
+                    }
+                    ASTConstant::SignedNumber(right) => {
+                        if left.starts_with("0x") {
+                            let l = i256
+                                ::from_str_hex(&left)
+                                .map_err(|_| ASTError::InvalidConversion(left.clone(), "Number".to_string()))?;
+                            match operator {
+                                LogicOperator::Equal => Ok(ASTConstant::Bool(l == right)),
+                                LogicOperator::NotEqual =>
+                                    Ok(ASTConstant::Bool(l != right)),
+                                LogicOperator::Greater => Ok(ASTConstant::Bool(l > right)),
+                                LogicOperator::Less => Ok(ASTConstant::Bool(l < right)),
+                                LogicOperator::GreaterOrEqual => {
+                                    Ok(ASTConstant::Bool(l >= right))
+                                }
+                                LogicOperator::LessOrEqual =>
+                                    Ok(ASTConstant::Bool(l <= right)),
+                                _ => Err(ASTError::InvalidBinaryOperator),
+                            }
+                        } else if left.starts_with("u256:") {
+                            let l = i256::from_str(&left[5..]).unwrap();
+                            match operator {
+                                LogicOperator::Equal => Ok(ASTConstant::Bool(l == right)),
+                                LogicOperator::NotEqual =>
+                                    Ok(ASTConstant::Bool(l != right)),
+                                LogicOperator::Greater => Ok(ASTConstant::Bool(l > right)),
+                                LogicOperator::Less => Ok(ASTConstant::Bool(l < right)),
+                                LogicOperator::GreaterOrEqual => {
+                                    Ok(ASTConstant::Bool(l >= right))
+                                }
+                                LogicOperator::LessOrEqual =>
+                                    Ok(ASTConstant::Bool(l <= right)),
+                                _ => Err(ASTError::InvalidBinaryOperator),
+                            }
+                        } else if left.starts_with("i256:") {
+                            let l = i256::from_str(&left[5..]).unwrap();
+                            match operator {
+                                LogicOperator::Equal => Ok(ASTConstant::Bool(l == right)),
+                                LogicOperator::NotEqual =>
+                                    Ok(ASTConstant::Bool(l != right)),
+                                LogicOperator::Greater => Ok(ASTConstant::Bool(l > right)),
+                                LogicOperator::Less => Ok(ASTConstant::Bool(l < right)),
+                                LogicOperator::GreaterOrEqual => {
+                                    Ok(ASTConstant::Bool(l >= right))
+                                }
+                                LogicOperator::LessOrEqual =>
+                                    Ok(ASTConstant::Bool(l <= right)),
+                                _ => Err(ASTError::InvalidBinaryOperator),
+                            }
+                        } else {
+                            Err(ASTError::InvalidConversion(left, "Number".to_string()))
+                        }
+                    }
+                    ASTConstant::Bool(right) =>
+                        match operator {
+                            LogicOperator::And => Ok(ASTConstant::Bool(right)),
+                            LogicOperator::Or => Ok(ASTConstant::Bool(right)),
+                            _ => Err(ASTError::InvalidBinaryOperator),
+                        }
+                    _ => Err(ASTError::InvalidConstant(operator.to_string().to_owned())),
+                }
+            ASTConstant::Array(left) => {
+                match right {
+                    ASTConstant::SignedNumber(right) =>
+                        match operator {
+                            LogicOperator::Greater =>
+                                Ok(
+                                    ASTConstant::Bool(
+                                        left.iter().all(|element| {
+                                            match element {
+                                                ASTConstant::SignedNumber(num) =>
+                                                    *num > right,
+                                                _ => unreachable!(),
+                                            }
+                                        })
+                                    )
+                                ),
+                            LogicOperator::Less =>
+                                Ok(
+                                    ASTConstant::Bool(
+                                        left.iter().all(|element| {
+                                            match element {
+                                                ASTConstant::SignedNumber(num) =>
+                                                    *num < right,
+                                                _ => unreachable!(),
+                                            }
+                                        })
+                                    )
+                                ),
+                            LogicOperator::GreaterOrEqual =>
+                                Ok(
+                                    ASTConstant::Bool(
+                                        left.iter().all(|element| {
+                                            match element {
+                                                ASTConstant::SignedNumber(num) =>
+                                                    *num >= right,
+                                                _ => unreachable!(),
+                                            }
+                                        })
+                                    )
+                                ),
+                            LogicOperator::LessOrEqual =>
+                                Ok(
+                                    ASTConstant::Bool(
+                                        left.iter().all(|element| {
+                                            match element {
+                                                ASTConstant::SignedNumber(num) =>
+                                                    *num <= right,
+                                                _ => unreachable!(),
+                                            }
+                                        })
+                                    )
+                                ),
+                            LogicOperator::Equal =>
+                                Ok(
+                                    ASTConstant::Bool(
+                                        left.iter().all(|element| {
+                                            match element {
+                                                ASTConstant::SignedNumber(num) =>
+                                                    *num == right,
+                                                _ => unreachable!(),
+                                            }
+                                        })
+                                    )
+                                ),
+                            LogicOperator::NotEqual =>
+                                Ok(
+                                    ASTConstant::Bool(
+                                        left.iter().all(|element| {
+                                            match element {
+                                                ASTConstant::SignedNumber(num) =>
+                                                    *num != right,
+                                                _ => unreachable!(),
+                                            }
+                                        })
+                                    )
+                                ),
+                            _ => Err(ASTError::InvalidBinaryOperator),
+                        }
+                    ASTConstant::Number(right) =>
+                        match operator {
+                            LogicOperator::Greater =>
+                                Ok(
+                                    ASTConstant::Bool(
+                                        left.iter().all(|element| {
+                                            match element {
+                                                ASTConstant::Number(num) => *num > right,
+                                                _ => unreachable!(),
+                                            }
+                                        })
+                                    )
+                                ),
+                            LogicOperator::Less =>
+                                Ok(
+                                    ASTConstant::Bool(
+                                        left.iter().all(|element| {
+                                            match element {
+                                                ASTConstant::Number(num) => *num < right,
+                                                _ => unreachable!(),
+                                            }
+                                        })
+                                    )
+                                ),
+                            LogicOperator::GreaterOrEqual =>
+                                Ok(
+                                    ASTConstant::Bool(
+                                        left.iter().all(|element| {
+                                            match element {
+                                                ASTConstant::Number(num) => *num >= right,
+                                                _ => unreachable!(),
+                                            }
+                                        })
+                                    )
+                                ),
+                            LogicOperator::LessOrEqual =>
+                                Ok(
+                                    ASTConstant::Bool(
+                                        left.iter().all(|element| {
+                                            match element {
+                                                ASTConstant::Number(num) => *num <= right,
+                                                _ => unreachable!(),
+                                            }
+                                        })
+                                    )
+                                ),
+                            LogicOperator::Equal =>
+                                Ok(
+                                    ASTConstant::Bool(
+                                        left.iter().all(|element| {
+                                            match element {
+                                                ASTConstant::Number(num) => *num == right,
+                                                _ => unreachable!(),
+                                            }
+                                        })
+                                    )
+                                ),
+                            LogicOperator::NotEqual =>
+                                Ok(
+                                    ASTConstant::Bool(
+                                        left.iter().all(|element| {
+                                            match element {
+                                                ASTConstant::Number(num) => *num != right,
+                                                _ => unreachable!(),
+                                            }
+                                        })
+                                    )
+                                ),
+                            _ => Err(ASTError::InvalidBinaryOperator),
                         }
-                    }
-                    ASTConstant::Map(m) => {
+                    ASTConstant::Array(right) => {
                         match operator {
                             LogicOperator::Equal => {
-                                match right {
-                                    ASTConstant::Map(hash_map) => {
-                                        for (key, value) in m.iter() {
-                                            if let Some(v) = hash_map.get(key) {
-                                                if v != value {
-                                                    return Ok(ASTConstant::Bool(false));
-                                                }
-                                            } else {
-                                                return Ok(ASTConstant::Bool(false));
-                                            }
-                                        }
-                                        return Ok(ASTConstant::Bool(true));
-                                    },
-                                    _ => {return Ok(ASTConstant::Bool(false));}
-                                }
-                            },
-                            LogicOperator::NotEqual => {
-                                match right {
-                                    ASTConstant::Map(hash_map) => {
-                                        for (key, value) in m.iter() {
-                                            if let Some(v) = hash_map.get(key) {
-                                                if v != value {
-                                                    return Ok(ASTConstant::Bool(true));
-                                                }
-                                            } else {
-                                                return Ok(ASTConstant::Bool(true));
-                                            }
-                                        }
+                                for i in 0..left.len() {
+                                    // println!("{:?}", left[i].get_value());
+                                    // println!("{:?}", right[i].get_value());
+                                    if left[i].get_value() != right[i].get_value() {
                                         return Ok(ASTConstant::Bool(false));
-                                    },
-                                    _ => {return Ok(ASTConstant::Bool(true));}
+                                    }
+                                    // println!("is equal");
                                 }
-                            },
-                            _ => return Err(ASTError::InvalidBinaryOperator),
-                        }
-                        Err(ASTError::InvalidBinaryOperator)
-                    },
-                }
-            }
-            ASTNode::Function(function_name, args) => {
-                match function_name {
-                    Functions::Contains => {
-                        let set = args[0].evaluate()?;
-                        let value = args[1].evaluate()?;
-                        match set {
-                            ASTConstant::Array(arr) => {
-                                // println!("{}\n{}", arr.len(), value.get_value());
-                                for element in arr {
-                                    // println!("{}", element.get_value());
-                                    if element.get_value() == value.get_value() {
+                                Ok(ASTConstant::Bool(true))
+                            }
+                            LogicOperator::NotEqual => {
+                                for i in 0..left.len() {
+                                    // println!("{:?}", left[i].get_value());
+                                    // println!("{:?}", right[i].get_value());
+                                    if left[i].get_value() != right[i].get_value() {
                                         return Ok(ASTConstant::Bool(true));
                                     }
+                                    // println!("is equal");
                                 }
                                 Ok(ASTConstant::Bool(false))
-                                //Ok(ASTConstant::Bool(arr.iter().any(|element| element.get_value() == value.get_value())))
                             }
-                            ASTConstant::String(s) => {
-                                Ok(ASTConstant::Bool(s.contains(&value.get_value())))
+                            | LogicOperator::Greater
+                            | LogicOperator::Less
+                            | LogicOperator::GreaterOrEqual
+                            | LogicOperator::LessOrEqual => {
+                                todo!("Implement logic operator for array");
                             }
-                            _ => Err(ASTError::InvalidFunctionInvocation("contains".to_owned())),
+                            _ => Err(ASTError::InvalidBinaryOperator),
                         }
                     }
-                    Functions::At => {
-                        let set = args[0].evaluate()?;
-                        let index = args[1].evaluate()?;
-
-                        let idx = index
-                            .get_value()
-                            .parse::<usize>()
-                            .expect("index must be an integer");
-
-                        match set {
-                            ASTConstant::Array(arr) => {
-                                let entry = &arr[idx];
-                                match entry {
-                                    ASTConstant::Bool(value) => Ok(ASTConstant::Bool(*value)),
-                                    ASTConstant::Number(value) => Ok(ASTConstant::Number(*value)),
-                                    ASTConstant::SignedNumber(value) => {
-                                        Ok(ASTConstant::SignedNumber(*value))
-                                    }
-                                    ASTConstant::String(value) => {
-                                        Ok(ASTConstant::String(value.to_string()))
-                                    }
-                                    ASTConstant::Array(value) => {
-                                        Ok(ASTConstant::Array(value.clone()))
-                                    }
-                                    ASTConstant::Map(m) => Ok(ASTConstant::Map(m.clone())),
-                                }
+                    _ => Err(ASTError::InvalidConstant(operator.to_string().to_owned())),
+                }
+            }
+            ASTConstant::Map(left) => {
+                match right {
+                    ASTConstant::Map(right) =>
+                        match operator {
+                            LogicOperator::Equal => Ok(ASTConstant::Bool(maps_equal(&left, &right))),
+                            LogicOperator::NotEqual => Ok(ASTConstant::Bool(!maps_equal(&left, &right))),
+                            _ => Err(ASTError::InvalidBinaryOperator),
+                        }
+                    _ => Err(ASTError::InvalidConstant(operator.to_string().to_owned())),
+                }
+            }
+        }
+    }
+
+    fn evaluate_function(function_name: &Functions, args: &Vec<Box<ASTNode>>) -> Result<ASTConstant, ASTError> {
+        match function_name {
+            Functions::Contains => {
+                let set = args[0].evaluate()?;
+                let value = args[1].evaluate()?;
+                match set {
+                    ASTConstant::Array(arr) => {
+                        // println!("{}\n{}", arr.len(), value.get_value());
+                        for element in arr {
+                            // println!("{}", element.get_value());
+                            if element.get_value() == value.get_value() {
+                                return Ok(ASTConstant::Bool(true));
                             }
-                            _ => Err(ASTError::InvalidFunctionInvocation("at".to_owned())),
                         }
+                        Ok(ASTConstant::Bool(false))
+                        //Ok(ASTConstant::Bool(arr.iter().any(|element| element.get_value() == value.get_value())))
                     }
-                    Functions::As => {
-                        let me = args[0].evaluate()?;
-                        let type_name = args[1].evaluate()?;
+                    ASTConstant::String(s) => {
+                        Ok(ASTConstant::Bool(s.contains(&value.get_value())))
+                    }
+                    _ => Err(ASTError::InvalidFunctionInvocation("contains".to_owned())),
+                }
+            }
+            Functions::At => {
+                let set = args[0].evaluate()?;
+                let index = args[1].evaluate()?;
+
+                // Negative indices count from the end, e.g., at(neg 1) is the last element
+                let idx = match index {
+                    ASTConstant::Number(n) => n.as_i256(),
+                    ASTConstant::SignedNumber(n) => n,
+                    _ =>
+                        i256
+                            ::from_str(&index.get_value())
+                            .map_err(|_| ASTError::InvalidNumberConversion(index.get_value()))?,
+                };
+
+                match set {
+                    ASTConstant::Array(arr) => {
+                        let len = arr.len().as_i256();
+                        let pos = if idx < 0 { idx + len } else { idx };
+                        if pos < 0 || pos >= len {
+                            return Err(ASTError::IndexOutOfBounds(idx, arr.len()));
+                        }
+                        let entry = &arr[pos.as_usize()];
+                        match entry {
+                            ASTConstant::Bool(value) => Ok(ASTConstant::Bool(*value)),
+                            ASTConstant::Number(value) => Ok(ASTConstant::Number(*value)),
+                            ASTConstant::SignedNumber(value) => {
+                                Ok(ASTConstant::SignedNumber(*value))
+                            }
+                            ASTConstant::String(value) => {
+                                Ok(ASTConstant::String(value.to_string()))
+                            }
+                            ASTConstant::Array(value) => {
+                                Ok(ASTConstant::Array(value.clone()))
+                            }
+                            ASTConstant::Map(m) => Ok(ASTConstant::Map(m.clone())),
+                        }
+                    }
+                    _ => Err(ASTError::InvalidFunctionInvocation("at".to_owned())),
+                }
+            }
+            Functions::As => {
+                let me = args[0].evaluate()?;
+                let type_name = args[1].evaluate()?;
 
-                        // println!("Type name: {}", type_name.get_value());
-                        // println!("Me: {}", me.get_value());
+                // println!("Type name: {}", type_name.get_value());
+                // println!("Me: {}", me.get_value());
 
-                        let conv = ConversionTarget::from(type_name.get_value().as_str());
+                let conv = ConversionTarget::from(type_name.get_value().as_str());
 
-                        let converted = me.convert(conv);
+                let converted = me.convert(conv);
 
-                        match converted {
-                            Ok(c) => Ok(c),
-                            Err(e) => {
-                                println!("Conversion failed: {}", e);
-                                Ok(me)
-                            }
-                        }
+                match converted {
+                    Ok(c) => Ok(c),
+                    Err(e) => {
+                        log_error!("Conversion failed: {}", e);
+                        Ok(me)
                     }
-                    Functions::Slice => {
-                        let me = args[0].evaluate()?;
-                        let start = args[1].evaluate()?;
-                        let end = args[2].evaluate()?;
-
-                        let start_index = start
-                            .convert(ConversionTarget::Number)
-                            .unwrap()
-                            .get_value()
-                            .parse::<usize>()
-                            .expect("start index must be an integer");
-                        let end_index = end
-                            .convert(ConversionTarget::Number)
-                            .unwrap()
-                            .get_value()
-                            .parse::<usize>()
-                            .expect("end index must be an integer");
-
-                        match me {
-                            ASTConstant::Array(arr) => {
-                                Ok(ASTConstant::Array(arr[start_index..end_index].to_vec()))
-                            }
-                            ASTConstant::String(s) => {
-                                if end_index > s.len() {
-                                    return Err(
-                                        ASTError::InvalidSlice(
-                                            s.clone(),
-                                            start_index,
-                                            end_index,
-                                            s.len()
-                                        )
-                                    );
-                                }
-                                Ok(ASTConstant::String(s[start_index..end_index].to_string()))
-                            }
-                            _ => Err(ASTError::InvalidFunctionInvocation("slice".to_owned())),
+                }
+            }
+            Functions::Slice => {
+                let me = args[0].evaluate()?;
+                let start = args[1].evaluate()?;
+                let end = args[2].evaluate()?;
+
+                let start_index = start
+                    .convert(ConversionTarget::Number)
+                    .unwrap()
+                    .get_value()
+                    .parse::<usize>()
+                    .expect("start index must be an integer");
+                let end_index = end
+                    .convert(ConversionTarget::Number)
+                    .unwrap()
+                    .get_value()
+                    .parse::<usize>()
+                    .expect("end index must be an integer");
+
+                match me {
+                    ASTConstant::Array(arr) => {
+                        Ok(ASTConstant::Array(arr[start_index..end_index].to_vec()))
+                    }
+                    ASTConstant::String(s) => {
+                        if end_index > s.len() {
+                            return Err(
+                                ASTError::InvalidSlice(
+                                    s.clone(),
+                                    start_index,
+                                    end_index,
+                                    s.len()
+                                )
+                            );
                         }
+                        Ok(ASTConstant::String(s[start_index..end_index].to_string()))
                     }
-                    Functions::Push => {
-                        let node = args[0].clone();
-                        let me = args[0].evaluate()?;
-                        let value = args[1].evaluate()?;
-                        match me {
-                            ASTConstant::Array(arr) => {
-                                if let ASTNode::Variable(name) = *node {
-                                    if let Some(a) = get_var!(&name) {
-                                        match a {
-                                            VarValues::Array(mut inner) =>
-                                                match value {
-                                                    ASTConstant::Array(arr) => {
-                                                        for item in arr {
-                                                            inner.push(VarValues::from(item));
-                                                        }
-                                                        set_var!(name, VarValues::Array(inner));
-                                                        Ok(ASTConstant::Bool(true))
-                                                    }
-                                                    ASTConstant::Bool(v) => {
-                                                        inner.push(VarValues::from(v));
-                                                        set_var!(name, VarValues::Array(inner));
-                                                        Ok(ASTConstant::Bool(true))
-                                                    }
-                                                    ASTConstant::Number(v) => {
-                                                        inner.push(VarValues::from(v));
-                                                        set_var!(name, VarValues::Array(inner));
-                                                        Ok(ASTConstant::Bool(true))
-                                                    }
-                                                    ASTConstant::SignedNumber(v) => {
-                                                        inner.push(VarValues::from(v));
-                                                        set_var!(name, VarValues::Array(inner));
-                                                        Ok(ASTConstant::Bool(true))
-                                                    }
-                                                    ASTConstant::String(v) => {
-                                                        inner.push(VarValues::from(v));
-                                                        set_var!(name, VarValues::Array(inner));
-                                                        Ok(ASTConstant::Bool(true))
-                                                    }
-                                                    ASTConstant::Map(map) => {
-                                                        inner.push(VarValues::from(map));
-                                                        set_var!(name, VarValues::Array(inner));
-                                                        Ok(ASTConstant::Bool(true))
-                                                    }
+                    _ => Err(ASTError::InvalidFunctionInvocation("slice".to_owned())),
+                }
+            }
+            Functions::Push => {
+                let node = args[0].clone();
+                let me = args[0].evaluate()?;
+                let value = args[1].evaluate()?;
+                match me {
+                    ASTConstant::Array(arr) => {
+                        if let ASTNode::Variable(name) = *node {
+                            if let Some(a) = get_var!(&name) {
+                                match a {
+                                    VarValues::Array(mut inner) =>
+                                        match value {
+                                            ASTConstant::Array(arr) => {
+                                                for item in arr {
+                                                    inner.push(VarValues::from(item));
                                                 }
-                                            _ => {
-                                                return Err(
-                                                    ASTError::InvalidFunctionInvocation(
-                                                        "push".to_owned()
-                                                    )
-                                                );
+                                                set_var!(name, VarValues::Array(inner));
+                                                Ok(ASTConstant::Bool(true))
                                             }
-                                        }
-                                    } else {
-                                        println!("Variable not found: {}", name);
-                                        // Build new Array and push
-                                        match value {
                                             ASTConstant::Bool(v) => {
-                                                let new_arr: Vec<VarValues> = vec![
-                                                    VarValues::from(v)
-                                                ];
-                                                set_var!(name, new_arr);
-                                                return Ok(ASTConstant::Bool(true));
+                                                inner.push(VarValues::from(v));
+                                                set_var!(name, VarValues::Array(inner));
+                                                Ok(ASTConstant::Bool(true))
                                             }
                                             ASTConstant::Number(v) => {
-                                                let new_arr: Vec<VarValues> = vec![
-                                                    VarValues::from(v)
-                                                ];
-                                                set_var!(name, new_arr);
-                                                return Ok(ASTConstant::Bool(true));
+                                                inner.push(VarValues::from(v));
+                                                set_var!(name, VarValues::Array(inner));
+                                                Ok(ASTConstant::Bool(true))
                                             }
                                             ASTConstant::SignedNumber(v) => {
-                                                let new_arr: Vec<VarValues> = vec![
-                                                    VarValues::from(v)
-                                                ];
-                                                set_var!(name, new_arr);
-                                                return Ok(ASTConstant::Bool(true));
+                                                inner.push(VarValues::from(v));
+                                                set_var!(name, VarValues::Array(inner));
+                                                Ok(ASTConstant::Bool(true))
                                             }
                                             ASTConstant::String(v) => {
-                                                let new_arr: Vec<VarValues> = vec![
-                                                    VarValues::from(v)
-                                                ];
-                                                set_var!(name, new_arr);
-                                                return Ok(ASTConstant::Bool(true));
+                                                inner.push(VarValues::from(v));
+                                                set_var!(name, VarValues::Array(inner));
+                                                Ok(ASTConstant::Bool(true))
                                             }
-                                            _ => {
-                                                return Err(
-                                                    ASTError::InvalidFunctionInvocation(
-                                                        "push".to_owned()
-                                                    )
-                                                );
+                                            ASTConstant::Map(map) => {
+                                                inner.push(VarValues::from(map));
+                                                set_var!(name, VarValues::Array(inner));
+                                                Ok(ASTConstant::Bool(true))
                                             }
                                         }
+                                    _ => {
+                                        return Err(
+                                            ASTError::InvalidFunctionInvocation(
+                                                "push".to_owned()
+                                            )
+                                        );
                                     }
-                                } else {
-                                    Err(ASTError::InvalidFunctionInvocation("push".to_owned()))
                                 }
-                            }
-                            ASTConstant::String(s) => {
-                                let new_string = format!("{}{}", s, value.get_value());
-                                if let ASTNode::Variable(name) = *node {
-                                    set_var!(name, new_string.clone());
+                            } else {
+                                log_error!("Variable not found: {}", name);
+                                // Build new Array and push
+                                match value {
+                                    ASTConstant::Bool(v) => {
+                                        let new_arr: Vec<VarValues> = vec![
+                                            VarValues::from(v)
+                                        ];
+                                        set_var!(name, new_arr);
+                                        return Ok(ASTConstant::Bool(true));
+                                    }
+                                    ASTConstant::Number(v) => {
+                                        let new_arr: Vec<VarValues> = vec![
+                                            VarValues::from(v)
+                                        ];
+                                        set_var!(name, new_arr);
+                                        return Ok(ASTConstant::Bool(true));
+                                    }
+                                    ASTConstant::SignedNumber(v) => {
+                                        let new_arr: Vec<VarValues> = vec![
+                                            VarValues::from(v)
+                                        ];
+                                        set_var!(name, new_arr);
+                                        return Ok(ASTConstant::Bool(true));
+                                    }
+                                    ASTConstant::String(v) => {
+                                        let new_arr: Vec<VarValues> = vec![
+                                            VarValues::from(v)
+                                        ];
+                                        set_var!(name, new_arr);
+                                        return Ok(ASTConstant::Bool(true));
+                                    }
+                                    _ => {
+                                        return Err(
+                                            ASTError::InvalidFunctionInvocation(
+                                                "push".to_owned()
+                                            )
+                                        );
+                                    }
                                 }
-                                Ok(ASTConstant::String(new_string))
-                            }
-                            ASTConstant::Number(n) => {
-                                let new_number = format!("{}{}", n, value.get_value());
-                                Ok(ASTConstant::String(new_number))
-                            }
-                            ASTConstant::SignedNumber(n) => {
-                                let new_number = format!("{}{}", n, value.get_value());
-                                Ok(ASTConstant::String(new_number))
-                            }
-                            _ => {
-                                return Err(ASTError::InvalidFunctionInvocation("push".to_owned()));
                             }
+                        } else {
+                            Err(ASTError::InvalidFunctionInvocation("push".to_owned()))
                         }
                     }
-                    Functions::Pop => {
-                        let me = args[0].clone().evaluate()?;
-                        match me {
-                            ASTConstant::Array(mut arr) => {
-                                let last = arr.pop().unwrap();
-                                if let ASTNode::Variable(name) = *args[0].clone() {
-                                    set_var!(name, arr);
-                                }
+                    ASTConstant::String(s) => {
+                        let new_string = format!("{}{}", s, value.get_value());
+                        if let ASTNode::Variable(name) = *node {
+                            set_var!(name, new_string.clone());
+                        }
+                        Ok(ASTConstant::String(new_string))
+                    }
+                    ASTConstant::Number(n) => {
+                        let new_number = format!("{}{}", n, value.get_value());
+                        Ok(ASTConstant::String(new_number))
+                    }
+                    ASTConstant::SignedNumber(n) => {
+                        let new_number = format!("{}{}", n, value.get_value());
+                        Ok(ASTConstant::String(new_number))
+                    }
+                    _ => {
+                        return Err(ASTError::InvalidFunctionInvocation("push".to_owned()));
+                    }
+                }
+            }
+            Functions::Pop => {
+                let me = args[0].clone().evaluate()?;
+                match me {
+                    ASTConstant::Array(mut arr) => {
+                        let last = arr.pop().ok_or(ASTError::EmptyArray)?;
+                        if let ASTNode::Variable(name) = *args[0].clone() {
+                            set_var!(name, arr);
+                        }
 
-                                match last {
-                                    ASTConstant::Bool(v) => Ok(ASTConstant::Bool(v)),
-                                    ASTConstant::Number(v) => Ok(ASTConstant::Number(v)),
-                                    ASTConstant::SignedNumber(v) => {
-                                        Ok(ASTConstant::SignedNumber(v))
-                                    }
-                                    ASTConstant::String(v) => Ok(ASTConstant::String(v)),
-                                    _ => Err(ASTError::InvalidFunctionInvocation("pop".to_owned())),
-                                }
+                        match last {
+                            ASTConstant::Bool(v) => Ok(ASTConstant::Bool(v)),
+                            ASTConstant::Number(v) => Ok(ASTConstant::Number(v)),
+                            ASTConstant::SignedNumber(v) => {
+                                Ok(ASTConstant::SignedNumber(v))
                             }
+                            ASTConstant::String(v) => Ok(ASTConstant::String(v)),
                             _ => Err(ASTError::InvalidFunctionInvocation("pop".to_owned())),
                         }
                     }
-                    Functions::Keccak256 => {
-                        let evalled_args = args
-                            .iter()
-                            .map(|x| x.evaluate().unwrap())
-                            .collect::<Vec<ASTConstant>>();
-
-                        let serialized_values = encode_packed(&evalled_args).unwrap();
-
-                        let concatenated_bytes = serialized_values.as_slice();
-
-                        // let mut hasher = sha3::Keccak256::digest(concatenated_bytes).to_vec();
-                        // let hex_string = hasher
-                        //     .iter()
-                        //     .map(|&num| format!("{:02x}", num))
-                        //     .collect::<Vec<String>>()
-                        //     .join("");
-                        // let s = "0x".to_string() + &hex_string;
-                        // // println!("Keccak256: {}", s);
-                        unimplemented!("Keccak256 is not implemented yet");
-                        // Ok(ASTConstant::String(s))
+                    _ => Err(ASTError::InvalidFunctionInvocation("pop".to_owned())),
+                }
+            }
+            Functions::Keccak256 => {
+                let evalled_args = args
+                    .iter()
+                    .map(|x| x.evaluate().unwrap())
+                    .collect::<Vec<ASTConstant>>();
+
+                let serialized_values = encode_packed(&evalled_args).unwrap();
+
+                let concatenated_bytes = serialized_values.as_slice();
+
+                // let mut hasher = sha3::Keccak256::digest(concatenated_bytes).to_vec();
+                // let hex_string = hasher
+                //     .iter()
+                //     .map(|&num| format!("{:02x}", num))
+                //     .collect::<Vec<String>>()
+                //     .join("");
+                // let s = "0x".to_string() + &hex_string;
+                // // println!("Keccak256: {}", s);
+                unimplemented!("Keccak256 is not implemented yet");
+                // Ok(ASTConstant::String(s))
+            }
+            Functions::Insert => {
+                let me = args[0].clone().evaluate()?;
+                let key = args[1].evaluate()?;
+                let value = args[2].evaluate()?;
+                match me {
+                    ASTConstant::Map(mut map) => {
+                        if !insert_nested(&mut map, &key.get_value(), value) {
+                            return Err(
+                                ASTError::InvalidFunctionInvocation("insert".to_owned())
+                            );
+                        }
+                        // println!("Insert: {}", key.get_value());
+                        if let ASTNode::Variable(name) = *args[0].clone() {
+                            // println!("Store: {}", name);
+                            set_var!(name, map);
+                            return Ok(ASTConstant::Bool(true));
+                        }
+                        return Ok(ASTConstant::Bool(false));
                     }
-                    Functions::Insert => {
-                        let me = args[0].clone().evaluate()?;
-                        let key = args[1].evaluate()?;
-                        let value = args[2].evaluate()?;
-                        match me {
-                            ASTConstant::Map(mut map) => {
-                                map.insert(key.get_value(), value);
-                                // println!("Insert: {}", key.get_value());
+                    _ => {
+                        return Err(
+                            ASTError::InvalidFunctionInvocation("insert".to_owned())
+                        );
+                    }
+                }
+            }
+            Functions::Remove => {
+                let me = args[0].evaluate()?;
+                let key = args[1].evaluate()?;
+
+                match me.clone() {
+                    ASTConstant::Map(mut map) =>
+                        match remove_nested(&mut map, &key.get_value()) {
+                            Some(v) => {
                                 if let ASTNode::Variable(name) = *args[0].clone() {
-                                    // println!("Store: {}", name);
                                     set_var!(name, map);
-                                    return Ok(ASTConstant::Bool(true));
                                 }
-                                return Ok(ASTConstant::Bool(false));
-                            }
-                            _ => {
-                                return Err(
-                                    ASTError::InvalidFunctionInvocation("insert".to_owned())
-                                );
+                                Ok(v)
                             }
+                            None => Err(ASTError::UnknownKey(key.get_value().to_string())),
+                        }
+                    ASTConstant::Array(mut arr) => {
+                        if arr.len() == 0 {
+                            return Err(ASTError::EmptyArray);
                         }
-                    }
-                    Functions::Remove => {
-                        let me = args[0].evaluate()?;
-                        let key = args[1].evaluate()?;
-
-                        match me.clone() {
-                            ASTConstant::Map(mut map) =>
-                                match map.remove(&key.get_value()) {
-                                    Some(v) => {
-                                        if let ASTNode::Variable(name) = *args[0].clone() {
-                                            set_var!(name, map);
-                                        }
-                                        Ok(v)
-                                    }
-                                    None => Err(ASTError::UnknownKey(key.get_value().to_string())),
-                                }
-                            ASTConstant::Array(mut arr) => {
-                                if arr.len() == 0 {
-                                    return Err(ASTError::EmptyArray);
-                                }
 
-                                let mut index = 0;
-                                for a in &arr {
-                                    if a.get_value() == key.get_value() {
-                                        break;
-                                    }
-                                    index += 1;
-                                }
+                        let mut index = 0;
+                        for a in &arr {
+                            if a.get_value() == key.get_value() {
+                                break;
+                            }
+                            index += 1;
+                        }
 
-                                if index > arr.len() - 1 {
-                                    return Err(
-                                        ASTError::KeyNotFound(key.get_value(), me.get_value())
-                                    );
-                                }
+                        if index > arr.len() - 1 {
+                            return Err(
+                                ASTError::KeyNotFound(key.get_value(), me.get_value())
+                            );
+                        }
 
-                                if index == 0 && arr[0].get_value() != key.get_value() {
-                                    return Err(
-                                        ASTError::KeyNotFound(key.get_value(), me.get_value())
-                                    );
-                                }
-                                let ret = arr.remove(index);
-                                if let ASTNode::Variable(name) = *args[0].clone() {
-                                    set_var!(name, arr);
-                                }
-                                return Ok(ret);
-                            }
-                            _ => {
-                                return Err(
-                                    ASTError::InvalidFunctionInvocation("remove".to_owned())
-                                );
-                            }
+                        if index == 0 && arr[0].get_value() != key.get_value() {
+                            return Err(
+                                ASTError::KeyNotFound(key.get_value(), me.get_value())
+                            );
                         }
-                    }
-                    Functions::Get => {
-                        let me = args[0].clone().evaluate()?;
-                        let key = args[1].evaluate()?;
-                        // println!("Me{:?} Get({:?})",me, &key.get_value());
-                        match me {
-                            ASTConstant::Map(map) =>
-                                match map.get(&key.get_value()) {
-                                    Some(value) => Ok(value.clone()),
-                                    None => Err(ASTError::UnknownKey(key.get_value().to_string())),
-                                }
-                            _ => {
-                                return Err(ASTError::InvalidFunctionInvocation("get".to_owned()));
-                            }
+                        let ret = arr.remove(index);
+                        if let ASTNode::Variable(name) = *args[0].clone() {
+                            set_var!(name, arr);
                         }
+                        return Ok(ret);
                     }
-                    Functions::Assign => {
-                        let key = args[0].clone().evaluate()?;
-                        let value = args[1].evaluate()?;
-                        match key {
-                            ASTConstant::String(s) => {
-                                set_var!(s, value);
-                                Ok(ASTConstant::Bool(true))
-                            }
-                            _ => {
-                                return Err(
-                                    ASTError::InvalidFunctionInvocation("assign".to_owned())
-                                );
-                            }
-                        }
+                    _ => {
+                        return Err(
+                            ASTError::InvalidFunctionInvocation("remove".to_owned())
+                        );
                     }
-                    Functions::ToLower => {
-                        let me = args[0].evaluate()?;
-                        match me {
-                            ASTConstant::String(s) => Ok(ASTConstant::String(s.to_lowercase())),
-                            _ => Err(ASTError::InvalidFunctionInvocation("tolower".to_owned())),
+                }
+            }
+            Functions::Get => {
+                let me = args[0].clone().evaluate()?;
+                let key = args[1].evaluate()?;
+                // println!("Me{:?} Get({:?})",me, &key.get_value());
+                match me {
+                    ASTConstant::Map(map) =>
+                        match get_nested(&map, &key.get_value()) {
+                            Some(value) => Ok(value.clone()),
+                            None => Err(ASTError::UnknownKey(key.get_value().to_string())),
                         }
+                    _ => {
+                        return Err(ASTError::InvalidFunctionInvocation("get".to_owned()));
                     }
-                    Functions::ToUpper => {
-                        let me = args[0].evaluate()?;
-                        match me {
-                            ASTConstant::String(s) => Ok(ASTConstant::String(s.to_uppercase())),
-                            _ => Err(ASTError::InvalidFunctionInvocation("toupper".to_owned())),
+                }
+            }
+            Functions::Assign | Functions::Let => {
+                let key = args[0].clone().evaluate()?;
+                let value = args[1].evaluate()?;
+                match key {
+                    ASTConstant::String(s) => {
+                        set_var!(s, value.clone());
+                        // Assign stays a bool, let can be chained with its value
+                        match function_name {
+                            Functions::Let => Ok(value),
+                            _ => Ok(ASTConstant::Bool(true)),
                         }
                     }
-                    Functions::Custom => {
-                        // Index 1 = Endpoint
-                        let endpoint = *args[0].clone();
-                        // Index 2 = Function Name
-                        let function_name = *args[1].clone();
-                        // Index 3 and all following = Args
-                        let args = args[2..].to_vec();
-
-                        // Find correct endpoint
-                        let connections: ConnectionConfig = ConnectionConfig::from_file("config/connections.json");
-                        let end = &endpoint.evaluate().unwrap().get_value();
-                        println!("Endpoint: {}", end);
-                        let con = connections.connections.iter().find(|x| x.name == end.to_string());
-                        if let Some(con) = con {
-                            let p = fs::canonicalize(format!("functions/{}/rpc.json", end)).unwrap();
-                            let mut contents: RPCRequest = serde_json
-                                ::from_str(
-                                    &fs
-                                        ::read_to_string(p)
-                                        .expect("Something went wrong reading the file")
-                                )
-                                .unwrap();
-                            let mut clear_args = args
-                                .iter()
-                                .map(|x| x.evaluate().unwrap().get_value())
-                                .collect::<Vec<String>>();
-                            clear_args.insert(0, function_name.evaluate().unwrap().get_value());
-                            let s = replace_args_in_value(&mut contents, &clear_args);
-                            if s.is_err() {
-                                return Err(
-                                    ASTError::RequestReplacementError(
-                                        format!("{:?}", s.unwrap_err())
-                                    )
-                                );
-                            }
-                            // Build Client and send request
-                            let client = reqwest::blocking::Client::builder().build().unwrap();
-                            // print!("Endpoint: {}\n", endpoint_address);
-                            let resp = client
-                                .post(con.rpc_url.clone())
-                                .json(&contents)
-                                .send()
-                                .unwrap();
-                            // let resp2 = client.post(endpoint_address).json(&json).build().unwrap();
-                            // println!("Request: {:?}", resp2);
-                            let body: Value = serde_json::from_str(&resp.text().unwrap()).unwrap();
-                            let body = serde_json::to_string_pretty(&body).unwrap();
-                            println!("Result: {}", body);
-                            let result: Value = serde_json::from_str(&body.as_str()).unwrap();
-
-                            // check if message contains an error
-                            if result.get("error").is_some() {
-                                return Err(
-                                    ASTError::InvalidFunctionInvocation(
-                                        format!(
-                                            "Error: {}",
-                                            result
-                                                .get("error")
-                                                .unwrap()
-                                                .get("message")
-                                                .unwrap()
-                                                .as_str()
-                                                .unwrap()
-                                        )
-                                    )
-                                );
-                            }
-
-                            let ret = ASTNode::from(result).evaluate();
+                    _ => {
+                        return Err(
+                            ASTError::InvalidFunctionInvocation(
+                                function_name.to_string().to_owned()
+                            )
+                        );
+                    }
+                }
+            }
+            Functions::ToLower => {
+                let me = args[0].evaluate()?;
+                match me {
+                    ASTConstant::String(s) => Ok(ASTConstant::String(s.to_lowercase())),
+                    _ => Err(ASTError::InvalidFunctionInvocation("tolower".to_owned())),
+                }
+            }
+            Functions::ToUpper => {
+                let me = args[0].evaluate()?;
+                match me {
+                    ASTConstant::String(s) => Ok(ASTConstant::String(s.to_uppercase())),
+                    _ => Err(ASTError::InvalidFunctionInvocation("toupper".to_owned())),
+                }
+            }
+            Functions::AbiEncode => {
+                let evalled_args = args
+                    .iter()
+                    .map(|x| x.evaluate())
+                    .collect::<Result<Vec<ASTConstant>, ASTError>>()?;
+                Ok(ASTConstant::String(abi_encode(&evalled_args)?))
+            }
+            Functions::Concat => {
+                let joined = args
+                    .iter()
+                    .map(|x| x.evaluate().map(|v| v.get_value()))
+                    .collect::<Result<Vec<String>, ASTError>>()?
+                    .concat();
+                Ok(ASTConstant::String(joined))
+            }
+            Functions::Scale | Functions::Unscale => {
+                let name = function_name.to_string();
+                let value = to_unsigned(args[0].evaluate()?, name)?;
+                let decimals = to_unsigned(args[1].evaluate()?, name)?;
+                let scaled = match function_name {
+                    Functions::Scale => scale(value, decimals)?,
+                    _ => unscale(value, decimals)?,
+                };
+                Ok(ASTConstant::Number(scaled))
+            }
+            Functions::Incr | Functions::Count => {
+                let key: String = args[0].evaluate()?.get_value();
+                let count = match function_name {
+                    Functions::Incr => increment_counter("map", &key),
+                    _ => get_counter("map", &key),
+                };
+                count
+                    .map(ASTConstant::Number)
+                    .ok_or(
+                        ASTError::InvalidFunctionInvocation(function_name.to_string().to_owned())
+                    )
+            }
+            Functions::DailyVolume => {
+                let key: String = args[0].evaluate()?.get_value();
+                let value = to_unsigned(args[1].evaluate()?, "daily_volume")?;
+                accumulate_daily("map", &key, value, Utc::now().date_naive())
+                    .map(ASTConstant::Number)
+                    .ok_or(
+                        ASTError::InvalidFunctionInvocation(function_name.to_string().to_owned())
+                    )
+            }
+            Functions::Selector => {
+                let signature: String = args[0].evaluate()?.get_value();
+                Ok(ASTConstant::String(utils::function_selector(&signature)))
+            }
+            Functions::Now => {
+                let now = Utc::now().timestamp().max(0) as u64;
+                Ok(ASTConstant::Number(u256::from(now)))
+            }
+            Functions::IsReorg => {
+                // Set with the block number of the event
+                Ok(ASTConstant::Bool(matches!(lookup_variable("reorg"), Some(VarValues::Bool(true)))))
+            }
+            Functions::BlockTimestamp => {
+                let chain: String = args[0].evaluate()?.get_value();
+                Ok(ASTConstant::Number(block_timestamp(&chain)?))
+            }
+            Functions::Prev => {
+                // prev(balance) and prev($balance) both refer to the variable balance
+                let key = match &*args[0] {
+                    ASTNode::Variable(name) => name.clone(),
+                    arg => arg.evaluate()?.get_value(),
+                };
+                match previous_value("map", &event_subject(), &key) {
+                    Some(value) => Ok(value.to_ASTNode().evaluate()?),
+                    None => Ok(ASTConstant::String(missing_variable_value())),
+                }
+            }
+            Functions::EventParam => {
+                let name: String = args[0].evaluate()?.get_value();
+                let signature: String = ASTNode::Variable("event_signature".to_string())
+                    .evaluate()?
+                    .get_value();
+                let topics = match ASTNode::Variable("event_topics".to_string()).evaluate()? {
+                    ASTConstant::Array(topics) => topics
+                        .iter()
+                        .map(|t| t.get_value())
+                        .collect::<Vec<String>>(),
+                    _ => vec![],
+                };
+                let data: String = ASTNode::Variable("event_data".to_string()).evaluate()?.get_value();
+                event_param(&signature, &topics, &data, &name)
+            }
+            Functions::Decode => {
+                let data = args[0].evaluate()?;
+                let types = args[1].evaluate()?;
+                match (data, types) {
+                    (ASTConstant::String(data), ASTConstant::Array(types)) => {
+                        let types = types
+                            .iter()
+                            .map(|t| t.get_value())
+                            .collect::<Vec<String>>();
+                        Ok(ASTConstant::Array(abi_decode(&data, &types)?))
+                    }
+                    _ => Err(ASTError::InvalidFunctionInvocation("decode".to_owned())),
+                }
+            }
+            Functions::Custom => {
+                // Index 1 = Endpoint
+                let endpoint = *args[0].clone();
+                // Index 2 = Function Name
+                let function_name = *args[1].clone();
+                // Index 3 and all following = Args
+                let args = args[2..].to_vec();
+
+                let end = &endpoint.evaluate().unwrap().get_value();
+                log_debug!("Endpoint: {}", end);
+                // Every call() is an RPC request to the endpoint
+                record_rpc_call(end);
+
+                // Find correct endpoint
+                let connections: ConnectionConfig = match ConnectionConfig::load() {
+                    Ok(connections) => connections,
+                    Err(e) => {
+                        return Err(ASTError::InvalidCustomCall("call".to_string(), e.to_string()));
+                    }
+                };
+                let con = connections.connections.iter().find(|x| x.name == end.to_string());
+                if let Some(con) = con {
+                    let rpc = utils::get_directories().base_path(format!("functions/{}/rpc.json", end));
+                    let p = fs::canonicalize(rpc).unwrap();
+                    let mut contents: RPCRequest = serde_json
+                        ::from_str(
+                            &fs
+                                ::read_to_string(p)
+                                .expect("Something went wrong reading the file")
+                        )
+                        .unwrap();
+                    let mut clear_args = args
+                        .iter()
+                        .map(|x| x.evaluate().unwrap().get_value())
+                        .collect::<Vec<String>>();
+                    clear_args.insert(0, function_name.evaluate().unwrap().get_value());
+                    replace_args_in_value(&mut contents, &clear_args)?;
+                    // Send the request to the first endpoint of the chain that answers
+                    let body = serde_json::to_value(&contents).unwrap();
+                    let resp = con
+                        .post(&body)
+                        .map_err(|e| ASTError::InvalidCustomCall("call".to_string(), e))?;
+                    // let resp2 = client.post(endpoint_address).json(&json).build().unwrap();
+                    // println!("Request: {:?}", resp2);
+                    let body: Value = serde_json::from_str(&resp.text().unwrap()).unwrap();
+                    let body = serde_json::to_string_pretty(&body).unwrap();
+                    log_debug!("Result: {}", body);
+                    let result: Value = serde_json::from_str(&body.as_str()).unwrap();
+
+                    // check if message contains an error
+                    if let Some(error) = result.get("error") {
+                        return Err(rpc_error(error));
+                    }
 
-                            match ret {
+                    Ok(ASTConstant::from(&result))
+                } else {
+                    return Err(
+                        ASTError::InvalidFunctionInvocation(
+                            format!(
+                                "wrong endpoint in call(). Check if functions/{}/connection.json exists",
+                                endpoint.evaluate().unwrap().get_value()
+                            )
+                        )
+                    );
+                }
+            }
+            Functions::Require => {
+                let cond = args[0].evaluate();
+                match cond {
+                    Ok(v) => {
+                        if v.get_value() == "true" {
+                            // If condition is true: execute statement
+                            let stmt = args[1].evaluate();
+                            match stmt {
                                 Ok(v) => Ok(v),
-                                Err(e) => Err(ASTError::ExpectedJSON),
+                                Err(e) => Err(e),
                             }
                         } else {
-                            return Err(
-                                ASTError::InvalidFunctionInvocation(
-                                    format!(
-                                        "wrong endpoint in call(). Check if functions/{}/connection.json exists",
-                                        endpoint.evaluate().unwrap().get_value()
-                                    )
-                                )
-                            );
-                        }
-                    }
-                    Functions::Require => {
-                        let cond = args[0].evaluate();
-                        match cond {
-                            Ok(v) => {
-                                if v.get_value() == "true" {
-                                    // If condition is true: execute statement
-                                    let stmt = args[1].evaluate();
-                                    match stmt {
-                                        Ok(v) => Ok(v),
-                                        Err(e) => Err(e),
-                                    }
-                                } else {
-                                    // else return false
-                                    Ok(ASTConstant::Bool(false))
-                                }
-                            }
-                            Err(e) => Err(ASTError::RequireError(e.to_string())),
+                            // else return false
+                            Ok(ASTConstant::Bool(false))
                         }
                     }
+                    Err(e) => Err(ASTError::RequireError(e.to_string())),
                 }
             }
-            ASTNode::Array(val) => {
-                let mut arr = vec![];
-                for v in val {
-                    arr.push(v.evaluate()?);
-                }
-                Ok(ASTConstant::Array(arr))
+        }
+    }
+
+    /// Evaluates an operand of a numeric operation.
+    /// A missing variable is reported by its name instead of failing to convert the placeholder value.
+    fn evaluate_numeric_operand(&self) -> Result<ASTConstant, ASTError> {
+        if let ASTNode::Variable(name) = self {
+            if get_var!(name).is_none() {
+                return Err(ASTError::VariableNotFound { var: name.clone() });
             }
         }
+        self.evaluate()
+    }
+
+    /// Returns true if the number the node evaluates to originates from a hex value,
+    /// i.e., a `0x` literal or a variable holding a hex string, also after arithmetic operations.
+    pub fn is_hex(&self) -> bool {
+        match self {
+            ASTNode::ConstantHexNumber(_) => true,
+            ASTNode::Variable(name) =>
+                get_var!(name)
+                    .and_then(|v| v.get_string().map(|s| s.starts_with("0x")))
+                    .unwrap_or(false),
+            ASTNode::UnaryArithmetic(_, value) => value.is_hex(),
+            ASTNode::BinaryArithmetic(_, left, right) => left.is_hex() || right.is_hex(),
+            _ => false,
+        }
+    }
+
+    /// Renders the evaluated value of the node for the logs.
+    /// Numbers that originate from hex values are rendered in hex, e.g., hashes and addresses.
+    pub fn display_value(&self, value: &ASTConstant) -> String {
+        match value {
+            ASTConstant::Number(n) if self.is_hex() => format!("0x{:x}", n),
+            _ => value.get_value(),
+        }
     }
 
     fn format(&self) -> String {
         match self {
             ASTNode::ConstantBool(value) => value.to_string(),
             ASTNode::ConstantNumber(value) => value.to_string(),
+            ASTNode::ConstantHexNumber(value) => format!("0x{:x}", value),
             ASTNode::ConstantSignedNumber(value) => value.to_string(),
             ASTNode::ConstantString(value) => value.clone(),
             ASTNode::Map(map) =>
                 format!(
                     "{}\n",
                     map
+                        .iter()
+                        .collect::<BTreeMap<_, _>>()
                         .iter()
                         .map(|(k, v)| format!("{}: {}", k, v.format()))
                         .collect::<Vec<String>>()
@@ -2392,7 +2810,7 @@ impl From<Value> for ASTNode {
     fn from(value: Value) -> Self {
         match value {
             Value::Bool(value) => ASTNode::ConstantBool(value),
-            Value::Number(value) => ASTNode::ConstantNumber(u256::from(value.as_u64().unwrap())),
+            Value::Number(value) => ASTNode::from(json_number(&value)),
             Value::String(value) => ASTNode::ConstantString(value),
             Value::Array(arr) => {
                 let v = arr
@@ -2422,9 +2840,12 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
     let mut arr: Vec<Box<ASTNode>> = vec![];
     let mut is_array = false;
 
+    // Number of arguments of the next variadic function
+    let mut arity: Option<usize> = None;
+
     // println!("Tokens: {:?}", tokens);
 
-    for (id, token) in tokens.iter().enumerate() {
+    for token in tokens.iter() {
         // stack.last().unwrap_or(&ASTNode::ConstantString("None".to_string())).print("");
         // println!("_____________________________________________________");
 
@@ -2437,14 +2858,14 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                         ArithmeticOperator::Negate => {
                             let node = ASTNode::UnaryArithmetic(
                                 ArithmeticOperator::Negate,
-                                Box::new(stack.pop().unwrap())
+                                Box::new(numeric_operand(pop_operand(&mut stack, token)?))
                             );
                             ast_vec.push(node.clone());
                             stack.push(node);
                         }
                         ArithmeticOperator::Add => {
-                            let right = stack.pop().unwrap();
-                            let left = stack.pop().unwrap();
+                            let right = numeric_operand(pop_operand(&mut stack, token)?);
+                            let left = numeric_operand(pop_operand(&mut stack, token)?);
                             let node = ASTNode::BinaryArithmetic(
                                 ArithmeticOperator::Add,
                                 Box::new(left),
@@ -2454,8 +2875,8 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                             stack.push(node);
                         }
                         ArithmeticOperator::Subtract => {
-                            let right = stack.pop().unwrap();
-                            let left = stack.pop().unwrap();
+                            let right = numeric_operand(pop_operand(&mut stack, token)?);
+                            let left = numeric_operand(pop_operand(&mut stack, token)?);
                             let node = ASTNode::BinaryArithmetic(
                                 ArithmeticOperator::Subtract,
                                 Box::new(left),
@@ -2465,8 +2886,8 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                             stack.push(node);
                         }
                         ArithmeticOperator::Multiply => {
-                            let right = stack.pop().unwrap();
-                            let left = stack.pop().unwrap();
+                            let right = numeric_operand(pop_operand(&mut stack, token)?);
+                            let left = numeric_operand(pop_operand(&mut stack, token)?);
                             let node = ASTNode::BinaryArithmetic(
                                 ArithmeticOperator::Multiply,
                                 Box::new(left),
@@ -2476,8 +2897,8 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                             stack.push(node);
                         }
                         ArithmeticOperator::Divide => {
-                            let right = stack.pop().unwrap();
-                            let left = stack.pop().unwrap();
+                            let right = numeric_operand(pop_operand(&mut stack, token)?);
+                            let left = numeric_operand(pop_operand(&mut stack, token)?);
                             let node = ASTNode::BinaryArithmetic(
                                 ArithmeticOperator::Divide,
                                 Box::new(left),
@@ -2487,8 +2908,8 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                             stack.push(node);
                         }
                         ArithmeticOperator::Modulo => {
-                            let right = stack.pop().unwrap();
-                            let left = stack.pop().unwrap();
+                            let right = numeric_operand(pop_operand(&mut stack, token)?);
+                            let left = numeric_operand(pop_operand(&mut stack, token)?);
                             let node = ASTNode::BinaryArithmetic(
                                 ArithmeticOperator::Modulo,
                                 Box::new(left),
@@ -2497,6 +2918,17 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                             ast_vec.push(node.clone());
                             stack.push(node);
                         }
+                        | ArithmeticOperator::BitAnd
+                        | ArithmeticOperator::BitOr
+                        | ArithmeticOperator::BitXor
+                        | ArithmeticOperator::ShiftLeft
+                        | ArithmeticOperator::ShiftRight => {
+                            let right = numeric_operand(pop_operand(&mut stack, token)?);
+                            let left = numeric_operand(pop_operand(&mut stack, token)?);
+                            let node = ASTNode::BinaryArithmetic(value, Box::new(left), Box::new(right));
+                            ast_vec.push(node.clone());
+                            stack.push(node);
+                        }
                     }
                 Err(_) => {
                     //println!("{} is not an Arithmetic Operator", token);
@@ -2506,14 +2938,14 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                                 LogicOperator::Not => {
                                     let node = ASTNode::UnaryLogic(
                                         LogicOperator::Not,
-                                        Box::new(stack.pop().unwrap())
+                                        Box::new(pop_operand(&mut stack, token)?)
                                     );
                                     ast_vec.push(node.clone());
                                     stack.push(node);
                                 }
                                 LogicOperator::And => {
-                                    let right = stack.pop().unwrap();
-                                    let left = stack.pop().unwrap();
+                                    let right = numeric_operand(pop_operand(&mut stack, token)?);
+                                    let left = numeric_operand(pop_operand(&mut stack, token)?);
                                     let node = ASTNode::BinaryLogic(
                                         LogicOperator::And,
                                         Box::new(left),
@@ -2523,8 +2955,8 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                                     stack.push(node);
                                 }
                                 LogicOperator::Or => {
-                                    let right = stack.pop().unwrap();
-                                    let left = stack.pop().unwrap();
+                                    let right = numeric_operand(pop_operand(&mut stack, token)?);
+                                    let left = numeric_operand(pop_operand(&mut stack, token)?);
                                     let node = ASTNode::BinaryLogic(
                                         LogicOperator::Or,
                                         Box::new(left),
@@ -2534,8 +2966,8 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                                     stack.push(node);
                                 }
                                 LogicOperator::Equal => {
-                                    let right = stack.pop().unwrap();
-                                    let left = stack.pop().unwrap();
+                                    let right = numeric_operand(pop_operand(&mut stack, token)?);
+                                    let left = numeric_operand(pop_operand(&mut stack, token)?);
                                     let node = ASTNode::BinaryLogic(
                                         LogicOperator::Equal,
                                         Box::new(left),
@@ -2545,8 +2977,8 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                                     stack.push(node);
                                 }
                                 LogicOperator::NotEqual => {
-                                    let right = stack.pop().unwrap();
-                                    let left = stack.pop().unwrap();
+                                    let right = numeric_operand(pop_operand(&mut stack, token)?);
+                                    let left = numeric_operand(pop_operand(&mut stack, token)?);
                                     let node = ASTNode::BinaryLogic(
                                         LogicOperator::NotEqual,
                                         Box::new(left),
@@ -2556,8 +2988,8 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                                     stack.push(node);
                                 }
                                 LogicOperator::Greater => {
-                                    let right = stack.pop().unwrap();
-                                    let left = stack.pop().unwrap();
+                                    let right = numeric_operand(pop_operand(&mut stack, token)?);
+                                    let left = numeric_operand(pop_operand(&mut stack, token)?);
                                     let node = ASTNode::BinaryLogic(
                                         LogicOperator::Greater,
                                         Box::new(left),
@@ -2567,8 +2999,8 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                                     stack.push(node);
                                 }
                                 LogicOperator::Less => {
-                                    let right = stack.pop().unwrap();
-                                    let left = stack.pop().unwrap();
+                                    let right = numeric_operand(pop_operand(&mut stack, token)?);
+                                    let left = numeric_operand(pop_operand(&mut stack, token)?);
                                     let node = ASTNode::BinaryLogic(
                                         LogicOperator::Less,
                                         Box::new(left),
@@ -2578,8 +3010,8 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                                     stack.push(node);
                                 }
                                 LogicOperator::GreaterOrEqual => {
-                                    let right = stack.pop().unwrap();
-                                    let left = stack.pop().unwrap();
+                                    let right = numeric_operand(pop_operand(&mut stack, token)?);
+                                    let left = numeric_operand(pop_operand(&mut stack, token)?);
                                     let node = ASTNode::BinaryLogic(
                                         LogicOperator::GreaterOrEqual,
                                         Box::new(left),
@@ -2589,8 +3021,8 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                                     stack.push(node);
                                 }
                                 LogicOperator::LessOrEqual => {
-                                    let right = stack.pop().unwrap();
-                                    let left = stack.pop().unwrap();
+                                    let right = numeric_operand(pop_operand(&mut stack, token)?);
+                                    let left = numeric_operand(pop_operand(&mut stack, token)?);
                                     let node = ASTNode::BinaryLogic(
                                         LogicOperator::LessOrEqual,
                                         Box::new(left),
@@ -2601,7 +3033,7 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                                 }
                             }
                         Err(_) => {
-                            println!("{} is not a Logic Operator", token);
+                            log_debug!("{} is not a Logic Operator", token);
                         }
                     }
                 }
@@ -2609,6 +3041,11 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
         } else {
             // Parse Operand in respective type
 
+            if let Some(n) = token.strip_prefix(ARITY_PREFIX) {
+                arity = n.parse::<usize>().ok();
+                continue;
+            }
+
             if let Ok(func) = Functions::from_str(token.as_str()) {
                 // Parse Functions
                 match func {
@@ -2767,27 +3204,8 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                         }
                     }
                     Functions::Keccak256 => {
-                        unimplemented!("Not implemented right now");
-                        // Keccak256 takes arbitrary arguments
-                        // The first argument indicates the length of arguments supplied
-                        let number_of_args = &tokens[id + 1].clone().parse::<u64>().unwrap_or(0);
-
-                        // Get the arguments
-                        let mut args = vec![];
-                        for i in 0..*number_of_args as usize {
-                            args.push(tokens[id + i + 2].clone());
-                        }
-                        let ast_args = args
-                            .iter()
-                            .map(|x| parse_token(x.clone()).unwrap())
-                            .collect::<Vec<ASTNode>>();
-                        let mut boxed_args = ast_args
-                            .iter()
-                            .map(|x| Box::new(x.clone()))
-                            .collect::<Vec<Box<ASTNode>>>();
-                        let node = ASTNode::Function(Functions::Keccak256, boxed_args);
-                        ast_vec.push(node.clone());
-                        stack.push(node);
+                        // keccak256() is not implemented yet
+                        return Err(ASTError::InvalidFunction("keccak256".to_string()));
                     }
                     Functions::Insert => {
                         // Insert takes two arguments (key, value) and the target
@@ -2879,13 +3297,13 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                             );
                         }
                     }
-                    Functions::Assign => {
-                        // Assign takes a variable name and a value as parameters
+                    Functions::Assign | Functions::Let => {
+                        // Assign and Let take a variable name and a value as parameters
 
                         if let Some(arg_1) = stack.pop() {
                             if let Some(arg_0) = stack.pop() {
                                 let node = ASTNode::Function(
-                                    Functions::Assign,
+                                    func,
                                     vec![Box::new(arg_0), Box::new(arg_1)]
                                 );
                                 ast_vec.push(node.clone());
@@ -2893,14 +3311,14 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                             } else {
                                 return Err(
                                     ASTError::InvalidFunctionInvocation(
-                                        format!("Missing argument .assign({:?})", arg_1)
+                                        format!("Missing argument .{}({:?})", func.to_string(), arg_1)
                                     )
                                 );
                             }
                         } else {
                             return Err(
                                 ASTError::InvalidFunctionInvocation(
-                                    format!("Missing argument .assign()")
+                                    format!("Missing argument .{}()", func.to_string())
                                 )
                             );
                         }
@@ -2933,6 +3351,101 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                             );
                         }
                     }
+                    Functions::Now | Functions::IsReorg => {
+                        // Now and IsReorg take no arguments
+                        let node = ASTNode::Function(func, vec![]);
+                        ast_vec.push(node.clone());
+                        stack.push(node);
+                    }
+                    | Functions::Incr
+                    | Functions::Count
+                    | Functions::BlockTimestamp
+                    | Functions::Prev
+                    | Functions::EventParam
+                    | Functions::Selector => {
+                        // Incr, Count and Prev take the key, BlockTimestamp the chain, EventParam the name and Selector the signature
+                        if let Some(arg) = stack.pop() {
+                            let node = ASTNode::Function(func, vec![Box::new(arg)]);
+                            ast_vec.push(node.clone());
+                            stack.push(node);
+                        } else {
+                            return Err(
+                                ASTError::InvalidFunctionInvocation(
+                                    format!("Missing argument .{}()", func.to_string())
+                                )
+                            );
+                        }
+                    }
+                    Functions::AbiEncode | Functions::Concat => {
+                        // Variadic functions take all arguments in their parentheses
+                        let n = arity.take().unwrap_or(0);
+                        if n == 0 || stack.len() < n {
+                            return Err(
+                                ASTError::MissingArgument(
+                                    func.to_string().to_string(),
+                                    "expected at least one argument".to_string()
+                                )
+                            );
+                        }
+                        let args = stack
+                            .split_off(stack.len() - n)
+                            .into_iter()
+                            .map(Box::new)
+                            .collect::<Vec<Box<ASTNode>>>();
+                        let node = ASTNode::Function(func, args);
+                        ast_vec.push(node.clone());
+                        stack.push(node);
+                    }
+                    Functions::Scale | Functions::Unscale | Functions::DailyVolume => {
+                        // Scale takes the value and the decimals, DailyVolume the key and the value
+                        if let Some(arg_1) = stack.pop() {
+                            if let Some(arg_0) = stack.pop() {
+                                let node = ASTNode::Function(
+                                    func,
+                                    vec![Box::new(arg_0), Box::new(arg_1)]
+                                );
+                                ast_vec.push(node.clone());
+                                stack.push(node);
+                            } else {
+                                return Err(
+                                    ASTError::InvalidFunctionInvocation(
+                                        format!("Missing argument .{}({:?})", func.to_string(), arg_1)
+                                    )
+                                );
+                            }
+                        } else {
+                            return Err(
+                                ASTError::InvalidFunctionInvocation(
+                                    format!("Missing argument .{}()", func.to_string())
+                                )
+                            );
+                        }
+                    }
+                    Functions::Decode => {
+                        // Decode takes the data and the array of types
+                        if let Some(arg_1) = stack.pop() {
+                            if let Some(arg_0) = stack.pop() {
+                                let node = ASTNode::Function(
+                                    Functions::Decode,
+                                    vec![Box::new(arg_0), Box::new(arg_1)]
+                                );
+                                ast_vec.push(node.clone());
+                                stack.push(node);
+                            } else {
+                                return Err(
+                                    ASTError::InvalidFunctionInvocation(
+                                        format!("Missing argument .decode({:?})", arg_1)
+                                    )
+                                );
+                            }
+                        } else {
+                            return Err(
+                                ASTError::InvalidFunctionInvocation(
+                                    format!("Missing argument .decode()")
+                                )
+                            );
+                        }
+                    }
                     Functions::Custom => {
                         // Not known in the beginning.
                         // First find out the target blockchain
@@ -3007,7 +3520,9 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
 
                 if is_array {
                     // Append to array
-                    let parsed_token = parse_token(token.clone()).unwrap();
+                    let parsed_token = parse_token(token.clone()).map_err(|e|
+                        ASTError::InvalidToken(token.clone(), e.to_string())
+                    )?;
                     arr.push(Box::new(parsed_token));
                     continue;
                 }
@@ -3019,7 +3534,7 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
                         stack.push(node);
                     }
                     Err(e) => {
-                        panic!("Error at token parsing {}", e);
+                        return Err(ASTError::InvalidToken(token.clone(), e.to_string()));
                     }
                 };
             }
@@ -3027,6 +3542,10 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
     }
 
     if let Some(root) = stack.pop(){
+        let max = max_expression_depth();
+        if root.depth() > max {
+            return Err(ASTError::MaxDepthExceeded(max));
+        }
         Ok((ast_vec, root))
     }else {
         Err(ASTError::MissingRoot)
@@ -3034,6 +3553,11 @@ pub fn parse_postfix(tokens: VecDeque<String>) -> Result<(Vec<ASTNode>, ASTNode)
 
 }
 
+/// Pops the operand of the operator from the stack of parse_postfix
+fn pop_operand(stack: &mut Vec<ASTNode>, operator: &str) -> Result<ASTNode, ASTError> {
+    stack.pop().ok_or_else(|| ASTError::MissingOperand(operator.to_string()))
+}
+
 pub fn parse_token(token: String) -> Result<ASTNode, &'static str> {
     match token.parse::<u256>() {
         Ok(value) => Ok(ASTNode::ConstantNumber(value)),
@@ -3047,6 +3571,8 @@ pub fn parse_token(token: String) -> Result<ASTNode, &'static str> {
                         Err(_) => {
                             if token.starts_with('$') {
                                 Ok(ASTNode::Variable(token[1..].to_string()))
+                            } else if is_string_literal(&token) {
+                                Ok(ASTNode::ConstantString(token[1..token.len() - 1].to_string()))
                             } else {
                                 Ok(ASTNode::ConstantString(token))
                             }
@@ -3057,15 +3583,168 @@ pub fn parse_token(token: String) -> Result<ASTNode, &'static str> {
     }
 }
 
+/// Parse `0x`, `0b` and `0o` prefixed literals into a number.
+/// Literals that exceed u256, e.g., calldata, stay strings.
+pub fn parse_prefixed_number(token: &str) -> Option<u256> {
+    let (digits, radix) = if let Some(d) = token.strip_prefix("0x") {
+        (d, 16)
+    } else if let Some(d) = token.strip_prefix("0b") {
+        (d, 2)
+    } else if let Some(d) = token.strip_prefix("0o") {
+        (d, 8)
+    } else {
+        return None;
+    };
+    if digits.is_empty() || digits.starts_with(['+', '-']) {
+        return None;
+    }
+    u256::from_str_radix(digits, radix).ok()
+}
+
+/// Operands of arithmetic and logic operators are parsed as numbers if they are prefixed literals, e.g., `0xff + 1`.
+/// Function arguments keep the literal, so addresses and map keys are stored exactly as written.
+fn numeric_operand(node: ASTNode) -> ASTNode {
+    match &node {
+        ASTNode::ConstantString(s) =>
+            match parse_prefixed_number(s) {
+                Some(value) if s.starts_with("0x") => ASTNode::ConstantHexNumber(value),
+                Some(value) => ASTNode::ConstantNumber(value),
+                None => node,
+            }
+        _ => node,
+    }
+}
+
+/// Removes a `#` or `//` comment from the line, e.g., `$value < 1000 // 1000 wei`.
+/// Comment markers inside quoted strings are kept.
+pub fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match quote {
+            Some(q) if c == q => {
+                quote = None;
+            }
+            Some(_) => {}
+            None =>
+                match c {
+                    '\'' | '"' => {
+                        quote = Some(c);
+                    }
+                    '#' => {
+                        return line[..i].trim_end();
+                    }
+                    '/' if matches!(chars.peek(), Some((_, '/'))) => {
+                        return line[..i].trim_end();
+                    }
+                    _ => {}
+                }
+        }
+    }
+    line
+}
+
+/// Returns true if the line is empty or only a comment
+pub fn is_comment_line(line: &str) -> bool {
+    strip_comment(line).trim().is_empty()
+}
+
+/// Parses a duration like 30s, 5m, 1h or 2d into seconds. A number without a unit is already in seconds
+fn parse_duration(duration: &str) -> Option<u256> {
+    let duration = duration.trim();
+    let (amount, unit) = match duration.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => duration.split_at(i),
+        None => (duration, "s"),
+    };
+    let seconds: u64 = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => {
+            return None;
+        }
+    };
+    let amount = u256::from_str(amount).ok()?;
+    amount.checked_mul(u256::from(seconds))
+}
+
+/// Two maps are equal if they have the same keys and the values of each key are stringified the same, independent of the order
+fn maps_equal(left: &HashMap<String, ASTConstant>, right: &HashMap<String, ASTConstant>) -> bool {
+    left.len() == right.len() &&
+        left
+            .iter()
+            .all(|(key, value)| {
+                right.get(key).is_some_and(|other| other.get_value() == value.get_value())
+            })
+}
+
+/// Inserts the value at a dotted key, e.g. `a.b`, creating the intermediate maps. Returns false if a key on the path holds no map.
+fn insert_nested(map: &mut HashMap<String, ASTConstant>, key: &str, value: ASTConstant) -> bool {
+    match key.split_once('.') {
+        None => {
+            map.insert(key.to_string(), value);
+            true
+        }
+        Some((first, rest)) =>
+            match map.entry(first.to_string()).or_insert_with(|| ASTConstant::Map(HashMap::new())) {
+                ASTConstant::Map(inner) => insert_nested(inner, rest, value),
+                _ => false,
+            }
+    }
+}
+
+/// Returns the value at a dotted key, e.g. `a.b`
+fn get_nested<'a>(map: &'a HashMap<String, ASTConstant>, key: &str) -> Option<&'a ASTConstant> {
+    match key.split_once('.') {
+        None => map.get(key),
+        Some((first, rest)) =>
+            match map.get(first) {
+                Some(ASTConstant::Map(inner)) => get_nested(inner, rest),
+                _ => None,
+            }
+    }
+}
+
+/// Removes the value at a dotted key, e.g. `a.b`, the intermediate maps are kept
+fn remove_nested(map: &mut HashMap<String, ASTConstant>, key: &str) -> Option<ASTConstant> {
+    match key.split_once('.') {
+        None => map.remove(key),
+        Some((first, rest)) =>
+            match map.get_mut(first) {
+                Some(ASTConstant::Map(inner)) => remove_nested(inner, rest),
+                _ => None,
+            }
+    }
+}
+
+/// Check if the token is a string literal enclosed in single or double quotes
+fn is_string_literal(token: &str) -> bool {
+    token.len() >= 2 &&
+        ((token.starts_with('\'') && token.ends_with('\'')) ||
+            (token.starts_with('"') && token.ends_with('"')))
+}
+
+/// Prefix of the token holding the number of arguments of a variadic function in the postfix notation
+const ARITY_PREFIX: &str = "#argc:";
+
 /// The shunting yard algorithm by Dijkstra transforms the infix logic expression into postfix.
-pub fn shunting_yard_algorithm(tokens: Vec<String>) -> Result<VecDeque<String>, &'static str> {
+pub fn shunting_yard_algorithm(tokens: Vec<String>) -> Result<VecDeque<String>, ASTError> {
     let mut stack: Vec<String> = vec![]; // Stack for operators
     let mut output_queue: VecDeque<String> = VecDeque::new();
+    // (commas, has argument) for each open parenthesis to count the arguments of variadic functions
+    let mut arg_frames: Vec<(usize, bool)> = vec![];
 
     for token in tokens.iter() {
         // println!("Stack: {:?}", stack);
         // println!("Output Queue: {:?}", output_queue);
         // println!("Token: {}", token);
+        if token != "," && !is_right_parenthesis(token) {
+            if let Some(frame) = arg_frames.last_mut() {
+                frame.1 = true;
+            }
+        }
+
         // Put functions on the Stack
         if Functions::from_str(token).is_ok() {
             stack.push(token.clone());
@@ -3073,31 +3752,64 @@ pub fn shunting_yard_algorithm(tokens: Vec<String>) -> Result<VecDeque<String>,
         }
 
         if is_left_parenthesis(token) {
+            // Array literals are delimited in the output for parse_postfix
+            if token == "[" {
+                output_queue.push_back(token.clone());
+            }
             stack.push(token.clone());
+            arg_frames.push((0, false));
+            if arg_frames.len() > max_expression_depth() {
+                return Err(ASTError::MaxDepthExceeded(max_expression_depth()));
+            }
             continue;
         }
 
         if is_right_parenthesis(token) {
-            while !is_left_parenthesis(stack.last().unwrap()) {
-                if stack.is_empty() {
-                    return Err("Unmatched Parentheses: Empty Stack. No preceeding parentheses");
+            loop {
+                match stack.last() {
+                    Some(top) if is_left_parenthesis(top) => break,
+                    Some(_) => output_queue.push_back(stack.pop().unwrap()),
+                    None => {
+                        return Err(
+                            ASTError::UnmatchedParentheses(
+                                "Empty Stack. No preceeding parentheses".to_string()
+                            )
+                        );
+                    }
                 }
-                output_queue.push_back(stack.pop().unwrap());
             }
             stack.pop(); // Remove parenthesis
+            let number_of_args = match arg_frames.pop() {
+                Some((commas, true)) => commas + 1,
+                _ => 0,
+            };
+            if token == "]" {
+                output_queue.push_back(token.clone());
+            }
             if
-                Functions::from_str(
+                let Ok(func) = Functions::from_str(
                     stack.last().unwrap_or(&"Stack seems to be empty".to_string())
-                ).is_ok()
+                )
             {
+                if func.is_variadic() {
+                    output_queue.push_back(format!("{}{}", ARITY_PREFIX, number_of_args));
+                }
                 output_queue.push_back(stack.pop().unwrap());
             }
             continue;
         }
 
         if token == "," {
-            while !is_left_parenthesis(stack.last().unwrap()) {
-                output_queue.push_back(stack.pop().unwrap());
+            // Separate the arguments of the innermost open parenthesis
+            loop {
+                match stack.last() {
+                    Some(top) if is_left_parenthesis(top) => break,
+                    Some(_) => output_queue.push_back(stack.pop().unwrap()),
+                    None => return Err(ASTError::MisplacedSeparator),
+                }
+            }
+            if let Some(frame) = arg_frames.last_mut() {
+                frame.0 += 1;
             }
             continue;
         }
@@ -3109,6 +3821,9 @@ pub fn shunting_yard_algorithm(tokens: Vec<String>) -> Result<VecDeque<String>,
                 }
                 if operator_precedence(stack.last().unwrap()) >= operator_precedence(token) {
                     output_queue.push_back(stack.pop().unwrap());
+                } else {
+                    // Operators with a lower precedence stay on the stack
+                    break;
                 }
             }
             stack.push(token.clone());
@@ -3158,7 +3873,9 @@ pub fn shunting_yard_algorithm(tokens: Vec<String>) -> Result<VecDeque<String>,
         let val = stack.pop().unwrap();
         if val == "(" {
             return Err(
-                "Unmatched Parentheses: Leftover on Stack. More parentheses are opening than closing"
+                ASTError::UnmatchedParentheses(
+                    "Leftover on Stack. More parentheses are opening than closing".to_string()
+                )
             );
         }
         output_queue.push_back(val);
@@ -3168,17 +3885,24 @@ pub fn shunting_yard_algorithm(tokens: Vec<String>) -> Result<VecDeque<String>,
     Ok(output_queue)
 }
 
-/// Return the precedence level of the operator
+/// Return the precedence level of the operator.
+/// Like in Rust, bitwise operators bind tighter than comparisons and looser than arithmetic,
+/// so `1 | 2 == 3` is `(1 | 2) == 3` and `1 << 2 + 1` is `1 << (2 + 1)`.
+/// Operators of the same level are left associative, e.g., `8 >> 1 >> 1` is `(8 >> 1) >> 1`.
 fn operator_precedence(operator: &str) -> Option<u8> {
     match operator {
         "||" => Some(0), // Or
         "&&" => Some(1), // And
         "==" | "!=" => Some(2), // Equality
         "<" | ">" | "<=" | ">=" => Some(3), // Comparison
-        "+" | "-" => Some(4), // Addition, Subtraction
-        "*" | "/" | "%" => Some(5), // Multiplication, Division, and Modulo
-        "!" | "neg" => Some(6), // Unary Operators
-        "(" | ")" | "[" | "]" | "{" | "}" => Some(7), // Parentheses and Brackets for Functions and arrays
+        "|" => Some(4), // Bitwise Or
+        "^" => Some(5), // Bitwise Xor
+        "&" => Some(6), // Bitwise And
+        "<<" | ">>" => Some(7), // Shifts
+        "+" | "-" => Some(8), // Addition, Subtraction
+        "*" | "/" | "%" => Some(9), // Multiplication, Division, and Modulo
+        "!" | "neg" => Some(10), // Unary Operators
+        "(" | ")" | "[" | "]" | "{" | "}" => Some(11), // Parentheses and Brackets for Functions and arrays
         _ => None, // No precedence for other operators
     }
 }
@@ -3200,6 +3924,11 @@ fn is_operator(token: &str) -> bool {
         token == "*" ||
         token == "/" ||
         token == "%" ||
+        token == "&" ||
+        token == "|" ||
+        token == "^" ||
+        token == "<<" ||
+        token == ">>" ||
         token == "||" ||
         token == "&&" ||
         token == "==" ||
@@ -3243,15 +3972,15 @@ fn replace_args_in_json(json: &mut Value, args: &mut Vec<String>) {
 fn replace_args_in_str(json: &str, args: &Vec<String>) -> Value {
     let mut json_string = json.to_string();
     let mut args_iter = args.iter();
-    println!("JSON: {}", json_string);
-    println!("Args: {:#?}", args);
+    log_debug!("JSON: {}", json_string);
+    log_debug!("Args: {:#?}", args);
     let re = regex::Regex::new(r"\$[a-zA-Z0-9_]*").unwrap();
     for cap in re.find_iter(&json_string.clone()) {
         if let Some(arg) = args_iter.next() {
             json_string = json_string.replace(cap.as_str(), arg);
         }
     }
-    println!("{:?}", json_string);
+    log_debug!("{:?}", json_string);
     serde_json::from_str(&json_string).unwrap()
 }
 
@@ -3277,6 +4006,32 @@ impl std::fmt::Display for RPCRequest {
     }
 }
 
+/// Number of params (min, max) of well known RPC methods, so a wrong call() fails before the request is sent
+fn rpc_param_range(method: &str) -> Option<(usize, usize)> {
+    match method {
+        "eth_blockNumber" | "eth_chainId" | "eth_gasPrice" | "net_version" => Some((0, 0)),
+        "eth_getBalance" | "eth_getCode" | "eth_getTransactionCount" | "eth_call" => Some((1, 2)),
+        | "eth_getTransactionByHash"
+        | "eth_getTransactionReceipt"
+        | "eth_getLogs"
+        | "eth_getBlockReceipts"
+        | "getBlockTime" => Some((1, 1)),
+        "eth_getBlockByNumber" | "eth_getBlockByHash" => Some((2, 2)),
+        "eth_getStorageAt" => Some((2, 3)),
+        | "getTransaction"
+        | "getBalance"
+        | "getAccountInfo"
+        | "getTokenSupply"
+        | "getTokenAccountBalance"
+        | "getSignaturesForAddress" => Some((1, 2)),
+        "getSlot" | "getBlockHeight" => Some((0, 1)),
+        _ => None,
+    }
+}
+
+/// Sets the method of the template to the first arg and fills the params with the remaining args.
+/// `$name` placeholders in the params of the template are replaced in order and must all be filled,
+/// otherwise the args are appended and their number is checked for well known methods.
 fn replace_args_in_value(json: &mut RPCRequest, args: &Vec<String>) -> Result<(), ASTError> {
     if args.is_empty() {
         return Err(ASTError::RequestReplacementError(format!("{}", json)));
@@ -3290,39 +4045,68 @@ fn replace_args_in_value(json: &mut RPCRequest, args: &Vec<String>) -> Result<()
             return Err(ASTError::RequestReplacementError(format!("{}", json)));
         }
     }
-    let remaining_args = args[1..].to_vec();
-    for arg in remaining_args {
-        json.params.push(parse_string(&arg));
-    }
-    // match args.get(1) {
-    //     Some(arg) => {
-    //         let v = parse_string(arg);
-    //         json.params = vec![v];
-    //     }
-    //     None => {
-    //         return Err(ASTError::RequestReplacementError(format!("{}", json)));
-    //     }
-    // }
+    let remaining_args = args[1..]
+        .iter()
+        .map(|arg| parse_string(arg))
+        .collect::<Result<Vec<Value>, ASTError>>()?;
+
+    let placeholders = json.params
+        .iter()
+        .filter(|p| p.as_str().is_some_and(|p| p.starts_with('$')))
+        .count();
+    if placeholders > 0 {
+        if remaining_args.len() != placeholders {
+            return Err(
+                ASTError::RpcArgumentCount(json.method.clone(), placeholders.to_string(), remaining_args.len())
+            );
+        }
+        let mut remaining_args = remaining_args.into_iter();
+        for param in json.params.iter_mut() {
+            if param.as_str().is_some_and(|p| p.starts_with('$')) {
+                *param = remaining_args.next().unwrap_or(Value::Null);
+            }
+        }
+        return Ok(());
+    }
+
+    let count = json.params.len() + remaining_args.len();
+    if let Some((min, max)) = rpc_param_range(&json.method) {
+        if count < min || count > max {
+            let expected = if min == max { min.to_string() } else { format!("{} to {}", min, max) };
+            return Err(ASTError::RpcArgumentCount(json.method.clone(), expected, count));
+        }
+    }
+    json.params.extend(remaining_args);
 
     Ok(())
 }
 
-fn parse_string(arg: &str) -> Value {
-    if let Ok(v) = serde_json::from_str(arg) {
-        return v;
+/// JSON-RPC param of a call() argument. Booleans, null and integers keep their JSON type, integers that don't fit
+/// into 64 bits stay strings so they aren't rounded, arrays and objects must be valid JSON and everything else is a string.
+fn parse_string(arg: &str) -> Result<Value, ASTError> {
+    let arg = arg.trim();
+    match arg {
+        "true" => {
+            return Ok(Value::Bool(true));
+        }
+        "false" => {
+            return Ok(Value::Bool(false));
+        }
+        "null" => {
+            return Ok(Value::Null);
+        }
+        _ => {}
     }
-    if arg.starts_with("[") {
-        return serde_json::from_str(&arg).unwrap();
+    if let Ok(n) = arg.parse::<u64>() {
+        return Ok(Value::from(n));
     }
-    if arg.starts_with("{") {
-        return serde_json::from_str(&arg).unwrap();
+    if let Ok(n) = arg.parse::<i64>() {
+        return Ok(Value::from(n));
     }
-    let re = regex::Regex::new(r"\w*").unwrap();
-    if re.is_match(&arg) {
-        let new_arg = format!("\"{}\"", arg);
-        return serde_json::from_str(&new_arg).unwrap();
+    if arg.starts_with('[') || arg.starts_with('{') || arg.starts_with('"') {
+        return serde_json::from_str(arg).map_err(|_| ASTError::MalformedRpcParam(arg.to_string()));
     }
-    serde_json::Value::Null
+    Ok(Value::String(arg.to_string()))
 }
 
 // #[derive(Debug, Clone)]
@@ -3400,34 +4184,54 @@ pub fn tokenize(text: String) -> Vec<String> {
     let mut tokens: Vec<String> = vec![];
 
     let mut current_token = String::new();
-    let mut state = 0; // 0 is outside function above is inside function depth
+    // Open parentheses and brackets
+    let mut groups: Vec<char> = vec![];
 
     let mut is_array = false;
+    // Quote character of the string literal that is currently read
+    let mut quote: Option<char> = None;
     for c in text.chars() {
+        if let Some(q) = quote {
+            // Everything inside of a string literal belongs to the token
+            current_token.push(c);
+            if c == q {
+                quote = None;
+                tokens.push(current_token.clone());
+                current_token.clear();
+            }
+            continue;
+        }
         match c {
-            ' ' | ',' | '.' => {
+            '\'' | '"' if current_token.is_empty() => {
+                quote = Some(c);
+                current_token.push(c);
+            }
+            ' ' | '.' => {
                 if !current_token.is_empty() && !is_array {
                     tokens.push(current_token.clone());
                     current_token.clear();
                 }
             }
-            '(' | '[' | '{' => {
-                if let Ok(f) = Functions::from_str(&current_token.clone()) {
-                    state += 1;
-                    tokens.push(current_token.clone());
-                    current_token.clear();
-                } else {
+            ',' => {
+                if !current_token.is_empty() {
                     tokens.push(current_token.clone());
                     current_token.clear();
                 }
+                // Commas separate the arguments of functions, in array literals they are whitespace
+                if groups.last() != Some(&'[') {
+                    tokens.push(c.to_string());
+                }
+            }
+            '(' | '[' | '{' => {
+                groups.push(c);
+                tokens.push(current_token.clone());
+                current_token.clear();
                 current_token.push(c);
                 tokens.push(current_token.clone());
                 current_token.clear();
             }
             ')' | ']' | '}' => {
-                if state != 0 {
-                    state -= 1;
-                }
+                groups.pop();
                 tokens.push(current_token.clone());
                 current_token.clear();
                 current_token.push(c);
@@ -3451,62 +4255,50 @@ pub fn tokenize(text: String) -> Vec<String> {
 
 /// Build an AST and return the root node
 pub fn build_ast_root(text: &str) -> Result<ASTNode, &'static str> {
-    let tokens = tokenize(text.to_string());
+    let tokens = tokenize(strip_comment(text).to_string());
 
     match shunting_yard_algorithm(tokens) {
         Ok(postfix) => {
-            println!("{:?}", postfix);
+            log_debug!("{:?}", postfix);
             match parse_postfix(postfix) {
                 Ok((_, root)) => {
                     return Ok(root);
                 }
                 Err(e) => {
-                    println!("Error: {:?}", e);
+                    log_error!("Error: {:?}", e);
                     return Err("Invalid Parsing of Postfix");
                 }
             }
         }
         Err(e) => {
-            println!("Error: {:?}", e);
+            log_error!("Error: {:?}", e);
             return Err("Invalid Shunting Yard Algorithm");
         }
     }
 }
 
-/// Build an AST and return each root
+/// Build an AST and return each root.
+/// The tokenizer drops newlines, so the lines are split before. Empty and comment lines are skipped.
 pub fn build_code(text: &str) -> Result<Vec<ASTNode>, &'static str> {
-    let tokens = tokenize(text.to_string());
-
-    let mut all_statements: Vec<Vec<String>> = vec![];
-
-    let mut stmt: Vec<String> = vec![];
-    for t in tokens.iter() {
-        if t == "\n" {
-            if stmt.is_empty() {
-                continue;
-            }
-            all_statements.push(stmt.clone());
-            stmt.clear();
+    let mut code = vec![];
+    for (line, stmt) in text.lines().enumerate() {
+        let tokens = tokenize(strip_comment(stmt).to_string());
+        if tokens.is_empty() {
             continue;
         }
-        stmt.push(t.clone());
-    }
-
-    let mut code = vec![];
-    for (line, stmt) in all_statements.iter().enumerate() {
-        match shunting_yard_algorithm(stmt.clone()) {
+        match shunting_yard_algorithm(tokens) {
             Ok(postfix) => {
-                // println!("{:?}", postfix);
+                // log_debug!("{:?}", postfix);
                 match parse_postfix(postfix) {
                     Ok((_, root)) => code.push(root),
                     Err(e) => {
-                        println!("Error in line {}: {:?}", line, e);
+                        log_error!("Error in line {}: {:?}", line, e);
                         return Err("Invalid Parsing of Postfix");
                     }
                 }
             }
             Err(e) => {
-                println!("Error in line {}: {:?}", line, e);
+                log_error!("Error in line {}: {:?}", line, e);
                 return Err("Invalid Shunting Yard Algorithm");
             }
         }
@@ -3554,29 +4346,306 @@ fn encode_token(token: &ASTConstant, out: &mut Vec<u8>, in_array: bool) {
             out.extend_from_slice(&buf[start..32]);
         }
         ASTConstant::Bool(b) => {
-            if in_array {
-                out.extend_from_slice(&[0; 31]);
-            }
+            if in_array {
+                out.extend_from_slice(&[0; 31]);
+            }
+            out.push(*b as u8);
+        }
+        ASTConstant::String(s) => {
+            if s.starts_with("0x") && s.len() == 42 {
+                // Address
+                if in_array {
+                    out.extend_from_slice(&[0; 12]);
+                }
+                out.extend_from_slice(&s.as_bytes());
+            } else {
+                out.extend_from_slice(s.as_bytes());
+            }
+        }
+        ASTConstant::Array(vec) => {
+            for t in vec {
+                encode_token(t, out, true);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Applies a bitwise operator. Unsigned numbers stay unsigned, a signed operand makes both signed (two's complement).
+/// Strings are converted like for the other arithmetic operators and shifts by 256 bits or more are an overflow.
+fn bitwise(operator: &ArithmeticOperator, left: ASTConstant, right: ASTConstant) -> Result<ASTConstant, ASTError> {
+    let integer = |value: ASTConstant| -> Result<ASTConstant, ASTError> {
+        match value {
+            ASTConstant::Number(_) | ASTConstant::SignedNumber(_) => Ok(value),
+            ASTConstant::String(ref s) if s.starts_with("i256:") => value.convert(ConversionTarget::SignedNumber),
+            ASTConstant::String(_) => value.convert(ConversionTarget::Number),
+            _ => Err(ASTError::InvalidConstant(operator.to_string().to_owned())),
+        }
+    };
+    let (left, right) = (integer(left)?, integer(right)?);
+    let overflow = || ASTError::OverflowError(format!("{} {} {}", left.get_value(), operator.to_string(), right.get_value()));
+
+    if matches!(operator, ArithmeticOperator::ShiftLeft | ArithmeticOperator::ShiftRight) {
+        let shift = match &right {
+            ASTConstant::Number(n) if *n < 256 => n.as_u32(),
+            ASTConstant::SignedNumber(n) if *n >= 0 && *n < 256 => n.as_u32(),
+            _ => {
+                return Err(overflow());
+            }
+        };
+        return match (&left, operator) {
+            (ASTConstant::Number(l), ArithmeticOperator::ShiftLeft) => Ok(ASTConstant::Number(l << shift)),
+            (ASTConstant::Number(l), _) => Ok(ASTConstant::Number(l >> shift)),
+            (ASTConstant::SignedNumber(l), ArithmeticOperator::ShiftLeft) => Ok(ASTConstant::SignedNumber(l << shift)),
+            // Arithmetic shift, the sign is kept
+            (ASTConstant::SignedNumber(l), _) => Ok(ASTConstant::SignedNumber(l >> shift)),
+            _ => Err(ASTError::InvalidConstant(operator.to_string().to_owned())),
+        };
+    }
+
+    match (left, right) {
+        (ASTConstant::Number(l), ASTConstant::Number(r)) =>
+            Ok(
+                ASTConstant::Number(match operator {
+                    ArithmeticOperator::BitAnd => l & r,
+                    ArithmeticOperator::BitOr => l | r,
+                    _ => l ^ r,
+                })
+            ),
+        (l, r) => {
+            let signed = |value: ASTConstant| match value {
+                ASTConstant::Number(n) => n.as_i256(),
+                ASTConstant::SignedNumber(n) => n,
+                _ => unreachable!(),
+            };
+            let (l, r) = (signed(l), signed(r));
+            Ok(
+                ASTConstant::SignedNumber(match operator {
+                    ArithmeticOperator::BitAnd => l & r,
+                    ArithmeticOperator::BitOr => l | r,
+                    _ => l ^ r,
+                })
+            )
+        }
+    }
+}
+
+/// Orders an unsigned and a signed number without wrapping the unsigned one into i256.
+/// A negative number is less than every unsigned number, otherwise both are compared as u256.
+fn cmp_unsigned_signed(unsigned: u256, signed: i256) -> Ordering {
+    if signed < 0 {
+        Ordering::Greater
+    } else {
+        unsigned.cmp(&signed.as_u256())
+    }
+}
+
+/// Applies a comparison operator to the ordering of the left and the right operand
+/// Parses strings holding numbers, i.e. `0x` hex numbers and `u256:` or `i256:` prefixed numbers.
+/// Returns `None` for all other strings.
+fn numeric_string(s: &str) -> Result<Option<ASTConstant>, ASTError> {
+    let invalid = |_| ASTError::InvalidConversion(s.to_string(), "Number".to_string());
+    if s.starts_with("0x") {
+        u256::from_str_hex(s).map(|n| Some(ASTConstant::Number(n))).map_err(invalid)
+    } else if let Some(n) = s.strip_prefix("u256:") {
+        u256::from_str(n).map(|n| Some(ASTConstant::Number(n))).map_err(invalid)
+    } else if let Some(n) = s.strip_prefix("i256:") {
+        i256::from_str(n).map(|n| Some(ASTConstant::SignedNumber(n))).map_err(invalid)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Compares two numbers of `numeric_string`
+fn cmp_numbers(left: &ASTConstant, right: &ASTConstant) -> Ordering {
+    match (left, right) {
+        (ASTConstant::Number(l), ASTConstant::Number(r)) => l.cmp(r),
+        (ASTConstant::Number(l), ASTConstant::SignedNumber(r)) => cmp_unsigned_signed(*l, *r),
+        (ASTConstant::SignedNumber(l), ASTConstant::Number(r)) => cmp_unsigned_signed(*r, *l).reverse(),
+        (ASTConstant::SignedNumber(l), ASTConstant::SignedNumber(r)) => l.cmp(r),
+        _ => unreachable!(),
+    }
+}
+
+fn compare_ordering(operator: &LogicOperator, ordering: Ordering) -> Result<ASTConstant, ASTError> {
+    let result = match operator {
+        LogicOperator::Equal => ordering == Ordering::Equal,
+        LogicOperator::NotEqual => ordering != Ordering::Equal,
+        LogicOperator::Greater => ordering == Ordering::Greater,
+        LogicOperator::Less => ordering == Ordering::Less,
+        LogicOperator::GreaterOrEqual => ordering != Ordering::Less,
+        LogicOperator::LessOrEqual => ordering != Ordering::Greater,
+        _ => {
+            return Err(ASTError::InvalidBinaryOperator);
+        }
+    };
+    Ok(ASTConstant::Bool(result))
+}
+
+/// Convert a function argument into an unsigned number
+fn to_unsigned(value: ASTConstant, function: &str) -> Result<u256, ASTError> {
+    match value.convert(ConversionTarget::Number)? {
+        ASTConstant::Number(n) => Ok(n),
+        ASTConstant::SignedNumber(n) if n >= 0 => Ok(n.as_u256()),
+        v => Err(ASTError::InvalidOperation(function.to_string(), v.get_constant_info().0.to_string(), "Number".to_string())),
+    }
+}
+
+/// 10^decimals or an error if it exceeds u256
+fn decimal_factor(decimals: u256) -> Result<u256, ASTError> {
+    u32
+        ::try_from(decimals)
+        .ok()
+        .and_then(|d| u256::new(10).checked_pow(d))
+        .ok_or(ASTError::OverflowError(format!("10^{}", decimals)))
+}
+
+/// Divide the value by 10^decimals and round down, e.g., `scale(1500000000000000000, 18)` is 1
+pub fn scale(value: u256, decimals: u256) -> Result<u256, ASTError> {
+    Ok(value / decimal_factor(decimals)?)
+}
+
+/// Multiply the value by 10^decimals, e.g., `unscale(100, 18)` is 100 ether in wei
+pub fn unscale(value: u256, decimals: u256) -> Result<u256, ASTError> {
+    value
+        .checked_mul(decimal_factor(decimals)?)
+        .ok_or(ASTError::OverflowError(format!("{} * 10^{}", value, decimals)))
+}
+
+/// Decode ABI encoded data, the inverse of `encode_token` for values in 32 byte words.
+/// Every type consumes one word of the hex string.
+/// Supported types are uintN, intN, address, bool and bytesN.
+pub fn abi_decode(data: &str, types: &[String]) -> Result<Vec<ASTConstant>, ASTError> {
+    let data = data.strip_prefix("0x").unwrap_or(data);
+    let mut decoded = Vec::with_capacity(types.len());
+    for (i, t) in types.iter().enumerate() {
+        let (start, end) = (i * 64, (i + 1) * 64);
+        if end > data.len() {
+            return Err(ASTError::InvalidSlice(data.to_string(), start, end, data.len()));
+        }
+        let word = &data[start..end];
+        let number = u256
+            ::from_str_radix(word, 16)
+            .map_err(|_| ASTError::InvalidNumberConversion(word.to_string()))?;
+        let t = t.trim();
+        let value = if t.starts_with("uint") {
+            ASTConstant::Number(number)
+        } else if t.starts_with("int") {
+            ASTConstant::SignedNumber(number.as_i256())
+        } else if t == "address" {
+            ASTConstant::String(format!("0x{}", &word[24..]))
+        } else if t == "bool" {
+            ASTConstant::Bool(number != 0)
+        } else if let Some(size) = t.strip_prefix("bytes") {
+            // bytesN is left aligned
+            let size = size.parse::<usize>().unwrap_or(32).min(32);
+            ASTConstant::String(format!("0x{}", &word[..size * 2]))
+        } else {
+            return Err(ASTError::UnknownConversionTarget(t.to_string()));
+        };
+        decoded.push(value);
+    }
+    Ok(decoded)
+}
+
+/// Converts the error object of a JSON-RPC response, e.g. `{"code": -32000, "message": "execution reverted"}`
+pub fn rpc_error(error: &Value) -> ASTError {
+    ASTError::RpcError {
+        code: error.get("code").and_then(|c| c.as_i64()).unwrap_or_default(),
+        message: error
+            .get("message")
+            .and_then(|m| m.as_str())
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+/// Decodes a parameter of a log by its name in the event signature, e.g.
+/// "Transfer(address indexed from, address indexed to, uint256 value)".
+/// Indexed parameters are read from the topics after topic0 and the others from the data.
+/// Indexed strings, bytes, arrays and tuples are only stored as their hash, which is returned as is.
+pub fn event_param(
+    signature: &str,
+    topics: &[String],
+    data: &str,
+    name: &str
+) -> Result<ASTConstant, ASTError> {
+    let parameters = utils::event_parameters(signature);
+    let position = parameters
+        .iter()
+        .position(|p| p.name == name)
+        .ok_or(ASTError::UnknownKey(name.to_string()))?;
+    let parameter = &parameters[position];
+    // Position among the parameters that are stored at the same location
+    let index = parameters[..position]
+        .iter()
+        .filter(|p| p.indexed == parameter.indexed)
+        .count();
+    let is_dynamic =
+        parameter.ty == "string" ||
+        parameter.ty == "bytes" ||
+        parameter.ty.ends_with(']') ||
+        parameter.ty.starts_with('(');
+
+    if parameter.indexed {
+        let topic = topics
+            .get(index + 1)
+            .ok_or(ASTError::IndexOutOfBounds((index + 1).as_i256(), topics.len()))?;
+        if is_dynamic {
+            return Ok(ASTConstant::String(topic.clone()));
+        }
+        return Ok(abi_decode(topic, &[parameter.ty.clone()])?.remove(0));
+    }
+    if is_dynamic {
+        return Err(ASTError::UnknownConversionTarget(parameter.ty.clone()));
+    }
+    let data = data.strip_prefix("0x").unwrap_or(data);
+    let (start, end) = (index * 64, (index + 1) * 64);
+    if end > data.len() {
+        return Err(ASTError::InvalidSlice(data.to_string(), start, end, data.len()));
+    }
+    Ok(abi_decode(&data[start..end], &[parameter.ty.clone()])?.remove(0))
+}
+
+/// Standard ABI encoding like `abi.encode`. Every value is padded to a 32 byte word:
+/// numbers are big endian, addresses are right aligned, bools are 0 or 1 and
+/// fixed size arrays are encoded inline. Dynamic strings are not supported.
+pub fn abi_encode(tokens: &[ASTConstant]) -> Result<String, ASTError> {
+    let mut out: Vec<u8> = Vec::with_capacity(tokens.len() * 32);
+    for token in tokens {
+        abi_encode_token(token, &mut out)?;
+    }
+    Ok(format!("0x{}", hex::encode(out)))
+}
+
+fn abi_encode_token(token: &ASTConstant, out: &mut Vec<u8>) -> Result<(), ASTError> {
+    match token {
+        ASTConstant::Number(n) => out.extend_from_slice(&n.to_be_bytes()),
+        ASTConstant::SignedNumber(n) => out.extend_from_slice(&n.to_be_bytes()),
+        ASTConstant::Bool(b) => {
+            out.extend_from_slice(&[0; 31]);
             out.push(*b as u8);
         }
         ASTConstant::String(s) => {
-            if s.starts_with("0x") && s.len() == 42 {
-                // Address
-                if in_array {
-                    out.extend_from_slice(&[0; 12]);
-                }
-                out.extend_from_slice(&s.as_bytes());
-            } else {
-                out.extend_from_slice(s.as_bytes());
-            }
+            // Addresses and other hex values up to 32 bytes
+            let bytes = s
+                .strip_prefix("0x")
+                .and_then(|h| hex::decode(if h.len() % 2 == 1 { format!("0{}", h) } else { h.to_string() }).ok())
+                .filter(|b| b.len() <= 32)
+                .ok_or(ASTError::InvalidConversion(s.clone(), "abi word".to_string()))?;
+            out.extend(std::iter::repeat(0).take(32 - bytes.len()));
+            out.extend_from_slice(&bytes);
         }
         ASTConstant::Array(vec) => {
             for t in vec {
-                encode_token(t, out, true);
+                abi_encode_token(t, out)?;
             }
         }
-        _ => {}
+        ASTConstant::Map(_) => {
+            return Err(ASTError::InvalidFunctionParameter("abi_encode".to_string()));
+        }
     }
+    Ok(())
 }
 
 fn max_encoded_length(t: &ASTConstant) -> usize {
@@ -3624,6 +4693,8 @@ mod test_ast {
     use ethnum::AsU256;
     use sha3::digest::typenum::SquareRoot;
 
+    use serde_json::json;
+
     use crate::properties::{ ast::*, environment::print_variables };
 
     #[test]
@@ -3640,6 +4711,180 @@ mod test_ast {
         println!("{:?}", test);
     }
 
+    #[test]
+    fn test_tokenizer_string_literals() {
+        let tokens = tokenize("$memo == 'hello world'".to_string());
+        assert_eq!(tokens, vec!["$memo", "==", "'hello world'"]);
+
+        let tokens = tokenize("['a b', \"c, d.e\"]".to_string());
+        assert_eq!(tokens, vec!["[", "'a b'", "\"c, d.e\"", "]"]);
+
+        let val = parse_token("'hello world'".to_string()).unwrap().evaluate().unwrap();
+        assert_eq!(val, ASTConstant::String("hello world".to_string()));
+        let val = parse_token("'5'".to_string()).unwrap().evaluate().unwrap();
+        assert_eq!(val, ASTConstant::String("5".to_string()));
+
+        set_var!("memo", "hello world");
+        let root = build_ast_root("$memo == 'hello world'").unwrap();
+        let val = root.evaluate().unwrap();
+        assert_eq!(val.get_value(), "true");
+
+        let root = build_ast_root("\"a, b\" == 'a, b'").unwrap();
+        let val = root.evaluate().unwrap();
+        assert_eq!(val.get_value(), "true");
+
+        let root = build_ast_root("['a b', 'c'].contains('a b')").unwrap();
+        let val = root.evaluate().unwrap();
+        assert_eq!(val.get_value(), "true");
+    }
+
+    #[test]
+    fn test_json_big_numbers() {
+        // 2^64 and a token amount of 10^30 wei
+        let value: Value = serde_json::from_str(
+            r#"{"a": 18446744073709551616, "b": 1000000000000000000000000000000, "c": -18446744073709551616, "d": 1.9, "e": -1.9, "f": 1e400}"#
+        ).unwrap();
+        let ASTConstant::Map(map) = ASTConstant::from(&value) else {
+            unreachable!("expected a map");
+        };
+        assert_eq!(map["a"], ASTConstant::Number(u256::from(u64::MAX) + 1));
+        assert_eq!(map["b"], ASTConstant::Number(u256::from_str("1000000000000000000000000000000").unwrap()));
+        assert_eq!(map["c"], ASTConstant::SignedNumber(-(i256::from(u64::MAX) + 1)));
+        assert_eq!(map["d"], ASTConstant::Number(u256::ONE));
+        assert_eq!(map["e"], ASTConstant::SignedNumber(i256::from(-1)));
+        assert_eq!(map["f"], ASTConstant::String("1e400".to_string()));
+
+        let node = ASTNode::from(serde_json::json!(18446744073709551616u128));
+        assert_eq!(node.evaluate().unwrap().get_value(), "18446744073709551616");
+    }
+
+    #[test]
+    fn test_concat() {
+        set_var!("concat_hash", "0xabc");
+        set_var!("concat_value", 1000u64);
+        let root = build_ast_root("concat('tx ', $concat_hash, ' value ', $concat_value)").unwrap();
+        assert_eq!(root.evaluate().unwrap(), ASTConstant::String("tx 0xabc value 1000".to_string()));
+
+        // Results of expressions and functions are stringified as well
+        let root = build_ast_root("concat($concat_value + 24, ' ', 1 < 2, ' ', 'ABC'.tolower())").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "1024 true abc");
+
+        assert!(build_ast_root("concat()").is_err());
+    }
+
+    #[test]
+    fn test_tokenize_separators() {
+        // The elements of arrays are not separated by comma tokens
+        let tokens = tokenize("abi_encode(1, [2, 3], (4 + 5))".to_string());
+        assert_eq!(tokens, vec!["abi_encode", "(", "1", ",", "[", "2", "3", "]", ",", "(", "4", "+", "5", ")", ")"]);
+
+        let tokens = tokenize("$map.insert($key, concat(a, b))".to_string());
+        assert_eq!(tokens, vec!["$map", "insert", "(", "$key", ",", "concat", "(", "a", ",", "b", ")", ")"]);
+
+        let tokens = tokenize("[[1, 2], 3]".to_string());
+        assert_eq!(tokens, vec!["[", "[", "1", "2", "]", "3", "]"]);
+
+        // Misplaced commas are kept for the shunting yard algorithm to reject them
+        let tokens = tokenize("1 < 2, 3".to_string());
+        assert_eq!(tokens, vec!["1", "<", "2", ",", "3"]);
+    }
+
+    #[test]
+    fn test_malformed_expressions() {
+        for text in ["1 < 2)", ")", "+", "1 +", "!", "1 < 2, 3", "keccak256(1)", "(1 + 2"] {
+            assert!(build_ast_root(text).is_err(), "{}", text);
+        }
+        let tokens = tokenize("1 +".to_string());
+        let postfix = shunting_yard_algorithm(tokens).unwrap();
+        assert!(matches!(parse_postfix(postfix), Err(ASTError::MissingOperand(op)) if op == "+"));
+    }
+
+    #[test]
+    fn test_misplaced_separator() {
+        let tokens = vec!["1", "<", "2", ",", "3"].into_iter().map(String::from).collect();
+        assert!(matches!(shunting_yard_algorithm(tokens), Err(ASTError::MisplacedSeparator)));
+
+        assert!(build_ast_root("1 < 2, 3").is_err());
+        assert!(build_ast_root("assign(a, 1), 2").is_err());
+        assert_eq!(build_ast_root("abi_encode(1, 2)").unwrap().evaluate().unwrap().get_value().len(), 130);
+    }
+
+    #[test]
+    fn test_abi_encode() {
+        // abi.encode(address(0xe575...), 1 ether, true)
+        let expected = format!(
+            "0x{}{}{}",
+            "000000000000000000000000e5752128b13c709d2a7e5348e601a016136a3f28",
+            "0000000000000000000000000000000000000000000000000de0b6b3a7640000",
+            "0000000000000000000000000000000000000000000000000000000000000001"
+        );
+        let encoded = abi_encode(
+            &vec![
+                ASTConstant::String("0xe5752128B13c709d2A7E5348E601a016136a3F28".to_string()),
+                ASTConstant::Number(1000000000000000000u64.as_u256()),
+                ASTConstant::Bool(true)
+            ]
+        ).unwrap();
+        assert_eq!(encoded, expected);
+
+        let root = build_ast_root(
+            "abi_encode(0xe5752128B13c709d2A7E5348E601a016136a3F28, 1000000000000000000, true)"
+        ).unwrap();
+        let val = root.evaluate().unwrap();
+        assert_eq!(val.get_value(), expected);
+
+        // Signed numbers use the two's complement
+        let root = build_ast_root("abi_encode(neg 1)").unwrap();
+        let val = root.evaluate().unwrap();
+        assert_eq!(val.get_value(), format!("0x{}", "ff".repeat(32)));
+
+        // The result can be decoded again
+        let root = build_ast_root(
+            "abi_encode(7, 0xe5752128B13c709d2A7E5348E601a016136a3F28).decode([uint256, address]).at(0) == 7"
+        ).unwrap();
+        let val = root.evaluate().unwrap();
+        assert_eq!(val.get_value(), "true");
+
+        assert!(abi_encode(&vec![ASTConstant::String("hello".to_string())]).is_err());
+        assert!(build_ast_root("abi_encode()").is_err());
+    }
+
+    #[test]
+    fn test_decode() {
+        let data = format!(
+            "0x{}{}{}{}",
+            "00000000000000000000000000000000000000000000000000000000000003e8",
+            "000000000000000000000000715cdda5e9ad30a0ced14940f9997ee611496de6",
+            "1111111111111111111111111111111111111111111111111111111111111111",
+            "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff"
+        );
+        let types = vec!["uint256", "address", "bytes32", "int256"]
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<String>>();
+        let decoded = abi_decode(&data, &types).unwrap();
+        assert_eq!(decoded[0], ASTConstant::Number(1000.as_u256()));
+        assert_eq!(
+            decoded[1],
+            ASTConstant::String("0x715cdda5e9ad30a0ced14940f9997ee611496de6".to_string())
+        );
+        assert_eq!(decoded[2], ASTConstant::String(format!("0x{}", "11".repeat(32))));
+        assert_eq!(decoded[3], ASTConstant::SignedNumber(i256::from(-1)));
+
+        // Missing words and unknown types are errors
+        assert!(abi_decode("0x03e8", &types).is_err());
+        assert!(abi_decode(&data, &vec!["string".to_string()]).is_err());
+
+        set_var!("payload", data.as_str());
+        let root = build_ast_root("$payload.decode([uint256, address]).at(1)").unwrap();
+        let val = root.evaluate().unwrap();
+        assert_eq!(val.get_value(), "0x715cdda5e9ad30a0ced14940f9997ee611496de6");
+
+        let root = build_ast_root("decode($payload, ['uint256']).at(0) == 1000").unwrap();
+        let val = root.evaluate().unwrap();
+        assert_eq!(val.get_value(), "true");
+    }
+
     #[test]
     fn test_ast() {
         // Example: 5 + 5 > 17 - 15
@@ -3888,9 +5133,64 @@ mod test_ast {
         assert_eq!(value, "true");
     }
 
+    #[test]
+    fn test_modulo_precedence() {
+        let tokens = tokenize("1 == 17 % 10".to_string());
+        let postfix = shunting_yard_algorithm(tokens).unwrap();
+        assert_eq!(postfix, vec!["1", "17", "10", "%", "=="]);
+
+        let root = build_ast_root("1 == 17 % 10 - 6").unwrap();
+        let val = root.evaluate().unwrap();
+        assert_eq!(val.get_value(), "true");
+
+        let root = build_ast_root("( 17 * 3 ) % ( 2 + 8 ) == 1").unwrap();
+        let val = root.evaluate().unwrap();
+        assert_eq!(val.get_value(), "true");
+
+        // Every stage of the pipeline
+        let tokens = tokenize("( 17 * 3 ) % 10 == 1".to_string());
+        assert_eq!(tokens, vec!["(", "17", "*", "3", ")", "%", "10", "==", "1"]);
+        let postfix = shunting_yard_algorithm(tokens).unwrap();
+        assert_eq!(postfix, vec!["17", "3", "*", "10", "%", "1", "=="]);
+        let (_, root) = parse_postfix(postfix).unwrap();
+        assert_eq!(root.evaluate().unwrap(), ASTConstant::Bool(true));
+    }
+
+    #[test]
+    fn test_bitwise_precedence() {
+        // Comparisons bind looser than bitwise operators
+        let postfix = shunting_yard_algorithm(tokenize("1 | 2 == 3".to_string())).unwrap();
+        assert_eq!(postfix, vec!["1", "2", "|", "3", "=="]);
+        assert_eq!(build_ast_root("1 | 2 == 3").unwrap().evaluate().unwrap(), ASTConstant::Bool(true));
+
+        set_var!("bit_a", 12u64);
+        set_var!("bit_b", 10u64);
+        set_var!("bit_c", 8u64);
+        let postfix = shunting_yard_algorithm(tokenize("$bit_a & $bit_b == $bit_c".to_string())).unwrap();
+        assert_eq!(postfix, vec!["$bit_a", "$bit_b", "&", "$bit_c", "=="]);
+        assert_eq!(build_ast_root("$bit_a & $bit_b == $bit_c").unwrap().evaluate().unwrap(), ASTConstant::Bool(true));
+
+        // & binds tighter than ^ and ^ tighter than |
+        let postfix = shunting_yard_algorithm(tokenize("1 | 6 ^ 3 & 5".to_string())).unwrap();
+        assert_eq!(postfix, vec!["1", "6", "3", "5", "&", "^", "|"]);
+        assert_eq!(build_ast_root("1 | 6 ^ 3 & 5").unwrap().evaluate().unwrap(), ASTConstant::Number(7u64.into()));
+
+        // Arithmetic binds tighter than shifts, shifts of the same level are left associative
+        assert_eq!(build_ast_root("1 << 2 + 1").unwrap().evaluate().unwrap(), ASTConstant::Number(8u64.into()));
+        assert_eq!(build_ast_root("64 >> 1 >> 2").unwrap().evaluate().unwrap(), ASTConstant::Number(8u64.into()));
+        assert_eq!(build_ast_root("( 1 | 2 ) * 2 == 6 && 5 ^ 1 == 4").unwrap().evaluate().unwrap(), ASTConstant::Bool(true));
+
+        // Signed numbers are shifted arithmetically
+        assert_eq!(
+            build_ast_root("i256:-8 >> 1").unwrap().evaluate().unwrap(),
+            ASTConstant::SignedNumber(i256::from(-4))
+        );
+        assert_eq!(build_ast_root("0xff & 0x0f").unwrap().evaluate().unwrap(), ASTConstant::Number(15u64.into()));
+        assert!(matches!(build_ast_root("1 << 256").unwrap().evaluate(), Err(ASTError::OverflowError(_))));
+    }
+
     #[test]
     fn test_variables() {
-        let map = get_variable_map_instance();
         set_var!("x", "5");
 
         let root = build_ast_root("$x == 5").unwrap();
@@ -3904,7 +5204,6 @@ mod test_ast {
 
     #[test]
     fn test_str_var() {
-        let map = get_variable_map_instance();
         set_var!("x", "airport");
 
         let root = build_ast_root("$x == milestone").unwrap();
@@ -3930,6 +5229,234 @@ mod test_ast {
         assert_eq!(v, "false");
     }
 
+    #[test]
+    fn test_function_at_negative() {
+        let root = build_ast_root("[10,20,30].at(neg 1) == 30").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+
+        let root = build_ast_root("[10,20,30].at(neg 3) == 10").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+
+        let root = build_ast_root("[10,20,30].at(neg 4)").unwrap();
+        assert!(matches!(root.evaluate(), Err(ASTError::IndexOutOfBounds(_, 3))));
+    }
+
+    #[test]
+    fn test_map_stringification_is_stable() {
+        let keys = ["to", "from", "value", "blockNumber", "hash", "input", "nonce", "gas"];
+        let mut forward = HashMap::new();
+        for (i, k) in keys.iter().enumerate() {
+            forward.insert(k.to_string(), ASTConstant::Number(u256::from(i as u64)));
+        }
+        let mut backward = HashMap::new();
+        for (i, k) in keys.iter().enumerate().rev() {
+            backward.insert(k.to_string(), ASTConstant::Number(u256::from(i as u64)));
+        }
+
+        let expected = "{blockNumber: 3,from: 1,gas: 7,hash: 4,input: 5,nonce: 6,to: 0,value: 2}";
+        assert_eq!(ASTConstant::Map(forward.clone()).get_value(), expected);
+        assert_eq!(ASTConstant::Map(backward).get_value(), expected);
+        assert_eq!(
+            ASTConstant::Map(forward.clone()).get_constant_info(),
+            ASTConstant::Map(forward).get_constant_info()
+        );
+    }
+
+    #[test]
+    fn test_scale() {
+        let root = build_ast_root("scale(1500000000000000000, 18) == 1").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+
+        set_var!("scaled_value", u256::from(250_000_000_000_000_000_000u128));
+        let root = build_ast_root("scale($scaled_value, 18) > 100").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+
+        let root = build_ast_root("unscale(100, 18) == $scaled_value").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "false");
+
+        let root = build_ast_root("unscale(2, 3)").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "2000");
+
+        // 10^78 and the product exceed u256
+        let root = build_ast_root("scale(1, 78)").unwrap();
+        assert!(matches!(root.evaluate(), Err(ASTError::OverflowError(_))));
+        let root = build_ast_root("unscale(1000000, 72)").unwrap();
+        assert!(matches!(root.evaluate(), Err(ASTError::OverflowError(_))));
+    }
+
+    #[test]
+    fn test_incr_count() {
+        // Other tests reset $map, the exact counts are tested in environment::test_counter
+        set_var!("counted_payer", "0x00000000000000000000000000000000000000c0");
+        let root = build_ast_root("incr($counted_payer) < 5").unwrap();
+        assert!(
+            matches!(&root, ASTNode::BinaryLogic(_, left, _) if matches!(**left, ASTNode::Function(Functions::Incr, _)))
+        );
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+
+        let root = build_ast_root("count($counted_payer) < 5").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+    }
+
+    #[test]
+    fn test_daily_volume() {
+        // The rollover is tested in environment::test_daily_volume
+        set_var!("volume_chain", "daily_volume_test_chain");
+        let root = build_ast_root("dailyVolume($volume_chain, 400) < 1000").unwrap();
+        assert!(
+            matches!(&root, ASTNode::BinaryLogic(_, left, _) if matches!(**left, ASTNode::Function(Functions::DailyVolume, _)))
+        );
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+    }
+
+    #[test]
+    fn test_selector() {
+        let root = build_ast_root("selector('transfer(address,uint256)')").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "0xa9059cbb");
+
+        set_var!(
+            "tx_input",
+            "0xa9059cbb000000000000000000000000e5752128b13c709d2a7e5348e601a016136a3f28"
+        );
+        let root = build_ast_root("$tx_input.slice(0,10) == selector('transfer(address,uint256)')").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+    }
+
+    #[test]
+    fn test_unsigned_signed_comparison() {
+        // u256::MAX wraps to -1 as i256
+        set_var!("huge_balance", u256::MAX);
+        set_var!("small_signed", i256::new(5));
+
+        let cases = [
+            ("$huge_balance > neg 1", "true"),
+            ("$huge_balance == neg 1", "false"),
+            ("$huge_balance < neg 1", "false"),
+            ("neg 1 < $huge_balance", "true"),
+            ("$huge_balance > $small_signed", "true"),
+            ("$small_signed >= $huge_balance", "false"),
+            ("$small_signed == 5", "true"),
+            ("4 <= $small_signed", "true"),
+            ("0 > neg 1", "true"),
+        ];
+        for (code, expected) in cases {
+            let root = build_ast_root(code).unwrap();
+            assert_eq!(root.evaluate().unwrap().get_value(), expected, "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_now() {
+        let root = build_ast_root("now()").unwrap();
+        match (root.evaluate().unwrap(), root.evaluate().unwrap()) {
+            (ASTConstant::Number(first), ASTConstant::Number(second)) => assert!(second >= first),
+            other => unreachable!("now() returned {:?}", other),
+        }
+
+        set_var!("message_timestamp", u256::from((Utc::now().timestamp() - 60) as u64));
+        let root = build_ast_root("now() - $message_timestamp < 3600").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+    }
+
+    #[test]
+    fn test_prev() {
+        // The values of consecutive events are tested in environment::test_previous_values
+        let _scope = crate::properties::environment::EventScope::enter();
+        set_var!("src_chain", "bsc");
+        set_var!("payer_address", "0x00000000000000000000000000000000000000d1");
+        set_var!("tracked_balance", u256::from(100u64));
+
+        // Without a previous event the current value is used
+        let root = build_ast_root("$tracked_balance - prev(tracked_balance)").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "0");
+        let root = build_ast_root("prev($tracked_balance)").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "100");
+    }
+
+    #[test]
+    fn test_event_param() {
+        let _scope = crate::properties::environment::EventScope::enter();
+        set_var!("event_signature", "Transfer(address indexed from, address indexed to, uint256 value)");
+        set_var!(
+            "event_topics",
+            vec![
+                "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef",
+                "0x000000000000000000000000a58a9d3a5e240b09da3bc0bfc011af3d20d31763",
+                "0x000000000000000000000000e5752128b13c709d2a7e5348e601a016136a3f28"
+            ]
+        );
+        set_var!("event_data", "0x0000000000000000000000000000000000000000000000000de0b6b3a7640000");
+
+        let root = build_ast_root("event_param(from)").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "0xa58a9d3a5e240b09da3bc0bfc011af3d20d31763");
+        let root = build_ast_root("event_param(to)").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "0xe5752128b13c709d2a7e5348e601a016136a3f28");
+        let root = build_ast_root("event_param(value) == 1000000000000000000").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+
+        let root = build_ast_root("event_param(amount)").unwrap();
+        assert!(matches!(root.evaluate(), Err(ASTError::UnknownKey(_))));
+    }
+
+    #[test]
+    fn test_hex_display() {
+        let hash = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+        let root = build_ast_root(&format!("{} + 0", hash)).unwrap();
+        let val = root.evaluate().unwrap();
+        // The value is still a number, only the logged form is hex
+        assert!(matches!(val, ASTConstant::Number(_)));
+        assert_eq!(root.display_value(&val), hash);
+        assert_eq!(val.get_value(), parse_prefixed_number(hash).unwrap().to_string());
+
+        let root = build_ast_root("0xff + 1").unwrap();
+        assert_eq!(root.display_value(&root.evaluate().unwrap()), "0x100");
+        let root = build_ast_root("255 + 1").unwrap();
+        assert_eq!(root.display_value(&root.evaluate().unwrap()), "256");
+        let root = build_ast_root("0xff == 255").unwrap();
+        assert_eq!(root.display_value(&root.evaluate().unwrap()), "true");
+    }
+
+    #[test]
+    fn test_hex_literals() {
+        let root = build_ast_root("0x10 == 16").unwrap();
+        assert!(
+            matches!(&root, ASTNode::BinaryLogic(_, left, _) if matches!(**left, ASTNode::ConstantHexNumber(_)))
+        );
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+
+        let root = build_ast_root("0xff + 1 == 256").unwrap();
+        assert!(
+            matches!(&root, ASTNode::BinaryLogic(_, left, _) if matches!(
+                &**left,
+                ASTNode::BinaryArithmetic(_, l, r)
+                    if matches!(**l, ASTNode::ConstantHexNumber(_)) && matches!(**r, ASTNode::ConstantNumber(_))
+            ))
+        );
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+
+        let root = build_ast_root("0b101 + 0o7 == 12").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+
+        // Function arguments keep the literal
+        let root = build_ast_root("[0x0A].contains(0x0A)").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+        assert!(matches!(parse_token("0x".to_string()), Ok(ASTNode::ConstantString(_))));
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        set_var!("oob_keystore", VarValues::Array(vec![]));
+
+        let root = build_ast_root("$oob_keystore.at(0)").unwrap();
+        assert!(matches!(root.evaluate(), Err(ASTError::IndexOutOfBounds(_, 0))));
+
+        let root = build_ast_root("[10,20,30].at(3)").unwrap();
+        assert!(matches!(root.evaluate(), Err(ASTError::IndexOutOfBounds(_, 3))));
+
+        let root = build_ast_root("$oob_keystore.pop()").unwrap();
+        assert!(matches!(root.evaluate(), Err(ASTError::EmptyArray)));
+    }
+
     #[test]
     fn test_arr() {
         set_var!("arr", "[0,1,2,3]");
@@ -4053,6 +5580,85 @@ mod test_ast {
         assert_eq!(ret, "0xff");
     }
 
+    #[test]
+    fn test_hexnum_conversion() {
+        let root = build_ast_root("'10'.as(hexnum) == 16").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+
+        let root = build_ast_root("'10'.as(u256) == 10").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+
+        let root = build_ast_root("'ff'.as(hexnum)").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "255");
+
+        set_var!("raw", "10");
+        let root = build_ast_root("$raw.as(hexnum)").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "16");
+
+        assert!(ASTConstant::String("xyz".to_string()).convert(ConversionTarget::HexNumber).is_err());
+    }
+
+    #[test]
+    fn test_base58_hex_conversion() {
+        let pubkey = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+        let hex = format!("0x{}", hex::encode(bs58::decode(pubkey).into_vec().unwrap()));
+        assert_eq!(hex.len(), 66);
+        assert_eq!(
+            ASTConstant::String(pubkey.to_string()).convert(ConversionTarget::Hex).unwrap(),
+            ASTConstant::String(hex)
+        );
+
+        // Valid base58 but not a public key
+        assert!(ASTConstant::String("transfer".to_string()).convert(ConversionTarget::Hex).is_err());
+        assert!(ASTConstant::String("xyz".repeat(11)).convert(ConversionTarget::Hex).is_err());
+        // Not base58
+        assert!(ASTConstant::String("0OIl".repeat(10)).convert(ConversionTarget::Hex).is_err());
+        // Hex strings still get the prefix
+        let root = build_ast_root("'ff'.as(hex)").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "0xff");
+    }
+
+    #[test]
+    fn test_bytes32_conversion() {
+        let hash = "0x0abc000000000000000000000000000000000000000000000000000000000001";
+        set_var!("hash", hash);
+        set_var!("trimmed", "0xabc000000000000000000000000000000000000000000000000000000000001");
+        let root = build_ast_root("$hash.as(bytes32) == $trimmed.as(bytes32)").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+        // As strings the hashes differ
+        let root = build_ast_root("$hash == $trimmed").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "false");
+
+        let root = build_ast_root("'0xABC'.as(bytes32)").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), format!("0x{:0>64}", "abc"));
+        assert_eq!(
+            ASTConstant::Number(u256::from(1u8)).convert(ConversionTarget::Bytes32).unwrap(),
+            ASTConstant::String(format!("0x{:0>64}", "1"))
+        );
+
+        // Not hex or longer than 32 bytes
+        assert!(ASTConstant::String("0xxyz".to_string()).convert(ConversionTarget::Bytes32).is_err());
+        assert!(ASTConstant::String(format!("0x1{}", "0".repeat(64))).convert(ConversionTarget::Bytes32).is_err());
+    }
+
+    #[test]
+    fn test_duration_conversion() {
+        let root = build_ast_root("'5m'.as(duration) == 300").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+
+        let root = build_ast_root("'1h'.as(duration) == 3600").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+
+        set_var!("ts_age", "120");
+        let root = build_ast_root("$ts_age < '5m'.as(duration)").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+
+        assert_eq!(parse_duration("30s"), Some(u256::from(30u64)));
+        assert_eq!(parse_duration("2d"), Some(u256::from(172800u64)));
+        assert_eq!(parse_duration("5y"), None);
+        assert_eq!(parse_duration("m"), None);
+    }
+
     #[test]
     fn test_string_arithmetic() {
         let root = build_ast_root("0xff.as(u256) - 1").unwrap();
@@ -4176,14 +5782,14 @@ mod test_ast {
         let ret = root.evaluate().unwrap();
         println!("{:?}", ret);
 
-        println!("{:?}", get_variable_map_instance());
+        println!("{:?}", *get_variable_map_instance());
     }
 
     #[test]
     fn test_ast_map() {
         set_var!("map", VarValues::Map(HashMap::new()));
 
-        println!("{:?}", get_variable_map_instance());
+        println!("{:?}", *get_variable_map_instance());
 
         let root = build_ast_root("$map.insert(aircraft, 0x12345)").unwrap();
         let val = root.evaluate().unwrap();
@@ -4191,7 +5797,7 @@ mod test_ast {
         println!("{}", ret);
 
         assert_eq!(ret, "true");
-        println!("{:?}", get_variable_map_instance());
+        println!("{:?}", *get_variable_map_instance());
 
         let root = build_ast_root("$map.get(aircraft)").unwrap();
         let val = root.evaluate().unwrap();
@@ -4199,7 +5805,7 @@ mod test_ast {
         println!("{}", ret);
 
         assert_eq!(ret, "0x12345");
-        println!("{:?}", get_variable_map_instance());
+        println!("{:?}", *get_variable_map_instance());
 
         let root = build_ast_root("$map.remove(aircraft)").unwrap();
         let val = root.evaluate().unwrap();
@@ -4207,14 +5813,213 @@ mod test_ast {
         println!("{}", ret);
 
         assert_eq!(ret, "0x12345");
-        println!("{:?}", get_variable_map_instance());
+        println!("{:?}", *get_variable_map_instance());
+    }
+
+    #[test]
+    fn test_empty_hex_conversion() {
+        set_var!("empty_value", "0x");
+        for (pattern, expected) in [
+            ("0x.as(u256) == 0", "true"),
+            ("'0x'.as(u256) == 0", "true"),
+            ("0x0.as(u256) == 0", "true"),
+            ("$empty_value.as(u256) + 1", "1"),
+            ("$empty_value.as(i256) == 0", "true"),
+            ("$empty_value.as(hexnum) == 0", "true"),
+        ] {
+            let root = build_ast_root(pattern).unwrap();
+            assert_eq!(root.evaluate().unwrap().get_value(), expected, "{}", pattern);
+        }
+        let empty = ASTConstant::String(String::new());
+        assert_eq!(empty.convert(ConversionTarget::Number).unwrap(), ASTConstant::Number(u256::ZERO));
+        // Other strings are still no numbers
+        assert!(ASTConstant::String("abc!".to_string()).convert(ConversionTarget::Number).is_err());
+
+        // The strict mode keeps rejecting empty hex values
+        let strict = empty.convert_empty_hex(&ConversionTarget::Number, true).unwrap();
+        assert!(matches!(strict, Err(ASTError::InvalidNumberConversion(_))));
+        assert!(empty.convert_empty_hex(&ConversionTarget::String, true).is_none());
+    }
+
+    #[test]
+    fn test_nested_rpc_response() {
+        let response: Value = serde_json::from_str(
+            r#"{
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": {
+                    "hash": "0x5c504ed432cb51138bcf09aa5e8a410dd4a1e204ef84bfed1be16dfba1b22060",
+                    "to": "0x388c818ca8b9251b393131c08a736a67ccb19297",
+                    "from": "0xa7d9ddbe1f17865597fbd27ec712455208b6b76d",
+                    "value": "0x2c68af0bb140000",
+                    "contractAddress": null,
+                    "gasRatio": 1.5,
+                    "delta": -3,
+                    "accessList": [{ "address": "0x1", "storageKeys": ["0xa", "0xb"] }],
+                    "receipt": { "status": "0x1", "logs": [] }
+                }
+            }"#
+        ).unwrap();
+        // The response of call() is converted as a whole
+        set_var!("rpc_response", ASTConstant::from(&response));
+        for (pattern, expected) in [
+            ("$rpc_response.get(result).get(to)", "0x388c818ca8b9251b393131c08a736a67ccb19297"),
+            ("$rpc_response.get(result).get(receipt).get(status)", "0x1"),
+            ("$rpc_response.get(result).get(accessList).at(0).get(storageKeys).at(1)", "0xb"),
+            ("$rpc_response.get(result).get(value).as(u256) > 0", "true"),
+            ("$rpc_response.get(result).get(contractAddress)", "None"),
+            // Floats are truncated
+            ("$rpc_response.get(result).get(gasRatio)", "1"),
+            ("$rpc_response.get(result).get(delta) < 0", "true"),
+        ] {
+            let root = build_ast_root(pattern).unwrap();
+            assert_eq!(root.evaluate().unwrap().get_value(), expected, "{}", pattern);
+        }
+        assert!(matches!(
+            build_ast_root("$rpc_response.get(result).get(missing)").unwrap().evaluate(),
+            Err(ASTError::UnknownKey(_))
+        ));
+
+        // The gets are chained onto the call
+        let root = build_ast_root("call(ethereum, eth_getTransactionByHash, [0x1]).get(result).get(to)").unwrap();
+        let ASTNode::Function(Functions::Get, args) = root else {
+            unreachable!("expected get(to)");
+        };
+        let ASTNode::Function(Functions::Get, inner) = *args[0].clone() else {
+            unreachable!("expected get(result)");
+        };
+        assert!(matches!(*inner[0], ASTNode::Function(Functions::Custom, _)));
+    }
+
+    #[test]
+    fn test_max_expression_depth() {
+        let max = max_expression_depth();
+        let nested = |depth: usize| {
+            (1..depth).fold(ASTNode::ConstantNumber(u256::ONE), |node, _| {
+                ASTNode::BinaryArithmetic(
+                    ArithmeticOperator::Add,
+                    Box::new(node),
+                    Box::new(ASTNode::ConstantNumber(u256::ONE))
+                )
+            })
+        };
+        // The default depth fits on the stack of a thread
+        let root = nested(max);
+        assert_eq!(root.depth(), max);
+        assert_eq!(root.evaluate().unwrap().get_value(), max.to_string());
+        assert!(matches!(nested(max + 1).evaluate(), Err(ASTError::MaxDepthExceeded(d)) if d == max));
+
+        // Pathological expressions are rejected while parsing
+        let sum = vec!["1"; 10 * max].join(" + ");
+        assert!(build_ast_root(&sum).is_err());
+        let parentheses = format!("{}1{}", "(".repeat(10 * max), ")".repeat(10 * max));
+        assert!(build_ast_root(&parentheses).is_err());
+        assert_eq!(build_ast_root("(((1 + 2)))").unwrap().evaluate().unwrap().get_value(), "3");
+    }
+
+    #[test]
+    fn test_long_flat_chain() {
+        let sum = format!("{} == 100", vec!["1"; 100].join(" + "));
+        assert_eq!(build_ast_root(&sum).unwrap().evaluate().unwrap().get_value(), "true");
+
+        let all = vec!["1 < 2"; 100].join(" && ");
+        assert_eq!(build_ast_root(&all).unwrap().evaluate().unwrap().get_value(), "true");
+    }
+
+    #[test]
+    fn test_string_ordering() {
+        set_var!("version", "1.4.2");
+        for (pattern, expected) in [
+            ("'abc' < 'abd'", "true"),
+            ("'abc' > 'abd'", "false"),
+            ("'abc' <= 'abc'", "true"),
+            ("'b' >= 'abc'", "true"),
+            ("$version >= '1.4.0'", "true"),
+            ("$version < '1.3'", "false"),
+            // Numbers in strings are still compared by value
+            ("'0x10' > '0x09'", "true"),
+            ("'0x9' < '0x10'", "true"),
+            ("'u256:9' < 'u256:10'", "true"),
+            ("'i256:-1' < 'u256:0'", "true"),
+        ] {
+            let root = build_ast_root(pattern).unwrap();
+            assert_eq!(root.evaluate().unwrap().get_value(), expected, "{}", pattern);
+        }
+        assert!(build_ast_root("'0xzz' < '0x10'").unwrap().evaluate().is_err());
+    }
+
+    #[test]
+    fn test_map_equality() {
+        let mut expected = HashMap::new();
+        expected.insert("to".to_string(), VarValues::String("0xabc".to_string()));
+        expected.insert("value".to_string(), VarValues::Number(u256::from(5u64)));
+        set_var!("expected_map", VarValues::Map(expected.clone()));
+
+        // Same keys and values, inserted in a different order
+        let mut same = HashMap::new();
+        same.insert("value".to_string(), VarValues::Number(u256::from(5u64)));
+        same.insert("to".to_string(), VarValues::String("0xabc".to_string()));
+        set_var!("same_map", VarValues::Map(same));
+
+        let mut other_value = expected.clone();
+        other_value.insert("value".to_string(), VarValues::Number(u256::from(6u64)));
+        set_var!("other_value_map", VarValues::Map(other_value));
+
+        let mut other_keys = expected.clone();
+        other_keys.insert("from".to_string(), VarValues::String("0xdef".to_string()));
+        set_var!("other_keys_map", VarValues::Map(other_keys));
+
+        for (pattern, expected) in [
+            ("$expected_map == $same_map", "true"),
+            ("$expected_map != $same_map", "false"),
+            ("$expected_map == $other_value_map", "false"),
+            ("$expected_map != $other_value_map", "true"),
+            ("$expected_map == $other_keys_map", "false"),
+            ("$other_keys_map == $expected_map", "false"),
+            ("$other_keys_map != $expected_map", "true"),
+        ] {
+            let root = build_ast_root(pattern).unwrap();
+            assert_eq!(root.evaluate().unwrap().get_value(), expected, "{}", pattern);
+        }
+
+        let root = build_ast_root("$expected_map > $same_map").unwrap();
+        assert!(matches!(root.evaluate(), Err(ASTError::InvalidBinaryOperator)));
+    }
+
+    #[test]
+    fn test_nested_map() {
+        set_var!("stats", VarValues::Map(HashMap::new()));
+
+        let root = build_ast_root("$stats.insert('a.b', 5)").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+        let root = build_ast_root("$stats.get('a.b') == 5").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+        let root = build_ast_root("$stats.get(a).get(b) == 5").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+
+        // Siblings share the intermediate map
+        let root = build_ast_root("$stats.insert('a.c', 6) && $stats.get('a.c') == 6 && $stats.get('a.b') == 5").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+
+        // A key on the path holding no map
+        let root = build_ast_root("$stats.insert('a.b.c', 7)").unwrap();
+        assert!(root.evaluate().is_err());
+        let root = build_ast_root("$stats.get('a.x')").unwrap();
+        assert!(root.evaluate().is_err());
+
+        let root = build_ast_root("$stats.remove('a.b')").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "5");
+        let root = build_ast_root("$stats.get('a.b')").unwrap();
+        assert!(root.evaluate().is_err());
+        let root = build_ast_root("$stats.get('a.c') == 6").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
     }
 
     #[test]
     fn test_map_variables() {
         set_var!("map", VarValues::Map(HashMap::new()));
 
-        println!("{:?}", get_variable_map_instance());
+        println!("{:?}", *get_variable_map_instance());
 
         set_var!("some_key", "0x123456");
         let root = build_ast_root("$map.insert($some_key, some_value)").unwrap();
@@ -4224,7 +6029,7 @@ mod test_ast {
 
         assert_eq!(ret, "true");
 
-        println!("{:?}", get_variable_map_instance());
+        println!("{:?}", *get_variable_map_instance());
 
         let root = build_ast_root("$map.get(0x123456)").unwrap();
         let val = root.evaluate().unwrap();
@@ -4276,6 +6081,19 @@ mod test_ast {
         println!("{:?}", get_var!("var").unwrap());
     }
 
+    #[test]
+    fn test_let() {
+        let root = build_ast_root("let(let_x, 5) + 1 == 6").unwrap();
+        assert_eq!(root.evaluate().unwrap(), ASTConstant::Bool(true));
+        let root = build_ast_root("$let_x == 5").unwrap();
+        assert_eq!(root.evaluate().unwrap(), ASTConstant::Bool(true));
+
+        // Assign still returns a bool
+        let root = build_ast_root("assign(let_y, 5)").unwrap();
+        assert_eq!(root.evaluate().unwrap(), ASTConstant::Bool(true));
+        assert!(build_ast_root("let(let_z)").is_err());
+    }
+
     #[test]
     fn test_contains_var() {
         set_var!("var", "hello");
@@ -4303,7 +6121,7 @@ mod test_ast {
         let new_val = new_command.evaluate().unwrap();
         let new_ret = new_val.get_value();
         new_command.print("");
-        println!("{:?}", get_variable_map_instance());
+        println!("{:?}", *get_variable_map_instance());
         println!("{}", new_ret);
         assert_eq!(new_ret, "false");
     }
@@ -4390,6 +6208,93 @@ mod test_ast {
         print_variables(&get_variable_map_instance());
     }
 
+    #[test]
+    fn test_rpc_argument_count() {
+        let template = || RPCRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "$method".to_string(),
+            params: vec![],
+            id: "1".to_string(),
+        };
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+        let hash = "0x97116cf6cd4f6412bb47914d6db18da9e16ab2142f543b86e207c24fbd16b23a";
+
+        let mut request = template();
+        replace_args_in_value(&mut request, &args(&["eth_getTransactionByHash", hash])).unwrap();
+        assert_eq!(request.params, vec![json!(hash)]);
+
+        // Too few args
+        let mut request = template();
+        assert!(
+            matches!(
+                replace_args_in_value(&mut request, &args(&["eth_getTransactionByHash"])),
+                Err(ASTError::RpcArgumentCount(_, _, 0))
+            )
+        );
+        // Too many args
+        let mut request = template();
+        assert!(
+            matches!(
+                replace_args_in_value(&mut request, &args(&["eth_getBalance", hash, "latest", "1"])),
+                Err(ASTError::RpcArgumentCount(_, _, 3))
+            )
+        );
+        // Unknown methods are not checked
+        let mut request = template();
+        replace_args_in_value(&mut request, &args(&["custom_method", "1", "2", "3"])).unwrap();
+        assert_eq!(request.params.len(), 3);
+
+        // Placeholders of the template must all be filled
+        let placeholders = || RPCRequest { params: vec![json!("$account"), json!("$blocknumber")], ..template() };
+        let mut request = placeholders();
+        replace_args_in_value(&mut request, &args(&["eth_getBalance", hash, "latest"])).unwrap();
+        assert_eq!(request.params, vec![json!(hash), json!("latest")]);
+        let mut request = placeholders();
+        assert!(
+            matches!(
+                replace_args_in_value(&mut request, &args(&["eth_getBalance", hash])),
+                Err(ASTError::RpcArgumentCount(_, _, 1))
+            )
+        );
+        let mut request = placeholders();
+        assert!(replace_args_in_value(&mut request, &args(&["eth_getBalance", hash, "latest", "1"])).is_err());
+
+        // Malformed params are rejected instead of sent
+        let mut request = template();
+        assert!(
+            matches!(
+                replace_args_in_value(&mut request, &args(&["eth_getLogs", "[{\"address\": "])),
+                Err(ASTError::MalformedRpcParam(_))
+            )
+        );
+    }
+
+    #[test]
+    fn test_rpc_param_types() {
+        let mut request = RPCRequest {
+            jsonrpc: "2.0".to_string(),
+            method: "$method".to_string(),
+            params: vec![],
+            id: "1".to_string(),
+        };
+        let args = vec!["eth_getBlockByNumber".to_string(), "0x10".to_string(), "true".to_string()];
+        replace_args_in_value(&mut request, &args).unwrap();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({"jsonrpc": "2.0", "method": "eth_getBlockByNumber", "params": ["0x10", true], "id": "1"})
+        );
+
+        assert_eq!(parse_string("false").unwrap(), json!(false));
+        assert_eq!(parse_string("16").unwrap(), json!(16));
+        assert_eq!(parse_string("-1").unwrap(), json!(-1));
+        // Too large for a JSON number without rounding
+        assert_eq!(parse_string("1000000000000000000000").unwrap(), json!("1000000000000000000000"));
+        assert_eq!(parse_string("latest").unwrap(), json!("latest"));
+        assert_eq!(parse_string("\"true\"").unwrap(), json!("true"));
+        assert_eq!(parse_string("[\"0x1\", 2]").unwrap(), json!(["0x1", 2]));
+        assert_eq!(parse_string("1.5").unwrap(), json!("1.5"));
+    }
+
     #[test]
     fn test_remove_arr() {
         set_var!("arr", VarValues::Array(vec![]));
@@ -4432,6 +6337,49 @@ mod test_ast {
         }
     }
 
+    #[test]
+    fn test_multi_line_code() {
+        let scope = crate::properties::environment::EventScope::enter();
+        set_var!("transfer_value", (500).as_u256());
+        let code = build_code(
+            "
+            assign(transfer_limit, 1000)
+
+            $transfer_value < $transfer_limit
+        "
+        ).unwrap();
+        assert_eq!(code.len(), 2);
+        let results = code
+            .iter()
+            .map(|r| r.evaluate().unwrap().get_value())
+            .collect::<Vec<String>>();
+        assert_eq!(results, vec!["true", "true"]);
+
+        // The assigned variable is local to the event
+        scope.exit();
+        assert!(get_var!("transfer_limit").is_none());
+    }
+
+    #[test]
+    fn test_comments() {
+        assert!(is_comment_line("# only a comment"));
+        assert!(is_comment_line("   // only a comment"));
+        assert!(!is_comment_line("1 < 2 # trailing"));
+        assert_eq!(strip_comment("1 < 2 // trailing"), "1 < 2");
+        // Comment markers inside quoted strings are kept
+        assert_eq!(strip_comment("$url == 'https://example.com' # url"), "$url == 'https://example.com'");
+        assert_eq!(strip_comment("$memo == \"#1\""), "$memo == \"#1\"");
+
+        let root = build_ast_root("1 < 2 // 2 > 1 would be false").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+        let root = build_ast_root("'a//b' == 'a//b' # quoted").unwrap();
+        assert_eq!(root.evaluate().unwrap().get_value(), "true");
+
+        let code = build_code("# limit of the bridge\n1 < 2 # trailing\n// 2 < 1\n").unwrap();
+        assert_eq!(code.len(), 1);
+        assert_eq!(code[0].evaluate().unwrap().get_value(), "true");
+    }
+
     #[test]
     fn test_insert_remove_map() {
         let mut map: HashMap<String, VarValues> = HashMap::new();
@@ -4462,4 +6410,35 @@ mod test_ast {
 
         print_variables(&get_variable_map_instance());
     }
+
+    #[test]
+    fn test_missing_variable_in_numeric_context() {
+        let _scope = crate::properties::environment::EventScope::enter();
+        let error = build_ast_root("$nonexistent + 1").unwrap().evaluate().unwrap_err();
+        assert!(matches!(error, ASTError::VariableNotFound { ref var } if var == "nonexistent"));
+        assert_eq!(error.to_string(), "the variable \"nonexistent\" does not exist");
+        let error = build_ast_root("$nonexistent > 1").unwrap().evaluate().unwrap_err();
+        assert!(matches!(error, ASTError::VariableNotFound { ref var } if var == "nonexistent"));
+
+        // Outside of numbers the placeholder is still the value of a missing variable
+        assert_eq!(build_ast_root("$nonexistent == NA").unwrap().evaluate().unwrap().get_value(), "true");
+    }
+
+    #[test]
+    fn test_rpc_error() {
+        // Mocked node answering with a JSON-RPC error
+        let (addr, server) = utils::mock_http_server(Some(1), |_| {
+            let response = r#"{"jsonrpc":"2.0","id":"1","error":{"code":-32000,"message":"execution reverted"}}"#;
+            ("200 OK", response.to_string())
+        });
+        std::env::set_var("POLYGON_API", format!("ws://{}", addr));
+
+        let error = build_ast_root("call(polygon, eth_call, 0x, latest)").unwrap().evaluate().unwrap_err();
+        server.join().unwrap();
+        assert!(
+            matches!(error, ASTError::RpcError { code: -32000, ref message } if message == "execution reverted"),
+            "{:?}",
+            error
+        );
+    }
 }
@@ -1,15 +1,20 @@
 use std::collections::{HashMap, HashSet};
 use std::f32::consts::E;
 use std::path::Path;
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 use std::{fs, vec};
 
-use serde_json::Value;
+use ethnum::u256;
+use serde_json::{json, Value};
 
 use super::error;
+use super::error::ASTError;
+use crate::configs::connection::ConnectionConfig;
 
 use crate::properties::ast::*;
 use crate::properties::environment::*;
-use crate::{get_var, set_var, utils, ChainConfig};
+use crate::{get_var, log_error, set_var, utils, ChainConfig};
 
 /// Create a custom struct from a json file
 /// The struct is parsed exactly as the json file
@@ -55,12 +60,12 @@ pub fn execute_custom_function(
                     set_var!(variable_name, r);
                 }
                 Err(e) => {
-                    println!("Failed to evaluate: {} with reason: {:?}", variable_name, e);
+                    log_error!("Failed to evaluate: {} with reason: {:?}", variable_name, e);
                 }
             },
             Err(e) => {
-                println!("Failed to parse: {}", variable_name);
-                eprintln!("Error: {:?}", e);
+                log_error!("Failed to parse: {}", variable_name);
+                log_error!("Error: {:?}", e);
             }
         }
     }
@@ -82,7 +87,7 @@ pub fn execute_custom_function(
     //                         set_var!(variable_name, r);
     //                     }
     //                     Err(e) => {
-    //                         println!("Failed to evaluate: {} with reason: {:?}", variable_name, e);
+    //                         log_error!("Failed to evaluate: {} with reason: {:?}", variable_name, e);
     //                     }
     //                 }
     //             }
@@ -550,3 +555,80 @@ fn test_execute_custom_function() {
 
     println!("{:?}", var);
 }
+
+/// How long a fetched block timestamp is reused before the node is asked again
+const BLOCK_TIMESTAMP_CACHE: Duration = Duration::from_secs(2);
+
+/// Latest block timestamp of each chain and when it was fetched
+fn get_block_timestamp_cache() -> MutexGuard<'static, HashMap<String, (Instant, u256)>> {
+    static CACHE: LazyLock<Mutex<HashMap<String, (Instant, u256)>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+    CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Returns the timestamp of the latest block of the chain in the connections config.
+/// The timestamp is cached briefly, so events in quick succession do not each query the node.
+pub fn block_timestamp(chain: &str) -> Result<u256, ASTError> {
+    cached_block_timestamp(chain, || {
+        let connections = ConnectionConfig::load()
+            .map_err(|e| ASTError::InvalidCustomCall("block_timestamp".to_string(), e.to_string()))?;
+        let connection = connections.connections.iter().find(|c| c.name == chain).ok_or(
+            ASTError::InvalidCustomCall("block_timestamp".to_string(), format!("unknown chain {}", chain))
+        )?;
+        fetch_block_timestamp(&connection.http_url())
+    })
+}
+
+fn cached_block_timestamp<F>(chain: &str, fetch: F) -> Result<u256, ASTError>
+where
+    F: FnOnce() -> Result<u256, ASTError>,
+{
+    if let Some((fetched, timestamp)) = get_block_timestamp_cache().get(chain) {
+        if fetched.elapsed() < BLOCK_TIMESTAMP_CACHE {
+            return Ok(*timestamp);
+        }
+    }
+    // The lock is not held during the request
+    let timestamp = fetch()?;
+    get_block_timestamp_cache().insert(chain.to_string(), (Instant::now(), timestamp));
+    Ok(timestamp)
+}
+
+/// Requests the latest block with `eth_getBlockByNumber` and returns its timestamp
+pub fn fetch_block_timestamp(url: &str) -> Result<u256, ASTError> {
+    let error = |e: String| ASTError::InvalidCustomCall("block_timestamp".to_string(), e);
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "eth_getBlockByNumber",
+        "params": ["latest", false],
+    });
+    let response: Value = utils::get_http_client()
+        .post(url)
+        .json(&request)
+        .send()
+        .and_then(|r| r.json())
+        .map_err(|e| error(e.to_string()))?;
+    let timestamp = find_value_by_path(&response, &["result", "timestamp"])
+        .and_then(|t| t.as_str().map(|t| t.to_string()))
+        .ok_or(error(format!("no block timestamp in {}", response)))?;
+    u256::from_str_hex(&timestamp).map_err(|_| error(format!("invalid block timestamp {}", timestamp)))
+}
+
+#[test]
+fn test_block_timestamp() {
+    // Mocked node answering a single request
+    let (addr, server) = crate::utils::mock_http_server(Some(1), |_| {
+        ("200 OK", r#"{"jsonrpc":"2.0","id":1,"result":{"number":"0x10","timestamp":"0x6553f100"}}"#.to_string())
+    });
+
+    let url = format!("http://{}", addr);
+    let timestamp = cached_block_timestamp("mock_chain", || fetch_block_timestamp(&url)).unwrap();
+    assert_eq!(timestamp, u256::new(0x6553f100));
+    let request = server.join().unwrap().remove(0);
+    assert_eq!(request["method"], "eth_getBlockByNumber");
+
+    // The cached timestamp is used without another request
+    let cached = cached_block_timestamp("mock_chain", || panic!("the node was queried again")).unwrap();
+    assert_eq!(cached, timestamp);
+}
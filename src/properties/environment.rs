@@ -1,11 +1,13 @@
 use std::collections::HashMap;
 
-use std::mem::MaybeUninit;
 use std::str::FromStr;
-use std::sync::Once;
+use std::sync::{ LazyLock, Mutex, MutexGuard, RwLock };
+use std::thread::ThreadId;
 
 use super::ast::{ASTConstant, ASTNode};
+use crate::utils::{ log_enabled, LogLevel, Paint };
 
+use chrono::NaiveDate;
 use ethnum::{i256, u256, AsI256, AsU256};
 use owo_colors::{
     colors::xterm::{LightAnakiwaBlue, LightCaribbeanGreen},
@@ -232,7 +234,9 @@ impl VarValues {
                 return format!("[{}]", s);
             }
             VarValues::Map(value) => {
-                let s = value
+                let mut entries = value.iter().collect::<Vec<_>>();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                let s = entries
                     .iter()
                     .map(|(key, value)| format!("{}:{}", key, value.get_value()))
                     .collect::<Vec<String>>()
@@ -672,12 +676,18 @@ pub fn list_variables(map: &VariableMap) -> Vec<String> {
     map.keys().cloned().collect()
 }
 
+/// Print the variables, only in debug output
 pub fn print_variables(map: &VariableMap) {
-    for (key, value) in map {
+    if !log_enabled(LogLevel::Debug) {
+        return;
+    }
+    let mut variables = map.iter().collect::<Vec<_>>();
+    variables.sort_by(|a, b| a.0.cmp(b.0));
+    for (key, value) in variables {
         println!(
             "{}: {:?}",
-            key.fg::<LightCaribbeanGreen>(),
-            value.fg::<LightAnakiwaBlue>()
+            key.if_color(|s| s.fg::<LightCaribbeanGreen>()),
+            value.if_color(|s| s.fg::<LightAnakiwaBlue>())
         );
     }
 }
@@ -691,17 +701,334 @@ pub fn print_variables(map: &VariableMap) {
 //     }
 // }
 
-pub fn get_variable_map_instance() -> &'static mut VariableMap {
-    static mut MAYBE: MaybeUninit<VariableMap> = MaybeUninit::uninit();
-    static ONLY: std::sync::Once = Once::new();
+/// Value of a variable that does not exist, "NA" unless configured otherwise
+static MISSING_VARIABLE_VALUE: RwLock<Option<String>> = RwLock::new(None);
+
+pub fn set_missing_variable_value(value: &str) {
+    *MISSING_VARIABLE_VALUE.write().unwrap() = Some(value.to_string());
+}
+
+pub fn missing_variable_value() -> String {
+    MISSING_VARIABLE_VALUE.read().unwrap().clone().unwrap_or("NA".to_string())
+}
+
+/// Variables that outlive the event besides `$keystore` and `$map`. A trailing `*` matches a prefix, e.g. `counter_*`
+static PERSISTENT_VARIABLES: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+pub fn set_persistent_variables(patterns: &[String]) {
+    *PERSISTENT_VARIABLES.write().unwrap() = patterns.to_vec();
+}
+
+/// True if the variable is stored globally instead of in the scope of the event
+pub fn is_persistent_variable(key: &str) -> bool {
+    key == "keystore" ||
+        key == "map" ||
+        PERSISTENT_VARIABLES.read()
+            .unwrap()
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => key.starts_with(prefix),
+                None => key == pattern,
+            })
+}
+
+/// Highest block number seen on each chain
+static HIGHEST_BLOCKS: Mutex<Option<HashMap<String, u256>>> = Mutex::new(None);
+
+/// Records the block number of an event of the chain. Returns true if it is lower than the highest block
+/// seen on the chain before, i.e., the chain reorganized or an old event is delivered again
+pub fn record_block_number(chain: &str, block: u256) -> bool {
+    let mut blocks = HIGHEST_BLOCKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let highest = blocks.get_or_insert_with(HashMap::new).entry(chain.to_string()).or_insert(block);
+    if block < *highest {
+        return true;
+    }
+    *highest = block;
+    false
+}
+
+/// Returns the lock guard of the global variable map.
+/// The map is shared by all event threads and protected by a mutex. The guard must only be held
+/// for a single operation, since evaluating a TALON expression while holding it deadlocks.
+/// `set_var!` and `get_var!` release the lock before returning, so they can be nested freely.
+pub fn get_variable_map_instance() -> MutexGuard<'static, VariableMap> {
+    static VARIABLES: LazyLock<Mutex<VariableMap>> = LazyLock::new(|| Mutex::new(VariableMap::new()));
+
+    // A panicking evaluation must not disable the variables of all other events
+    VARIABLES.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn get_event_scopes() -> MutexGuard<'static, HashMap<ThreadId, VariableMap>> {
+    static SCOPES: LazyLock<Mutex<HashMap<ThreadId, VariableMap>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+    SCOPES.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Transient variables of a single event, layered over the global variable map.
+/// While the scope is alive, new variables of the current thread are stored in the scope
+/// and only variables that already exist globally (e.g. `$keystore` and `$map`) are updated in place.
+/// All transient variables are dropped with the scope.
+pub struct EventScope {
+    thread: ThreadId,
+}
+
+impl EventScope {
+    pub fn enter() -> Self {
+        let thread = std::thread::current().id();
+        get_event_scopes().insert(thread, VariableMap::new());
+        EventScope { thread }
+    }
+
+    /// Enters a scope that starts with the given transient variables,
+    /// e.g., in a worker thread that evaluates a property file of the event
+    pub fn enter_with(variables: VariableMap) -> Self {
+        let thread = std::thread::current().id();
+        get_event_scopes().insert(thread, variables);
+        EventScope { thread }
+    }
+
+    /// Transient variables of the scope of the current thread
+    pub fn transient_variables() -> VariableMap {
+        let thread = std::thread::current().id();
+        get_event_scopes().get(&thread).cloned().unwrap_or_default()
+    }
+
+    /// All variables visible in the scope, transient variables shadow the global ones
+    pub fn variables(&self) -> VariableMap {
+        let mut variables = get_variable_map_instance().clone();
+        if let Some(scope) = get_event_scopes().get(&self.thread) {
+            variables.extend(scope.clone());
+        }
+        variables
+    }
+
+    /// Ends the scope and returns its transient variables
+    pub fn exit(self) -> VariableMap {
+        get_event_scopes().remove(&self.thread).unwrap_or_default()
+    }
+}
+
+impl Drop for EventScope {
+    fn drop(&mut self) {
+        get_event_scopes().remove(&self.thread);
+    }
+}
+
+/// Looks up a variable in the event scope of the current thread and then in the global map
+pub fn lookup_variable(key: &str) -> Option<VarValues> {
+    let thread = std::thread::current().id();
+    let scoped = get_event_scopes()
+        .get(&thread)
+        .and_then(|scope| scope.get(key).cloned());
+    scoped.or_else(|| get_variable_map_instance().get(key).cloned())
+}
+
+/// Stores a variable in the event scope of the current thread unless it exists globally or is persistent
+pub fn store_variable(key: String, value: VarValues) {
+    let thread = std::thread::current().id();
+    {
+        let mut map = get_variable_map_instance();
+        if map.contains_key(&key) || is_persistent_variable(&key) {
+            map.insert(key, value);
+            return;
+        }
+    }
+    let mut scopes = get_event_scopes();
+    match scopes.get_mut(&thread) {
+        Some(scope) => {
+            scope.insert(key, value);
+        }
+        None => {
+            drop(scopes);
+            get_variable_map_instance().insert(key, value);
+        }
+    }
+}
+
+/// Increments the counter of the key in the global map variable and returns the new count.
+/// The map variable is created if it does not exist. The counter is read and written under one lock,
+/// so concurrent events never lose a count. Returns `None` if the map or the entry is of another type.
+pub fn increment_counter(map_name: &str, key: &str) -> Option<u256> {
+    let mut variables = get_variable_map_instance();
+    let map = variables
+        .entry(map_name.to_string())
+        .or_insert_with(|| VarValues::Map(HashMap::new()));
+    match map {
+        VarValues::Map(map) => {
+            let count = match map.get(key) {
+                None => u256::ZERO,
+                Some(VarValues::Number(n)) => *n,
+                Some(_) => {
+                    return None;
+                }
+            };
+            let count = count.saturating_add(u256::ONE);
+            map.insert(key.to_string(), VarValues::Number(count));
+            Some(count)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the counter of the key in the global map variable, 0 if the key was never incremented
+pub fn get_counter(map_name: &str, key: &str) -> Option<u256> {
+    match get_variable_map_instance().get(map_name) {
+        None => Some(u256::ZERO),
+        Some(VarValues::Map(map)) =>
+            match map.get(key) {
+                None => Some(u256::ZERO),
+                Some(VarValues::Number(n)) => Some(*n),
+                Some(_) => None,
+            }
+        Some(_) => None,
+    }
+}
+
+/// Adds the value to the total of the key for the given date in the global map variable and returns the new total.
+/// The total is stored together with its date and starts again at the value when the date changes.
+/// Returns `None` if the map or the entry is of another type or the total overflows.
+pub fn accumulate_daily(map_name: &str, key: &str, value: u256, date: NaiveDate) -> Option<u256> {
+    let mut variables = get_variable_map_instance();
+    let map = variables
+        .entry(map_name.to_string())
+        .or_insert_with(|| VarValues::Map(HashMap::new()));
+    let VarValues::Map(map) = map else {
+        return None;
+    };
+    let daily_key = format!("daily:{}", key);
+    let date = date.format("%Y-%m-%d").to_string();
+    let total = match map.get(&daily_key) {
+        None => u256::ZERO,
+        Some(VarValues::Array(entry)) =>
+            match entry.as_slice() {
+                [VarValues::String(d), VarValues::Number(total)] if *d == date => *total,
+                [VarValues::String(_), VarValues::Number(_)] => u256::ZERO,
+                _ => {
+                    return None;
+                }
+            }
+        Some(_) => {
+            return None;
+        }
+    };
+    let total = total.checked_add(value)?;
+    map.insert(daily_key, VarValues::Array(vec![VarValues::String(date), VarValues::Number(total)]));
+    Some(total)
+}
+
+/// Variables whose value is read with `prev()` in the current event of each thread
+fn get_tracked_variables() -> MutexGuard<'static, HashMap<ThreadId, Vec<String>>> {
+    static TRACKED: LazyLock<Mutex<HashMap<ThreadId, Vec<String>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
 
-    unsafe {
-        ONLY.call_once(|| {
-            let var_map = VariableMap::new();
-            // VARMAPS.push(var_map.clone());
-            MAYBE.write(var_map);
-        });
-        MAYBE.assume_init_mut()
+    TRACKED.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The address whose previous values are used, i.e. the payer of the event on its chain
+pub fn event_subject() -> String {
+    let value = |key: &str| {
+        lookup_variable(key)
+            .and_then(|v| v.get_string().map(|s| s.to_lowercase()))
+            .unwrap_or_default()
+    };
+    format!("{}:{}", value("src_chain"), value("payer_address"))
+}
+
+fn previous_key(subject: &str, key: &str) -> String {
+    format!("prev:{}:{}", subject, key)
+}
+
+/// Returns the value the variable had on the previous event of the subject.
+/// Without a previous event the current value is returned.
+/// The variable is tracked, so its current value is stored by `record_previous_values`.
+pub fn previous_value(map_name: &str, subject: &str, key: &str) -> Option<VarValues> {
+    let tracked_key = key.to_string();
+    let thread = std::thread::current().id();
+    {
+        let mut tracked = get_tracked_variables();
+        let keys = tracked.entry(thread).or_default();
+        if !keys.contains(&tracked_key) {
+            keys.push(tracked_key);
+        }
+    }
+    let previous = match get_variable_map_instance().get(map_name) {
+        Some(VarValues::Map(map)) => map.get(&previous_key(subject, key)).cloned(),
+        _ => None,
+    };
+    previous.or_else(|| lookup_variable(key))
+}
+
+/// Removes the variables tracked by `prev()` in the current thread and returns them with their current values,
+/// so a worker thread can hand them over to the thread of the event
+pub fn take_tracked_values() -> Vec<(String, VarValues)> {
+    let thread = std::thread::current().id();
+    let keys = get_tracked_variables().remove(&thread).unwrap_or_default();
+    keys.into_iter()
+        .filter_map(|key| lookup_variable(&key).map(|value| (key, value)))
+        .collect()
+}
+
+/// Stores the values and tracks them in the current thread, see `take_tracked_values`
+pub fn adopt_tracked_values(values: Vec<(String, VarValues)>) {
+    let thread = std::thread::current().id();
+    for (key, value) in values {
+        {
+            let mut tracked = get_tracked_variables();
+            let keys = tracked.entry(thread).or_default();
+            if !keys.contains(&key) {
+                keys.push(key.clone());
+            }
+        }
+        store_variable(key, value);
+    }
+}
+
+/// Stores the current values of the variables tracked by `prev()` in the current thread as the previous values
+/// of the subject. Called after the evaluation of an event.
+pub fn record_previous_values(map_name: &str, subject: &str) {
+    let thread = std::thread::current().id();
+    let keys = get_tracked_variables().remove(&thread).unwrap_or_default();
+    let values = keys
+        .into_iter()
+        .filter_map(|key| lookup_variable(&key).map(|value| (previous_key(subject, &key), value)))
+        .collect::<Vec<(String, VarValues)>>();
+    if values.is_empty() {
+        return;
+    }
+    let mut variables = get_variable_map_instance();
+    let map = variables
+        .entry(map_name.to_string())
+        .or_insert_with(|| VarValues::Map(HashMap::new()));
+    if let VarValues::Map(map) = map {
+        map.extend(values);
+    }
+}
+
+/// Number of RPC requests made by `call()` for each chain in the current event of each thread
+fn get_rpc_calls() -> MutexGuard<'static, HashMap<ThreadId, HashMap<String, u64>>> {
+    static CALLS: LazyLock<Mutex<HashMap<ThreadId, HashMap<String, u64>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+    CALLS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Counts an RPC request to the chain in the current thread
+pub fn record_rpc_call(chain: &str) {
+    let thread = std::thread::current().id();
+    *get_rpc_calls().entry(thread).or_default().entry(chain.to_string()).or_default() += 1;
+}
+
+/// Removes the RPC requests counted in the current thread and returns them for each chain
+pub fn take_rpc_calls() -> HashMap<String, u64> {
+    let thread = std::thread::current().id();
+    get_rpc_calls().remove(&thread).unwrap_or_default()
+}
+
+/// Adds the RPC requests of a worker thread to the current thread, see `take_rpc_calls`
+pub fn adopt_rpc_calls(calls: HashMap<String, u64>) {
+    let thread = std::thread::current().id();
+    let mut rpc_calls = get_rpc_calls();
+    let counts = rpc_calls.entry(thread).or_default();
+    for (chain, count) in calls {
+        *counts.entry(chain).or_default() += count;
     }
 }
 
@@ -718,84 +1045,81 @@ where
 macro_rules! set_var {
     ($key:expr, $value:expr) => {
         let value: VarValues = VarValues::from($value);
-        get_variable_map_instance().insert($key.to_owned(), value);
+        $crate::properties::environment::store_variable($key.to_owned(), value);
     };
 }
 
 #[macro_export]
 macro_rules! get_var {
     ($key:expr) => {
-        match get_variable_map_instance().get($key) {
-            Some(value) => Some(value.clone()),
-            None => None,
+        {
+            let value = $crate::properties::environment::lookup_variable($key);
+            value
         }
     };
 
     (ast_node $key:expr) => {
-        match get_variable_map_instance().get($key) {
-            Some(value) => Some(value.to_ASTNode()),
-            None => None,
+        {
+            let value = $crate::properties::environment::lookup_variable($key);
+            value.map(|value| value.to_ASTNode())
         }
     };
 
     (value $key:expr) => {
-        match get_variable_map_instance().get($key) {
-            Some(value) => Some(value.get_value()),
-            None => None,
+        {
+            let value = $crate::properties::environment::lookup_variable($key);
+            value.map(|value| value.get_value())
         }
     };
 
     ($key:expr) => {
-        match get_variable_map_instance().get($key) {
-            Some(value) => Some(value.clone()),
-            None => None,
+        {
+            let value = $crate::properties::environment::lookup_variable($key);
+            value
         }
     };
 
     (i256 $key:expr) => {
-        match get_variable_map_instance().get($key) {
-            Some(value) => Some(i256::get_value(value.clone()).unwrap()),
-            None => None,
+        {
+            let value = $crate::properties::environment::lookup_variable($key);
+            value.map(|value| i256::get_value(value).unwrap())
         }
     };
 
     (u256 $key:expr) => {
-        match get_variable_map_instance().get($key) {
-            Some(value) => Some(u256::get_value(value.clone()).unwrap()),
-            None => None,
+        {
+            let value = $crate::properties::environment::lookup_variable($key);
+            value.map(|value| u256::get_value(value).unwrap())
         }
     };
 
     (bool $key:expr) => {
-        match get_variable_map_instance().get($key) {
-            Some(value) => Some(bool::get_value(value.clone()).unwrap()),
-            None => None,
+        {
+            let value = $crate::properties::environment::lookup_variable($key);
+            value.map(|value| bool::get_value(value).unwrap())
         }
     };
 
     (String $key:expr) => {
-        match get_variable_map_instance().get($key) {
-            Some(value) => Some(String::get_value(value.clone()).unwrap()),
-            None => None,
+        {
+            let value = $crate::properties::environment::lookup_variable($key);
+            value.map(|value| String::get_value(value).unwrap())
         }
     };
 
     (Array $key:expr) => {
-        match get_variable_map_instance().get($key) {
-            Some(value) => Some(Vec::get_value(value.clone()).unwrap()),
-            None => None,
+        {
+            let value = $crate::properties::environment::lookup_variable($key);
+            value.map(|value| Vec::get_value(value).unwrap())
         }
     };
 }
 
 #[test]
 fn test_static_var_map() {
-    let map = get_variable_map_instance();
-
     set_var!("a", "1");
 
-    let test_map = get_variable_map_instance();
-    let a = get_variable(test_map, "a");
+    let a = get_variable(&get_variable_map_instance(), "a");
 
     assert_eq!(a.unwrap().evaluate().unwrap().get_value().as_str(), "1");
 }
@@ -855,9 +1179,9 @@ fn test_var_u256() {
     let v = "u256:1000001";
     set_var!("num", v);
 
-    set_variable(get_variable_map_instance(), "k", v.to_string());
+    set_variable(&mut get_variable_map_instance(), "k", v.to_string());
 
-    println!("{:?}", get_variable_map_instance());
+    println!("{:?}", *get_variable_map_instance());
 }
 
 #[test]
@@ -865,14 +1189,14 @@ fn test_keystore() {
     set_var!("keystore", [0, 1, 2, 3]);
     let keystore: VarValues = get_var!("keystore").expect("Value not found");
 
-    println!("{:?}", get_variable_map_instance());
+    println!("{:?}", *get_variable_map_instance());
 
     let mut key_vec: Vec<u64> = Vec::get_value(keystore).unwrap();
     key_vec.push(9);
 
     set_var!("keystore", key_vec.clone());
 
-    println!("{:?}", get_variable_map_instance());
+    println!("{:?}", *get_variable_map_instance());
 }
 
 #[test]
@@ -904,8 +1228,160 @@ fn test_clear_map() {
     set_var!("map", VarValues::Map(HashMap::new()));
 
     // Clear all non persistent variables
-    let map = get_variable_map_instance();
-    map.retain(|k, _| *k == "keystore" || *k == "map");
+    let mut map = get_variable_map_instance();
+    map.retain(|k, _| is_persistent_variable(k));
 
     println!("{:?}", map);
 }
+
+#[test]
+fn test_concurrent_evaluation() {
+    use super::ast::build_ast_root;
+
+    let handles: Vec<_> = (0..8)
+        .map(|t| {
+            std::thread::spawn(move || {
+                let name = format!("stress_{}", t);
+                let count = format!("stress_{}_count", t);
+                set_var!(&name, VarValues::Array(vec![]));
+                for i in 0..50 {
+                    // Nested macros must not deadlock
+                    set_var!(&count, get_var!(i256 count.as_str()).unwrap_or_default() + 1);
+
+                    // Evaluating reads and writes the map in between
+                    let root = build_ast_root(format!("${}.push({})", name, i).as_str()).unwrap();
+                    root.evaluate().unwrap();
+                }
+                // Other tests may clear the map concurrently
+                if let Some(count) = get_var!(i256 count.as_str()) {
+                    assert!(count <= 50);
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+#[test]
+fn test_event_scope() {
+    use super::ast::build_ast_root;
+
+    set_var!("scope_keystore", VarValues::Array(vec![]));
+
+    // Event A
+    let scope = EventScope::enter();
+    build_ast_root("assign(scope_transient, 15)").unwrap().evaluate().unwrap();
+    set_var!("scope_my_contract", "0xaa");
+    build_ast_root("$scope_keystore.push(1)").unwrap().evaluate().unwrap();
+    assert_eq!(get_var!(i256 "scope_transient"), Some(15.as_i256()));
+    assert!(scope.variables().contains_key("scope_transient"));
+    assert!(!get_variable_map_instance().contains_key("scope_transient"));
+    let transients = scope.exit();
+    assert!(transients.contains_key("scope_my_contract"));
+
+    // Event B
+    let _scope = EventScope::enter();
+    assert_eq!(get_var!("scope_transient"), None);
+    assert_eq!(get_var!("scope_my_contract"), None);
+    let keystore: Vec<u64> = get_var!(Array "scope_keystore").unwrap();
+    assert_eq!(keystore, vec![1]);
+}
+
+#[test]
+fn test_persistent_variables() {
+    set_persistent_variables(&["counter_*".to_string(), "last_seen".to_string()]);
+    assert!(is_persistent_variable("keystore"));
+    assert!(is_persistent_variable("counter_payer"));
+    assert!(is_persistent_variable("last_seen"));
+    assert!(!is_persistent_variable("last_seen_block"));
+
+    // Event A
+    let scope = EventScope::enter();
+    set_var!("counter_persist_test", 1u64);
+    set_var!("last_seen", "0xaa");
+    set_var!("transient_persist_test", 2u64);
+    scope.exit();
+
+    // Event B
+    let _scope = EventScope::enter();
+    assert_eq!(get_var!(u256 "counter_persist_test"), Some(u256::ONE));
+    assert_eq!(get_var!("last_seen"), Some(VarValues::from("0xaa")));
+    assert_eq!(get_var!("transient_persist_test"), None);
+}
+
+#[test]
+fn test_reorg_detection() {
+    assert!(!record_block_number("reorg_test_chain", 100u64.as_u256()));
+    assert!(!record_block_number("reorg_test_chain", 100u64.as_u256()));
+    assert!(!record_block_number("reorg_test_chain", 105u64.as_u256()));
+    // Lower than the highest block, not only the last one
+    assert!(record_block_number("reorg_test_chain", 103u64.as_u256()));
+    assert!(record_block_number("reorg_test_chain", 104u64.as_u256()));
+    assert!(!record_block_number("reorg_test_chain", 106u64.as_u256()));
+    // Chains are tracked separately
+    assert!(!record_block_number("reorg_other_chain", 1u64.as_u256()));
+}
+
+#[test]
+fn test_counter() {
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            std::thread::spawn(|| {
+                for _ in 0..25 {
+                    increment_counter("test_counters", "0xaa").unwrap();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert_eq!(get_counter("test_counters", "0xaa"), Some(100.as_u256()));
+    assert_eq!(get_counter("test_counters", "0xbb"), Some(u256::ZERO));
+    assert_eq!(increment_counter("test_counters", "0xbb"), Some(u256::ONE));
+
+    set_var!("test_counters_invalid", "no map");
+    assert_eq!(increment_counter("test_counters_invalid", "0xaa"), None);
+}
+
+#[test]
+fn test_daily_volume() {
+    let day = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+    let next_day = day.succ_opt().unwrap();
+
+    assert_eq!(accumulate_daily("test_daily", "ethereum", 100.as_u256(), day), Some(100.as_u256()));
+    assert_eq!(accumulate_daily("test_daily", "ethereum", 50.as_u256(), day), Some(150.as_u256()));
+    // Every key has its own total
+    assert_eq!(accumulate_daily("test_daily", "solana", 7.as_u256(), day), Some(7.as_u256()));
+
+    // The total resets when the date advances
+    assert_eq!(accumulate_daily("test_daily", "ethereum", 30.as_u256(), next_day), Some(30.as_u256()));
+    assert_eq!(accumulate_daily("test_daily", "ethereum", u256::MAX, next_day), None);
+
+    set_var!("test_daily_invalid", "no map");
+    assert_eq!(accumulate_daily("test_daily_invalid", "ethereum", u256::ONE, day), None);
+}
+
+#[test]
+fn test_previous_values() {
+    let events = [("0xaa", 100u64), ("0xbb", 7), ("0xaa", 150)];
+    let mut previous = vec![];
+    for (payer, balance) in events {
+        let _scope = EventScope::enter();
+        set_var!("src_chain", "ethereum");
+        set_var!("payer_address", payer);
+        set_var!("prev_test_balance", u256::from(balance));
+
+        let subject = event_subject();
+        let value = previous_value("test_previous", &subject, "prev_test_balance").unwrap();
+        previous.push(u256::get_value(value).unwrap());
+        record_previous_values("test_previous", &subject);
+    }
+
+    // The first event of a payer sees its own value, the next one the value of event A
+    assert_eq!(previous, vec![100.as_u256(), 7.as_u256(), 100.as_u256()]);
+}
@@ -1,3 +1,4 @@
+use ethnum::i256;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -36,6 +37,8 @@ pub enum ASTError {
     UnknownKey(String),
     #[error("can't find key {0} in object {1}")]
     KeyNotFound(String, String),
+    #[error("index {0} is out of bounds for length {1}")]
+    IndexOutOfBounds(i256, usize),
     #[error("the array is empty.")]
     EmptyArray,
     #[error("unexpected JSON Format.")]
@@ -50,10 +53,26 @@ pub enum ASTError {
     RequireError(String),
     #[error("overflow occured: {0}")]
     OverflowError(String),
+    #[error("the rpc call failed with code {code}: {message}")]
+    RpcError { code: i64, message: String },
     #[error("failed to parse request {0}")]
     RequestReplacementError(String),
+    #[error("the rpc method {0} expects {1} parameters but got {2}")]
+    RpcArgumentCount(String, String, usize),
+    #[error("the rpc parameter {0} is no valid JSON")]
+    MalformedRpcParam(String),
     #[error("missing root of token parsing")]
     MissingRoot,
+    #[error("the expression is nested deeper than {0} levels")]
+    MaxDepthExceeded(usize),
+    #[error("unmatched parentheses: {0}")]
+    UnmatchedParentheses(String),
+    #[error("the separator , is only allowed between the arguments of a function")]
+    MisplacedSeparator,
+    #[error("the operator {0} is missing an operand")]
+    MissingOperand(String),
+    #[error("can't parse token {0}: {1}")]
+    InvalidToken(String, String),
 }
 
 #[derive(Error, Debug)]
@@ -0,0 +1,144 @@
+use std::fs;
+use std::path::{ Path, PathBuf };
+
+use serde_json::Value;
+
+use super::ast::{ build_ast_root, is_comment_line };
+use super::talon::TalonFile;
+use super::PropertyError;
+
+/// Keys every property file needs
+const REQUIRED_KEYS: [&str; 3] = ["event", "pattern", "chain_name"];
+
+/// Checks the property files of `properties_dir` and the `.talon` files of `rules_dir` without running them.
+/// Returns the number of checked files or all errors. A missing rules directory has no rules.
+pub fn lint(properties_dir: &Path, rules_dir: &Path) -> Result<usize, Vec<PropertyError>> {
+    let mut errors = vec![];
+    let mut checked = 0;
+
+    let properties = sorted_files(properties_dir).map_err(|_| vec![PropertyError::PropertyFolderNotFound])?;
+    for path in properties {
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        let definition = match
+            fs
+                ::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|s| serde_json::from_str::<Value>(&s).map_err(|e| e.to_string()))
+        {
+            Ok(definition) => definition,
+            Err(e) => {
+                errors.push(PropertyError::InvalidFile(name, e));
+                continue;
+            }
+        };
+        // Auxiliary files like features.json are no properties
+        if definition.get("event").is_none() && definition.get("pattern").is_none() {
+            continue;
+        }
+        checked += 1;
+
+        // A chain_id replaces the chain_name
+        let missing = REQUIRED_KEYS.iter()
+            .filter(|key| definition.get(**key).is_none())
+            .filter(|key| !(**key == "chain_name" && definition.get("chain_id").is_some()))
+            .copied()
+            .collect::<Vec<&str>>();
+        if !missing.is_empty() {
+            let hint = if missing.contains(&"chain_name") { ", use \"*\" for all chains" } else { "" };
+            errors.push(PropertyError::InvalidFile(name.clone(), format!("missing {}{}", missing.join(", "), hint)));
+        }
+        let Some(lines) = definition.get("pattern").and_then(|p| p.as_array()) else {
+            continue;
+        };
+        for (i, line) in lines.iter().enumerate() {
+            let result = match line.as_str() {
+                Some(line) if is_comment_line(line) => Ok(()),
+                Some(line) => compile(line),
+                None => Err("pattern line is not a string".to_string()),
+            };
+            if let Err(e) = result {
+                errors.push(PropertyError::InvalidPattern(name.clone(), i + 1, e));
+            }
+        }
+    }
+
+    let rules = sorted_files(rules_dir)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|p| p.extension().is_some_and(|ext| ext == "talon"));
+    for path in rules {
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        checked += 1;
+        let file = match TalonFile::read_from_file(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                errors.push(PropertyError::InvalidFile(name, e));
+                continue;
+            }
+        };
+        for (i, rule) in file.rules.lines().enumerate() {
+            if let Err(e) = compile(rule) {
+                errors.push(PropertyError::InvalidPattern(name.clone(), i + 1, e));
+            }
+        }
+    }
+
+    if errors.is_empty() { Ok(checked) } else { Err(errors) }
+}
+
+fn sorted_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths = fs
+        ::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect::<Vec<PathBuf>>();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Builds the AST of the line
+fn compile(line: &str) -> Result<(), String> {
+    build_ast_root(line).map(|_| ()).map_err(|e| e.to_string())
+}
+
+#[test]
+fn test_lint() {
+    let dir = crate::utils::TempDir::new("lint");
+    let (properties, rules) = (dir.join("properties"), dir.join("rules"));
+    fs::create_dir_all(&properties).unwrap();
+    fs::create_dir_all(&rules).unwrap();
+    fs::write(
+        properties.join("a_valid.json"),
+        r##"{"event": "Limit()", "chain_name": "*", "pattern": ["# limit", "1 < 2"]}"##
+    ).unwrap();
+    fs::write(properties.join("b_broken.json"), r#"{"event": "Limit()", "#).unwrap();
+    fs::write(properties.join("c_missing.json"), r#"{"event": "Limit()", "pattern": ["1 < 2"]}"#).unwrap();
+    fs::write(properties.join("c_chain_id.json"), r#"{"event": "Limit()", "chain_id": 10, "pattern": ["1 < 2"]}"#).unwrap();
+    fs::write(
+        properties.join("d_pattern.json"),
+        r#"{"event": "Limit()", "chain_name": "bsc", "pattern": ["1 < 2", "1 <"]}"#
+    ).unwrap();
+    fs::write(properties.join("features.json"), r#"{"features": []}"#).unwrap();
+    fs::write(rules.join("limit.talon"), "event: Limit()\n{\n1 < 2\n}\n").unwrap();
+    fs::write(rules.join("open.talon"), "event: Limit()\n{\n1 < 2\n").unwrap();
+
+    let errors = lint(&properties, &rules).unwrap_err();
+    let errors = errors
+        .iter()
+        .map(|e| e.to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(errors.len(), 4);
+    assert!(errors[0].starts_with("the property file b_broken.json is invalid"));
+    assert_eq!(errors[1], "the property file c_missing.json is invalid: missing chain_name, use \"*\" for all chains");
+    assert!(errors[2].starts_with("the pattern of d_pattern.json in line 2 does not compile"));
+    assert_eq!(errors[3], "the property file open.talon is invalid: open.talon has no closing brace");
+
+    for file in ["b_broken.json", "c_missing.json", "d_pattern.json"] {
+        fs::remove_file(properties.join(file)).unwrap();
+    }
+    fs::remove_file(rules.join("open.talon")).unwrap();
+    assert_eq!(lint(&properties, &rules).unwrap(), 3);
+    assert!(lint(&dir.join("does_not_exist"), &rules).is_err());
+
+}
@@ -1,10 +1,12 @@
-use std::{path::PathBuf, str::FromStr, fs};
+use std::{collections::HashMap, path::PathBuf, str::FromStr, fs};
 
 use ethnum::{u256, uint};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use thiserror::Error;
 
+use environment::VarValues;
+
 
 mod description_parser;
 #[macro_use]
@@ -18,6 +20,10 @@ mod error;
 
 pub mod talon;
 
+pub mod store;
+
+pub mod lint;
+
 /// List of properties
 /// 
 /// LockLogic:
@@ -40,13 +46,28 @@ pub struct Properties{
     pub(crate) occured_event: Option<String>,
     pub(crate) transaction_hash: Option<String>,
     pub(crate) block_number: Option<u256>,
-    //pub(crate) payer: Option<Account>,
+    /// Payer with its balance after and before the block of the transaction
+    pub(crate) payer: Option<Account>,
     pub(crate) payer_address: Option<String>,
     pub(crate) payer_balance_before: Option<u256>,
     pub(crate) payer_balance_after: Option<u256>,
     pub(crate) value: Option<u256>,
     pub(crate) src_chain: Option<String>,
-    pub(crate) event_data: Option<String>
+    /// EIP-155 chain id of the source chain if it is configured
+    pub(crate) src_chain_id: Option<u64>,
+    pub(crate) event_data: Option<String>,
+    pub(crate) program_id: Option<String>,
+    pub(crate) log_index: Option<u64>,
+    /// True if the transaction failed on chain, e.g. a Solana transaction with an error
+    pub(crate) transaction_failed: Option<bool>,
+    /// Mint of the SPL token whose amount is the value of a Solana transaction
+    pub(crate) token_mint: Option<String>,
+    /// Raw topics of the log, topic0 is the event
+    #[serde(default)]
+    pub(crate) topics: Vec<String>,
+    /// Raw data of the log, or the instruction data of a Solana transaction, as 0x-prefixed hex
+    #[serde(default)]
+    pub(crate) data: String,
 }
 
 impl Properties{
@@ -55,18 +76,25 @@ impl Properties{
             occured_event: None,
             transaction_hash: None,
             block_number: None,
-            //payer: None,
+            payer: None,
             payer_address: None,
             value: None,
             payer_balance_before: None,
             payer_balance_after: None,
             src_chain: None,
+            src_chain_id: None,
             event_data: None,
+            program_id: None,
+            log_index: None,
+            transaction_failed: None,
+            token_mint: None,
+            topics: vec![],
+            data: String::new(),
         }
     }
 
     pub fn check(&mut self) -> Result<&'static str, PropertyError>{
-        let mut property_description = PathBuf::from_str("properties").unwrap();
+        let mut property_description = crate::utils::get_directories().base_path("properties");
         property_description = property_description.canonicalize().unwrap();
 
         if !property_description.exists(){
@@ -82,6 +110,27 @@ impl Properties{
         Ok("Transaction can be processed!")
     }
 
+    /// Sets the payer and its balance after the transaction and at the prior block.
+    /// The loose payer fields are kept in sync for the `$payer_address` and `$payer_balance_*` variables.
+    pub fn set_payer(&mut self, address: String, balance: u256, previous_balance: u256) {
+        self.payer_address = Some(address.clone());
+        self.payer_balance_after = Some(balance);
+        self.payer_balance_before = Some(previous_balance);
+        self.payer = Some(Account {
+            address: Some(address),
+            balance: Some(balance),
+            previous_balance: Some(previous_balance),
+        });
+    }
+
+    /// Removes the payer, e.g. if it could not be found
+    pub fn clear_payer(&mut self) {
+        self.payer = None;
+        self.payer_address = None;
+        self.payer_balance_before = None;
+        self.payer_balance_after = None;
+    }
+
     pub fn serialize(&self) -> Value {
         serde_json::json!({
             "payer_address": self.payer_address,
@@ -90,7 +139,13 @@ impl Properties{
             "block_number": format!("u256:{}",self.block_number.unwrap_or(uint!("0"))),
             "occured_event": self.occured_event,
             "src_chain": self.src_chain,
+            "src_chain_id": self.src_chain_id,
             "transaction_hash": self.transaction_hash,
+            "program_id": self.program_id,
+            "transaction_failed": self.transaction_failed.unwrap_or(false),
+            "token_mint": self.token_mint,
+            "topics": self.topics,
+            "data": self.data,
             "value": format!("u256:{}",self.value.unwrap_or(uint!("0")))
         })
     }
@@ -102,13 +157,37 @@ pub enum PropertyError{
     PropertyFolderNotFound,
     #[error("the property is invalid")]
     InvalidProperty,
+    #[error("the property file {0} is invalid: {1}")]
+    InvalidFile(String, String),
+    #[error("the pattern of {0} in line {1} does not compile: {2}")]
+    InvalidPattern(String, usize, String),
+    #[error("property {0} line {1}: {2}")]
+    LineFailed(String, usize, String),
 }
 /// Struct to manage Accounts 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct Account{
     pub address: Option<String>,
-    pub balance: Option<String>,
-    pub previous_balance: Option<String>
+    pub balance: Option<u256>,
+    pub previous_balance: Option<u256>
+}
+
+impl Account {
+    /// The account as the map variable, e.g. `$payer.get(balance) - $payer.get(previous_balance)`.
+    /// Missing fields are omitted.
+    pub fn to_var(&self) -> VarValues {
+        let mut map = HashMap::new();
+        if let Some(address) = &self.address {
+            map.insert("address".to_string(), VarValues::String(address.clone()));
+        }
+        if let Some(balance) = self.balance {
+            map.insert("balance".to_string(), VarValues::Number(balance));
+        }
+        if let Some(previous_balance) = self.previous_balance {
+            map.insert("previous_balance".to_string(), VarValues::Number(previous_balance));
+        }
+        VarValues::Map(map)
+    }
 }
 
 #[test]
@@ -151,4 +230,54 @@ fn test_prp_as_vars() {
 
     let v = u256::get_value(get_var!("payer_balance_before").expect("Value not found")).unwrap();
     assert_eq!(v, 15000.as_u256());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_payer_account() {
+    use crate::properties::ast::build_ast_root;
+    use crate::properties::environment::EventScope;
+    use crate::set_var;
+    use ethnum::AsU256;
+
+    let mut prp = Properties::new();
+    prp.set_payer("0xa58a".to_string(), 1500.as_u256(), 1000.as_u256());
+    assert_eq!(prp.payer_balance_before, Some(1000.as_u256()));
+    assert_eq!(prp.payer.clone().unwrap().balance, Some(1500.as_u256()));
+
+    let _scope = EventScope::enter();
+    set_var!("payer", prp.payer.unwrap().to_var());
+    let delta = |pattern: &str| build_ast_root(pattern).unwrap().evaluate().unwrap().get_value();
+    assert_eq!(delta("$payer.get(balance) - $payer.get(previous_balance)"), "500");
+    assert_eq!(delta("$payer.get(balance) - $payer.get(previous_balance) > 100"), "true");
+    assert_eq!(delta("$payer.get(address)"), "0xa58a");
+}
+
+#[test]
+fn test_raw_log_fields() {
+    use crate::properties::ast::build_ast_root;
+    use crate::properties::environment::EventScope;
+    use crate::set_var;
+
+    let mut prp = Properties::new();
+    prp.topics = vec![
+        "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef".to_string(),
+        "0x000000000000000000000000a58a9d3a5e240b09da3bc0bfc011af3d20d31763".to_string(),
+    ];
+    prp.data = "0x00000000000000000000000000000000000000000000000000000000000003e8".to_string();
+
+    let serialized = prp.serialize();
+    assert_eq!(serialized["topics"], serde_json::json!(prp.topics));
+    assert_eq!(serialized["data"], prp.data);
+    let _scope = EventScope::enter();
+    for key in ["topics", "data"] {
+        set_var!(key, serialized[key].clone());
+    }
+    let eval = |pattern: &str| build_ast_root(pattern).unwrap().evaluate().unwrap().get_value();
+    assert_eq!(eval("$topics.at(1)"), prp.topics[1]);
+    assert_eq!(eval("$data"), prp.data);
+
+    // Events serialized without the fields, e.g. for `brigade check`, have no topics
+    let prp: Properties = serde_json::from_str(r#"{"occured_event": "Lock()", "src_chain": "ethereum"}"#).unwrap();
+    assert!(prp.topics.is_empty());
+    assert_eq!(prp.serialize()["data"], "");
+}
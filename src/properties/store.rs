@@ -0,0 +1,383 @@
+use std::fs;
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, OnceLock, RwLock };
+use std::thread::{ self, JoinHandle };
+
+use serde_json::Value;
+
+use super::ast::{ build_ast_root, is_comment_line, ASTNode };
+use super::talon::TalonFile;
+use super::PropertyError;
+use crate::{ log_debug, log_error, log_info };
+
+/// A compiled line of the pattern
+#[derive(Debug, Clone)]
+pub struct PatternLine {
+    /// Line number in the pattern array, starting at 1
+    pub line: usize,
+    /// The AST or the error if the line does not compile
+    pub root: Result<ASTNode, String>,
+}
+
+/// Format of a property file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PropertyFormat {
+    /// A `.json` file of `properties/`, its pattern lines are joined with `&&`
+    Json,
+    /// A `.talon` file of `rules/`, every rule line is checked on its own
+    Talon,
+}
+
+/// A property file with its compiled pattern lines
+#[derive(Debug, Clone)]
+pub struct PropertyFile {
+    pub name: String,
+    /// The JSON definition, for Talon rules only the `event`, `chain_name` and `chain_id` headers
+    pub definition: Value,
+    /// One AST per pattern line, a line that does not compile evaluates to false. Comment lines are skipped
+    pub patterns: Vec<PatternLine>,
+    pub format: PropertyFormat,
+}
+
+impl PropertyFile {
+    /// Evaluates the pattern lines and returns the result of each line and the errors with their line numbers.
+    /// A line that fails evaluates to false.
+    pub fn evaluate_lines(&self) -> (Vec<String>, Vec<PropertyError>) {
+        let mut results = vec![];
+        let mut errors = vec![];
+        for pattern in self.patterns.iter() {
+            let result = pattern.root.clone().and_then(|root| {
+                root.print("");
+                let v = root.evaluate().map_err(|e| e.to_string())?;
+                log_debug!("Result: {}", root.display_value(&v));
+                Ok(v.get_value())
+            });
+            match result {
+                Ok(result) => results.push(result),
+                Err(e) => {
+                    errors.push(PropertyError::LineFailed(self.name.clone(), pattern.line, e));
+                    results.push("false".to_string());
+                }
+            }
+        }
+        (results, errors)
+    }
+}
+
+/// All property files of the property directory
+#[derive(Debug, Clone, Default)]
+pub struct PropertySet {
+    pub files: Vec<PropertyFile>,
+}
+
+impl PropertySet {
+    /// Reads and compiles all property files of the directory.
+    /// Returns the set and the errors of the files that could not be read or compiled.
+    /// Files without an event, e.g. features.json, are no properties and are skipped.
+    pub fn load(dir: &Path) -> Result<(Self, Vec<PropertyError>), PropertyError> {
+        let entries = fs::read_dir(dir).map_err(|_| PropertyError::PropertyFolderNotFound)?;
+        let mut paths = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file())
+            .collect::<Vec<PathBuf>>();
+        paths.sort();
+
+        let mut files = vec![];
+        let mut errors = vec![];
+        for path in paths {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            let definition = match
+                fs
+                    ::read_to_string(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|s| serde_json::from_str::<Value>(&s).map_err(|e| e.to_string()))
+            {
+                Ok(definition) => definition,
+                Err(e) => {
+                    errors.push(PropertyError::InvalidFile(name, e));
+                    continue;
+                }
+            };
+            if definition.get("event").is_none() {
+                continue;
+            }
+
+            let lines = match definition.get("pattern").and_then(|p| p.as_array()) {
+                Some(lines) => lines.clone(),
+                None => {
+                    errors.push(PropertyError::InvalidFile(name, "missing pattern".to_string()));
+                    continue;
+                }
+            };
+            let mut patterns = vec![];
+            for (i, line) in lines.iter().enumerate() {
+                if line.as_str().is_some_and(is_comment_line) {
+                    continue;
+                }
+                let compiled = match line.as_str() {
+                    Some(line) => build_ast_root(line).map_err(|e| e.to_string()),
+                    None => Err("pattern line is not a string".to_string()),
+                };
+                if let Err(e) = &compiled {
+                    errors.push(PropertyError::InvalidPattern(name.clone(), i + 1, e.clone()));
+                }
+                patterns.push(PatternLine { line: i + 1, root: compiled });
+            }
+            files.push(PropertyFile { name, definition, patterns, format: PropertyFormat::Json });
+        }
+        Ok((Self { files }, errors))
+    }
+
+    /// Reads and compiles the `.talon` files of the directory and adds them after the property files.
+    /// Returns the errors of the files that could not be read or compiled. A missing directory has no rules.
+    pub fn load_rules(&mut self, dir: &Path) -> Vec<PropertyError> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return vec![];
+        };
+        let mut paths = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "talon"))
+            .collect::<Vec<PathBuf>>();
+        paths.sort();
+
+        let mut errors = vec![];
+        for path in paths {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            let file = match TalonFile::read_from_file(&path) {
+                Ok(file) => file,
+                Err(e) => {
+                    errors.push(PropertyError::InvalidFile(name, e));
+                    continue;
+                }
+            };
+            let mut definition = serde_json::json!({ "event": file.event });
+            if let Some(chain_name) = &file.chain_name {
+                definition["chain_name"] = Value::from(chain_name.as_str());
+            }
+            if let Some(chain_id) = file.chain_id {
+                definition["chain_id"] = Value::from(chain_id);
+            }
+            // Every line is a rule
+            let patterns = file.rules
+                .lines()
+                .enumerate()
+                .map(|(i, rule)| {
+                    let compiled = build_ast_root(rule).map_err(|e| e.to_string());
+                    if let Err(e) = &compiled {
+                        errors.push(PropertyError::InvalidPattern(name.clone(), i + 1, e.clone()));
+                    }
+                    PatternLine { line: i + 1, root: compiled }
+                })
+                .collect();
+            self.files.push(PropertyFile { name, definition, patterns, format: PropertyFormat::Talon });
+        }
+        errors
+    }
+}
+
+/// Property files and Talon rules that can be reloaded at runtime.
+/// Events keep the set they started with, so a reload never affects an event in flight.
+pub struct PropertyStore {
+    dir: PathBuf,
+    rules_dir: PathBuf,
+    current: RwLock<Arc<PropertySet>>,
+}
+
+impl PropertyStore {
+    pub fn new(dir: PathBuf, rules_dir: PathBuf) -> Self {
+        Self {
+            dir,
+            rules_dir,
+            current: RwLock::new(Arc::new(PropertySet::default())),
+        }
+    }
+
+    /// Loads the property files and the Talon rules
+    fn load(&self) -> Result<(PropertySet, Vec<PropertyError>), PropertyError> {
+        let (mut set, mut errors) = PropertySet::load(&self.dir)?;
+        errors.extend(set.load_rules(&self.rules_dir));
+        Ok((set, errors))
+    }
+
+    /// The currently active property set
+    pub fn current(&self) -> Arc<PropertySet> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Reloads all property files and rules and swaps them in at once.
+    /// If any file fails to load, the previous set stays active and the errors are returned.
+    /// Returns the number of loaded properties.
+    pub fn reload(&self) -> Result<usize, Vec<PropertyError>> {
+        let (set, errors) = self.load().map_err(|e| vec![e])?;
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let count = set.files.len();
+        *self.current.write().unwrap() = Arc::new(set);
+        Ok(count)
+    }
+
+    /// Loads the property files and rules at startup and returns the errors.
+    /// Files with errors are skipped or use the pattern lines that compiled.
+    pub fn init(&self) -> Vec<PropertyError> {
+        match self.load() {
+            Ok((set, errors)) => {
+                *self.current.write().unwrap() = Arc::new(set);
+                errors
+            }
+            Err(e) => vec![e],
+        }
+    }
+}
+
+/// The store of the `properties` and `rules` directories, loaded on first use
+pub fn get_property_store() -> &'static PropertyStore {
+    static STORE: OnceLock<PropertyStore> = OnceLock::new();
+
+    STORE.get_or_init(|| {
+        let directories = crate::utils::get_directories();
+        let store = PropertyStore::new(directories.base_path("properties"), directories.base_path("rules"));
+        for e in store.init() {
+            log_error!("Error: {}", e);
+        }
+        store
+    })
+}
+
+/// Reloads the properties of the store whenever the process receives SIGHUP, e.g. `kill -HUP <pid>`
+#[cfg(unix)]
+pub fn setup_reload_signal(store: &'static PropertyStore) -> JoinHandle<()> {
+    use tokio::signal::unix::{ signal, SignalKind };
+
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let mut hangup = signal(SignalKind::hangup()).unwrap();
+            while hangup.recv().await.is_some() {
+                match store.reload() {
+                    Ok(count) => log_info!("Reloaded properties: {}", count),
+                    Err(errors) => {
+                        log_error!("Reload failed, keeping the previous properties");
+                        for e in errors {
+                            log_error!("Error: {}", e);
+                        }
+                    }
+                }
+            }
+        })
+    })
+}
+
+#[test]
+fn test_property_store_reload() {
+    let dir = crate::utils::TempDir::new("properties");
+    let file = dir.join("limit.json");
+    fs::write(&file, r##"{"event": "Limit()", "chain_name": "ethereum", "pattern": ["# limit", "1 < 2"]}"##).unwrap();
+    fs::write(dir.join("features.json"), r#"{"features": []}"#).unwrap();
+
+    let store = PropertyStore::new(dir.to_path_buf(), dir.join("rules"));
+    assert_eq!(store.reload().unwrap(), 1);
+    let before = store.current();
+    // Comment lines are skipped
+    assert_eq!(before.files[0].patterns.len(), 1);
+    assert_eq!(before.files[0].patterns[0].line, 2);
+
+    // A broken edit keeps the previous properties
+    fs::write(&file, r#"{"event": "Limit()", "#).unwrap();
+    let errors = store.reload().unwrap_err();
+    assert!(matches!(errors[0], PropertyError::InvalidFile(ref name, _) if name == "limit.json"));
+    assert!(Arc::ptr_eq(&before, &store.current()));
+
+    fs::write(&file, r#"{"event": "Limit()", "chain_name": "ethereum", "pattern": ["2 < 1"]}"#).unwrap();
+    assert_eq!(store.reload().unwrap(), 1);
+    let after = store.current();
+    // The old set is still usable by events that started before the reload
+    let result = |set: &PropertySet| -> String {
+        set.files[0].patterns[0].root.as_ref().unwrap().evaluate().unwrap().get_value()
+    };
+    assert_eq!(result(&before), "true");
+    assert_eq!(result(&after), "false");
+
+    // Talon rules are reloaded together with the property files
+    let rules = dir.join("rules");
+    fs::create_dir_all(&rules).unwrap();
+    fs::write(rules.join("limit.talon"), "event: Limit()\nchain_id: 42161\n{\n1 < 2\n// comment\n2 < 1\n}\n").unwrap();
+    assert_eq!(store.reload().unwrap(), 2);
+    let talon = &store.current().files[1];
+    assert_eq!(talon.format, PropertyFormat::Talon);
+    assert_eq!(talon.definition, serde_json::json!({ "event": "Limit()", "chain_id": 42161 }));
+    assert_eq!(talon.patterns.iter().map(|p| p.line).collect::<Vec<usize>>(), vec![1, 2]);
+
+    fs::write(rules.join("limit.talon"), "event: Limit()\n{\n1 <\n").unwrap();
+    let errors = store.reload().unwrap_err();
+    assert!(matches!(errors[0], PropertyError::InvalidFile(ref name, _) if name == "limit.talon"));
+    assert_eq!(store.current().files.len(), 2);
+
+}
+
+#[test]
+fn test_logged_hex_result() {
+    let dir = crate::utils::TempDir::new("hex_result");
+    let hash = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+    fs::write(
+        dir.join("hash.json"),
+        format!(r#"{{"event": "Transfer()", "chain_name": "ethereum", "pattern": ["{} + 0", "0xff + 1", "255 + 1"]}}"#, hash)
+    ).unwrap();
+
+    let (set, errors) = PropertySet::load(&dir).unwrap();
+    assert!(errors.is_empty());
+    let ((results, errors), logs) = crate::utils::capture_logs(|| set.files[0].evaluate_lines());
+    assert!(errors.is_empty());
+    // The results stay decimal, only the logged form is hex
+    assert_eq!(results[1], "256");
+    let logged: Vec<&str> = logs
+        .iter()
+        .filter(|(level, line)| *level == crate::utils::LogLevel::Debug && line.starts_with("Result: "))
+        .map(|(_, line)| line.as_str())
+        .collect();
+    assert_eq!(logged, vec![format!("Result: {}", hash).as_str(), "Result: 0x100", "Result: 256"]);
+
+}
+
+#[test]
+fn test_failing_line_number() {
+    let dir = crate::utils::TempDir::new("line");
+    fs::write(
+        dir.join("limit.json"),
+        r#"{"event": "Limit()", "chain_name": "ethereum", "pattern": ["1 < 2", "$line_missing + 1 > 2", "2 < 3"]}"#
+    ).unwrap();
+
+    let (set, errors) = PropertySet::load(&dir).unwrap();
+    assert!(errors.is_empty());
+    let _scope = super::environment::EventScope::enter();
+    let (results, errors) = set.files[0].evaluate_lines();
+    assert_eq!(results, vec!["true", "false", "true"]);
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(errors[0], PropertyError::LineFailed(ref name, 2, _) if name == "limit.json"));
+    assert!(errors[0].to_string().starts_with("property limit.json line 2: "));
+
+}
+
+#[test]
+fn test_malformed_pattern_line() {
+    let dir = crate::utils::TempDir::new("malformed");
+    fs::write(
+        dir.join("limit.json"),
+        r#"{"event": "Limit()", "chain_name": "ethereum", "pattern": ["1 < 2", "1 < 2)", "1 < 2, 3", "1 +"]}"#
+    ).unwrap();
+
+    // The malformed lines are reported instead of crashing the load
+    let (set, errors) = PropertySet::load(&dir).unwrap();
+    let lines = errors
+        .iter()
+        .filter_map(|e| match e {
+            PropertyError::InvalidPattern(_, line, _) => Some(*line),
+            _ => None,
+        })
+        .collect::<Vec<usize>>();
+    assert_eq!(lines, vec![2, 3, 4]);
+    assert!(set.files[0].patterns[0].root.is_ok());
+
+}
@@ -1,11 +1,29 @@
-use std::borrow::BorrowMut;
 use std::fs;
 use std::path::Path;
+use crate::log_debug;
+use crate::properties::ast::{ is_comment_line, strip_comment };
 
+/// A rule file in the Talon format.
+///
+/// Example:
+/// ```text
+/// event: Transfer(address indexed from, address indexed to, uint256 value)
+/// chain_name: ethereum
+/// {
+/// event_param(value) < 1000000
+/// $payer_address != 0x000000000000000000000000000000000000dEaD
+/// }
+/// ```
+/// The `chain_name` is optional, without it the rules apply to all chains.
+/// A `chain_id` header matches the id of EVM chains instead of the name, like in property files.
+/// Every line between the braces is a rule and all rules must evaluate to true.
+/// Lines can be commented with `#` or `//`.
 #[derive(Debug, Clone)]
 pub struct TalonFile {
     pub(crate) name: String,
     pub(crate) event: String,
+    pub(crate) chain_name: Option<String>,
+    pub(crate) chain_id: Option<u64>,
     pub(crate) rules: String,
 }
 
@@ -15,6 +33,8 @@ impl TalonFile {
         Self {
             name: name.to_string(),
             event: event.to_string(),
+            chain_name: None,
+            chain_id: None,
             rules: rules.to_string(),
         }
     }
@@ -23,39 +43,89 @@ impl TalonFile {
         Ok(format!("{}{}", self.event, self.rules))
     }
 
-    pub fn read_from_file(path: &Path) -> Result<Self, &'static str> {
-        let f = fs::read_to_string(path).expect("Unable to read file");
+    pub fn read_from_file(path: &Path) -> Result<Self, String> {
+        let f = fs::read_to_string(path).map_err(|e| e.to_string())?;
         let name = path.file_name().unwrap().to_str().unwrap().to_string();
-        let lines = f.split("\n");
-        let mut event = "".to_string();
+        let mut event = None;
+        let mut chain_name = None;
+        let mut chain_id = None;
         let mut is_rule = false;
         let mut code_lines = vec![];
-        for mut l in lines {
-            println!("{}", l);
-            if l.contains("event") {
-                event = l.replace(r#"event":"#, "");
-            }
-            if l.contains("{") {
-                is_rule = true;
+        for l in f.lines() {
+            log_debug!("{}", l);
+            let line = strip_comment(l).trim();
+            if is_rule {
+                if line == "}" {
+                    is_rule = false;
+                    break;
+                }
+                if !is_comment_line(line) {
+                    code_lines.push(line);
+                }
                 continue;
             }
 
-            if l.contains("}") {
-                is_rule = false;
-                break;
-            }
-
-            if is_rule {
-                code_lines.push(l);
+            if line == "{" {
+                is_rule = true;
+            } else if let Some(value) = header_value(line, "event") {
+                event = Some(value);
+            } else if let Some(value) = header_value(line, "chain_name") {
+                chain_name = Some(value);
+            } else if let Some(value) = header_value(line, "chain_id") {
+                let id = value.parse::<u64>().map_err(|_| format!("{} has an invalid chain_id: {}", name, value))?;
+                chain_id = Some(id);
             }
         }
 
-        let rules = code_lines.join("\n");
+        let event = event.ok_or(format!("{} has no event", name))?;
+        if is_rule {
+            return Err(format!("{} has no closing brace", name));
+        }
+        // Every rule is terminated by a newline
+        let rules = code_lines
+            .iter()
+            .map(|l| format!("{}\n", l))
+            .collect::<String>();
 
         Ok(Self {
-            event: event.to_string(),
-            rules: rules,
-            name: name,
+            event,
+            chain_name,
+            chain_id,
+            rules,
+            name,
         })
     }
 }
+
+/// Value of a header line like `event: Transfer(address,address,uint256)`, the key may be quoted
+fn header_value(line: &str, key: &str) -> Option<String> {
+    let rest = line.trim_start_matches('"').strip_prefix(key)?;
+    let value = rest.trim_start_matches('"').trim_start().strip_prefix(':')?;
+    Some(value.trim().trim_matches('"').to_string())
+}
+
+#[test]
+fn test_read_talon_file() {
+    let dir = crate::utils::TempDir::new("rule");
+    let path = dir.join("rule.talon");
+    fs::write(
+        &path,
+        "# Transfers\nevent: Transfer(address,address,uint256)\nchain_name: ethereum\n{\n1 < 2\n\n// Limit\n$value > 10 # wei\n}\n"
+    ).unwrap();
+    let file = TalonFile::read_from_file(&path).unwrap();
+    assert_eq!(file.event, "Transfer(address,address,uint256)");
+    assert_eq!(file.chain_name, Some("ethereum".to_string()));
+    assert_eq!(file.chain_id, None);
+    assert_eq!(file.rules, "1 < 2\n$value > 10\n");
+
+    fs::write(&path, "event: Limit()\nchain_id: 42161\n{\n1 < 2\n}\n").unwrap();
+    assert_eq!(TalonFile::read_from_file(&path).unwrap().chain_id, Some(42161));
+    fs::write(&path, "event: Limit()\nchain_id: arbitrum\n{\n1 < 2\n}\n").unwrap();
+    assert!(TalonFile::read_from_file(&path).is_err());
+
+    fs::write(&path, "\"event\": \"Limit()\"\n{\n1 < 2\n").unwrap();
+    assert!(TalonFile::read_from_file(&path).is_err());
+    fs::write(&path, "{\n1 < 2\n}\n").unwrap();
+    assert!(TalonFile::read_from_file(&path).is_err());
+
+}
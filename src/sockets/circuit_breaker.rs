@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::sync::{ LazyLock, Mutex };
+use std::time::{ Duration, Instant };
+
+use serde::Serialize;
+
+/// State of the circuit breaker of a chain
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Events are enriched and evaluated
+    Closed,
+    /// Too many events failed, the events of the chain get the decision of the breaker policy
+    Open,
+    /// The cooldown passed, the next event is evaluated to test if the chain recovered
+    HalfOpen,
+}
+
+/// When the breaker of a chain opens and for how long
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BreakerConfig {
+    /// Number of consecutive failed events that open the breaker, 0 disables the breakers
+    pub threshold: u32,
+    /// The failures must happen within this window, older failures are forgotten
+    pub window: Duration,
+    /// Time the breaker stays open before an event is evaluated again
+    pub cooldown: Duration,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 0,
+            window: Duration::from_secs(60),
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Circuit breaker of a single chain
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    state: BreakerState,
+    /// Consecutive failures since `first_failure`
+    failures: u32,
+    first_failure: Option<Instant>,
+    opened_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new() -> Self {
+        Self { state: BreakerState::Closed, failures: 0, first_failure: None, opened_at: None }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        self.state
+    }
+
+    /// Returns true if the event should be evaluated. An open breaker is half open after the cooldown.
+    pub fn allows(&mut self, config: &BreakerConfig, now: Instant) -> bool {
+        match self.state {
+            BreakerState::Closed | BreakerState::HalfOpen => true,
+            BreakerState::Open => {
+                let cooled_down = self.opened_at.is_none_or(|opened| now.duration_since(opened) >= config.cooldown);
+                if cooled_down {
+                    self.state = BreakerState::HalfOpen;
+                }
+                cooled_down
+            }
+        }
+    }
+
+    /// Records the outcome of an evaluated event and returns true if the breaker opened because of it.
+    /// A failure while half open opens the breaker again, a success closes it.
+    pub fn record(&mut self, config: &BreakerConfig, failed: bool, now: Instant) -> bool {
+        if !failed {
+            *self = Self::new();
+            return false;
+        }
+        if self.state == BreakerState::HalfOpen {
+            self.state = BreakerState::Open;
+            self.opened_at = Some(now);
+            return false;
+        }
+        let expired = self.first_failure.is_some_and(|first| now.duration_since(first) > config.window);
+        if expired || self.first_failure.is_none() {
+            self.failures = 0;
+            self.first_failure = Some(now);
+        }
+        self.failures += 1;
+        if config.threshold > 0 && self.failures >= config.threshold && self.state == BreakerState::Closed {
+            self.state = BreakerState::Open;
+            self.opened_at = Some(now);
+            return true;
+        }
+        false
+    }
+
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+}
+
+/// The breakers of all chains with their config
+#[derive(Debug, Default)]
+pub struct CircuitBreakers {
+    pub(crate) config: BreakerConfig,
+    pub(crate) chains: HashMap<String, CircuitBreaker>,
+}
+
+impl CircuitBreakers {
+    pub fn configure(&mut self, config: BreakerConfig) {
+        self.config = config;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.threshold > 0
+    }
+
+    /// Returns true if the event of the chain should be enriched and evaluated
+    pub fn allows(&mut self, chain: &str, now: Instant) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+        let config = self.config;
+        self.chains.entry(chain.to_string()).or_insert_with(CircuitBreaker::new).allows(&config, now)
+    }
+
+    /// Records the outcome of an event of the chain and returns true if the breaker of the chain opened
+    pub fn record(&mut self, chain: &str, failed: bool, now: Instant) -> bool {
+        if !self.is_enabled() {
+            return false;
+        }
+        let config = self.config;
+        self.chains.entry(chain.to_string()).or_insert_with(CircuitBreaker::new).record(&config, failed, now)
+    }
+
+    pub fn state(&self, chain: &str) -> BreakerState {
+        self.chains.get(chain).map(|b| b.state()).unwrap_or(BreakerState::Closed)
+    }
+}
+
+pub fn get_circuit_breakers() -> &'static Mutex<CircuitBreakers> {
+    static BREAKERS: LazyLock<Mutex<CircuitBreakers>> = LazyLock::new(|| Mutex::new(CircuitBreakers::default()));
+
+    &BREAKERS
+}
+
+#[test]
+fn test_circuit_breaker() {
+    let config = BreakerConfig {
+        threshold: 3,
+        window: Duration::from_secs(60),
+        cooldown: Duration::from_secs(30),
+    };
+    let mut breakers = CircuitBreakers::default();
+    let start = Instant::now();
+
+    // Disabled breakers let every event pass
+    assert!(!breakers.record("ethereum", true, start));
+    assert!(breakers.allows("ethereum", start));
+    breakers.configure(config);
+
+    // A success resets the consecutive failures
+    assert!(!breakers.record("ethereum", true, start));
+    assert!(!breakers.record("ethereum", true, start));
+    assert!(!breakers.record("ethereum", false, start));
+    assert!(!breakers.record("ethereum", true, start));
+    assert!(!breakers.record("ethereum", true, start));
+    assert_eq!(breakers.state("ethereum"), BreakerState::Closed);
+
+    // The third consecutive failure opens the breaker once
+    assert!(breakers.record("ethereum", true, start + Duration::from_secs(1)));
+    assert_eq!(breakers.state("ethereum"), BreakerState::Open);
+    assert!(!breakers.allows("ethereum", start + Duration::from_secs(10)));
+    // Other chains are not affected
+    assert!(breakers.allows("solana", start + Duration::from_secs(10)));
+    assert_eq!(breakers.state("solana"), BreakerState::Closed);
+
+    // After the cooldown a trial event is evaluated, its failure opens the breaker again
+    assert!(breakers.allows("ethereum", start + Duration::from_secs(31)));
+    assert_eq!(breakers.state("ethereum"), BreakerState::HalfOpen);
+    assert!(!breakers.record("ethereum", true, start + Duration::from_secs(31)));
+    assert_eq!(breakers.state("ethereum"), BreakerState::Open);
+    assert!(!breakers.allows("ethereum", start + Duration::from_secs(40)));
+
+    // A successful trial closes it
+    assert!(breakers.allows("ethereum", start + Duration::from_secs(62)));
+    assert!(!breakers.record("ethereum", false, start + Duration::from_secs(62)));
+    assert_eq!(breakers.state("ethereum"), BreakerState::Closed);
+
+    // Failures outside of the window are not consecutive
+    let later = start + Duration::from_secs(100);
+    breakers.record("ethereum", true, later);
+    breakers.record("ethereum", true, later);
+    assert!(!breakers.record("ethereum", true, later + Duration::from_secs(61)));
+    assert_eq!(breakers.chains["ethereum"].failures(), 1);
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use ethnum::{ u256, AsU256 };
+
+use crate::properties::ast::{ build_ast_root, ASTConstant };
+use crate::properties::Properties;
+use crate::log_debug;
+
+/// Transaction fields that are stored in the evaluation log of an event
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TransactionFields {
+    pub contract_address: String,
+    pub msg_sender: String,
+    pub block_number: String,
+    pub msg_value: u256,
+    /// Block number of the L1 on L2s that report it, e.g. Arbitrum
+    pub l1_block_number: Option<String>,
+}
+
+/// Enriches a received event with the transaction fields of its chain.
+/// Adding a new chain only requires a new implementation and an entry in `get_enricher`.
+pub trait ChainEnricher {
+    /// Returns the transaction fields of the event or `None` if they cannot be retrieved
+    fn enrich(&self, property: &Properties) -> Option<TransactionFields>;
+
+    /// Returns false if the enricher never provides fields, so `None` is no failure of the chain
+    fn provides_fields(&self) -> bool {
+        true
+    }
+}
+
+/// Requests the transaction with `eth_getTransactionByHash` from the node of the chain
+pub struct EthereumEnricher {
+    pub chain_name: String,
+}
+
+impl ChainEnricher for EthereumEnricher {
+    fn enrich(&self, property: &Properties) -> Option<TransactionFields> {
+        let map = get_transaction_by_hash(&self.chain_name, property)?;
+        let value = map.get("value")?.get_value();
+        Some(TransactionFields {
+            contract_address: map.get("to")?.get_value(),
+            msg_sender: map.get("from")?.get_value(),
+            block_number: map.get("blockNumber")?.get_value(),
+            msg_value: u256::from_str_hex(&value).ok()?,
+            l1_block_number: None,
+        })
+    }
+}
+
+/// Requests the transaction of the event with `eth_getTransactionByHash` from the node of the chain
+fn get_transaction_by_hash(chain_name: &str, property: &Properties) -> Option<HashMap<String, ASTConstant>> {
+    let call = format!(
+        "call({}, eth_getTransactionByHash, [{}]).get(result)",
+        chain_name,
+        property.transaction_hash.clone()?
+    );
+    let root = build_ast_root(call.as_str()).ok()?;
+    root.print("");
+    let val = root.evaluate().ok()?;
+    log_debug!("{}", val.get_value());
+    match val {
+        ASTConstant::Map(map) => Some(map),
+        _ => None,
+    }
+}
+
+/// Rollups like Arbitrum and Optimism answer `eth_getTransactionByHash` like Ethereum, but add fields
+/// like `l1BlockNumber` and omit others for their own transaction types, e.g. `to` and `value` of deposits.
+pub struct L2Enricher {
+    pub chain_name: String,
+}
+
+impl ChainEnricher for L2Enricher {
+    fn enrich(&self, property: &Properties) -> Option<TransactionFields> {
+        let map = get_transaction_by_hash(&self.chain_name, property)?;
+        Some(l2_transaction_fields(&map, property))
+    }
+}
+
+/// Maps an L2 transaction object to the fields. Missing fields are taken from the event or are empty.
+pub fn l2_transaction_fields(map: &HashMap<String, ASTConstant>, property: &Properties) -> TransactionFields {
+    // Missing fields are null, which is converted to the string None
+    let field = |key: &str| {
+        map.get(key)
+            .map(|v| v.get_value())
+            .filter(|v| v != "None" && !v.is_empty())
+    };
+    TransactionFields {
+        contract_address: field("to").unwrap_or_default(),
+        msg_sender: field("from").or(property.payer_address.clone()).unwrap_or_default(),
+        block_number: field("blockNumber")
+            .or(property.block_number.map(|b| format!("0x{:x}", b)))
+            .unwrap_or_default(),
+        msg_value: field("value")
+            .and_then(|v| u256::from_str_hex(&v).ok())
+            .unwrap_or(0.as_u256()),
+        l1_block_number: field("l1BlockNumber"),
+    }
+}
+
+/// The Solana socket already decodes the transaction, so the fields are taken from the event
+pub struct SolanaEnricher;
+
+impl ChainEnricher for SolanaEnricher {
+    fn enrich(&self, property: &Properties) -> Option<TransactionFields> {
+        Some(TransactionFields {
+            contract_address: property.program_id.clone().unwrap_or_default(),
+            msg_sender: property.payer_address.clone().unwrap_or_default(),
+            block_number: property.block_number.unwrap_or_default().to_string(),
+            msg_value: property.value.unwrap_or(0.as_u256()),
+            l1_block_number: None,
+        })
+    }
+}
+
+/// Chains without an enricher do not provide any transaction fields
+pub struct GenericEnricher;
+
+impl ChainEnricher for GenericEnricher {
+    fn enrich(&self, _property: &Properties) -> Option<TransactionFields> {
+        None
+    }
+
+    fn provides_fields(&self) -> bool {
+        false
+    }
+}
+
+/// Returns the enricher for the source chain of an event, dispatched like `ChainConfig::connect`.
+/// Chains with a chain_id speak Ethereum JSON-RPC but may be L2s, so they get the tolerant mapping.
+pub fn get_enricher(chain: &str, chain_id: Option<u64>) -> Box<dyn ChainEnricher> {
+    match chain.to_lowercase().as_str() {
+        "solana" => Box::new(SolanaEnricher),
+        _ if chain_id.is_some() => Box::new(L2Enricher { chain_name: chain.to_string() }),
+        "ethereum" => Box::new(EthereumEnricher { chain_name: chain.to_string() }),
+        _ => Box::new(GenericEnricher),
+    }
+}
+
+#[test]
+fn test_mock_enricher() {
+    struct MockEnricher;
+
+    impl ChainEnricher for MockEnricher {
+        fn enrich(&self, property: &Properties) -> Option<TransactionFields> {
+            Some(TransactionFields {
+                contract_address: "0xcontract".to_string(),
+                msg_sender: property.payer_address.clone()?,
+                block_number: "0x10".to_string(),
+                msg_value: 42.as_u256(),
+                l1_block_number: None,
+            })
+        }
+    }
+
+    let mut prp = Properties::new();
+    assert_eq!(MockEnricher.enrich(&prp), None);
+
+    prp.payer_address = Some("0xsender".to_string());
+    let fields = MockEnricher.enrich(&prp).unwrap();
+    assert_eq!(fields.contract_address, "0xcontract");
+    assert_eq!(fields.msg_sender, "0xsender");
+    assert_eq!(fields.block_number, "0x10");
+    assert_eq!(fields.msg_value, 42.as_u256());
+
+    assert_eq!(get_enricher("bsc", None).enrich(&prp), None);
+    assert!(!get_enricher("bsc", None).provides_fields());
+    assert!(get_enricher("bsc", Some(56)).provides_fields());
+
+    prp.program_id = Some("worm2ZoG2kUd4vFXhvjh93UUH596ayRfgQ2MgjNMTth".to_string());
+    prp.block_number = Some(5208470.as_u256());
+    prp.value = Some(100.as_u256());
+    let fields = get_enricher("solana", None).enrich(&prp).unwrap();
+    assert_eq!(fields.contract_address, "worm2ZoG2kUd4vFXhvjh93UUH596ayRfgQ2MgjNMTth");
+    assert_eq!(fields.msg_sender, "0xsender");
+    assert_eq!(fields.block_number, "5208470");
+    assert_eq!(fields.msg_value, 100.as_u256());
+}
+
+#[test]
+fn test_l2_enricher() {
+    use crate::properties::ast::ASTNode;
+
+    // Hand-written in the shape of an Arbitrum retryable redeem (type 0x69), which has no `value`,
+    // a null `to` and the additional fields `l1BlockNumber` and `requestId`
+    let tx: serde_json::Value = serde_json
+        ::from_str(
+            r#"{
+            "blockHash": "0x01",
+            "blockNumber": "0x9a1c3f2",
+            "from": "0x0000000000000000000000000000000000000001",
+            "gas": "0x1e8480",
+            "gasPrice": "0x5f5e100",
+            "hash": "0x02",
+            "input": "0x",
+            "l1BlockNumber": "0x10d4f23",
+            "nonce": "0x0",
+            "requestId": "0x03",
+            "to": null,
+            "transactionIndex": "0x1",
+            "type": "0x69",
+            "chainId": "0xa4b1"
+        }"#
+        )
+        .unwrap();
+    let map = ASTConstant::from(&tx).get_map().clone();
+
+    let mut prp = Properties::new();
+    prp.payer_address = Some("0xpayer".to_string());
+    let fields = l2_transaction_fields(&map, &prp);
+    assert_eq!(fields.contract_address, "");
+    assert_eq!(fields.msg_sender, "0x0000000000000000000000000000000000000001");
+    assert_eq!(fields.block_number, "0x9a1c3f2");
+    assert_eq!(fields.msg_value, 0.as_u256());
+    assert_eq!(fields.l1_block_number, Some("0x10d4f23".to_string()));
+
+    // Fields of the event are used if the transaction lacks them
+    prp.block_number = Some(16.as_u256());
+    let fields = l2_transaction_fields(&HashMap::new(), &prp);
+    assert_eq!(fields.msg_sender, "0xpayer");
+    assert_eq!(fields.block_number, "0x10");
+    assert_eq!(fields.l1_block_number, None);
+}
@@ -8,14 +8,21 @@ use crate::get_variable_map_instance;
 use crate::utils::get_startup_time;
 use crate::VarValues;
 use crate::{message_formats::ethereum_message::*, properties::Properties, set_var, utils};
+use crate::{ log_debug, log_error };
+use super::subscription::Subscription;
+use super::transport::{ drive, MessageHandler };
 
 /// Ethereum Websocket Handler
 pub struct EthereumSocketHandler {
     // State of the Client
     pub(crate) chain_name: String,
+    /// EIP-155 chain id, distinguishes chains that speak the same JSON-RPC, e.g. L2s
+    pub(crate) chain_id: Option<u64>,
     pub(crate) properties: Vec<Properties>,
     pub(crate) event_channel: Sender<Properties>,
     request_url: String,
+    /// Confirmation of the eth_subscribe request
+    pub(crate) subscription: Subscription,
 }
 
 impl EthereumSocketHandler {
@@ -26,25 +33,36 @@ impl EthereumSocketHandler {
     ) -> Self {
         Self {
             chain_name: "ethereum".to_string(),
+            chain_id: None,
             properties,
             event_channel,
             request_url,
+            subscription: Subscription::new("ethereum", "eth_subscribe"),
         }
     }
 
+    /// Sets the chain the events are attributed to
+    pub fn with_chain(mut self, chain_name: String, chain_id: Option<u64>) -> Self {
+        self.subscription = Subscription::new(&chain_name, "eth_subscribe");
+        self.chain_name = chain_name;
+        self.chain_id = chain_id;
+        self
+    }
+
     fn handle_ethereum(&mut self, message: Value) {
         if let Ok(ethereum_msg) = serde_json::from_value::<EthereumEventMessage>(message.clone()) {
             // Add event message params to the variables
-            println!("Message: {}", ethereum_msg);
+            log_debug!("Message: {}", ethereum_msg);
 
             // let event_data = message.clone().get("")
 
             let event_data = ethereum_msg.params.result.data.clone();
             if event_data.len() <= 2 {
-                println!("No Event Data");
+                log_debug!("No Event Data");
             }
             // println!("Variables in Map: {:p}", get_variable_map_instance());
             set_var!("event_data", event_data);
+            set_var!("event_topics", ethereum_msg.params.result.topics.clone());
 
             // A new Event is emitted --> A new Index in the properties list must be added
             self.properties.push(Properties::new());
@@ -58,6 +76,11 @@ impl EthereumSocketHandler {
             let event = ethereum_msg.params.result.topics[0].clone();
             self.properties[index].occured_event = Some(event.clone());
             self.properties[index].src_chain = Some(self.chain_name.clone());
+            self.properties[index].src_chain_id = self.chain_id;
+            self.properties[index].log_index =
+                Some(utils::hex_string_to_u64(ethereum_msg.params.result.log_index.as_str()));
+            self.properties[index].topics = ethereum_msg.params.result.topics.clone();
+            self.properties[index].data = ethereum_msg.params.result.data.clone();
 
             // println!("Ethereum Message: {}", ethereum_msg);
 
@@ -157,7 +180,8 @@ impl EthereumSocketHandler {
                 serde_json::from_str::<EthereumBalanceMessage>(&body).unwrap();
             let balance_before =
                 utils::hex_string_to_u256(balance_before_block.result.clone().as_str());
-            self.properties[index].payer_balance_before = Some(balance_before);
+            let payer_address = self.properties[index].payer_address.clone().unwrap();
+            self.properties[index].set_payer(payer_address, balance_after, balance_before);
 
             // println!("Properties full: {:?}", self.properties[index]);
             self.event_channel
@@ -166,25 +190,33 @@ impl EthereumSocketHandler {
         } else if let Ok(ethereum_confirm_msg) =
             serde_json::from_value::<EthereumConfirmMessage>(message.clone())
         {
-            println!("Ethereum Confirm Message: {}", ethereum_confirm_msg);
+            log_debug!("Ethereum Confirm Message: {}", ethereum_confirm_msg);
         } else {
             if let Ok(pretty_json) = serde_json::to_string_pretty(&message) {
                 // Print the pretty-printed JSON string
-                println!("{}", pretty_json);
+                log_debug!("{}", pretty_json);
             } else {
-                println!("Invalid JSON");
+                log_error!("Invalid JSON");
             }
         }
     }
 }
 
+impl MessageHandler for EthereumSocketHandler {
+    fn handle_message(&mut self, message: Value) {
+        if self.subscription.handle_response(&message) {
+            return;
+        }
+        self.handle_ethereum(message);
+    }
+}
+
 /// Here the WebSocket Handles the basic workflow
 impl Handler for EthereumSocketHandler {
     fn on_message(&mut self, msg: ws::Message) -> ws::Result<()> {
-        // Try to parse the message into json message
         println!("Ethereum Message");
-        let message: Value = serde_json::from_str(&msg.to_string()).unwrap();
-        self.handle_ethereum(message);
+        // Messages that are no JSON are logged and skipped
+        drive(self, &mut Some(msg));
         Ok(())
     }
 
@@ -194,6 +226,11 @@ impl Handler for EthereumSocketHandler {
         let msg = format!("{} opened", self.chain_name);
         Ok(())
     }
+
+    fn on_timeout(&mut self, event: ws::util::Token) -> ws::Result<()> {
+        self.subscription.on_timeout(event);
+        Ok(())
+    }
 }
 
 #[test]
@@ -203,3 +240,73 @@ fn get_topic_ids() {
     let event_header = "SendEthToSol(address,string,uint256)";
     utils::get_ethereum_topic_ids(event_header);
 }
+
+#[test]
+fn test_transfer_notification() {
+    use std::sync::mpsc;
+
+    use ethnum::AsU256;
+    use serde_json::json;
+
+    use super::transport::MockTransport;
+    use crate::properties::environment::EventScope;
+
+    // Mocked node answering eth_getTransactionByHash and eth_getBalance
+    let (addr, server) = utils::mock_http_server(Some(3), |request| {
+        let result = match request["method"].as_str().unwrap() {
+            "eth_getTransactionByHash" => json!({ "from": "0xpayer", "value": "0x64", "blockNumber": "0x10" }),
+            // Balance at the block of the transaction and at the prior block
+            _ if request["params"][1] == "0x10" => json!("0x3e8"),
+            _ => json!("0x7d0"),
+        };
+        ("200 OK", json!({ "jsonrpc": "2.0", "id": 0, "result": result }).to_string())
+    });
+
+    let transfer = utils::get_ethereum_topic_ids("Transfer(address,address,uint256)");
+    let notification = json!({
+        "jsonrpc": "2.0",
+        "method": "eth_subscription",
+        "params": {
+            "subscription": "0x1",
+            "result": {
+                "address": "0x63a993502e74828ddba5710327afc6dc78d661b2",
+                "blockHash": "0xb1",
+                "blockNumber": "0x10",
+                "data": "0x0000000000000000000000000000000000000000000000000000000000000064",
+                "logIndex": "0x2",
+                "removed": false,
+                "topics": [transfer],
+                "transactionHash": "0xabc",
+                "transactionIndex": "0x0"
+            }
+        }
+    });
+    // The subscription confirmation produces no event
+    let confirmation = json!({ "jsonrpc": "2.0", "id": 1, "result": 1 });
+
+    let _scope = EventScope::enter();
+    let (tx, rx) = mpsc::channel();
+    let mut handler = EthereumSocketHandler::new(vec![], tx, format!("http://{}", addr));
+    drive(&mut handler, &mut MockTransport::new(vec![confirmation, notification]));
+    drop(handler);
+
+    let properties: Vec<Properties> = rx.iter().collect();
+    assert_eq!(properties.len(), 1);
+    let property = &properties[0];
+    assert_eq!(property.occured_event, Some(transfer));
+    assert_eq!(property.transaction_hash.as_deref(), Some("0xabc"));
+    assert_eq!(property.block_number, Some(16.as_u256()));
+    assert_eq!(property.log_index, Some(2));
+    assert_eq!(property.src_chain.as_deref(), Some("ethereum"));
+    assert_eq!(property.value, Some(100.as_u256()));
+    assert_eq!(property.payer_address.as_deref(), Some("0xpayer"));
+    assert_eq!(property.payer_balance_after, Some(1000.as_u256()));
+    assert_eq!(property.payer_balance_before, Some(2000.as_u256()));
+    let methods = server
+        .join()
+        .unwrap()
+        .iter()
+        .map(|request| request["method"].as_str().unwrap().to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(methods, vec!["eth_getTransactionByHash", "eth_getBalance", "eth_getBalance"]);
+}
@@ -1,39 +1,114 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Mutex, Condvar};
 
 use ws::{Handler, Sender};
 
+/// Default number of events the queue holds before the policy applies
+pub const DEFAULT_QUEUE_CAPACITY: usize = 10000;
+
+/// What happens when an item is pushed onto a full queue
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum QueuePolicy {
+    /// Wait until the consumer took an item
+    Block,
+    /// Drop the oldest item and count it as dropped
+    DropOldest,
+}
+
+/// Bounded queue between the event loop and the TCP broadcaster
 pub struct BlockingQueue<T>{
-    data: Mutex<Vec<T>>,
-    condvar: Condvar,
+    data: Mutex<VecDeque<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    policy: QueuePolicy,
+    dropped: AtomicU64,
 }
 
 impl<T> BlockingQueue<T>{
     pub fn new() -> Self {
-        Self { data: Mutex::new(Vec::new()), condvar: Condvar::new() }
+        Self::with_capacity(DEFAULT_QUEUE_CAPACITY, QueuePolicy::Block)
+    }
+
+    pub fn with_capacity(capacity: usize, policy: QueuePolicy) -> Self {
+        Self {
+            data: Mutex::new(VecDeque::new()),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity: capacity.max(1),
+            policy,
+            dropped: AtomicU64::new(0),
+        }
     }
 
     pub fn push(&self, item: T){
         let mut data = self.data.lock().unwrap();
-        data.push(item);
-        self.condvar.notify_one();
+        while data.len() >= self.capacity {
+            match self.policy {
+                QueuePolicy::Block => {
+                    data = self.not_full.wait(data).unwrap();
+                }
+                QueuePolicy::DropOldest => {
+                    data.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        data.push_back(item);
+        self.not_empty.notify_one();
     }
 
     pub fn pop(&self) -> T{
         let mut data = self.data.lock().unwrap();
         while data.is_empty() {
-            data = self.condvar.wait(data).unwrap();
+            data = self.not_empty.wait(data).unwrap();
+        }
+        let item = data.pop_front().unwrap();
+        self.not_full.notify_all();
+        item
+    }
+
+    /// Waits until the consumer took all items
+    pub fn wait_until_empty(&self) {
+        let mut data = self.data.lock().unwrap();
+        while !data.is_empty() {
+            data = self.not_full.wait(data).unwrap();
         }
-        data.remove(0)
+    }
+
+    /// Number of queued items
+    pub fn len(&self) -> usize {
+        self.data.lock().unwrap().len()
+    }
+
+    /// Number of items dropped with `QueuePolicy::DropOldest`
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> fmt::Debug for BlockingQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockingQueue")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity)
+            .field("policy", &self.policy)
+            .field("dropped", &self.dropped())
+            .finish()
     }
 }
 
 
 /// An Enum handling whether a transaction is allowed or not 
 /// In case of deniance a reason should be given
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum Allowance{
     Allow,
-    Deny(Vec<String>)
+    Deny(Vec<String>),
+    /// No property or rule applies to the event, so it is not monitored. Brigade lets it pass
+    NoMatch,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -48,6 +123,58 @@ pub struct Event {
     pub transaction_hash: String
 }
 
+/// Serialization of the events sent on the TCP port
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum EventFormat {
+    /// Compact JSON without a delimiter between the events
+    #[default]
+    Compact,
+    /// Compact JSON with a newline after each event, for clients that read line by line
+    Ndjson,
+    /// Indented JSON with a newline after each event, for debugging
+    Pretty,
+}
+
+impl EventFormat {
+    pub fn encode(&self, event: &Event) -> Vec<u8> {
+        let mut bytes = match self {
+            EventFormat::Pretty => serde_json::to_vec_pretty(event).unwrap(),
+            _ => serde_json::to_vec(event).unwrap(),
+        };
+        if *self != EventFormat::Compact {
+            bytes.push(b'\n');
+        }
+        bytes
+    }
+}
+
+/// Ring buffer of the last events which are sent to newly connected clients
+pub struct EventBacklog {
+    events: VecDeque<Event>,
+    capacity: usize,
+}
+
+impl EventBacklog {
+    pub fn new(capacity: usize) -> Self {
+        Self { events: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    pub fn push(&mut self, event: Event) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    /// Events from the oldest to the newest
+    pub fn events(&self) -> impl Iterator<Item = &Event> {
+        self.events.iter()
+    }
+}
+
 pub struct HubSocket {
     pub(crate) num_clients: u64,
     pub(crate) sender: Sender,
@@ -65,3 +192,64 @@ impl Handler for HubSocket {
         println!("Client disconnected: {:?}", code);
     }
 }
+
+#[test]
+fn test_queue_blocks_at_capacity() {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+    use std::time::Duration;
+
+    let queue = Arc::new(BlockingQueue::with_capacity(1, QueuePolicy::Block));
+    queue.push(1);
+
+    let pushed = Arc::new(AtomicBool::new(false));
+    let producer = {
+        let queue = queue.clone();
+        let pushed = pushed.clone();
+        thread::spawn(move || {
+            queue.push(2);
+            pushed.store(true, Ordering::SeqCst);
+        })
+    };
+    // The producer waits until the consumer takes an item
+    thread::sleep(Duration::from_millis(100));
+    assert!(!pushed.load(Ordering::SeqCst));
+    assert_eq!(queue.len(), 1);
+
+    assert_eq!(queue.pop(), 1);
+    producer.join().unwrap();
+    assert!(pushed.load(Ordering::SeqCst));
+    assert_eq!(queue.pop(), 2);
+    assert_eq!(queue.dropped(), 0);
+}
+
+#[test]
+fn test_queue_drops_oldest_at_capacity() {
+    let queue = BlockingQueue::with_capacity(2, QueuePolicy::DropOldest);
+    for i in 1..=5 {
+        queue.push(i);
+    }
+    assert_eq!(queue.len(), 2);
+    assert_eq!(queue.dropped(), 3);
+    assert_eq!(queue.pop(), 4);
+    assert_eq!(queue.pop(), 5);
+}
+
+#[test]
+fn test_event_format() {
+    let event = Event {
+        result: Allowance::Allow,
+        checked: vec![],
+        chain: "ethereum".to_string(),
+        transaction_hash: "0x1".to_string(),
+    };
+    let compact = EventFormat::Compact.encode(&event);
+    assert_eq!(compact, serde_json::to_vec(&event).unwrap());
+    let ndjson = String::from_utf8(EventFormat::Ndjson.encode(&event)).unwrap();
+    assert_eq!(ndjson.trim_end(), String::from_utf8(compact).unwrap());
+    assert_eq!(ndjson.matches('\n').count(), 1);
+    let pretty = String::from_utf8(EventFormat::Pretty.encode(&event)).unwrap();
+    assert!(pretty.ends_with("}\n"));
+    assert_eq!(serde_json::from_str::<Event>(&pretty).unwrap().transaction_hash, "0x1");
+}
@@ -0,0 +1,206 @@
+use std::collections::{ HashMap, HashSet };
+use std::io::{ BufRead, BufReader, Write };
+use std::net::{ TcpListener, TcpStream };
+use std::sync::{ Arc, LazyLock, Mutex };
+use std::thread::{ self, JoinHandle };
+
+use chrono::{ DateTime, Local };
+use serde_json::{ json, Value };
+
+use crate::configs::connection::{ get_established_connections, ConnectionList };
+use crate::sockets::circuit_breaker::BreakerState;
+use crate::sockets::event_socket::{ BlockingQueue, Event };
+use crate::log_error;
+
+/// Connection status of the configured chains
+#[derive(Debug, Default)]
+pub struct HealthState {
+    /// Chains that were configured at startup
+    pub(crate) chains: Vec<String>,
+    /// Time of the last event received for each chain
+    pub(crate) last_event: HashMap<String, DateTime<Local>>,
+    /// Chains whose connection failed or terminated
+    pub(crate) dead: HashSet<String>,
+    /// Queue of the events that are broadcasted on the TCP port
+    pub(crate) event_queue: Option<Arc<BlockingQueue<Event>>>,
+    /// Number of RPC requests made by `call()` for each chain since the start
+    pub(crate) rpc_calls: HashMap<String, u64>,
+    /// State of the circuit breaker of each chain that had an event
+    pub(crate) breakers: HashMap<String, BreakerState>,
+}
+
+impl HealthState {
+    pub fn register_chain(&mut self, chain: &str) {
+        if !self.chains.iter().any(|c| c == chain) {
+            self.chains.push(chain.to_string());
+        }
+    }
+
+    pub fn record_event(&mut self, chain: &str) {
+        self.last_event.insert(chain.to_string(), Local::now());
+    }
+
+    pub fn mark_dead(&mut self, chain: &str) {
+        self.dead.insert(chain.to_string());
+    }
+
+    pub fn record_rpc_calls(&mut self, calls: &HashMap<String, u64>) {
+        for (chain, count) in calls {
+            *self.rpc_calls.entry(chain.clone()).or_default() += count;
+        }
+    }
+
+    pub fn set_breaker_state(&mut self, chain: &str, state: BreakerState) {
+        self.breakers.insert(chain.to_string(), state);
+    }
+
+    pub fn set_event_queue(&mut self, queue: Arc<BlockingQueue<Event>>) {
+        self.event_queue = Some(queue);
+    }
+
+    /// Returns the HTTP status and the report for each chain.
+    /// The status is 200 if all configured chains are connected and 503 otherwise.
+    pub fn report(&self, connections: &ConnectionList) -> (u16, Value) {
+        let mut healthy = true;
+        let mut chains = serde_json::Map::new();
+        for chain in self.chains.iter() {
+            let connected = connections.get(chain).is_some();
+            let dead = self.dead.contains(chain);
+            healthy &= connected && !dead;
+            chains.insert(
+                chain.clone(),
+                json!({
+                    "connected": connected,
+                    "last_event": self.last_event.get(chain).map(|t| t.to_rfc3339()),
+                    "dead": dead,
+                })
+            );
+        }
+        let status = if healthy { 200 } else { 503 };
+        let mut report = json!({
+            "healthy": healthy,
+            "chains": chains,
+            "rpc_calls": self.rpc_calls,
+            "circuit_breakers": self.breakers,
+        });
+        if let Some(queue) = &self.event_queue {
+            report["event_queue"] = json!({ "depth": queue.len(), "dropped": queue.dropped() });
+        }
+        (status, report)
+    }
+}
+
+pub fn get_health_state() -> &'static Mutex<HealthState> {
+    static HEALTH: LazyLock<Mutex<HealthState>> = LazyLock::new(|| Mutex::new(HealthState::default()));
+
+    &HEALTH
+}
+
+/// Builds the raw HTTP response
+fn http_response(status: u16, body: &Value) -> String {
+    let reason = match status {
+        200 => "OK",
+        404 => "Not Found",
+        _ => "Service Unavailable",
+    };
+    let body = body.to_string();
+    format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )
+}
+
+fn handle_request(mut stream: TcpStream) {
+    let mut request_line = String::new();
+    if BufReader::new(&stream).read_line(&mut request_line).is_err() {
+        return;
+    }
+    let response = if request_line.starts_with("GET /health ") {
+        let (status, body) = get_health_state()
+            .lock()
+            .unwrap()
+            .report(get_established_connections());
+        http_response(status, &body)
+    } else {
+        http_response(404, &json!({ "error": "not found" }))
+    };
+    if let Err(e) = stream.write_all(response.as_bytes()) {
+        log_error!("Error: {}", e);
+    }
+}
+
+/// Serves `GET /health` at the address
+pub fn setup_health_endpoint(addr: String) -> Result<JoinHandle<()>, String> {
+    let listener = TcpListener::bind(&addr).map_err(|e| format!("Failed to bind {}: {}", addr, e))?;
+    Ok(
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => handle_request(stream),
+                    Err(e) => log_error!("Error: {}", e),
+                }
+            }
+        })
+    )
+}
+
+#[test]
+fn test_health_report() {
+    let mut state = HealthState::default();
+    state.register_chain("ethereum");
+    state.register_chain("solana");
+
+    // Mock connection without a remote endpoint
+    let socket = ws::Builder::new().build(|_| |_| Ok(())).unwrap();
+    let mut connections = ConnectionList::new();
+    connections.insert("ethereum".to_string(), socket.broadcaster());
+
+    // Solana is absent
+    let (status, report) = state.report(&connections);
+    assert_eq!(status, 503);
+    assert_eq!(report["chains"]["ethereum"]["connected"], true);
+    assert_eq!(report["chains"]["solana"]["connected"], false);
+
+    // All chains are present
+    connections.insert("solana".to_string(), socket.broadcaster());
+    state.record_event("solana");
+    let (status, report) = state.report(&connections);
+    assert_eq!(status, 200);
+    assert_eq!(report["healthy"], true);
+    assert!(report["chains"]["solana"]["last_event"].is_string());
+    assert!(report["chains"]["ethereum"]["last_event"].is_null());
+    assert!(report.get("event_queue").is_none());
+    assert_eq!(report["rpc_calls"], json!({}));
+
+    state.record_rpc_calls(&HashMap::from([("ethereum".to_string(), 2)]));
+    state.record_rpc_calls(&HashMap::from([("ethereum".to_string(), 1)]));
+    let (_, report) = state.report(&connections);
+    assert_eq!(report["rpc_calls"]["ethereum"], 3);
+    assert_eq!(report["circuit_breakers"], json!({}));
+    state.set_breaker_state("ethereum", BreakerState::HalfOpen);
+    let (_, report) = state.report(&connections);
+    assert_eq!(report["circuit_breakers"]["ethereum"], "half_open");
+    assert!(http_response(status, &report).starts_with("HTTP/1.1 200 OK\r\n"));
+
+    // A died chain is unhealthy although the entry still exists
+    state.mark_dead("ethereum");
+    let (status, _) = state.report(&connections);
+    assert_eq!(status, 503);
+    assert!(http_response(status, &report).starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+
+    // The depth of the event queue is reported once it is registered
+    let queue = Arc::new(BlockingQueue::new());
+    queue.push(Event {
+        result: crate::sockets::event_socket::Allowance::Allow,
+        checked: vec![],
+        chain: "ethereum".to_string(),
+        transaction_hash: "0x1".to_string(),
+    });
+    state.set_event_queue(queue);
+    let (_, report) = state.report(&connections);
+    assert_eq!(report["event_queue"]["depth"], 1);
+    assert_eq!(report["event_queue"]["dropped"], 0);
+}
@@ -1,8 +1,14 @@
+pub mod circuit_breaker;
+pub mod enricher;
 pub mod ethereum_socket;
 pub mod event_socket;
+pub mod health;
 pub mod socket;
 pub mod solana_socket;
+pub mod subscription;
 pub mod replay_ethereum_socket;
+pub mod transport;
+pub mod webhook;
 
 pub enum SocketTypes {
     Ethereum(ethereum_socket::EthereumSocketHandler),
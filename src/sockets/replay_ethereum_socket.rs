@@ -4,19 +4,61 @@ use reqwest::blocking::Client;
 use serde::{ Deserialize, Serialize };
 use serde_json::{ Value, json };
 
+use crate::configs::ethereum_config::{ topic_id, topic_position };
 use crate::get_variable_map_instance;
 use crate::message_formats::solana_message::{ Res, Val };
-use crate::properties::ast::build_ast_root;
-use crate::VarValues;
-use crate::{ message_formats::ethereum_message::*, properties::Properties, set_var, utils };
+use crate::{ message_formats::ethereum_message::*, properties::{ Account, Properties }, set_var, utils };
 
 use anyhow::Result;
+use rand::Rng;
+use std::collections::{ BTreeMap, HashSet };
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{ Path, PathBuf };
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{ Duration, Instant };
+use crate::{ log_debug, log_error, log_info };
+
+/// Initial waiting time after a provider answered with 429 Too Many Requests
+const RATE_LIMIT_BACKOFF_MS: u64 = 500;
+/// Maximum number of retries of a rate limited request
+const RATE_LIMIT_RETRIES: u32 = 5;
+/// Default file of the replay checkpoints
+const CHECKPOINT_FILE: &str = "replay_checkpoint.json";
+/// Default number of attempts to fetch a page before it is skipped
+const PAGE_ATTEMPTS: u32 = 5;
+/// Initial waiting time before a failed page is fetched again
+const PAGE_BACKOFF_MS: u64 = 1000;
+
+/// The provider rejected the block range of a `get_logs` request, e.g. "query returned more than 10000 results"
+#[derive(thiserror::Error, Debug)]
+#[error("block range {from}-{to} is too large: {message}")]
+pub struct RangeTooLarge {
+    pub from: String,
+    pub to: String,
+    pub message: String,
+}
+
+/// Returns true if the JSON-RPC error of `eth_getLogs` rejects the size of the block range or of the result
+fn is_range_too_large(error: &Value) -> bool {
+    let message = error
+        .get("message")
+        .and_then(|m| m.as_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    ["more than", "block range", "range is too large", "range too large", "exceed", "too many"]
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
 
 pub struct ReplayEthereumSocketHandler {
     // State of the Client
     pub(crate) chain_name: String,
     pub(crate) config: Chain,
     pub(crate) rpc_url: String,
+    pub(crate) rate_limiter: RateLimiter,
 }
 
 impl ReplayEthereumSocketHandler {
@@ -47,7 +89,7 @@ impl ReplayEthereumSocketHandler {
             "params": [
                 {
                 "address": self.config.address,
-                "topics": self.config.topics
+                "topics": self.config.topic_filter()
                 }
             ],
             "id": 1
@@ -57,12 +99,12 @@ impl ReplayEthereumSocketHandler {
         match res {
             Ok(res) => {
                 let log_res: LogResponse = serde_json::from_str(&res.text().unwrap()).unwrap();
-                println!("Logs: {:#?}", log_res);
+                log_debug!("Logs: {:#?}", log_res);
                 let hashes: Vec<(String, String)> = log_res.result
                     .iter()
                     .map(|r| (r.transaction_hash.clone(), r.data.clone()))
                     .collect();
-                println!("Hashes: {:#?}", hashes);
+                log_debug!("Hashes: {:#?}", hashes);
                 Ok(self.find_corresponding_transaction(hashes))
             }
             Err(err) => { Err(anyhow!("Failed to send rpc {}", err)) }
@@ -80,28 +122,38 @@ impl ReplayEthereumSocketHandler {
                 "fromBlock": from_block,
                 "toBlock": to_block,
                 "address": self.config.address,
-                "topics": self.config.topics
+                "topics": self.config.topic_filter()
                 }
             ],
             "id": 1
             });
-        println!("Get Logs: {}", serde_json::to_string_pretty(&get_logs).unwrap());
-        let res = client.post(self.rpc_url.clone()).header("Content-Type", "application/json").json(&get_logs).send();
+        log_debug!("Get Logs: {}", serde_json::to_string_pretty(&get_logs).unwrap());
+        let res = self.send_rate_limited(&client, &get_logs);
         match res {
             Ok(res) => {
                 let text = &res.text();
                 match text {
                     Ok(text) => {
-                        println!("Text: {}", text);
+                        log_debug!("Text: {}", text);
+                        let error = serde_json::from_str::<Value>(text).ok().and_then(|v| v.get("error").cloned());
+                        if let Some(error) = error.filter(is_range_too_large) {
+                            return Err(
+                                RangeTooLarge {
+                                    from: from_block,
+                                    to: to_block,
+                                    message: error["message"].as_str().unwrap_or_default().to_string(),
+                                }.into()
+                            );
+                        }
                         let log_res: std::result::Result<LogResponse, serde_json::Error> = serde_json::from_str(text);
                         match log_res {
                             Ok(log_res) => {
-                                println!("Logs: {:#?}", log_res);
+                                log_debug!("Logs: {:#?}", log_res);
                                 let hashes: Vec<(String, String)> = log_res.result
                                     .iter()
                                     .map(|r| (r.transaction_hash.clone(), r.data.clone()))
                                     .collect();
-                                println!("Hashes: {:#?}", hashes);
+                                log_debug!("Hashes: {:#?}", hashes);
                                 Ok(self.find_corresponding_transaction(hashes))
                             }
                             Err(err) => Err(anyhow!("Failed Serde: {}", err)),
@@ -114,88 +166,131 @@ impl ReplayEthereumSocketHandler {
         }
     }
 
-    pub fn retrieve_block(&self, block_number: u256) -> Value {
-        let call = format!(
-            "call(ethereum, eth_getBlockByNumber, [{}]).get(result)",
-            format!("{:#x}", block_number)
-        ); // Blocknumber
-        println!("Call: {}", call);
-        let root = build_ast_root(call.as_str()).unwrap();
-        root.print("");
-        let val = root.evaluate().unwrap();
-        print!("Val: {:?}\n", val);
-        Value::from(VarValues::from(val))
+    /// Sends the request once the rate limiter allows it.
+    /// If the provider answers with 429 the request is retried with an exponential backoff.
+    fn send_rate_limited(&self, client: &Client, request: &Value) -> Result<reqwest::blocking::Response> {
+        let mut backoff = Duration::from_millis(RATE_LIMIT_BACKOFF_MS);
+        for attempt in 0..=RATE_LIMIT_RETRIES {
+            self.rate_limiter.acquire();
+            let res = client
+                .post(self.rpc_url.clone())
+                .header("Content-Type", "application/json")
+                .json(request)
+                .send()?;
+            if res.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                return Ok(res);
+            }
+            if attempt == RATE_LIMIT_RETRIES {
+                break;
+            }
+            log_error!("Rate limited by {}, retrying in {:?}", self.chain_name, backoff);
+            thread::sleep(backoff);
+            backoff *= 2;
+        }
+        Err(anyhow!("Rate limited by {} after {} retries", self.chain_name, RATE_LIMIT_RETRIES))
+    }
+
+    /// Sends a JSON-RPC request to the chain through the rate limiter and returns its result
+    fn rpc_result(&self, method: &str, params: Value) -> Result<Value> {
+        let request = json!({"jsonrpc": "2.0", "method": method, "params": params, "id": 1});
+        log_debug!("Call: {}", request);
+        let res = self.send_rate_limited(utils::get_http_client(), &request)?;
+        let response: Value = serde_json::from_str(&res.text()?)?;
+        if let Some(error) = response.get("error") {
+            return Err(anyhow!("{} failed: {}", method, error));
+        }
+        Ok(response.get("result").cloned().unwrap_or(Value::Null))
+    }
+
+    pub fn retrieve_block(&self, block_number: u256) -> Result<Value> {
+        self.rpc_result("eth_getBlockByNumber", json!([format!("{:#x}", block_number), false]))
+    }
+
+    /// Returns the transaction of the hash
+    fn get_transaction_by_hash(&self, hash: &str) -> Result<Value> {
+        self.rpc_result("eth_getTransactionByHash", json!([hash]))
+    }
+
+    /// Returns the balance of the address at the end of the block
+    fn get_balance_at_block(&self, address: &str, block_number: u256) -> Result<u256> {
+        let balance = self.rpc_result("eth_getBalance", json!([address, format!("{:#x}", block_number)]))?;
+        let balance = balance.as_str().ok_or(anyhow!("Invalid balance of {}: {}", address, balance))?;
+        Ok(u256::from_str_hex(balance)?)
+    }
+
+    /// Value of the transaction and the balances of the payer before and after the block
+    fn payer_fields(&self, hash: &str, payer: &str, block: u256) -> Result<(u256, u256, u256)> {
+        let tx = self.get_transaction_by_hash(hash)?;
+        let value = tx
+            .get("value")
+            .and_then(|v| v.as_str())
+            .ok_or(anyhow!("Transaction {} without value", hash))?;
+        let value = u256::from_str_hex(value)?;
+        let before = self.get_balance_at_block(payer, block - 1)?;
+        let after = self.get_balance_at_block(payer, block)?;
+        Ok((value, before, after))
     }
 
     fn find_corresponding_transaction(&self, hashes: Vec<(String, String)>) -> Vec<Properties> {
         let mut properties: Vec<Properties> = Vec::new();
         for h in hashes {
-            let call = format!("call(ethereum, eth_getTransactionReceipt, [{}]).get(result)", h.0); // Blocknumber
-
-
-            let r = build_ast_root(call.as_str());
-            if r.is_err() {
-                println!("Error: {}", r.err().unwrap());
-                println!("Call: {}", call);
-                continue;
-            }
-            let root = r.unwrap();
-            root.print("");
-            let val = root.evaluate().unwrap();
-            let receipt = Value::from(VarValues::from(val));
+            // Every request of the log shares the rate limit of the chain
+            let receipt = match self.rpc_result("eth_getTransactionReceipt", json!([h.0])) {
+                Ok(receipt) => receipt,
+                Err(e) => {
+                    log_error!("Failed to get the receipt of {}: {}", h.0, e);
+                    continue;
+                }
+            };
 
             // check if topics match
             if let Some(logs) = receipt.get("logs").and_then(|l| l.as_array()) {
                 for log in logs {
-                    if let Some(removed) = log.get("removed") {
-                        // If the log was removed due to consensus we skip it
-                        if removed.as_bool().unwrap() {
-                            continue;
-                        }
-                    }
-                    if let Some(_topics) = log.get("topics").and_then(|t| t.as_array()) {
-                        if
-                            _topics
-                                .iter()
-                                .any(|t|
-                                    self.config.topics.contains(&t.as_str().unwrap().to_string())
-                                )
+                    if let Some(topnum) = self.matching_topic(log) {
+                        let payer = receipt.get("from").unwrap().as_str().unwrap();
+
+                        let block = u256
+                            ::from_str_hex(log.get("blockNumber").unwrap().as_str().unwrap())
+                            .unwrap();
+                        let (value, payer_balance_before, payer_balance_after) = match
+                            self.payer_fields(&h.0, payer, block)
                         {
-                            let topnum = _topics
-                                .iter()
-                                .find(|t|
-                                    self.config.topics.contains(&t.as_str().unwrap().to_string())
-                                )
-                                .and_then(|t| Some(t.as_str().unwrap().to_string()));
-                            let payer = receipt.get("from").unwrap().as_str().unwrap();
-
-                            let tx = get_transaction_by_hash(h.0.clone());
-                            let value = tx.get("value").unwrap().as_str().unwrap();
-                            let block = u256
-                                ::from_str_hex(log.get("blockNumber").unwrap().as_str().unwrap())
-                                .unwrap();
-                            let payer_balance_before = get_balance_at_block(
-                                payer.to_string(),
-                                block - 1
-                            );
-                            let payer_balance_after = get_balance_at_block(
-                                payer.to_string(),
-                                block
-                            );
+                            Ok(fields) => fields,
+                            Err(e) => {
+                                log_error!("Failed to enrich {}: {}", h.0, e);
+                                continue;
+                            }
+                        };
 
-                            let p = Properties {
-                                occured_event: topnum,
-                                transaction_hash: Some(h.0.clone()),
-                                block_number: Some(block),
-                                payer_address: Some(payer.to_string()),
-                                payer_balance_before: Some(payer_balance_before),
-                                payer_balance_after: Some(payer_balance_after),
-                                value: Some(u256::from_str_hex(value).unwrap()),
-                                src_chain: Some("ethereum".to_string()),
-                                event_data: Some(h.1.clone()),
-                            };
-                            properties.push(p);
-                        }
+                        let log_index = log
+                            .get("logIndex")
+                            .and_then(|i| i.as_str())
+                            .map(|i| utils::hex_string_to_u64(i));
+
+                        let p = Properties {
+                            occured_event: Some(topnum),
+                            transaction_hash: Some(h.0.clone()),
+                            block_number: Some(block),
+                            payer: Some(Account {
+                                address: Some(payer.to_string()),
+                                balance: Some(payer_balance_after),
+                                previous_balance: Some(payer_balance_before),
+                            }),
+                            payer_address: Some(payer.to_string()),
+                            payer_balance_before: Some(payer_balance_before),
+                            payer_balance_after: Some(payer_balance_after),
+                            value: Some(value),
+                            src_chain: Some(self.chain_name.clone()),
+                            src_chain_id: self.config.chain_id,
+                            event_data: Some(h.1.clone()),
+                            program_id: None,
+                            log_index,
+                            transaction_failed: None,
+                            token_mint: None,
+                            topics: log_topics(log),
+                            data: log.get("data").and_then(|d| d.as_str()).unwrap_or(&h.1).to_string(),
+                        };
+                        properties.push(p);
                     }
                 }
             }
@@ -203,6 +298,56 @@ impl ReplayEthereumSocketHandler {
         properties
     }
 
+    /// Topic of the log that is replayed.
+    /// None if the log was removed due to consensus or has none of the topics of the chain
+    fn matching_topic(&self, log: &Value) -> Option<String> {
+        if log.get("removed").and_then(|r| r.as_bool()).unwrap_or(false) {
+            return None;
+        }
+        log.get("topics")
+            .and_then(|t| t.as_array())?
+            .iter()
+            .filter_map(|t| t.as_str())
+            .find(|t| self.config.topic_ids().contains(&t.to_lowercase()))
+            .map(|t| t.to_string())
+    }
+
+    /// Properties of the captured logs in the inclusive block range, see `ReplaySource::File`.
+    /// Without an RPC only the fields of the log are known, e.g., there is no payer or value.
+    pub fn file_logs(&self, logs: &[Value], from: u64, to: u64) -> Result<Vec<Properties>> {
+        let mut properties = Vec::new();
+        for log in logs {
+            let block = log
+                .get("blockNumber")
+                .and_then(|b| b.as_str())
+                .ok_or(anyhow!("Log without blockNumber: {}", log))?;
+            let block = u64::from_str_radix(block.trim_start_matches("0x"), 16)?;
+            if block < from || block > to {
+                continue;
+            }
+            let address = log.get("address").and_then(|a| a.as_str()).unwrap_or_default();
+            if !self.config.address.is_empty() && !self.config.address.eq_ignore_ascii_case(address) {
+                continue;
+            }
+            let Some(topic) = self.matching_topic(log) else {
+                continue;
+            };
+            let field = |key: &str| log.get(key).and_then(|v| v.as_str()).map(|v| v.to_string());
+            let mut p = Properties::new();
+            p.occured_event = Some(topic);
+            p.transaction_hash = field("transactionHash");
+            p.block_number = Some(u256::from(block));
+            p.src_chain = Some(self.chain_name.clone());
+            p.src_chain_id = self.config.chain_id;
+            p.event_data = field("data");
+            p.topics = log_topics(log);
+            p.data = field("data").unwrap_or_default();
+            p.log_index = field("logIndex").map(|i| utils::hex_string_to_u64(&i));
+            properties.push(p);
+        }
+        Ok(properties)
+    }
+
     // pub fn filter_block(&self, block: Value) -> Vec<Properties> {
     //     // for each transaction hash in the block, get the transaction
     //     let mut hashes = Vec::new();
@@ -249,49 +394,415 @@ fn find_transaction_hashes(value: &Value, hashes: &mut Vec<String>) {
     }
 }
 
-/// Given a transaction hash, returns the transaction receipt from the Ethereum node.
-///
-/// # Arguments
-///
-/// * `hash` - The transaction hash to look up.
-///
-/// # Returns
-///
-/// A `Value` containing the transaction receipt.
-fn get_transaction_by_hash(hash: String) -> Value {
-    let call = format!("call(ethereum, eth_getTransactionByHash, [{}]).get(result)", hash); // Blocknumber
+/// Token bucket that limits the requests per second of a chain.
+/// The limiter is shared by all workers replaying the chain.
+pub struct RateLimiter {
+    requests_per_second: Option<f64>,
+    // Maximal number of tokens, i.e., requests that may be sent at once
+    capacity: f64,
+    // (available tokens, last refill)
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter, `None` does not limit the requests
+    pub fn new(requests_per_second: Option<f64>) -> Self {
+        Self::with_capacity(requests_per_second, requests_per_second.unwrap_or(0.0).max(1.0))
+    }
+
+    /// Creates a limiter without bursts, every request waits for its own slot
+    pub fn paced(requests_per_second: Option<f64>) -> Self {
+        Self::with_capacity(requests_per_second, 1.0)
+    }
+
+    fn with_capacity(requests_per_second: Option<f64>, capacity: f64) -> Self {
+        Self {
+            requests_per_second: requests_per_second.filter(|r| *r > 0.0),
+            capacity,
+            bucket: Mutex::new((capacity, Instant::now())),
+        }
+    }
+
+    /// Blocks until a request may be sent
+    pub fn acquire(&self) {
+        let rate = match self.requests_per_second {
+            Some(rate) => rate,
+            None => {
+                return;
+            }
+        };
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let now = Instant::now();
+                let refill = now.duration_since(bucket.1).as_secs_f64() * rate;
+                bucket.0 = (bucket.0 + refill).min(self.capacity);
+                bucket.1 = now;
+                if bucket.0 >= 1.0 {
+                    bucket.0 -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - bucket.0) / rate)
+            };
+            thread::sleep(wait);
+        }
+    }
+}
+
+/// Forwards the replayed properties to `output` at most `limiter` times per second, so the event loop and
+/// the consumers are not flooded. Returns the sender for the chains; the thread ends once all its clones are dropped.
+pub fn pace_events(output: Sender<Properties>, limiter: RateLimiter) -> (Sender<Properties>, thread::JoinHandle<()>) {
+    let (input, events) = std::sync::mpsc::channel::<Properties>();
+    let handle = thread::spawn(move || {
+        for event in events {
+            limiter.acquire();
+            if output.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    (input, handle)
+}
+
+/// Raw topics of a log object of eth_getLogs or a receipt
+fn log_topics(log: &Value) -> Vec<String> {
+    log.get("topics")
+        .and_then(|t| t.as_array())
+        .map(|topics| topics.iter().filter_map(|t| t.as_str().map(|t| t.to_string())).collect())
+        .unwrap_or_default()
+}
+
+/// Splits the inclusive block range into consecutive pages of `step` blocks.
+/// The pages do not overlap, so every block is requested exactly once.
+pub fn page_ranges(start: u64, end: u64, step: u64) -> Vec<(u64, u64)> {
+    let step = step.max(1);
+    (start..=end)
+        .step_by(step as usize)
+        .map(|i| (i, end.min(i.saturating_add(step - 1))))
+        .collect()
+}
+
+/// Tracks the progress of a replayed block range
+pub struct ReplayProgress {
+    pub(crate) chain_name: String,
+    pub(crate) total: u64,
+    pub(crate) processed: u64,
+    pub(crate) started: Instant,
+}
+
+impl ReplayProgress {
+    pub fn new(chain_name: String, start: u64, end: u64) -> Self {
+        Self {
+            chain_name,
+            total: end.saturating_sub(start) + 1,
+            processed: 0,
+            started: Instant::now(),
+        }
+    }
+
+    /// Marks a page as processed
+    pub fn advance(&mut self, from: u64, to: u64) {
+        self.processed = self.total.min(self.processed + (to.saturating_sub(from) + 1));
+    }
+
+    /// Processed blocks in percent
+    pub fn percentage(&self) -> f64 {
+        (self.processed as f64) * 100.0 / (self.total as f64)
+    }
+
+    /// Processed blocks per second after `elapsed`
+    pub fn blocks_per_second(&self, elapsed: Duration) -> f64 {
+        let secs = elapsed.as_secs_f64();
+        if secs == 0.0 { 0.0 } else { (self.processed as f64) / secs }
+    }
+
+    /// Progress line with the elapsed time, the throughput and the estimated remaining time
+    pub fn report(&self, elapsed: Duration) -> String {
+        let rate = self.blocks_per_second(elapsed);
+        let eta = if rate > 0.0 {
+            format!("{:.0}s", ((self.total - self.processed) as f64) / rate)
+        } else {
+            "unknown".to_string()
+        };
+        format!(
+            "Replay {}: {}/{} blocks ({:.2}%), elapsed {:.0}s, {:.2} blocks/s, ETA {}",
+            self.chain_name,
+            self.processed,
+            self.total,
+            self.percentage(),
+            elapsed.as_secs_f64(),
+            rate,
+            eta
+        )
+    }
+}
+
+/// Last fully processed block of a chain, persisted so that an interrupted replay can be resumed.
+/// The file holds the blocks of all chains, e.g., `{"ethereum": 19000000}`
+#[derive(Debug, Clone)]
+pub struct ReplayCheckpoint {
+    path: PathBuf,
+    chain_name: String,
+}
+
+impl ReplayCheckpoint {
+    pub fn new(path: PathBuf, chain_name: String) -> Self {
+        Self { path, chain_name }
+    }
+
+    fn read_all(path: &Path) -> BTreeMap<String, u64> {
+        std::fs
+            ::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    /// Last processed block of the chain, `None` if the chain has no checkpoint
+    pub fn load(&self) -> Option<u64> {
+        Self::read_all(&self.path).get(&self.chain_name).copied()
+    }
+
+    /// First block to replay, i.e., the block after the checkpoint or `start` without a checkpoint
+    pub fn resume_from(&self, start: u64) -> u64 {
+        self.load().map_or(start, |block| start.max(block.saturating_add(1)))
+    }
+
+    /// Stores the block as processed. The file is replaced at once, so a crash never leaves a partial checkpoint
+    pub fn save(&self, block: u64) -> Result<()> {
+        let mut blocks = Self::read_all(&self.path);
+        blocks.insert(self.chain_name.clone(), block);
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&blocks)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Retries of a page whose logs could not be fetched
+#[derive(Debug, Clone)]
+pub struct PageRetry {
+    /// Attempts of a page before it is skipped
+    pub attempts: u32,
+    /// Waiting time before the first retry, doubled for each further retry and extended by a random jitter
+    pub backoff: Duration,
+    /// File to which the ranges of skipped pages are appended, so they can be replayed later
+    pub gaps_file: Option<PathBuf>,
+    /// Smallest range a page is split into when the provider rejects its block range
+    pub min_block_range: u64,
+}
+
+impl Default for PageRetry {
+    fn default() -> Self {
+        Self {
+            attempts: PAGE_ATTEMPTS,
+            backoff: Duration::from_millis(PAGE_BACKOFF_MS),
+            gaps_file: None,
+            min_block_range: 1,
+        }
+    }
+}
+
+impl PageRetry {
+    /// Fetches the page until it succeeds or all attempts failed
+    fn fetch<F>(&self, fetch: &F, from: u64, to: u64) -> Result<Vec<Properties>>
+        where F: Fn(u64, u64) -> Result<Vec<Properties>>
+    {
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+        loop {
+            match fetch_split(fetch, from, to, self.min_block_range) {
+                Ok(txs) => {
+                    return Ok(txs);
+                }
+                Err(e) if attempt >= self.attempts => {
+                    return Err(e);
+                }
+                Err(e) => {
+                    // The jitter spreads the retries of concurrent workers
+                    let jitter = rand::thread_rng().gen_range(0..=(backoff.as_millis() as u64) / 2);
+                    let wait = backoff + Duration::from_millis(jitter);
+                    log_error!("Error: {}, retrying blocks {}-{} in {:?}", e, from, to, wait);
+                    thread::sleep(wait);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Logs the skipped page and appends its range to the gaps file
+    fn record_gap(&self, from: u64, to: u64, error: &anyhow::Error) {
+        log_error!(
+            "Skipping blocks {}-{} after {} attempts: {}",
+            from,
+            to,
+            self.attempts,
+            error
+        );
+        let Some(path) = &self.gaps_file else {
+            return;
+        };
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut f| writeln!(f, "0x{:x} 0x{:x}", from, to));
+        if let Err(e) = written {
+            log_error!("Error: failed to write the gap to {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Fetches the inclusive range. If the provider rejects the range as too large, the range is halved
+/// and both halves are fetched the same way until they are `min_range` blocks long.
+pub fn fetch_split<F>(fetch: &F, from: u64, to: u64, min_range: u64) -> Result<Vec<Properties>>
+    where F: Fn(u64, u64) -> Result<Vec<Properties>>
+{
+    match fetch(from, to) {
+        Err(e) if e.is::<RangeTooLarge>() && to - from + 1 > min_range.max(1) => {
+            let middle = from + (to - from) / 2;
+            log_debug!("Splitting blocks {}-{} at {}: {}", from, to, middle, e);
+            let mut txs = fetch_split(fetch, from, middle, min_range)?;
+            txs.extend(fetch_split(fetch, middle + 1, to, min_range)?);
+            Ok(txs)
+        }
+        result => result,
+    }
+}
+
+/// Logs that were already sent, by block.
+/// Logs without a block number are kept until the replay ends.
+#[derive(Debug, Default)]
+struct SeenLogs {
+    blocks: BTreeMap<u64, HashSet<(String, u64)>>,
+}
 
-    let root = build_ast_root(call.as_str()).unwrap();
-    root.print("");
-    let val = root.evaluate().unwrap();
-    let tx = Value::from(VarValues::from(val));
-    tx
+impl SeenLogs {
+    /// Returns false if the log was already seen
+    fn insert(&mut self, block: Option<u64>, hash: &str, log_index: u64) -> bool {
+        self.blocks
+            .entry(block.unwrap_or(u64::MAX))
+            .or_default()
+            .insert((hash.to_string(), log_index))
+    }
+
+    /// Forgets the logs of the blocks before `block`
+    fn forget_before(&mut self, block: u64) {
+        self.blocks = self.blocks.split_off(&block);
+    }
 }
 
-/// Given an Ethereum address and block number, returns the balance of that address at
-/// the specified block number.
+/// Replays the pages with at most `workers` concurrent `fetch` calls.
 ///
-/// # Arguments
+/// Ordering guarantees:
+/// * The pages are fetched concurrently in batches of `workers` pages.
+/// * The properties are sent in page order, i.e., in ascending block order of a chain,
+///   so the event loop sees the same order as a sequential replay.
+/// * There is no ordering between different chains.
 ///
-/// * `address` - The Ethereum address to look up.
-/// * `block_number` - The block number at which to look up the balance.
+/// Logs are deduplicated by `(transaction_hash, log_index)`, so a log that is returned
+/// for several pages is only sent once. The logs of a block are forgotten once no remaining
+/// page starts at or before it, so the memory is bounded by the pages of a batch.
 ///
-/// # Returns
+/// A failed page is retried as configured by `retry` and skipped after the last attempt.
 ///
-/// A `u256` containing the balance of the address at the specified block number.
-fn get_balance_at_block(address: String, block_number: u256) -> u256 {
-    let hex_block_number = format!("{:#x}", block_number);
-    let call = format!(
-        "call(ethereum, eth_getBalance, [\"{}\", {}]).get(result)",
-        address,
-        hex_block_number
-    ); // Blocknumber
-
-    let root = build_ast_root(call.as_str()).unwrap();
-    root.print("");
-    let val = root.evaluate().unwrap();
-    let balance = Value::from(VarValues::from(val));
-    u256::from_str_hex(balance.as_str().unwrap()).unwrap() // Return the balance
+/// After each batch of pages the last block of the batch is saved to the `checkpoint`.
+/// The properties of the batch are sent before, so the checkpoint never skips unsent logs.
+///
+/// Returns the number of properties sent over the channel.
+pub fn replay_pages<F>(
+    pages: &[(u64, u64)],
+    workers: usize,
+    fetch: F,
+    event_channel: &Sender<Properties>,
+    mut progress: Option<&mut ReplayProgress>,
+    retry: &PageRetry,
+    checkpoint: Option<&ReplayCheckpoint>
+) -> usize
+    where F: Fn(u64, u64) -> Result<Vec<Properties>> + Sync
+{
+    let mut sent = 0;
+    let workers = workers.max(1);
+    let mut seen = SeenLogs::default();
+    for (i, batch) in pages.chunks(workers).enumerate() {
+        let results: Vec<Result<Vec<Properties>>> = thread::scope(|s| {
+            let handles: Vec<_> = batch
+                .iter()
+                .map(|(from, to)| {
+                    let fetch = &fetch;
+                    s.spawn(move || retry.fetch(fetch, *from, *to))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or_else(|_| Err(anyhow!("Replay worker panicked"))))
+                .collect()
+        });
+
+        for ((from, to), txs) in batch.iter().zip(results) {
+            match txs {
+                Ok(txs) => {
+                    log_debug!("Length of txs: {}", txs.len());
+                    // Send to tx
+                    for t in txs {
+                        if let (Some(hash), Some(log_index)) = (&t.transaction_hash, t.log_index) {
+                            if !seen.insert(t.block_number.map(|b| b.as_u64()), hash, log_index) {
+                                // Log was already sent for a previous page
+                                continue;
+                            }
+                        }
+                        event_channel.send(t).unwrap();
+                        sent += 1;
+                    }
+                }
+                Err(e) => retry.record_gap(*from, *to, &e),
+            }
+        }
+        // The remaining pages can only return logs from their first block on
+        let remaining = pages.get((i + 1) * workers..).unwrap_or_default();
+        match remaining.iter().map(|(from, _)| *from).min() {
+            Some(next) => seen.forget_before(next),
+            None => seen = SeenLogs::default(),
+        }
+
+        if let (Some(checkpoint), Some((_, to))) = (checkpoint, batch.last()) {
+            if let Err(e) = checkpoint.save(*to) {
+                log_error!("Error: failed to save the replay checkpoint: {}", e);
+            }
+        }
+
+        if let Some(progress) = progress.as_mut() {
+            for (from, to) in batch {
+                progress.advance(*from, *to);
+            }
+            log_info!("{}", progress.report(progress.started.elapsed()));
+        }
+    }
+    sent
+}
+
+/// Where the logs of a replay come from
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplaySource {
+    /// `eth_getLogs` of the chain in `config/connections.json`
+    Rpc,
+    /// JSON file with an array of raw log objects as returned by `eth_getLogs`
+    File(PathBuf),
+}
+
+impl ReplaySource {
+    /// Reads the logs of a `File` source once, the pages are then taken from memory
+    pub fn read_logs(&self) -> Result<Vec<Value>> {
+        match self {
+            ReplaySource::Rpc => Ok(vec![]),
+            ReplaySource::File(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+                Ok(serde_json::from_str(&contents)?)
+            }
+        }
+    }
 }
 
 /// The serialized version of a Config for the replay
@@ -308,6 +819,77 @@ pub struct ReplayConfig {
     pub page_length: Option<u64>,
     pub chains: Vec<Chain>,
     pub comment: Option<String>,
+    /// Number of pages that are fetched concurrently. Default: 1
+    pub workers: Option<usize>,
+    /// Maximum number of requests per second for each chain. Default: unlimited
+    #[serde(rename = "requests_per_second")]
+    pub requests_per_second: Option<f64>,
+    /// Attempts to fetch a page before it is skipped. Default: 5
+    #[serde(rename = "page_attempts")]
+    pub page_attempts: Option<u32>,
+    /// File for the ranges of skipped pages. Default: replay_gaps_<chain>.txt
+    #[serde(rename = "gaps_file")]
+    pub gaps_file: Option<String>,
+    /// File for the last processed block of each chain. Default: replay_checkpoint.json
+    #[serde(rename = "checkpoint_file")]
+    pub checkpoint_file: Option<String>,
+    /// Maximum number of blocks of a get_logs request, longer pages are split. Default: unlimited
+    #[serde(rename = "max_block_range")]
+    pub max_block_range: Option<u64>,
+    /// Smallest range a page is split into after the provider rejected its block range. Default: 1
+    #[serde(rename = "min_block_range")]
+    pub min_block_range: Option<u64>,
+    /// Source of the logs, "rpc" or "file". Default: rpc
+    pub source: Option<String>,
+    /// File with the logs of the "file" source
+    pub path: Option<String>,
+}
+
+impl ReplayConfig {
+    pub fn source(&self) -> Result<ReplaySource> {
+        match (self.source.as_deref().unwrap_or("rpc"), &self.path) {
+            ("rpc", _) => Ok(ReplaySource::Rpc),
+            ("file", Some(path)) => Ok(ReplaySource::File(utils::get_directories().base_path(path))),
+            ("file", None) => Err(anyhow!("The file source of the replay needs a path")),
+            (source, _) => Err(anyhow!("Unknown replay source {}", source)),
+        }
+    }
+
+    /// Checkpoint of the chain
+    pub fn checkpoint(&self, chain_name: &str) -> ReplayCheckpoint {
+        ReplayCheckpoint::new(
+            PathBuf::from(self.checkpoint_file.as_deref().unwrap_or(CHECKPOINT_FILE)),
+            chain_name.to_string()
+        )
+    }
+
+    /// Retries of the pages of the chain
+    pub fn page_retry(&self, chain_name: &str) -> PageRetry {
+        PageRetry {
+            attempts: self.page_attempts.unwrap_or(PAGE_ATTEMPTS).max(1),
+            gaps_file: Some(
+                PathBuf::from(self.gaps_file.clone().unwrap_or(format!("replay_gaps_{}.txt", chain_name)))
+            ),
+            min_block_range: self.min_block_range.unwrap_or(1).max(1),
+            ..PageRetry::default()
+        }
+    }
+
+    /// Number of blocks requested by a single `get_logs` call for the inclusive range.
+    /// Without paging the whole range is requested at once, with paging `page_length` is the window.
+    /// Both are capped by `max_block_range`.
+    pub fn page_size(&self, start: u64, end: u64) -> u64 {
+        let whole_range = end.saturating_sub(start) + 1;
+        let size = match (self.paging.unwrap_or(false), self.page_length) {
+            (true, Some(page_length)) => page_length.max(1),
+            (true, None) => {
+                log_error!("Paging is enabled without page_length, requesting the whole range");
+                whole_range
+            }
+            (false, _) => whole_range,
+        };
+        size.min(self.max_block_range.unwrap_or(u64::MAX).max(1))
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -317,7 +899,30 @@ pub struct Chain {
     pub ending_block: String,
     pub name: String,
     pub address: String,
+    /// Event topics or signatures of the chain, a log matches if its event is any of them
     pub topics: Vec<String>,
+    /// EIP-155 chain id, set as `src_chain_id` of the replayed events
+    #[serde(rename = "chain_id", default, skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<u64>,
+}
+
+impl Chain {
+    /// Lowercased topics with event signatures hashed
+    pub fn topic_ids(&self) -> Vec<String> {
+        self.topics
+            .iter()
+            .map(|t| topic_id(t))
+            .collect()
+    }
+
+    /// `topics` param of `eth_getLogs`, the topics of the chain are the OR-set of the first position
+    pub fn topic_filter(&self) -> Value {
+        if self.topics.is_empty() {
+            json!([])
+        } else {
+            json!([topic_position(&self.topics)])
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -343,6 +948,367 @@ pub struct LogResult {
     pub removed: bool,
 }
 
+#[test]
+fn test_replay_pages_delivers_once() {
+    use ethnum::AsU256;
+    use std::sync::mpsc;
+
+    // Fixture: a log in every fifth block
+    let log_blocks: Vec<u64> = (100..=1000).filter(|b| b % 5 == 0).collect();
+    let pages = page_ranges(100, 1000, 64);
+    assert_eq!(pages.first(), Some(&(100, 163)));
+    assert_eq!(pages.last(), Some(&(996, 1000)));
+
+    let (tx, rx) = mpsc::channel();
+    let sent = replay_pages(
+        &pages,
+        4,
+        |from, to| {
+            Ok(
+                log_blocks
+                    .iter()
+                    .filter(|b| **b >= from && **b <= to)
+                    .map(|b| {
+                        let mut p = Properties::new();
+                        p.block_number = Some(b.as_u256());
+                        p.transaction_hash = Some(format!("{:#x}", b));
+                        p
+                    })
+                    .collect()
+            )
+        },
+        &tx,
+        None,
+        &PageRetry::default(),
+        None
+    );
+    drop(tx);
+
+    let received: Vec<u64> = rx
+        .iter()
+        .map(|p| p.block_number.unwrap().as_u64())
+        .collect();
+    assert_eq!(sent, log_blocks.len());
+    // Exactly once and in block order
+    assert_eq!(received, log_blocks);
+}
+
+#[test]
+fn test_seen_logs() {
+    let mut seen = SeenLogs::default();
+    assert!(seen.insert(Some(10), "0xa", 0));
+    assert!(!seen.insert(Some(10), "0xa", 0));
+    assert!(seen.insert(Some(10), "0xa", 1));
+    assert!(seen.insert(Some(11), "0xb", 0));
+    assert!(seen.insert(None, "0xc", 0));
+
+    // Only the blocks from 11 on are kept
+    seen.forget_before(11);
+    assert_eq!(seen.blocks.keys().copied().collect::<Vec<u64>>(), vec![11, u64::MAX]);
+    assert!(!seen.insert(Some(11), "0xb", 0));
+    assert!(!seen.insert(None, "0xc", 0));
+    assert!(seen.insert(Some(10), "0xa", 0));
+}
+
+#[test]
+fn test_replay_pages_dedup_overlapping() {
+    use ethnum::AsU256;
+    use std::sync::mpsc;
+
+    // Fixture: (block, transaction hash, log index)
+    let logs = vec![
+        (150, "0xa", 0),
+        (200, "0xb", 0),
+        (200, "0xb", 1),
+        (250, "0xc", 3),
+        (300, "0xd", 0),
+    ];
+    // Pages overlap at their boundaries
+    let pages = vec![(100, 200), (200, 300), (300, 300)];
+
+    let (tx, rx) = mpsc::channel();
+    let sent = replay_pages(
+        &pages,
+        2,
+        |from, to| {
+            let mut found: Vec<Properties> = logs
+                .iter()
+                .filter(|(b, _, _)| *b >= from && *b <= to)
+                .map(|(b, hash, idx)| {
+                    let mut p = Properties::new();
+                    p.block_number = Some(b.as_u256());
+                    p.transaction_hash = Some(hash.to_string());
+                    p.log_index = Some(*idx);
+                    p
+                })
+                .collect();
+            // Providers also return duplicates within a page
+            found.extend(found.clone());
+            Ok(found)
+        },
+        &tx,
+        None,
+        &PageRetry::default(),
+        None
+    );
+    drop(tx);
+
+    let received: Vec<(String, u64)> = rx
+        .iter()
+        .map(|p| (p.transaction_hash.unwrap(), p.log_index.unwrap()))
+        .collect();
+    assert_eq!(sent, 5);
+    assert_eq!(
+        received,
+        vec![
+            ("0xa".to_string(), 0),
+            ("0xb".to_string(), 0),
+            ("0xb".to_string(), 1),
+            ("0xc".to_string(), 3),
+            ("0xd".to_string(), 0)
+        ]
+    );
+}
+
+#[test]
+fn test_rate_limiter() {
+    let limiter = RateLimiter::new(Some(20.0));
+    let now = Instant::now();
+    // The bucket starts full, the remaining 10 requests have to wait 500ms for new tokens
+    for _ in 0..30 {
+        limiter.acquire();
+    }
+    // Only a lower bound, a loaded machine may take longer
+    assert!(now.elapsed() >= Duration::from_millis(400));
+
+    // Without a rate no token is taken, so acquire never waits
+    let unlimited = RateLimiter::new(None);
+    for _ in 0..1000 {
+        unlimited.acquire();
+    }
+    assert_eq!(unlimited.bucket.lock().unwrap().0, unlimited.capacity);
+}
+
+#[test]
+fn test_replay_rate() {
+    use std::sync::mpsc;
+
+    let (tx, rx) = mpsc::channel();
+    let (paced, handle) = pace_events(tx, RateLimiter::paced(Some(50.0)));
+    let now = Instant::now();
+    for i in 0..25u64 {
+        let mut p = Properties::new();
+        p.log_index = Some(i);
+        paced.send(p).unwrap();
+    }
+    drop(paced);
+    let received: Vec<u64> = rx
+        .iter()
+        .filter_map(|p| p.log_index)
+        .collect();
+    handle.join().unwrap();
+
+    // 25 events at 50 per second, the first one is sent immediately and the others wait 480ms.
+    // Only a lower bound, a loaded machine may take longer
+    let elapsed = now.elapsed();
+    assert!(elapsed >= Duration::from_millis(400), "{:?}", elapsed);
+    assert_eq!(received, (0..25).collect::<Vec<u64>>());
+}
+
+#[test]
+fn test_get_logs_retries_on_429() {
+    use std::sync::atomic::{ AtomicUsize, Ordering };
+
+    // The first request is rate limited, the retry succeeds
+    let attempts = AtomicUsize::new(0);
+    let (addr, server) = crate::utils::mock_http_server(Some(2), move |_| {
+        match attempts.fetch_add(1, Ordering::SeqCst) {
+            0 => ("429 Too Many Requests", String::new()),
+            _ => ("200 OK", r#"{"jsonrpc":"2.0","id":1,"result":[]}"#.to_string()),
+        }
+    });
+
+    let replayer = ReplayEthereumSocketHandler {
+        chain_name: "ethereum".to_string(),
+        config: Chain::default(),
+        rpc_url: format!("http://{}", addr),
+        rate_limiter: RateLimiter::new(Some(10.0)),
+    };
+    let txs = replayer.get_logs("0x1".to_string(), "0x2".to_string()).unwrap();
+    assert!(txs.is_empty());
+    server.join().unwrap();
+}
+
+#[test]
+fn test_enrichment_retries_on_429() {
+    use std::sync::atomic::{ AtomicBool, Ordering };
+
+    let topic = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+    let log =
+        json!({
+        "address": "0x01", "topics": [topic], "data": "0x", "blockHash": "0x02", "blockNumber": "0x10",
+        "blockTimestamp": "0x0", "transactionHash": "0xaa", "transactionIndex": "0x0", "logIndex": "0x0", "removed": false
+    });
+    // The receipt of the log is rate limited once, the other requests of the log succeed
+    let limited = AtomicBool::new(false);
+    let (addr, server) = crate::utils::mock_http_server(Some(6), move |request| {
+        let result = match request["method"].as_str().unwrap_or_default() {
+            "eth_getLogs" => json!([log]),
+            "eth_getTransactionReceipt" if !limited.swap(true, Ordering::SeqCst) => {
+                return ("429 Too Many Requests", String::new());
+            }
+            "eth_getTransactionReceipt" => json!({ "from": "0xbb", "logs": [log] }),
+            "eth_getTransactionByHash" => json!({ "value": "0x5" }),
+            _ if request["params"][1] == "0xf" => json!("0x64"),
+            _ => json!("0x5f"),
+        };
+        ("200 OK", json!({ "jsonrpc": "2.0", "id": 1, "result": result }).to_string())
+    });
+
+    let replayer = ReplayEthereumSocketHandler {
+        chain_name: "arbitrum".to_string(),
+        config: Chain { topics: vec![topic.to_string()], chain_id: Some(42161), ..Chain::default() },
+        rpc_url: format!("http://{}", addr),
+        rate_limiter: RateLimiter::new(Some(100.0)),
+    };
+    let properties = replayer.get_logs("0x10".to_string(), "0x10".to_string()).unwrap();
+    assert_eq!(properties.len(), 1);
+    assert_eq!(properties[0].value, Some(u256::new(5)));
+    assert_eq!(properties[0].payer_balance_before, Some(u256::new(100)));
+    assert_eq!(properties[0].payer_balance_after, Some(u256::new(95)));
+    assert_eq!(properties[0].src_chain_id, Some(42161));
+
+    // All requests went to the node of the replayed chain, the rate limited receipt was requested again
+    let methods: Vec<String> = server
+        .join()
+        .unwrap()
+        .iter()
+        .map(|r| r["method"].as_str().unwrap_or_default().to_string())
+        .collect();
+    assert_eq!(methods.iter().filter(|m| *m == "eth_getTransactionReceipt").count(), 2);
+    assert_eq!(methods.iter().filter(|m| *m == "eth_getBalance").count(), 2);
+}
+
+#[test]
+fn test_split_rejected_block_ranges() {
+    use std::sync::mpsc;
+
+    // The provider rejects more than 1000 blocks, there is a log every 100 blocks
+    let requested = Mutex::new(vec![]);
+    let fetch = |from: u64, to: u64| -> Result<Vec<Properties>> {
+        requested.lock().unwrap().push((from, to));
+        if to - from + 1 > 1000 {
+            return Err(
+                RangeTooLarge {
+                    from: format!("0x{:x}", from),
+                    to: format!("0x{:x}", to),
+                    message: "query returned more than 10000 results".to_string(),
+                }.into()
+            );
+        }
+        Ok(
+            (from..=to)
+                .filter(|block| block % 100 == 0)
+                .map(|block| {
+                    let mut p = Properties::new();
+                    p.transaction_hash = Some(format!("0x{:x}", block));
+                    p.log_index = Some(0);
+                    p
+                })
+                .collect()
+        )
+    };
+    let (tx, rx) = mpsc::channel();
+    let sent = replay_pages(&[(0, 4999)], 1, fetch, &tx, None, &PageRetry::default(), None);
+    drop(tx);
+    assert_eq!(sent, 50);
+    assert_eq!(rx.iter().count(), 50);
+    let requested = requested.into_inner().unwrap();
+    // 5000 -> 2500 -> 1250 -> 625 blocks
+    assert_eq!(requested.iter().filter(|(from, to)| to - from + 1 <= 1000).count(), 8);
+    assert_eq!(requested.len(), 1 + 2 + 4 + 8);
+
+    // The floor stops the splitting
+    let always_rejected = |from: u64, to: u64| -> Result<Vec<Properties>> {
+        Err(RangeTooLarge { from: from.to_string(), to: to.to_string(), message: String::new() }.into())
+    };
+    assert!(fetch_split(&always_rejected, 0, 999, 250).is_err());
+    // Other errors are not split
+    let failing = |_: u64, _: u64| -> Result<Vec<Properties>> { Err(anyhow!("connection refused")) };
+    assert!(fetch_split(&failing, 0, 999, 1).is_err());
+
+    assert!(is_range_too_large(&json!({ "code": -32005, "message": "query returned more than 10000 results" })));
+    assert!(is_range_too_large(&json!({ "code": -32602, "message": "eth_getLogs block range is too large, max is 2k" })));
+    assert!(!is_range_too_large(&json!({ "code": -32000, "message": "header not found" })));
+
+    // The configured maximum caps the pages
+    let mut config = ReplayConfig::default();
+    config.max_block_range = Some(2000);
+    assert_eq!(config.page_size(0, 99_999), 2000);
+    config.paging = Some(true);
+    config.page_length = Some(500);
+    assert_eq!(config.page_size(0, 99_999), 500);
+}
+
+#[test]
+fn test_paging_get_logs_calls() {
+    use std::sync::atomic::{ AtomicUsize, Ordering };
+    use std::sync::mpsc;
+
+    let count_calls = |config: &ReplayConfig, start: u64, end: u64| {
+        let calls = AtomicUsize::new(0);
+        let (tx, _rx) = mpsc::channel();
+        let pages = page_ranges(start, end, config.page_size(start, end));
+        replay_pages(
+            &pages,
+            1,
+            |_, _| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                Ok(vec![])
+            },
+            &tx,
+            None,
+            &PageRetry::default(),
+            None
+        );
+        calls.load(Ordering::Relaxed)
+    };
+
+    // Without paging the whole range is one request, regardless of page_length
+    let mut config = ReplayConfig::default();
+    config.page_length = Some(500);
+    assert_eq!(count_calls(&config, 0, 99_999), 1);
+    config.paging = Some(false);
+    assert_eq!(count_calls(&config, 0, 99_999), 1);
+
+    // With paging page_length is the window
+    config.paging = Some(true);
+    assert_eq!(count_calls(&config, 0, 99_999), 200);
+    assert_eq!(count_calls(&config, 0, 1000), 3);
+    config.page_length = Some(10_000);
+    assert_eq!(count_calls(&config, 0, 99_999), 10);
+}
+
+#[test]
+fn test_replay_progress() {
+    let mut progress = ReplayProgress::new("ethereum".to_string(), 1000, 1999);
+    assert_eq!(progress.total, 1000);
+    assert_eq!(progress.percentage(), 0.0);
+
+    progress.advance(1000, 1249);
+    assert_eq!(progress.percentage(), 25.0);
+    progress.advance(1250, 1499);
+    assert_eq!(progress.percentage(), 50.0);
+    assert_eq!(progress.blocks_per_second(Duration::from_secs(10)), 50.0);
+    assert_eq!(
+        progress.report(Duration::from_secs(10)),
+        "Replay ethereum: 500/1000 blocks (50.00%), elapsed 10s, 50.00 blocks/s, ETA 10s"
+    );
+
+    progress.advance(1500, 1999);
+    assert_eq!(progress.percentage(), 100.0);
+}
+
 // #[test]
 // fn test_replay_config_deser() {
 //     let json = r#"{"topics":["0x123456","0x7890ab"]}"#;
@@ -354,3 +1320,236 @@ pub struct LogResult {
 
 //     println!("{:?}", json);
 // }
+
+#[test]
+fn test_replay_pages_retries_failed_page() {
+    use ethnum::AsU256;
+    use std::sync::atomic::{ AtomicUsize, Ordering };
+    use std::sync::mpsc;
+
+    let dir = crate::utils::TempDir::new("gaps");
+    let gaps_file = dir.join("gaps.txt");
+    let retry = PageRetry {
+        attempts: 3,
+        backoff: Duration::from_millis(10),
+        gaps_file: Some(gaps_file.clone()),
+        ..PageRetry::default()
+    };
+
+    // The first page fails twice, the second page always fails
+    let calls = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+    let sent = replay_pages(
+        &[(1, 10), (11, 20)],
+        1,
+        |from, _| {
+            if from == 11 {
+                return Err(anyhow!("Failed to send rpc"));
+            }
+            if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                return Err(anyhow!("Failed Serde"));
+            }
+            let mut p = Properties::new();
+            p.block_number = Some(5.as_u256());
+            Ok(vec![p])
+        },
+        &tx,
+        None,
+        &retry,
+        None
+    );
+    drop(tx);
+
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    assert_eq!(sent, 1);
+    assert_eq!(rx.iter().next().unwrap().block_number, Some(5.as_u256()));
+    // Only the page that failed all attempts is a gap
+    assert_eq!(std::fs::read_to_string(&gaps_file).unwrap(), "0xb 0x14\n");
+}
+
+#[test]
+fn test_replay_resumes_from_checkpoint() {
+    use ethnum::AsU256;
+    use std::sync::mpsc;
+
+    let dir = crate::utils::TempDir::new("checkpoint");
+    let path = dir.join("checkpoint.json");
+    let checkpoint = ReplayCheckpoint::new(path.clone(), "ethereum".to_string());
+    // Checkpoints of other chains are kept
+    ReplayCheckpoint::new(path.clone(), "bsc".to_string()).save(7).unwrap();
+    assert_eq!(checkpoint.load(), None);
+    assert_eq!(checkpoint.resume_from(0), 0);
+
+    let fetched = Mutex::new(vec![]);
+    let fetch = |from: u64, _: u64| {
+        fetched.lock().unwrap().push(from);
+        let mut p = Properties::new();
+        p.block_number = Some(from.as_u256());
+        p.transaction_hash = Some(format!("0x{:x}", from));
+        p.log_index = Some(0);
+        Ok(vec![p])
+    };
+    let pages = page_ranges(0, 49, 10);
+
+    // The replay crashes after the first two pages
+    let (tx, rx) = mpsc::channel();
+    replay_pages(&pages[..2], 1, fetch, &tx, None, &PageRetry::default(), Some(&checkpoint));
+    drop(tx);
+    assert_eq!(rx.iter().count(), 2);
+    assert_eq!(checkpoint.load(), Some(19));
+
+    // The resumed replay starts after the checkpoint
+    fetched.lock().unwrap().clear();
+    let start = checkpoint.resume_from(0);
+    assert_eq!(start, 20);
+    let (tx, rx) = mpsc::channel();
+    replay_pages(&page_ranges(start, 49, 10), 2, fetch, &tx, None, &PageRetry::default(), Some(&checkpoint));
+    drop(tx);
+    let mut received: Vec<u64> = rx
+        .iter()
+        .map(|p| p.block_number.unwrap().as_u64())
+        .collect();
+    received.sort();
+    assert_eq!(received, vec![20, 30, 40]);
+    assert!(fetched.lock().unwrap().iter().all(|from| *from >= 20));
+    assert_eq!(checkpoint.load(), Some(49));
+    assert_eq!(ReplayCheckpoint::new(path.clone(), "bsc".to_string()).load(), Some(7));
+
+}
+
+#[test]
+fn test_replay_from_file() {
+    use std::sync::mpsc;
+
+    let topic = "0x33e13ecb54c3076d8e8bb8c2881800a4d972b792045ffae98fdf46df365fed75";
+    let dir = crate::utils::TempDir::new("replay_logs");
+    let path = dir.join("replay_logs.json");
+    let logs = json!([
+        {
+            "address": "0x715cdda5e9ad30a0ced14940f9997ee611496de6",
+            "topics": [topic],
+            "data": "0x01",
+            "blockNumber": "0x10",
+            "transactionHash": "0xaa",
+            "logIndex": "0x0",
+            "removed": false
+        },
+        // Other topic
+        {
+            "address": "0x715cdda5e9ad30a0ced14940f9997ee611496de6",
+            "topics": ["0xdd"],
+            "data": "0x02",
+            "blockNumber": "0x11",
+            "transactionHash": "0xbb",
+            "logIndex": "0x0",
+            "removed": false
+        },
+        // Removed due to a reorg
+        {
+            "address": "0x715cdda5e9ad30a0ced14940f9997ee611496de6",
+            "topics": [topic],
+            "data": "0x03",
+            "blockNumber": "0x12",
+            "transactionHash": "0xcc",
+            "logIndex": "0x0",
+            "removed": true
+        },
+        {
+            "address": "0x715cdda5e9ad30a0ced14940f9997ee611496de6",
+            "topics": [topic],
+            "data": "0x04",
+            "blockNumber": "0x20",
+            "transactionHash": "0xdd",
+            "logIndex": "0x2",
+            "removed": false
+        }
+    ]);
+    std::fs::write(&path, logs.to_string()).unwrap();
+
+    let config = ReplayConfig {
+        source: Some("file".to_string()),
+        path: Some(path.to_string_lossy().to_string()),
+        ..ReplayConfig::default()
+    };
+    let source = config.source().unwrap();
+    assert_eq!(source, ReplaySource::File(path.clone()));
+    let logs = source.read_logs().unwrap();
+
+    let replayer = ReplayEthereumSocketHandler {
+        chain_name: "ethereum".to_string(),
+        config: Chain {
+            address: "0x715CdDa5e9Ad30A0cEd14940F9997EE611496De6".to_string(),
+            topics: vec![topic.to_string()],
+            ..Chain::default()
+        },
+        rpc_url: String::new(),
+        rate_limiter: RateLimiter::new(None),
+    };
+    let (tx, rx) = mpsc::channel();
+    let sent = replay_pages(
+        &page_ranges(0x10, 0x1f, 8),
+        1,
+        |from, to| replayer.file_logs(&logs, from, to),
+        &tx,
+        None,
+        &PageRetry::default(),
+        None
+    );
+    drop(tx);
+
+    // The log of block 0x20 is out of the range
+    assert_eq!(sent, 1);
+    let properties: Vec<Properties> = rx.iter().collect();
+    assert_eq!(properties[0].transaction_hash.as_deref(), Some("0xaa"));
+    assert_eq!(properties[0].occured_event.as_deref(), Some(topic));
+    assert_eq!(properties[0].block_number, Some(u256::from(0x10u64)));
+    assert_eq!(properties[0].event_data.as_deref(), Some("0x01"));
+    assert_eq!(properties[0].data, "0x01");
+    assert_eq!(properties[0].topics[0], topic);
+    assert_eq!(properties[0].src_chain.as_deref(), Some("ethereum"));
+    assert_eq!(properties[0].log_index, Some(0));
+
+    assert!(ReplayConfig { source: Some("file".to_string()), ..ReplayConfig::default() }.source().is_err());
+    assert_eq!(ReplayConfig::default().source().unwrap(), ReplaySource::Rpc);
+}
+
+#[test]
+fn test_topic_or_set() {
+    let transfer = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+    let approval = "0x8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925";
+    let replayer = ReplayEthereumSocketHandler {
+        chain_name: "ethereum".to_string(),
+        config: Chain {
+            topics: vec![
+                "Transfer(address indexed from, address indexed to, uint256 value)".to_string(),
+                approval.to_uppercase().replace("0X", "0x"),
+            ],
+            ..Chain::default()
+        },
+        rpc_url: String::new(),
+        rate_limiter: RateLimiter::new(None),
+    };
+    assert_eq!(replayer.config.topic_filter(), json!([[transfer, approval]]));
+    let single = Chain { topics: vec![transfer.to_string()], ..Chain::default() };
+    assert_eq!(single.topic_filter(), json!([transfer]));
+    assert_eq!(Chain::default().topic_filter(), json!([]));
+
+    let log = |topic: &str, block: &str| json!({"topics": [topic], "blockNumber": block, "logIndex": "0x0"});
+    let logs = vec![log(transfer, "0x1"), log(approval, "0x2"), log("0xdd", "0x3")];
+    let properties = replayer.file_logs(&logs, 0, 10).unwrap();
+    let events: Vec<&str> = properties
+        .iter()
+        .filter_map(|p| p.occured_event.as_deref())
+        .collect();
+    assert_eq!(events, vec![transfer, approval]);
+    assert_eq!(properties[0].src_chain_id, None);
+
+    // Replayed events of an L2 carry the chain_id of the replay config
+    let chain: Chain = serde_json::from_value(
+        json!({"name": "arbitrum", "startingBlock": "0x0", "endingBlock": "0xa", "address": "", "topics": [transfer], "chain_id": 42161})
+    ).unwrap();
+    let replayer = ReplayEthereumSocketHandler { chain_name: chain.name.clone(), config: chain, ..replayer };
+    let properties = replayer.file_logs(&logs, 0, 10).unwrap();
+    assert_eq!(properties[0].src_chain.as_deref(), Some("arbitrum"));
+    assert_eq!(properties[0].src_chain_id, Some(42161));
+}
@@ -1,6 +1,7 @@
 use crate::properties::Properties;
 use serde_json::Value;
 use ws::Handler;
+use crate::log_debug;
 
 /// The Endpoint Client for the Blockchain Smart Contracts
 /// Here a Handler will fetch and process the events and take care of the websocket connection
@@ -27,14 +28,14 @@ impl Handler for WebSocketClientHandler {
 
         match self.chain_name.as_str() {
             "solana" | "Solana" => {
-                println!("Solana Message: {}", message);
+                log_debug!("Solana Message: {}", message);
             }
             "ethereum" | "Ethereum" => {
-                println!("Ethereum Message: {}", message);
-                println!("For better parsing check the EthereumWebsocketHandler");
+                log_debug!("Ethereum Message: {}", message);
+                log_debug!("For better parsing check the EthereumWebsocketHandler");
             }
             _ => {
-                println!("Custom Message: {}", message);
+                log_debug!("Custom Message: {}", message);
             }
         }
 
@@ -1,6 +1,8 @@
+use std::collections::HashMap;
 use std::sync::mpsc::Sender;
+use std::sync::Mutex;
 
-use ethnum::AsU256;
+use ethnum::{ u256, AsU256 };
 use reqwest::blocking::Client;
 use serde_json::{json, Value};
 use ws::Handler;
@@ -11,6 +13,9 @@ use crate::VarValues;
 use crate::{message_formats::ethereum_message::*, properties::Properties, set_var, utils};
 
 use crate::message_formats::solana_message::*;
+use crate::{ log_debug, log_error };
+use super::subscription::Subscription;
+use super::transport::{ drive, MessageHandler };
 
 /// Solana Websocket Handler
 pub struct SolanaSocketHandler {
@@ -19,6 +24,8 @@ pub struct SolanaSocketHandler {
     pub(crate) properties: Vec<Properties>,
     pub(crate) event_channel: Sender<Properties>,
     request_url: String,
+    /// Confirmation of the logsSubscribe request
+    pub(crate) subscription: Subscription,
 }
 
 impl SolanaSocketHandler {
@@ -32,6 +39,7 @@ impl SolanaSocketHandler {
             properties,
             event_channel,
             request_url,
+            subscription: Subscription::new("solana", "logsSubscribe"),
         }
     }
 
@@ -41,118 +49,80 @@ impl SolanaSocketHandler {
         // Interpret Message if Event: resume else quit
         if let Ok(msg) = serde_json::from_value::<LogMessage>(message) {
             // Check which event occured
-            let event_string = msg
-                .params
-                .result
-                .value
-                .logs
-                .iter()
-                .find(|x| x.to_lowercase().contains("event:"));
-            match event_string {
-                Some(event_content) => {
-                    // println!("Event: {}", event_string.unwrap());
-                    self.properties.push(Properties::new());
-                    let index = self.properties.len() - 1;
-
-                    // Event was found
-                    let event = &event_content[13..];
-                    self.properties[index].occured_event = Some(event.to_string());
-
-                    // concat logs as event data
-                    let event_data = msg.params.result.value.logs.concat();
-                    set_var!("solana_event_data", event_data);
-
-                    // transaction signature
-                    let transaction_signature = msg.params.result.value.signature.clone();
-                    self.properties[index].transaction_hash = Some(transaction_signature.clone());
-
-                    // Get Transaction
-                    let get_transaction = json!({
-                        "jsonrpc": "2.0",
-                        "method": "getTransaction",
-                        "params": [transaction_signature, {"encoding": "jsonParsed","maxSupportedTransactionVersion":0}],
-                        "id": 1
-                    }).to_string();
-
-                    // Build HTTP Post for Transaction Data
-                    let client = Client::new();
-                    let request_body: Value =
-                        serde_json::from_str(get_transaction.as_str()).unwrap();
-                    // println!("Request Body: {}", serde_json::to_string_pretty(&request_body).unwrap());
-                    let res = client
-                        .post(self.request_url.clone())
-                        .json(&request_body)
-                        .send()
-                        .unwrap();
-
-                    let body = res.text().unwrap();
-                    if let Ok(transaction_msg) =
-                        serde_json::from_str::<TransactionMessage>(&body.as_str())
-                    {
-                        // println!("Transaction Message: {}", serde_json::to_string_pretty(&transaction_msg).unwrap());
-
-                        // Get Slot
-                        self.properties[index].block_number =
-                            Some(transaction_msg.result.slot.clone().as_u256());
-
-                        // Find Payer
-                        let payer = find_payer(&transaction_msg);
-                        match payer {
-                            Some(idx) => {
-                                self.properties[index].payer_address = Some(
-                                    transaction_msg.result.transaction.message.account_keys[idx]
-                                        .pubkey
-                                        .clone(),
-                                );
-                                self.properties[index].payer_balance_before = Some(
-                                    transaction_msg.result.meta.pre_balances[idx]
-                                        .clone()
-                                        .as_u256(),
-                                );
-                                self.properties[index].payer_balance_after = Some(
-                                    transaction_msg.result.meta.post_balances[idx]
-                                        .clone()
-                                        .as_u256(),
-                                );
-                            }
-                            None => {
-                                self.properties[index].payer_address = None;
-                                self.properties[index].payer_balance_before = None;
-                                self.properties[index].payer_balance_after = None;
-                            }
-                        }
-
-                        // Solana has no value like Ethereum
-                        self.properties[index].value = Some(0.as_u256());
-
-                        self.properties[index].src_chain = Some(self.chain_name.clone());
-
-                        // Send the Event to the Event Channel
-                        self.event_channel
-                            .send(self.properties[index].clone())
-                            .unwrap();
-                    } else {
-                        println!("Wrong Transaction Message Format");
-                        // println!("Body: {}", serde_json::to_string_pretty(&body).unwrap());
-                        return;
-                    }
-                }
+            let mut property = match decode_log_notification(&msg) {
+                Some(property) => property,
                 None => {
                     // Quitting handling as no Event was found
-                    println!("No Event");
+                    log_debug!("No Event");
                     return;
                 }
+            };
+            property.src_chain = Some(self.chain_name.clone());
+            if msg.params.result.value.is_failed() {
+                log_debug!(
+                    "Failed transaction {}: {}",
+                    msg.params.result.value.signature,
+                    msg.params.result.value.err
+                );
+            }
+
+            // concat logs as event data
+            let event_data = msg.params.result.value.logs.concat();
+            set_var!("solana_event_data", event_data);
+
+            // Get Transaction
+            let get_transaction = json!({
+                "jsonrpc": "2.0",
+                "method": "getTransaction",
+                "params": [msg.params.result.value.signature, {"encoding": "jsonParsed","maxSupportedTransactionVersion":0}],
+                "id": 1
+            }).to_string();
+
+            // Build HTTP Post for Transaction Data
+            let client = Client::new();
+            let request_body: Value = serde_json::from_str(get_transaction.as_str()).unwrap();
+            // println!("Request Body: {}", serde_json::to_string_pretty(&request_body).unwrap());
+            let res = client
+                .post(self.request_url.clone())
+                .json(&request_body)
+                .send()
+                .unwrap();
+
+            let body = res.text().unwrap();
+            if let Ok(transaction_msg) = serde_json::from_str::<TransactionMessage>(&body.as_str()) {
+                // println!("Transaction Message: {}", serde_json::to_string_pretty(&transaction_msg).unwrap());
+                decode_transaction(&mut property, &transaction_msg);
+                decode_token_transfer(&mut property, &transaction_msg, |mint| {
+                    mint_decimals(&self.request_url, mint)
+                });
+
+                self.properties.push(property.clone());
+
+                // Send the Event to the Event Channel
+                self.event_channel.send(property).unwrap();
+            } else {
+                log_error!("Wrong Transaction Message Format");
+                // println!("Body: {}", serde_json::to_string_pretty(&body).unwrap());
+                return;
             }
         }
     }
 }
 
+impl MessageHandler for SolanaSocketHandler {
+    fn handle_message(&mut self, message: Value) {
+        if self.subscription.handle_response(&message) {
+            return;
+        }
+        self.handle(message);
+    }
+}
+
 /// Here the WebSocket Handles the basic workflow
 impl Handler for SolanaSocketHandler {
     fn on_message(&mut self, msg: ws::Message) -> ws::Result<()> {
-        // Try to parse the message into json message
-        let message: Value = serde_json::from_str(&msg.to_string()).unwrap();
-        self.handle(message);
+        // Messages that are no JSON are logged and skipped
+        drive(self, &mut Some(msg));
         Ok(())
     }
 
@@ -161,6 +131,11 @@ impl Handler for SolanaSocketHandler {
         let msg = format!("{} opened", self.chain_name);
         Ok(())
     }
+
+    fn on_timeout(&mut self, event: ws::util::Token) -> ws::Result<()> {
+        self.subscription.on_timeout(event);
+        Ok(())
+    }
 }
 
 pub fn find_payer(transaction: &TransactionMessage) -> Option<usize> {
@@ -170,7 +145,7 @@ pub fn find_payer(transaction: &TransactionMessage) -> Option<usize> {
         .message
         .account_keys
         .iter()
-        .map(|x| x.signer == true)
+        .filter(|x| x.signer)
         .count();
     if num_signer == 1 {
         for count in 0..transaction.result.transaction.message.account_keys.len() {
@@ -185,9 +160,359 @@ pub fn find_payer(transaction: &TransactionMessage) -> Option<usize> {
     let fee = transaction.result.meta.fee;
 
     for index in 0..post_balances.len() {
-        if post_balances[index] - pre_balances[index] == fee {
+        if post_balances[index].checked_sub(pre_balances[index]) == Some(fee) {
             return Some(index);
         }
     }
     None
 }
+
+/// Decodes a Solana `logsNotification` into the fields that are available without
+/// querying the transaction: the event, the signature, the slot, the invoked program
+/// and whether the transaction failed.
+///
+/// Returns `None` if the logs do not contain an event.
+pub fn decode_log_notification(msg: &LogMessage) -> Option<Properties> {
+    let logs = &msg.params.result.value.logs;
+    let event_content = logs.iter().find(|x| x.to_lowercase().contains("event:"))?;
+
+    let mut property = Properties::new();
+    // Event was found
+    property.occured_event = Some(event_content[13..].to_string());
+    // transaction signature
+    property.transaction_hash = Some(msg.params.result.value.signature.clone());
+    // Slot of the notification, later replaced by the slot of the transaction
+    property.block_number = Some(msg.params.result.context.slot.as_u256());
+    property.program_id = find_program_id(logs);
+    property.src_chain = Some("solana".to_string());
+    property.transaction_failed = Some(msg.params.result.value.is_failed());
+    Some(property)
+}
+
+/// Finds the program that was invoked at the top level, e.g. `Program <id> invoke [1]`.
+pub fn find_program_id(logs: &[String]) -> Option<String> {
+    logs.iter().find_map(|log| {
+        let parts: Vec<&str> = log.split_whitespace().collect();
+        match parts.as_slice() {
+            ["Program", id, "invoke", "[1]"] => Some(id.to_string()),
+            _ => None,
+        }
+    })
+}
+
+/// Fills the payer, balances, value and instruction data from a `getTransaction` response.
+pub fn decode_transaction(property: &mut Properties, transaction_msg: &TransactionMessage) {
+    // Get Slot
+    property.block_number = Some(transaction_msg.result.slot.as_u256());
+
+    // Find Payer
+    match find_payer(transaction_msg) {
+        Some(idx) => {
+            let pre_balance = transaction_msg.result.meta.pre_balances[idx];
+            let post_balance = transaction_msg.result.meta.post_balances[idx];
+            property.set_payer(
+                transaction_msg.result.transaction.message.account_keys[idx].pubkey.clone(),
+                post_balance.as_u256(),
+                pre_balance.as_u256()
+            );
+            property.value = Some(lamports_spent(pre_balance, post_balance, transaction_msg.result.meta.fee));
+        }
+        None => {
+            property.clear_payer();
+            property.value = Some(0.as_u256());
+        }
+    }
+
+    // Decode the instruction data of the invoked program
+    let instructions = &transaction_msg.result.transaction.message.instructions;
+    let instruction = instructions
+        .iter()
+        .find(|i| Some(&i.program_id) == property.program_id.as_ref())
+        .or(instructions.first());
+    if let Some(instruction) = instruction {
+        if property.program_id.is_none() {
+            property.program_id = Some(instruction.program_id.clone());
+        }
+        if let Ok(data) = bs58::decode(&instruction.data).into_vec() {
+            property.data = format!("0x{}", hex::encode(data));
+            property.event_data = Some(property.data.clone());
+        }
+    }
+}
+
+/// Decimals of SOL, one SOL are 10^9 lamports
+pub const LAMPORT_DECIMALS: u32 = 9;
+
+/// Lamports that left the payer apart from the fee. A balance that grew spent nothing
+pub fn lamports_spent(pre_balance: u64, post_balance: u64, fee: u64) -> u256 {
+    pre_balance.saturating_sub(post_balance).saturating_sub(fee).as_u256()
+}
+
+/// Converts the raw amount of an SPL token with the decimals of its mint to the 9 decimals of lamports,
+/// so `$value` has the same unit for SOL and token transfers. Digits below a lamport are cut off.
+pub fn spl_amount_to_value(raw_amount: &str, decimals: u32) -> Option<u256> {
+    let amount = raw_amount.parse::<u64>().ok()?.as_u256();
+    if decimals >= LAMPORT_DECIMALS {
+        Some(amount / u256::from(10u64).pow(decimals - LAMPORT_DECIMALS))
+    } else {
+        amount.checked_mul(u256::from(10u64).pow(LAMPORT_DECIMALS - decimals))
+    }
+}
+
+/// Finds the SPL token the owner sent, i.e. the first token account of the owner whose balance decreased.
+/// Returns the mint and the raw amount.
+pub fn find_token_spent(meta: &Meta, owner: &str) -> Option<(String, String)> {
+    let amount = |balance: &Value| {
+        balance
+            .pointer("/uiTokenAmount/amount")
+            .and_then(|a| a.as_str())
+            .and_then(|a| a.parse::<u64>().ok())
+    };
+    meta.pre_token_balances
+        .iter()
+        .filter(|pre| pre.get("owner").and_then(|o| o.as_str()) == Some(owner))
+        .find_map(|pre| {
+            let index = pre.get("accountIndex")?;
+            // A closed token account has no balance after the transaction
+            let post = meta.post_token_balances
+                .iter()
+                .find(|post| post.get("accountIndex") == Some(index))
+                .and_then(amount)
+                .unwrap_or(0);
+            let spent = amount(pre)?.checked_sub(post).filter(|spent| *spent > 0)?;
+            Some((pre.get("mint")?.as_str()?.to_string(), spent.to_string()))
+        })
+}
+
+/// Uses the SPL token amount the payer sent as the value if the transaction did not move any lamports.
+/// The decimals of the mint are looked up with `decimals`.
+pub fn decode_token_transfer<F>(property: &mut Properties, transaction_msg: &TransactionMessage, decimals: F)
+    where F: Fn(&str) -> Option<u32>
+{
+    if property.value.is_some_and(|value| value > 0) {
+        return;
+    }
+    let Some(payer) = property.payer_address.clone() else {
+        return;
+    };
+    let Some((mint, raw_amount)) = find_token_spent(&transaction_msg.result.meta, &payer) else {
+        return;
+    };
+    match decimals(&mint).and_then(|decimals| spl_amount_to_value(&raw_amount, decimals)) {
+        Some(value) => {
+            property.value = Some(value);
+            property.token_mint = Some(mint);
+        }
+        None => log_error!("Error: unknown decimals of the mint {}", mint),
+    }
+}
+
+/// Decimals of the mints, they never change
+static MINT_DECIMALS: Mutex<Option<HashMap<String, u32>>> = Mutex::new(None);
+
+/// Returns the decimals of the mint, requested with `getTokenSupply` from the node once
+pub fn mint_decimals(url: &str, mint: &str) -> Option<u32> {
+    cached_mint_decimals(mint, || fetch_mint_decimals(url, mint))
+}
+
+fn cached_mint_decimals<F>(mint: &str, fetch: F) -> Option<u32> where F: FnOnce() -> Option<u32> {
+    if let Some(decimals) = MINT_DECIMALS.lock().unwrap().as_ref().and_then(|m| m.get(mint)) {
+        return Some(*decimals);
+    }
+    // The lock is not held during the request
+    let decimals = fetch()?;
+    MINT_DECIMALS.lock().unwrap().get_or_insert_with(HashMap::new).insert(mint.to_string(), decimals);
+    Some(decimals)
+}
+
+fn fetch_mint_decimals(url: &str, mint: &str) -> Option<u32> {
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "getTokenSupply", "params": [mint] });
+    let response: Value = Client::new().post(url).json(&request).send().ok()?.json().ok()?;
+    response.pointer("/result/value/decimals")?.as_u64().map(|d| d as u32)
+}
+
+#[test]
+fn test_lamports_and_spl_amounts() {
+    // Balances beyond i64::MAX are still lamports
+    assert_eq!(lamports_spent(u64::MAX, 1_000, 5_000), (u64::MAX - 6_000).as_u256());
+    // The payer received lamports
+    assert_eq!(lamports_spent(1_000, 2_000, 5_000), 0.as_u256());
+
+    // 1.5 USDC with 6 decimals and 1.5 of a token with 12 decimals are 1.5 * 10^9
+    assert_eq!(spl_amount_to_value("1500000", 6), Some(1_500_000_000.as_u256()));
+    assert_eq!(spl_amount_to_value("1500000000000", 12), Some(1_500_000_000.as_u256()));
+    assert_eq!(spl_amount_to_value("-1", 6), None);
+
+    let mut transaction_msg = TransactionMessage::default();
+    transaction_msg.result.meta.fee = 5000;
+    transaction_msg.result.meta.pre_balances = vec![1_000_000];
+    transaction_msg.result.meta.post_balances = vec![995_000];
+    transaction_msg.result.meta.pre_token_balances = vec![
+        json!({ "accountIndex": 1, "mint": "Mint1111", "owner": "Payer1111", "uiTokenAmount": { "amount": "2500000", "decimals": 6 } }),
+        json!({ "accountIndex": 2, "mint": "Mint1111", "owner": "Receiver1111", "uiTokenAmount": { "amount": "0", "decimals": 6 } }),
+    ];
+    transaction_msg.result.meta.post_token_balances = vec![
+        json!({ "accountIndex": 1, "mint": "Mint1111", "owner": "Payer1111", "uiTokenAmount": { "amount": "1000000", "decimals": 6 } }),
+        json!({ "accountIndex": 2, "mint": "Mint1111", "owner": "Receiver1111", "uiTokenAmount": { "amount": "1500000", "decimals": 6 } }),
+    ];
+    transaction_msg.result.transaction.message.account_keys = vec![
+        AccountKey { pubkey: "Payer1111".to_string(), signer: true, source: "transaction".to_string(), writable: true },
+    ];
+    let mut property = Properties::new();
+    decode_transaction(&mut property, &transaction_msg);
+    // Only the fee left the payer
+    assert_eq!(property.value, Some(0.as_u256()));
+
+    // The decimals are fetched once per mint
+    let fetches = std::cell::Cell::new(0);
+    let decimals = |mint: &str| {
+        cached_mint_decimals(mint, || {
+            fetches.set(fetches.get() + 1);
+            Some(6)
+        })
+    };
+    decode_token_transfer(&mut property, &transaction_msg, decimals);
+    assert_eq!(property.value, Some(1_500_000_000.as_u256()));
+    assert_eq!(property.token_mint.as_deref(), Some("Mint1111"));
+    assert_eq!(decimals("Mint1111"), Some(6));
+    assert_eq!(fetches.get(), 1);
+}
+
+#[test]
+fn test_solana_log_decoding() {
+    let notification = r#"{
+        "jsonrpc": "2.0",
+        "method": "logsNotification",
+        "params": {
+            "result": {
+                "context": { "slot": 5208469 },
+                "value": {
+                    "signature": "5h6xBEauJ3PK6SWCZ1PGjBvj8vDdWG3KpwATGy1ARAXFSDwt8GFXM7W5Ncn16wmqokgpiKRLuS83KUxyZyv2sUYv",
+                    "err": null,
+                    "logs": [
+                        "Program worm2ZoG2kUd4vFXhvjh93UUH596ayRfgQ2MgjNMTth invoke [1]",
+                        "Program log: EVENT: signature is verified",
+                        "Program worm2ZoG2kUd4vFXhvjh93UUH596ayRfgQ2MgjNMTth consumed 2000 of 200000 compute units",
+                        "Program worm2ZoG2kUd4vFXhvjh93UUH596ayRfgQ2MgjNMTth success"
+                    ]
+                }
+            },
+            "subscription": 24040
+        }
+    }"#;
+    let msg: LogMessage = serde_json::from_str(notification).unwrap();
+    let mut property = decode_log_notification(&msg).unwrap();
+
+    assert_eq!(property.occured_event.clone().unwrap(), "EVENT: signature is verified");
+    assert_eq!(
+        property.transaction_hash.clone().unwrap(),
+        "5h6xBEauJ3PK6SWCZ1PGjBvj8vDdWG3KpwATGy1ARAXFSDwt8GFXM7W5Ncn16wmqokgpiKRLuS83KUxyZyv2sUYv"
+    );
+    assert_eq!(property.block_number.unwrap(), 5208469.as_u256());
+    assert_eq!(property.program_id.clone().unwrap(), "worm2ZoG2kUd4vFXhvjh93UUH596ayRfgQ2MgjNMTth");
+    assert_eq!(property.transaction_failed, Some(false));
+
+    let mut transaction_msg = TransactionMessage::default();
+    transaction_msg.result.slot = 5208470;
+    transaction_msg.result.meta.fee = 5000;
+    transaction_msg.result.meta.pre_balances = vec![1_000_000, 0];
+    transaction_msg.result.meta.post_balances = vec![895_000, 100_000];
+    transaction_msg.result.transaction.message.account_keys = vec![
+        AccountKey { pubkey: "Payer1111".to_string(), signer: true, source: "transaction".to_string(), writable: true },
+        AccountKey { pubkey: "Receiver1111".to_string(), signer: false, source: "transaction".to_string(), writable: true },
+    ];
+    transaction_msg.result.transaction.message.instructions = vec![Instruction {
+        accounts: vec!["Payer1111".to_string()],
+        data: bs58::encode(vec![1u8, 2, 255]).into_string(),
+        program_id: "worm2ZoG2kUd4vFXhvjh93UUH596ayRfgQ2MgjNMTth".to_string(),
+    }];
+    decode_transaction(&mut property, &transaction_msg);
+
+    assert_eq!(property.block_number.unwrap(), 5208470.as_u256());
+    assert_eq!(property.payer_address.clone().unwrap(), "Payer1111");
+    assert_eq!(property.payer_balance_before.unwrap(), 1_000_000.as_u256());
+    assert_eq!(property.payer_balance_after.unwrap(), 895_000.as_u256());
+    assert_eq!(property.value.unwrap(), 100_000.as_u256());
+    assert_eq!(property.event_data.clone().unwrap(), "0x0102ff");
+}
+
+#[test]
+fn test_failed_log_notification() {
+    // Notification of a failed transaction as sent by a mainnet node
+    let notification = r#"{"jsonrpc":"2.0","method":"logsNotification","params":{"result":{"context":{"slot":245317562},"value":{"signature":"3hbS7XjbtLJFYqk7DcHTTuqxZHwGzfQaS4kv1fNw6mvwnVc2X5ZVMo6nmvYm8vvUA4JuYUqGbGZJpeDXH3pjSoiL","err":{"InstructionError":[0,{"Custom":1}]},"logs":["Program worm2ZoG2kUd4vFXhvjh93UUH596ayRfgQ2MgjNMTth invoke [1]","Program log: EVENT: post message","Program log: Error: insufficient fees","Program worm2ZoG2kUd4vFXhvjh93UUH596ayRfgQ2MgjNMTth consumed 4512 of 200000 compute units","Program worm2ZoG2kUd4vFXhvjh93UUH596ayRfgQ2MgjNMTth failed: custom program error: 0x1"]}},"subscription":6612}}"#;
+    let msg: LogMessage = serde_json::from_str(notification).unwrap();
+    assert_eq!(msg.method, "logsNotification");
+    assert_eq!(msg.params.subscription, 6612);
+    assert_eq!(msg.params.result.context.slot, 245317562);
+    assert!(msg.params.result.value.is_failed());
+    assert_eq!(msg.params.result.value.logs.len(), 5);
+
+    let property = decode_log_notification(&msg).unwrap();
+    assert_eq!(property.src_chain.clone().unwrap(), "solana");
+    assert_eq!(property.block_number.unwrap(), 245317562.as_u256());
+    assert_eq!(property.transaction_hash, Some(msg.params.result.value.signature.clone()));
+    assert_eq!(property.transaction_failed, Some(true));
+    assert_eq!(property.serialize()["transaction_failed"], true);
+}
+
+#[test]
+fn test_solana_handler() {
+    use std::sync::mpsc;
+
+    use super::transport::MockTransport;
+    use crate::properties::environment::EventScope;
+
+    // Mocked node answering getTransaction
+    let mut transaction_msg = TransactionMessage::default();
+    transaction_msg.result.slot = 5208470;
+    transaction_msg.result.meta.fee = 5000;
+    transaction_msg.result.meta.pre_balances = vec![1_000_000, 0];
+    transaction_msg.result.meta.post_balances = vec![895_000, 100_000];
+    transaction_msg.result.transaction.message.account_keys = vec![
+        AccountKey { pubkey: "Payer1111".to_string(), signer: true, source: "transaction".to_string(), writable: true },
+        AccountKey { pubkey: "Receiver1111".to_string(), signer: false, source: "transaction".to_string(), writable: true },
+    ];
+    let response = serde_json::to_string(&transaction_msg).unwrap();
+    let (addr, server) = utils::mock_http_server(Some(1), move |_| ("200 OK", response.clone()));
+
+    let notification = json!({
+        "jsonrpc": "2.0",
+        "method": "logsNotification",
+        "params": {
+            "result": {
+                "context": { "slot": 5208469 },
+                "value": {
+                    "signature": "5h6xBEauJ3PK6SWCZ1PGjBvj8vDdWG3KpwATGy1ARAXFSDwt8GFXM7W5Ncn16wmqokgpiKRLuS83KUxyZyv2sUYv",
+                    "err": null,
+                    "logs": [
+                        "Program worm2ZoG2kUd4vFXhvjh93UUH596ayRfgQ2MgjNMTth invoke [1]",
+                        "Program log: EVENT: signature is verified",
+                        "Program worm2ZoG2kUd4vFXhvjh93UUH596ayRfgQ2MgjNMTth success"
+                    ]
+                }
+            },
+            "subscription": 24040
+        }
+    });
+    // The subscription confirmation produces no event
+    let confirmation = json!({ "jsonrpc": "2.0", "id": 1, "result": 24040 });
+
+    let _scope = EventScope::enter();
+    let (tx, rx) = mpsc::channel();
+    let mut handler = SolanaSocketHandler::new(vec![], tx, format!("http://{}", addr));
+    drive(&mut handler, &mut MockTransport::new(vec![confirmation, notification]));
+    // A message that is no JSON is skipped
+    drive(&mut handler, &mut Some(ws::Message::text("not json")));
+    drop(handler);
+
+    let properties: Vec<Properties> = rx.iter().collect();
+    assert_eq!(properties.len(), 1);
+    let property = &properties[0];
+    assert_eq!(property.occured_event.as_deref(), Some("EVENT: signature is verified"));
+    assert_eq!(property.src_chain.as_deref(), Some("solana"));
+    assert_eq!(property.block_number, Some(5208470.as_u256()));
+    assert_eq!(property.payer_address.as_deref(), Some("Payer1111"));
+    assert_eq!(property.value, Some(100_000.as_u256()));
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0]["method"], "getTransaction");
+}
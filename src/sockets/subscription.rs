@@ -0,0 +1,272 @@
+use std::collections::{ BTreeMap, HashMap };
+use std::sync::atomic::{ AtomicU64, Ordering };
+use std::sync::RwLock;
+use std::time::Duration;
+
+use serde_json::{ json, Value };
+use thiserror::Error;
+use ws::util::Token;
+
+use crate::sockets::health::get_health_state;
+use crate::{ log_error, log_info };
+
+/// Id of the (first) subscription request sent after connecting, further filters of a chain count up from it
+pub const SUBSCRIPTION_REQUEST_ID: u64 = 1;
+/// Token of the websocket timeout that checks the confirmation of the subscription
+pub const SUBSCRIPTION_TIMEOUT_TOKEN: Token = Token(1);
+/// Default time the node has to confirm a subscription
+const SUBSCRIPTION_TIMEOUT_MS: u64 = 10_000;
+
+static SUBSCRIPTION_TIMEOUT: AtomicU64 = AtomicU64::new(SUBSCRIPTION_TIMEOUT_MS);
+
+pub fn set_subscription_timeout(timeout: Duration) {
+    SUBSCRIPTION_TIMEOUT.store(timeout.as_millis() as u64, Ordering::Relaxed);
+}
+
+pub fn subscription_timeout() -> Duration {
+    Duration::from_millis(SUBSCRIPTION_TIMEOUT.load(Ordering::Relaxed))
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SubscriptionError {
+    #[error("{chain} rejected the subscription: {message} ({code})")]
+    Rejected {
+        chain: String,
+        code: i64,
+        message: String,
+    },
+    #[error("{chain} answered the subscription without an id: {response}")]
+    MissingId {
+        chain: String,
+        response: String,
+    },
+    #[error("{chain} did not confirm all subscriptions within {timeout:?}")]
+    Timeout {
+        chain: String,
+        timeout: Duration,
+    },
+}
+
+/// Unsubscribe method and the confirmed subscription ids by their request id
+type ChainSubscriptions = (String, BTreeMap<u64, String>);
+
+/// Confirmed subscriptions of each chain
+static SUBSCRIPTIONS: RwLock<Option<HashMap<String, ChainSubscriptions>>> = RwLock::new(None);
+
+/// Requests that cancel the confirmed subscriptions of the chain, e.g. `eth_unsubscribe` on shutdown
+pub fn unsubscribe_requests(chain: &str) -> Vec<Value> {
+    let subscriptions = SUBSCRIPTIONS.read().unwrap();
+    let Some((method, ids)) = subscriptions.as_ref().and_then(|s| s.get(chain)) else {
+        return vec![];
+    };
+    ids.values()
+        .map(|id| json!({ "jsonrpc": "2.0", "method": method, "params": [id], "id": SUBSCRIPTION_REQUEST_ID }))
+        .collect()
+}
+
+/// Removes the subscriptions of the chain and returns the requests that cancel them
+pub fn forget_subscription(chain: &str) -> Vec<Value> {
+    let requests = unsubscribe_requests(chain);
+    if let Some(subscriptions) = SUBSCRIPTIONS.write().unwrap().as_mut() {
+        subscriptions.remove(chain);
+    }
+    requests
+}
+
+/// State of the subscription requests of a websocket handler
+#[derive(Debug, Clone)]
+pub struct Subscription {
+    chain: String,
+    /// Method of the subscription request, e.g. eth_subscribe or logsSubscribe
+    method: String,
+    /// Number of subscription requests, their ids start at `SUBSCRIPTION_REQUEST_ID`
+    requests: u64,
+    /// Confirmed subscription ids by request id
+    pub(crate) ids: BTreeMap<u64, String>,
+}
+
+impl Subscription {
+    pub fn new(chain: &str, method: &str) -> Self {
+        Self { chain: chain.to_string(), method: method.to_string(), requests: 1, ids: BTreeMap::new() }
+    }
+
+    /// Expects a confirmation for each of the `requests` subscription requests, e.g. one per filter
+    pub fn with_requests(mut self, requests: u64) -> Self {
+        self.requests = requests.max(1);
+        self
+    }
+
+    /// eth_subscribe is cancelled by eth_unsubscribe and logsSubscribe by logsUnsubscribe
+    fn unsubscribe_method(&self) -> String {
+        match self.method.strip_suffix("_subscribe") {
+            Some(prefix) => format!("{}_unsubscribe", prefix),
+            None => self.method.replace("Subscribe", "Unsubscribe"),
+        }
+    }
+
+    /// Parses the response to one of the subscription requests.
+    /// Returns `None` for other messages, e.g. notifications.
+    pub fn confirm(&mut self, message: &Value) -> Option<Result<String, SubscriptionError>> {
+        let request_id = message.get("id")?.as_u64()?;
+        let requests = SUBSCRIPTION_REQUEST_ID..SUBSCRIPTION_REQUEST_ID + self.requests;
+        if message.get("method").is_some() || !requests.contains(&request_id) {
+            return None;
+        }
+        if let Some(error) = message.get("error") {
+            return Some(
+                Err(SubscriptionError::Rejected {
+                    chain: self.chain.clone(),
+                    code: error.get("code").and_then(|c| c.as_i64()).unwrap_or_default(),
+                    message: error
+                        .get("message")
+                        .and_then(|m| m.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                })
+            );
+        }
+        let id = match message.get("result") {
+            Some(Value::String(id)) => id.clone(),
+            Some(Value::Number(id)) => id.to_string(),
+            _ => {
+                return Some(
+                    Err(SubscriptionError::MissingId {
+                        chain: self.chain.clone(),
+                        response: message.to_string(),
+                    })
+                );
+            }
+        };
+        self.ids.insert(request_id, id.clone());
+        let unsubscribe_method = self.unsubscribe_method();
+        SUBSCRIPTIONS.write()
+            .unwrap()
+            .get_or_insert_with(HashMap::new)
+            .entry(self.chain.clone())
+            .or_insert_with(|| (unsubscribe_method, BTreeMap::new()))
+            .1.insert(request_id, id.clone());
+        Some(Ok(id))
+    }
+
+    /// Logs the response to the subscription request and marks the chain as dead if it failed.
+    /// Returns true if the message was the response.
+    pub fn handle_response(&mut self, message: &Value) -> bool {
+        match self.confirm(message) {
+            None => false,
+            Some(Ok(id)) => {
+                log_info!("Subscribed to {} with id {}", self.chain, id);
+                true
+            }
+            Some(Err(e)) => {
+                log_error!("Error: {}", e);
+                get_health_state().lock().unwrap().mark_dead(&self.chain);
+                true
+            }
+        }
+    }
+
+    /// Error if a subscription was not confirmed, called once the timeout is over
+    pub fn check_timeout(&self) -> Result<(), SubscriptionError> {
+        match self.ids.len() as u64 >= self.requests {
+            true => Ok(()),
+            false =>
+                Err(SubscriptionError::Timeout {
+                    chain: self.chain.clone(),
+                    timeout: subscription_timeout(),
+                }),
+        }
+    }
+
+    /// Handles the websocket timeout of the subscription
+    pub fn on_timeout(&self, event: Token) {
+        if event != SUBSCRIPTION_TIMEOUT_TOKEN {
+            return;
+        }
+        if let Err(e) = self.check_timeout() {
+            log_error!("Error: {}", e);
+            get_health_state().lock().unwrap().mark_dead(&self.chain);
+        }
+    }
+}
+
+#[test]
+fn test_subscription_confirmation() {
+    let mut subscription = Subscription::new("subscription_test_chain", "eth_subscribe");
+    // Notifications are no response
+    let notification = json!({ "jsonrpc": "2.0", "method": "eth_subscription", "params": {} });
+    assert_eq!(subscription.confirm(&notification), None);
+    assert_eq!(
+        subscription.check_timeout(),
+        Err(SubscriptionError::Timeout {
+            chain: "subscription_test_chain".to_string(),
+            timeout: subscription_timeout(),
+        })
+    );
+
+    let confirmation = json!({ "jsonrpc": "2.0", "id": 1, "result": "0xcd0c3e8af590364c09d0fa6a1210faf5" });
+    assert_eq!(subscription.confirm(&confirmation), Some(Ok("0xcd0c3e8af590364c09d0fa6a1210faf5".to_string())));
+    assert!(subscription.check_timeout().is_ok());
+    let unsubscribe = &unsubscribe_requests("subscription_test_chain")[0];
+    assert_eq!(unsubscribe["method"], "eth_unsubscribe");
+    assert_eq!(unsubscribe["params"], json!(["0xcd0c3e8af590364c09d0fa6a1210faf5"]));
+    assert_eq!(Subscription::new("solana", "logsSubscribe").unsubscribe_method(), "logsUnsubscribe");
+}
+
+#[test]
+fn test_rejected_subscription() {
+    use super::ethereum_socket::EthereumSocketHandler;
+    use super::transport::{ drive, MockTransport };
+
+    let rejection = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "error": { "code": -32602, "message": "invalid topic" }
+    });
+    let mut subscription = Subscription::new("rejecting_chain", "eth_subscribe");
+    assert_eq!(
+        subscription.confirm(&rejection),
+        Some(
+            Err(SubscriptionError::Rejected {
+                chain: "rejecting_chain".to_string(),
+                code: -32602,
+                message: "invalid topic".to_string(),
+            })
+        )
+    );
+    assert!(subscription.check_timeout().is_err());
+
+    // The handler marks the chain as dead, so the failure shows up in the health report
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut handler = EthereumSocketHandler::new(vec![], tx, String::new()).with_chain(
+        "rejecting_chain".to_string(),
+        None
+    );
+    drive(&mut handler, &mut MockTransport::new(vec![rejection]));
+    drop(handler);
+    assert_eq!(rx.iter().count(), 0);
+    assert!(unsubscribe_requests("rejecting_chain").is_empty());
+    assert!(get_health_state().lock().unwrap().dead.contains("rejecting_chain"));
+}
+
+#[test]
+fn test_multiple_subscriptions() {
+    let mut subscription = Subscription::new("multi_filter_chain", "eth_subscribe").with_requests(2);
+    let first = json!({ "jsonrpc": "2.0", "id": 1, "result": "0xa1" });
+    assert_eq!(subscription.confirm(&first), Some(Ok("0xa1".to_string())));
+    // Both filters must be confirmed
+    assert!(subscription.check_timeout().is_err());
+    // Responses to other requests are no confirmation
+    assert_eq!(subscription.confirm(&json!({ "jsonrpc": "2.0", "id": 3, "result": "0xc3" })), None);
+
+    let second = json!({ "jsonrpc": "2.0", "id": 2, "result": "0xb2" });
+    assert_eq!(subscription.confirm(&second), Some(Ok("0xb2".to_string())));
+    assert!(subscription.check_timeout().is_ok());
+    assert_eq!(unsubscribe_requests("multi_filter_chain").len(), 2);
+
+    let requests = forget_subscription("multi_filter_chain");
+    assert_eq!(
+        requests.iter().map(|r| r["params"][0].clone()).collect::<Vec<Value>>(),
+        vec![json!("0xa1"), json!("0xb2")]
+    );
+    assert!(unsubscribe_requests("multi_filter_chain").is_empty());
+}
@@ -0,0 +1,58 @@
+use serde_json::Value;
+
+use crate::log_error;
+
+/// Handles the raw messages of a chain, independent of how they are received
+pub trait MessageHandler {
+    fn handle_message(&mut self, message: Value);
+}
+
+/// Source of the raw messages of a chain, passed to a handler with `drive`
+pub trait Transport {
+    /// Returns the next message or `None` once the source is exhausted
+    fn next_message(&mut self) -> Option<String>;
+}
+
+/// A message the websocket received, it is handled like a transport with a single message
+impl Transport for Option<ws::Message> {
+    fn next_message(&mut self) -> Option<String> {
+        self.take().map(|message| message.to_string())
+    }
+}
+
+/// In-memory transport with canned notifications, e.g. to test a handler without a node
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+    messages: std::collections::VecDeque<String>,
+}
+
+#[cfg(test)]
+impl MockTransport {
+    pub fn new(messages: Vec<Value>) -> Self {
+        Self {
+            messages: messages
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Transport for MockTransport {
+    fn next_message(&mut self) -> Option<String> {
+        self.messages.pop_front()
+    }
+}
+
+/// Passes every message of the transport to the handler until the transport is exhausted.
+/// Messages that are no JSON are skipped.
+pub fn drive<H: MessageHandler, T: Transport>(handler: &mut H, transport: &mut T) {
+    while let Some(message) = transport.next_message() {
+        match serde_json::from_str::<Value>(&message) {
+            Ok(message) => handler.handle_message(message),
+            Err(e) => log_error!("Error: {}", e),
+        }
+    }
+}
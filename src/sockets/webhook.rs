@@ -0,0 +1,121 @@
+use std::sync::mpsc::{ self, Sender };
+use std::thread::{ self, JoinHandle };
+use std::time::Duration;
+
+use chrono::Local;
+use serde_json::{ json, Value };
+
+use crate::sockets::event_socket::{ Allowance, Event };
+use crate::{ log_error, utils };
+
+/// Waiting time before the first retry of a failed alert
+const WEBHOOK_BACKOFF_MS: u64 = 500;
+
+/// Posts denied transactions to an incident webhook.
+/// The alerts are queued and sent by a dedicated thread, so the event loop is never blocked.
+pub struct AlertWebhook {
+    queue: Sender<Value>,
+}
+
+impl AlertWebhook {
+    /// Starts the sender thread. An alert is dropped after `max_retries` failed retries.
+    pub fn spawn(url: String, max_retries: u32) -> (Self, JoinHandle<()>) {
+        let (queue, alerts) = mpsc::channel::<Value>();
+        let handle = thread::spawn(move || {
+            let client = utils::get_http_client();
+            for alert in alerts {
+                let mut backoff = Duration::from_millis(WEBHOOK_BACKOFF_MS);
+                let mut attempt = 0;
+                loop {
+                    match client.post(url.clone()).json(&alert).send() {
+                        Ok(res) if res.status().is_success() => {
+                            break;
+                        }
+                        Ok(res) => log_error!("Webhook answered with {}", res.status()),
+                        Err(e) => log_error!("Error: {}", e),
+                    }
+                    if attempt == max_retries {
+                        log_error!("Dropping alert for {} after {} retries", alert["transaction_hash"], max_retries);
+                        break;
+                    }
+                    attempt += 1;
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                }
+            }
+        });
+        (Self { queue }, handle)
+    }
+
+    /// Queues an alert if the event was denied
+    pub fn alert(&self, event: &Event) {
+        if let Some(payload) = alert_payload(event) {
+            if let Err(e) = self.queue.send(payload) {
+                log_error!("Error: {}", e);
+            }
+        }
+    }
+
+    /// Queues an alert that the circuit breaker of the chain opened after `failures` failed events
+    pub fn alert_breaker(&self, chain: &str, failures: u32) {
+        if let Err(e) = self.queue.send(breaker_payload(chain, failures)) {
+            log_error!("Error: {}", e);
+        }
+    }
+}
+
+/// JSON body of the alert of an opened circuit breaker
+pub fn breaker_payload(chain: &str, failures: u32) -> Value {
+    json!({
+        "chain": chain,
+        "circuit_breaker": "open",
+        "failures": failures,
+        "timestamp": Local::now().to_rfc3339(),
+    })
+}
+
+/// JSON body of the alert, `None` for allowed transactions
+pub fn alert_payload(event: &Event) -> Option<Value> {
+    match &event.result {
+        Allowance::Allow | Allowance::NoMatch => None,
+        Allowance::Deny(reasons) =>
+            Some(
+                json!({
+                "transaction_hash": event.transaction_hash,
+                "chain": event.chain,
+                "fail_reasons": reasons,
+                "timestamp": Local::now().to_rfc3339(),
+            })
+            ),
+    }
+}
+
+#[test]
+fn test_webhook_on_deny() {
+    let (addr, server) = utils::mock_http_server(Some(1), |_| ("200 OK", String::new()));
+
+    let (webhook, _handle) = AlertWebhook::spawn(format!("http://{}", addr), 0);
+    let allowed = Event {
+        result: Allowance::Allow,
+        checked: vec![],
+        chain: "ethereum".to_string(),
+        transaction_hash: "0x1".to_string(),
+    };
+    assert_eq!(alert_payload(&allowed), None);
+    webhook.alert(&allowed);
+    webhook.alert(
+        &(Event {
+            result: Allowance::Deny(vec!["thorchain_.json: Line 2".to_string()]),
+            checked: vec!["thorchain_.json".to_string()],
+            chain: "ethereum".to_string(),
+            transaction_hash: "0x2".to_string(),
+        })
+    );
+
+    // Only the denied transaction is posted
+    let payload = server.join().unwrap().remove(0);
+    assert_eq!(payload["transaction_hash"], "0x2");
+    assert_eq!(payload["chain"], "ethereum");
+    assert_eq!(payload["fail_reasons"], json!(["thorchain_.json: Line 2"]));
+    assert!(payload["timestamp"].is_string());
+}
@@ -1,10 +1,225 @@
-use std::{fs::{File, OpenOptions}, mem::MaybeUninit, sync::Once, path::Path, time::{Instant, Duration}, io::Write};
+use std::{fs::{File, OpenOptions}, mem::MaybeUninit, sync::Once, path::Path, time::{Instant, Duration}, io::{self, IsTerminal, Write}, fmt};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::path::PathBuf;
+use std::sync::{ OnceLock, RwLock };
 
 use chrono::{DateTime, Local, Datelike, Timelike};
 use ethnum::{u256, uint, i256, int};
 
 use crate::properties::Properties;
 
+/// Verbosity of the console output.
+/// `Debug` prints the ASTs and variables, `Info` the decisions and `Error` only failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error = 0,
+    Info = 1,
+    Debug = 2,
+}
+
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+pub fn set_log_level(level: LogLevel) {
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn get_log_level() -> LogLevel {
+    match LOG_LEVEL.load(Ordering::Relaxed) {
+        0 => LogLevel::Error,
+        1 => LogLevel::Info,
+        _ => LogLevel::Debug,
+    }
+}
+
+pub fn log_enabled(level: LogLevel) -> bool {
+    level <= get_log_level()
+}
+
+static COLOR_ENABLED: AtomicBool = AtomicBool::new(true);
+
+pub fn set_color_enabled(enabled: bool) {
+    COLOR_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn color_enabled() -> bool {
+    COLOR_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Colors are only used if stdout is a terminal, unless disabled by `--no-color` or a non-empty `NO_COLOR`
+pub fn detect_color(no_color: bool) -> bool {
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    !no_color && !no_color_env && io::stdout().is_terminal()
+}
+
+/// A value that is displayed with or without its colors, see `Paint::if_color`
+pub enum Painted<'a, T, P> {
+    Plain(&'a T),
+    Colored(P),
+}
+
+impl<T: fmt::Display, P: fmt::Display> fmt::Display for Painted<'_, T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Painted::Plain(v) => v.fmt(f),
+            Painted::Colored(v) => v.fmt(f),
+        }
+    }
+}
+
+impl<T: fmt::Debug, P: fmt::Debug> fmt::Debug for Painted<'_, T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Painted::Plain(v) => v.fmt(f),
+            Painted::Colored(v) => v.fmt(f),
+        }
+    }
+}
+
+/// Applies an owo_colors style only if colors are enabled, e.g. `"Deny".if_color(|s| s.red())`
+pub trait Paint: Sized {
+    fn if_color<'a, P>(&'a self, style: impl FnOnce(&'a Self) -> P) -> Painted<'a, Self, P> {
+        if color_enabled() {
+            Painted::Colored(style(self))
+        } else {
+            Painted::Plain(self)
+        }
+    }
+}
+
+impl<T> Paint for T {}
+
+/// Directories that the `config`, `properties`, `rules` and `functions` paths are resolved against
+#[derive(Debug, Clone, PartialEq)]
+pub struct Directories {
+    /// Directory with `properties`, `rules` and `functions`
+    pub base: PathBuf,
+    /// Directory with the config files, `<base>/config` by default
+    pub config: PathBuf,
+}
+
+impl Default for Directories {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+impl Directories {
+    /// Without a base directory the paths are relative to the working directory like before
+    pub fn new(base: Option<PathBuf>, config: Option<PathBuf>) -> Self {
+        let base = base.unwrap_or_default();
+        let config = config.unwrap_or(base.join("config"));
+        Self { base, config }
+    }
+
+    /// Path in the base directory, e.g. `base_path("properties")`
+    pub fn base_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.base.join(path)
+    }
+
+    /// Path in the config directory, e.g. `config_path("connections.json")`
+    pub fn config_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.config.join(path)
+    }
+}
+
+static DIRECTORIES: RwLock<Option<Directories>> = RwLock::new(None);
+
+/// Sets the directories once at startup, before any config or property is loaded
+pub fn set_directories(directories: Directories) {
+    *DIRECTORIES.write().unwrap() = Some(directories);
+}
+
+pub fn get_directories() -> Directories {
+    DIRECTORIES.read().unwrap().clone().unwrap_or_default()
+}
+
+/// Write a line to `out` if the message level is enabled by `max_level`
+pub fn write_log<W: Write>(out: &mut W, max_level: LogLevel, level: LogLevel, args: fmt::Arguments) {
+    if level <= max_level {
+        let _ = writeln!(out, "{}", args);
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    static CAPTURED_LOGS: std::cell::RefCell<Option<Vec<(LogLevel, String)>>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Runs `f` and returns the lines it logged on the current thread, of every level.
+/// The captured lines are not written to the console
+#[cfg(test)]
+pub fn capture_logs<R>(f: impl FnOnce() -> R) -> (R, Vec<(LogLevel, String)>) {
+    CAPTURED_LOGS.with(|logs| *logs.borrow_mut() = Some(vec![]));
+    let result = f();
+    let lines = CAPTURED_LOGS.with(|logs| logs.borrow_mut().take()).unwrap_or_default();
+    (result, lines)
+}
+
+/// Write a line to stdout, or stderr for errors, if the level is enabled
+pub fn log(level: LogLevel, args: fmt::Arguments) {
+    #[cfg(test)]
+    if CAPTURED_LOGS.with(|logs| logs.borrow_mut().as_mut().map(|lines| lines.push((level, args.to_string()))).is_some()) {
+        return;
+    }
+    match level {
+        LogLevel::Error => write_log(&mut io::stderr(), get_log_level(), level, args),
+        _ => write_log(&mut io::stdout(), get_log_level(), level, args),
+    }
+}
+
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)*) => {
+        $crate::utils::log($crate::utils::LogLevel::Debug, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)*) => {
+        $crate::utils::log($crate::utils::LogLevel::Info, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)*) => {
+        $crate::utils::log($crate::utils::LogLevel::Error, format_args!($($arg)*))
+    };
+}
+
+#[test]
+fn test_log_levels() {
+    let mut info = Vec::new();
+    let mut debug = Vec::new();
+    for out in [(&mut info, LogLevel::Info), (&mut debug, LogLevel::Debug)] {
+        write_log(out.0, out.1, LogLevel::Debug, format_args!("└── Variable: {}", "x"));
+        write_log(out.0, out.1, LogLevel::Info, format_args!("Allow transaction: {}", "0x1"));
+        write_log(out.0, out.1, LogLevel::Error, format_args!("Error: {}", "failed"));
+    }
+
+    assert_eq!(String::from_utf8(info).unwrap(), "Allow transaction: 0x1\nError: failed\n");
+    assert_eq!(
+        String::from_utf8(debug).unwrap(),
+        "└── Variable: x\nAllow transaction: 0x1\nError: failed\n"
+    );
+    assert!(LogLevel::Error < LogLevel::Info);
+}
+
+#[test]
+fn test_no_color() {
+    use owo_colors::OwoColorize;
+
+    set_color_enabled(false);
+    let plain = format!("{} {:?}", "Deny".if_color(|s| s.red()), "map".if_color(|s| s.green()));
+    set_color_enabled(true);
+    let colored = format!("{}", "Deny".if_color(|s| s.red()));
+
+    assert_eq!(plain, "Deny \"map\"");
+    assert!(!plain.contains('\x1b'));
+    assert!(colored.contains('\x1b'));
+}
+
 /// Convert hex string to u64 and remove leading 0x
 pub fn hex_string_to_u64(hex_string: &str) -> u64 {
     let mut string_hex = hex_string;
@@ -91,6 +306,234 @@ pub fn get_ethereum_topic_ids(event_header: &str) -> String {
     s
 }
 
+/// 4 byte selector of a function signature, e.g. "transfer(address,uint256)" becomes "0xa9059cbb".
+/// The signature is canonicalized first, so parameter names and whitespace are ignored.
+pub fn function_selector(signature: &str) -> String {
+    get_ethereum_topic_ids(&canonical_event_signature(signature))[..10].to_string()
+}
+
+/// Canonical form of an event signature that is hashed into the topic, e.g.
+/// "Transfer(address indexed from, address indexed to, uint value)" becomes "Transfer(address,address,uint256)".
+/// Whitespace, parameter names and `indexed` are removed and `uint`/`int` are expanded to 256 bits.
+/// Strings without a parameter list are returned trimmed.
+pub fn canonical_event_signature(signature: &str) -> String {
+    let signature = signature.trim();
+    match (signature.find('('), signature.rfind(')')) {
+        (Some(start), Some(end)) if start < end => {
+            let name = signature[..start].trim();
+            format!("{}({})", name, canonical_parameters(&signature[start + 1..end]))
+        }
+        _ => signature.to_string(),
+    }
+}
+
+/// Canonical types of a comma separated parameter list, tuples are normalized recursively
+fn canonical_parameters(parameters: &str) -> String {
+    split_parameters(parameters)
+        .into_iter()
+        .map(canonical_type)
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// Splits a parameter list at the commas that are not inside a tuple
+fn split_parameters(parameters: &str) -> Vec<&str> {
+    let mut split = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in parameters.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                split.push(&parameters[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !parameters[start..].trim().is_empty() {
+        split.push(&parameters[start..]);
+    }
+    split
+}
+
+/// A parameter of an event signature
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventParameter {
+    /// Canonical type, e.g. uint256
+    pub ty: String,
+    /// Name of the parameter, empty if the signature has none
+    pub name: String,
+    /// Indexed parameters are stored in the topics, the others in the data of the log
+    pub indexed: bool,
+}
+
+/// Parameters of an event signature like "Transfer(address indexed from, address indexed to, uint256 value)"
+pub fn event_parameters(signature: &str) -> Vec<EventParameter> {
+    let (start, end) = match (signature.find('('), signature.rfind(')')) {
+        (Some(start), Some(end)) if start < end => (start, end),
+        _ => {
+            return vec![];
+        }
+    };
+    split_parameters(&signature[start + 1..end])
+        .into_iter()
+        .map(|parameter| {
+            let parameter = parameter.trim();
+            // The words after the type, e.g. "indexed from"
+            let words: Vec<&str> = if parameter.starts_with('(') {
+                let tuple_end = parameter.rfind(')').unwrap_or(0);
+                parameter[tuple_end + 1..]
+                    .split_whitespace()
+                    .filter(|w| !w.starts_with('['))
+                    .collect()
+            } else {
+                parameter.split_whitespace().skip(1).collect()
+            };
+            EventParameter {
+                ty: canonical_type(parameter),
+                name: words
+                    .iter()
+                    .rev()
+                    .find(|w| **w != "indexed")
+                    .unwrap_or(&"")
+                    .to_string(),
+                indexed: words.contains(&"indexed"),
+            }
+        })
+        .collect()
+}
+
+fn canonical_type(parameter: &str) -> String {
+    let parameter = parameter.trim();
+    if parameter.starts_with('(') {
+        // Tuple with an optional array suffix, e.g. "(address, uint)[] indexed orders"
+        if let Some(end) = parameter.rfind(')') {
+            let suffix = parameter[end + 1..].split_whitespace().next().unwrap_or("");
+            return format!("({}){}", canonical_parameters(&parameter[1..end]), suffix);
+        }
+    }
+    let ty = parameter.split_whitespace().next().unwrap_or("");
+    for (short, full) in [("uint", "uint256"), ("int", "int256")] {
+        if let Some(rest) = ty.strip_prefix(short) {
+            if rest.is_empty() || rest.starts_with('[') {
+                return format!("{}{}", full, rest);
+            }
+        }
+    }
+    ty.to_string()
+}
+
+/// Returns true if the event of a property matches the topic0 of a log.
+/// The event is either the topic itself or an event signature that is hashed into the topic.
+pub fn event_matches_topic(event: &str, topic: &str) -> bool {
+    if event == topic || (event.starts_with("0x") && event.eq_ignore_ascii_case(topic)) {
+        return true;
+    }
+    event.contains('(') && get_ethereum_topic_ids(&canonical_event_signature(event)).eq_ignore_ascii_case(topic)
+}
+
+/// HTTP client shared by the RPC calls, so the connections to the nodes are reused
+pub fn get_http_client() -> &'static reqwest::blocking::Client {
+    static CLIENT: OnceLock<reqwest::blocking::Client> = OnceLock::new();
+
+    CLIENT.get_or_init(reqwest::blocking::Client::new)
+}
+
+/// Mocked HTTP server for tests, e.g. a node or a webhook. Each connection is served in its own thread and
+/// answered with the status and body that `respond` returns for the JSON body of the request.
+/// Serves `requests` connections or any number for `None`. The handle returns the received request bodies.
+#[cfg(test)]
+pub fn mock_http_server<F>(
+    requests: Option<usize>,
+    respond: F
+) -> (std::net::SocketAddr, std::thread::JoinHandle<Vec<serde_json::Value>>)
+    where F: Fn(&serde_json::Value) -> (&'static str, String) + Send + Sync + 'static
+{
+    use std::io::{ BufRead, BufReader, Read };
+    use std::net::TcpListener;
+    use std::sync::{ Arc, Mutex };
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let respond = Arc::new(respond);
+    let handle = std::thread::spawn(move || {
+        let received = Arc::new(Mutex::new(vec![]));
+        let workers = listener
+            .incoming()
+            .take(requests.unwrap_or(usize::MAX))
+            .map(|stream| {
+                let (respond, received) = (respond.clone(), received.clone());
+                let mut reader = BufReader::new(stream.unwrap());
+                std::thread::spawn(move || {
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line == "\r\n" || line.is_empty() {
+                            break;
+                        }
+                        if let Some(len) = line.to_lowercase().strip_prefix("content-length:") {
+                            content_length = len.trim().parse::<usize>().unwrap();
+                        }
+                    }
+                    let mut body = vec![0u8; content_length];
+                    reader.read_exact(&mut body).unwrap();
+                    let request = serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null);
+                    received.lock().unwrap().push(request.clone());
+                    let (status, body) = respond(&request);
+                    // The client may have given up already
+                    let _ = write!(
+                        reader.get_mut(),
+                        "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+                })
+            })
+            .collect::<Vec<std::thread::JoinHandle<()>>>();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        let received = received.lock().unwrap().clone();
+        received
+    });
+    (addr, handle)
+}
+
+/// Empty directory `brigade_<name>_<pid>` in the temp directory of the system for a test.
+/// The directory is removed when the guard is dropped, also if the test fails.
+#[cfg(test)]
+pub struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("brigade_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
 /// Get Startup instant
 pub fn get_startup_time() -> &'static mut Instant {
     static mut MAYBE: MaybeUninit<Instant> = MaybeUninit::uninit();
@@ -163,6 +606,51 @@ fn test_topic_ids_ethereum(){
     println!("{}", topic_id);
 }
 
+#[test]
+fn test_event_signature_matching() {
+    let transfer = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+    let signatures = [
+        "Transfer(address,address,uint256)",
+        "Transfer(address, address, uint256)",
+        " Transfer ( address indexed from , address indexed to, uint value ) ",
+    ];
+    for signature in signatures {
+        assert_eq!(canonical_event_signature(signature), "Transfer(address,address,uint256)");
+        assert!(event_matches_topic(signature, transfer));
+        assert!(event_matches_topic(signature, &transfer.to_uppercase().replace("0X", "0x")));
+    }
+    assert!(!event_matches_topic("Transfer(address,uint256)", transfer));
+
+    // Topics and non Ethereum events are compared directly
+    assert!(event_matches_topic(transfer, transfer));
+    assert!(event_matches_topic("EVENT: signature is verified", "EVENT: signature is verified"));
+
+    assert_eq!(canonical_event_signature("PrivilegedEvent()"), "PrivilegedEvent()");
+    assert_eq!(
+        canonical_event_signature("Filled((address maker, uint[] amounts)[] orders, int8 side)"),
+        "Filled((address,uint256[])[],int8)"
+    );
+}
+
+#[test]
+fn test_event_parameters() {
+    let parameters = event_parameters("Transfer(address indexed from, address indexed to, uint value)");
+    assert_eq!(
+        parameters,
+        vec![
+            EventParameter { ty: "address".to_string(), name: "from".to_string(), indexed: true },
+            EventParameter { ty: "address".to_string(), name: "to".to_string(), indexed: true },
+            EventParameter { ty: "uint256".to_string(), name: "value".to_string(), indexed: false }
+        ]
+    );
+    let parameters = event_parameters("Filled((address maker, uint amount)[] indexed orders, bool)");
+    assert_eq!(parameters[0].ty, "(address,uint256)[]");
+    assert_eq!(parameters[0].name, "orders");
+    assert!(parameters[0].indexed);
+    assert_eq!(parameters[1].name, "");
+    assert!(event_parameters("EVENT: signature is verified").is_empty());
+}
+
 // TODO: Implement more logs for the evaluation
 #[derive(Debug, Clone, Default)]
 pub struct Evaluation {
@@ -189,4 +677,42 @@ impl Evaluation {
             },
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_directories() {
+    // Without a base directory everything stays relative to the working directory
+    let directories = Directories::default();
+    assert_eq!(directories.config_path("connections.json"), Path::new("config/connections.json"));
+    assert_eq!(directories.base_path("properties"), Path::new("properties"));
+
+    // A base directory with a minimal config
+    let base = TempDir::new("base");
+    std::fs::create_dir_all(base.join("config")).unwrap();
+    std::fs::create_dir_all(base.join("properties")).unwrap();
+    std::fs::write(
+        base.join("config/connections.json"),
+        r#"{"connections": [{"name": "ethereum", "rpc_url": "BRIGADE_TEST_UNSET_API"}]}"#
+    ).unwrap();
+    std::fs::write(
+        base.join("properties/limit.json"),
+        r#"{"event": "Limit()", "chain_name": "*", "properties": {}, "pattern": ["1 < 2"]}"#
+    ).unwrap();
+
+    let directories = Directories::new(Some(base.to_path_buf()), None);
+    let connections = crate::configs::connection::ConnectionConfig
+        ::from_file(directories.config_path("connections.json").to_str().unwrap())
+        .unwrap();
+    assert_eq!(connections.connections[0].name, "ethereum");
+    let (properties, errors) = crate::properties::store::PropertySet
+        ::load(&directories.base_path("properties"))
+        .unwrap();
+    assert!(errors.is_empty());
+    assert_eq!(properties.files[0].name, "limit.json");
+
+    // The config directory can be set on its own
+    let directories = Directories::new(Some(base.to_path_buf()), Some(PathBuf::from("/etc/brigade")));
+    assert_eq!(directories.config_path("allowlist.json"), Path::new("/etc/brigade/allowlist.json"));
+    assert_eq!(directories.base_path("rules"), base.join("rules"));
+
+}
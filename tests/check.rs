@@ -0,0 +1,53 @@
+use std::fs;
+use std::io::Write;
+use std::process::{ Command, Stdio };
+
+use serde_json::Value;
+
+/// Pipes the event into `brigade check` with the property directory and returns the exit code and the decision
+fn check(base_dir: &std::path::Path, event: &str) -> (i32, Value) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_brigade"))
+        .args(["--no-color", "--quiet", "--base-dir", base_dir.to_str().unwrap(), "check"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(event.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let decision = stdout.lines().last().map(|l| serde_json::from_str(l).unwrap()).unwrap_or_default();
+    (output.status.code().unwrap(), decision)
+}
+
+#[test]
+fn test_check_event_from_stdin() {
+    let dir = std::env::temp_dir().join(format!("brigade_check_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("properties")).unwrap();
+    fs::create_dir_all(dir.join("config")).unwrap();
+    let event = fs::read_to_string("tests/fixtures/check_event.json").unwrap();
+
+    // The value of the fixture is 1000
+    fs::write(
+        dir.join("properties/limit.json"),
+        r#"{"event": "Limit", "chain_name": "solana", "properties": {}, "pattern": ["$value <= 1000"]}"#
+    ).unwrap();
+    let (code, decision) = check(&dir, &event);
+    assert_eq!(code, 0);
+    assert_eq!(decision["result"], "allow");
+    assert_eq!(decision["checked"][0], "limit.json");
+
+    fs::write(
+        dir.join("properties/limit.json"),
+        r#"{"event": "Limit", "chain_name": "solana", "properties": {}, "pattern": ["$value < 1000"]}"#
+    ).unwrap();
+    let (code, decision) = check(&dir, &event);
+    assert_eq!(code, 1);
+    assert_eq!(decision["result"], "deny");
+    assert_eq!(decision["reasons"][0], "limit.json");
+
+    let (code, _) = check(&dir, "not an event");
+    assert_eq!(code, 2);
+
+    fs::remove_dir_all(&dir).unwrap();
+}